@@ -0,0 +1,104 @@
+use std::{error::Error, fmt, path::PathBuf};
+use thiserror::Error;
+use todo_fs::db::{Db, DbLayout, DbLayoutArgError, DbLayoutArgs, DB_LAYOUT_ARGS_HELP};
+
+#[derive(Debug, Error)]
+enum ArgParseError {
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
+    #[error("no argument after --dest")]
+    DestArgNotProvided,
+    #[error("--dest not provided")]
+    DestNotProvided,
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+struct Args {
+    layout: DbLayout,
+    dest: PathBuf,
+}
+
+impl Args {
+    fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
+        let program_name = it.next().unwrap_or_else(|| "backup".to_string());
+
+        let mut layout_args = DbLayoutArgs::default();
+        let mut dest = None;
+        while let Some(arg) = it.next() {
+            if layout_args
+                .parse_arg(&arg, &mut it)
+                .map_err(ArgParseError::Layout)?
+            {
+                continue;
+            }
+
+            match arg.as_ref() {
+                "--dest" => {
+                    dest = Some(it.next().ok_or(ArgParseError::DestArgNotProvided)?);
+                }
+                "--help" => help(&program_name),
+                _ => return Err(ArgParseError::UnhandledArg(arg)),
+            }
+        }
+
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
+
+        let dest = dest.ok_or(ArgParseError::DestNotProvided)?.into();
+
+        Ok(Args { layout, dest })
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Copies the database and item content to a new location. Safe to run while the\n\
+        filesystem is mounted\n\
+        \n\
+        Args:\n\
+        {DB_LAYOUT_ARGS_HELP}\
+        --dest <path>: Directory to back up to, can be used as a --db-path afterwards\n"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("argument parsing failed")]
+    ArgParse(#[source] ArgParseError),
+    #[error("failed to open database")]
+    OpenDb(#[source] todo_fs::db::OpenDbError),
+    #[error("failed to back up database")]
+    Backup(#[source] todo_fs::db::BackupError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    env_logger::init();
+
+    let args = Args::parse(std::env::args()).map_err(MainError::ArgParse)?;
+    let db = Db::with_layout(args.layout).map_err(MainError::OpenDb)?;
+    db.backup_to(&args.dest).map_err(MainError::Backup)?;
+
+    Ok(())
+}
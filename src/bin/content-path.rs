@@ -0,0 +1,93 @@
+use std::{error::Error, fmt};
+use thiserror::Error;
+use todo_fs::fuse::api::{
+    self, ClientRequest, ClientResponse, GetContentPathRequest, GetContentPathResponse,
+};
+
+fn get_item_id_from_args<It: Iterator<Item = String>>(mut it: It) -> i64 {
+    let program_name = it.next().unwrap_or_else(|| "content-path".to_string());
+
+    let mut item_id = None;
+    for arg in it {
+        if arg == "--help" {
+            help(&program_name)
+        }
+
+        if item_id.is_some() {
+            println!("Unexpected extra argument");
+            help(&program_name);
+        }
+
+        item_id = Some(arg);
+    }
+
+    let Some(item_id) = item_id else {
+        println!("Please provide item id");
+        help(&program_name)
+    };
+
+    match item_id.parse() {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Failed to parse item id: {e}");
+            help(&program_name);
+        }
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} item_id\n\
+        \n\
+        Prints the path of the item's content folder on the underlying file system\n\
+    "
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let item_id = get_item_id_from_args(std::env::args());
+
+    let request = ClientRequest::GetContentPath(GetContentPathRequest { id: item_id });
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::GetContentPath(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    match response {
+        GetContentPathResponse::Found { path } => println!("{}", path.display()),
+        GetContentPathResponse::ItemNotFound => {
+            println!("Item {item_id} does not exist");
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -1,22 +1,45 @@
+use std::{error::Error, fmt};
 use todo_fs::{
-    db::{ItemFilterRule, RelationshipId},
-    fuse::api::{self, ClientRequest, CreateFilterRequest},
+    db::{FilterId, ItemFilterRule, ParseCompareOpError, ParseFilterOrderError},
+    fuse::api::{self, ClientRequest, ClientResponse, CreateFilterRequest, PreviewFilterRequest},
 };
 
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 enum ArgParseError {
-    #[error("missing side for no_relationship filter")]
+    #[error("missing side for filter")]
     MissingSide,
-    #[error("missing relationship id for no_relationship filter")]
+    #[error("missing relationship id for filter")]
     MissingRelationshipId,
     #[error("failed to parse relationship side")]
     ParseRelationshipSide,
-    #[error("failed to parse relationship id")]
-    ParseRelationshipId(#[source] std::num::ParseIntError),
+    #[error("failed to resolve relationship")]
+    ResolveRelationship(#[source] api::ResolveRelationshipError),
+    #[error("missing comparison operator for relationship_count filter")]
+    MissingCompareOp,
+    #[error("failed to parse comparison operator")]
+    ParseCompareOp(#[source] ParseCompareOpError),
+    #[error("missing count for relationship_count filter")]
+    MissingCount,
+    #[error("missing filter id for related_to_filter filter")]
+    MissingSubFilterId,
+    #[error("failed to parse sub filter id")]
+    ParseSubFilterId(#[source] std::num::ParseIntError),
+    #[error("failed to parse count")]
+    ParseCount(#[source] std::num::ParseIntError),
+    #[error("missing condition filter id")]
+    MissingConditionId,
+    #[error("failed to parse condition filter id")]
+    ParseConditionId(#[source] std::num::ParseIntError),
+    #[error("missing order for --order-by")]
+    MissingOrder,
+    #[error("failed to parse order")]
+    ParseOrder(#[source] ParseFilterOrderError),
     #[error("missing filter name")]
     MissingFilterName,
+    #[error("--count and --preview cannot be combined")]
+    CountWithPreview,
     #[error("missing filter type")]
     MissingFilterType,
     #[error("unknown filter name {0}")]
@@ -27,9 +50,14 @@ enum ArgParseError {
 
 fn parse_filter<It: Iterator<Item = String>>(it: &mut It) -> Result<ItemFilterRule, ArgParseError> {
     let filter_name = it.next().ok_or(ArgParseError::MissingFilterType)?;
-    if filter_name != "no_relationship" {
-        return Err(ArgParseError::UnknownFilter(filter_name));
-    }
+    let make_rule = match filter_name.as_ref() {
+        "no_relationship" => ItemFilterRule::NoRelationship,
+        "has_relationship_with_variable_item" => ItemFilterRule::HasRelationshipWithVariableItem,
+        "relationship_count" => return parse_relationship_count(it),
+        "orphan" => return Ok(ItemFilterRule::Orphan),
+        "related_to_filter" => return parse_related_to_filter(it),
+        _ => return Err(ArgParseError::UnknownFilter(filter_name)),
+    };
 
     let side = it.next().ok_or(ArgParseError::MissingSide)?;
     let relationship_id = it.next().ok_or(ArgParseError::MissingRelationshipId)?;
@@ -37,20 +65,63 @@ fn parse_filter<It: Iterator<Item = String>>(it: &mut It) -> Result<ItemFilterRu
     let side = side
         .parse()
         .map_err(|_| ArgParseError::ParseRelationshipSide)?;
-    let id: i64 = relationship_id
+    let id =
+        api::resolve_relationship(&relationship_id).map_err(ArgParseError::ResolveRelationship)?;
+
+    Ok(make_rule(side, id))
+}
+
+fn parse_relationship_count<It: Iterator<Item = String>>(
+    it: &mut It,
+) -> Result<ItemFilterRule, ArgParseError> {
+    let side = it.next().ok_or(ArgParseError::MissingSide)?;
+    let relationship_id = it.next().ok_or(ArgParseError::MissingRelationshipId)?;
+    let op = it.next().ok_or(ArgParseError::MissingCompareOp)?;
+    let count = it.next().ok_or(ArgParseError::MissingCount)?;
+
+    let side = side
         .parse()
-        .map_err(ArgParseError::ParseRelationshipId)?;
+        .map_err(|_| ArgParseError::ParseRelationshipSide)?;
+    let id =
+        api::resolve_relationship(&relationship_id).map_err(ArgParseError::ResolveRelationship)?;
+    let op = op.parse().map_err(ArgParseError::ParseCompareOp)?;
+    let count = count.parse().map_err(ArgParseError::ParseCount)?;
 
-    Ok(ItemFilterRule::NoRelationship(side, RelationshipId(id)))
+    Ok(ItemFilterRule::RelationshipCount(side, id, op, count))
 }
 
-fn parse_args<It: Iterator<Item = String>>(
-    mut it: It,
-) -> Result<CreateFilterRequest, ArgParseError> {
+fn parse_related_to_filter<It: Iterator<Item = String>>(
+    it: &mut It,
+) -> Result<ItemFilterRule, ArgParseError> {
+    let side = it.next().ok_or(ArgParseError::MissingSide)?;
+    let relationship_id = it.next().ok_or(ArgParseError::MissingRelationshipId)?;
+    let sub_filter_id = it.next().ok_or(ArgParseError::MissingSubFilterId)?;
+
+    let side = side
+        .parse()
+        .map_err(|_| ArgParseError::ParseRelationshipSide)?;
+    let id =
+        api::resolve_relationship(&relationship_id).map_err(ArgParseError::ResolveRelationship)?;
+    let sub_filter_id = sub_filter_id
+        .parse()
+        .map_err(ArgParseError::ParseSubFilterId)?;
+
+    Ok(ItemFilterRule::RelatedToFilter(
+        side,
+        id,
+        FilterId(sub_filter_id),
+    ))
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> Result<ClientRequest, ArgParseError> {
     let _program_name = it.next();
 
     let mut filters = Vec::new();
     let mut name = None;
+    let mut conditions = Vec::new();
+    let mut preview = false;
+    let mut count = false;
+    let mut order_by = None;
 
     while let Some(arg) = it.next() {
         match arg.as_ref() {
@@ -58,6 +129,17 @@ fn parse_args<It: Iterator<Item = String>>(
                 name = it.next();
             }
             "--filter" => filters.push(parse_filter(&mut it)?),
+            "--condition" => {
+                let condition = it.next().ok_or(ArgParseError::MissingConditionId)?;
+                let condition = condition.parse().map_err(ArgParseError::ParseConditionId)?;
+                conditions.push(condition);
+            }
+            "--order-by" => {
+                let order = it.next().ok_or(ArgParseError::MissingOrder)?;
+                order_by = Some(order.parse().map_err(ArgParseError::ParseOrder)?);
+            }
+            "--preview" => preview = true,
+            "--count" => count = true,
             "--help" => {
                 help();
             }
@@ -65,9 +147,32 @@ fn parse_args<It: Iterator<Item = String>>(
         }
     }
 
+    if count && preview {
+        return Err(ArgParseError::CountWithPreview);
+    }
+
+    // Conditions only decide which item folders a filter shows up in, they do not change what it
+    // matches
+    if preview {
+        return Ok(ClientRequest::PreviewFilter(PreviewFilterRequest {
+            filters,
+        }));
+    }
+
+    if count {
+        return Ok(ClientRequest::CountFilterMatches(PreviewFilterRequest {
+            filters,
+        }));
+    }
+
     let name = name.ok_or(ArgParseError::MissingFilterName)?;
 
-    Ok(CreateFilterRequest { name, filters })
+    Ok(ClientRequest::CreateFilter(CreateFilterRequest {
+        name,
+        filters,
+        conditions,
+        order_by,
+    }))
 }
 
 fn help() -> ! {
@@ -79,11 +184,37 @@ fn help() -> ! {
              Usage: {} [args]\n\
              \n\
              --name: Name for filter\n\
-             --filter: Can be passed multiple times to combine filters (in order)\n\
+             --filter: Can be passed multiple times to combine filters (in order). Without any\n\
+             \t--filter, every item is shown\n\
+             --condition [filter_id]: Only show this filter in the folders of items that match\n\
+             \tthe given filter, with the item itself as context. Can be passed multiple times\n\
+             --order-by [order]: Order the filter lists its items in, one of name_asc,\n\
+             \tname_desc, created_asc, created_desc\n\
+             --preview: Print the items the filter would match instead of creating it. --name is\n\
+             \tnot required\n\
+             --count: Print how many items the filter would match instead of creating it. --name\n\
+             \tis not required. Cannot be combined with --preview\n\
              \n\
              Filter options:\n\
-             no_relationship [side] [relationship_id]\n\
+             relationship is either a relationship id or one of its names\n\
+             no_relationship [side] [relationship]\n\
              \tShows elements that do not have a relationship where they are on the provided side\n\
+             \tside: [dest, source]\n\
+             has_relationship_with_variable_item [side] [relationship]\n\
+             \tShows elements that are on the provided side of a relationship with the item the\n\
+             \tfilter is viewed from. Such filters are shown in every item folder instead of the\n\
+             \troot\n\
+             \tside: [dest, source]\n\
+             relationship_count [side] [relationship] [op] [count]\n\
+             \tShows elements where the number of relationships they are on the provided side of\n\
+             \tcompares to count\n\
+             \tside: [dest, source]\n\
+             \top: [<, <=, =, >=, >]\n\
+             orphan\n\
+             \tShows elements that are not related to any other element\n\
+             related_to_filter [side] [relationship] [filter_id]\n\
+             \tShows elements that are on the provided side of a relationship with any element\n\
+             \tshown by the given filter\n\
              \tside: [dest, source]\
              ",
         program_name
@@ -92,14 +223,50 @@ fn help() -> ! {
     std::process::exit(1);
 }
 
-fn main() {
-    let filter = match parse_args(std::env::args()) {
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let request = match parse_args(std::env::args()) {
         Ok(v) => v,
         Err(e) => {
             println!("{e}");
             help();
         }
     };
-    let request = ClientRequest::CreateFilter(filter);
-    api::send_client_request(&request);
+
+    match api::send_client_request(&request).map_err(MainError::SendRequest)? {
+        Some(ClientResponse::PreviewFilter(items)) => {
+            for (id, name) in items {
+                println!("{id}\t{name}");
+            }
+        }
+        Some(ClientResponse::CountFilterMatches(count)) => println!("{count}"),
+        Some(_) => return Err(MainError::UnexpectedResponse),
+        None => (),
+    }
+
+    Ok(())
 }
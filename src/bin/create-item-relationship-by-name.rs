@@ -0,0 +1,144 @@
+use std::{error::Error, fmt};
+use todo_fs::fuse::api::{
+    self, ClientRequest, ClientResponse, CreateItemRelationshipByNameRequest,
+    CreateItemRelationshipByNameResponse,
+};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum ArgParseError {
+    #[error("no relationship provided")]
+    NoRelationshipProvided,
+    #[error("no from name provided")]
+    NoFromNameProvided,
+    #[error("no to name provided")]
+    NoToNameProvided,
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateItemRelationshipByNameRequest {
+    let program_name = it
+        .next()
+        .unwrap_or_else(|| "create-item-relationship-by-name".to_string());
+
+    let res = (|| -> Result<CreateItemRelationshipByNameRequest, ArgParseError> {
+        let mut relationship = None;
+        let mut from_name = None;
+        let mut to_name = None;
+        while let Some(arg) = it.next() {
+            match arg.as_ref() {
+                "--relationship" => {
+                    relationship = it.next();
+                }
+                "--from-name" => {
+                    from_name = it.next();
+                }
+                "--to-name" => {
+                    to_name = it.next();
+                }
+                "--help" => {
+                    help(&program_name);
+                }
+                s => return Err(ArgParseError::UnhandledArg(s.to_string())),
+            }
+        }
+
+        Ok(CreateItemRelationshipByNameRequest {
+            relationship: relationship.ok_or(ArgParseError::NoRelationshipProvided)?,
+            from_name: from_name.ok_or(ArgParseError::NoFromNameProvided)?,
+            to_name: to_name.ok_or(ArgParseError::NoToNameProvided)?,
+        })
+    })();
+
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{e}");
+            help(&program_name);
+        }
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Links two items by name. If a name matches several items, the candidate ids are printed\n\
+        and nothing is linked\n\
+        \n\
+        Args:\n\
+        --relationship <relationship id or name>\n\
+        --from-name <item name>\n\
+        --to-name <item name>\n"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let request = parse_args(std::env::args());
+
+    let request = ClientRequest::CreateItemRelationshipByName(request);
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::CreateItemRelationshipByName(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    match response {
+        CreateItemRelationshipByNameResponse::Created { from_id, to_id } => {
+            println!("Linked {from_id} -> {to_id}");
+        }
+        CreateItemRelationshipByNameResponse::RelationshipNotFound => {
+            println!("No matching relationship");
+            std::process::exit(1);
+        }
+        CreateItemRelationshipByNameResponse::ItemNotFound { name } => {
+            println!("No item named {name}");
+            std::process::exit(1);
+        }
+        CreateItemRelationshipByNameResponse::AmbiguousItemName { name, candidates } => {
+            println!("Multiple items named {name}, candidates:");
+            for id in candidates {
+                println!("{id}");
+            }
+            std::process::exit(1);
+        }
+        CreateItemRelationshipByNameResponse::AmbiguousRelationshipName { candidates } => {
+            println!("Multiple relationships match, candidates:");
+            for id in candidates {
+                println!("{id}");
+            }
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
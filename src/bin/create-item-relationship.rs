@@ -1,3 +1,4 @@
+use std::{error::Error, fmt};
 use todo_fs::fuse::api::{self, ClientRequest, CreateItemRelationshipRequest};
 
 use thiserror::Error;
@@ -10,8 +11,8 @@ enum ArgParseError {
     NoFromIdProvided,
     #[error("no to id provided")]
     NoToIdProvided,
-    #[error("failed to parse relationship id")]
-    ParseRelationshipId(#[source] std::num::ParseIntError),
+    #[error("failed to resolve relationship")]
+    ResolveRelationship(#[source] api::ResolveRelationshipError),
     #[error("failed to parse from id")]
     ParseFromId(#[source] std::num::ParseIntError),
     #[error("failed to parse to id")]
@@ -32,7 +33,7 @@ fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateItemRelationship
         while let Some(arg) = it.next() {
             match arg.as_ref() {
                 "--relationship" => {
-                    relationship_id = it.next().map(|x| x.parse::<i64>());
+                    relationship_id = it.next().map(|x| api::resolve_relationship(&x));
                 }
                 "--from" => {
                     from_id = it.next().map(|x| x.parse::<i64>());
@@ -49,7 +50,8 @@ fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateItemRelationship
 
         let relationship_id = relationship_id
             .ok_or(ArgParseError::NoRelationshipProvided)?
-            .map_err(ArgParseError::ParseRelationshipId)?;
+            .map_err(ArgParseError::ResolveRelationship)?
+            .0;
 
         let from_id = from_id
             .ok_or(ArgParseError::NoFromIdProvided)?
@@ -81,7 +83,7 @@ fn help(program_name: &str) -> ! {
         Usage: {program_name} [args]\n\
         \n\
         Args:\n\
-        --relationship <relationship id>\n\
+        --relationship <relationship id or name>\n\
         --from <item id>\n\
         --to <item id>\n"
     );
@@ -89,9 +91,34 @@ fn help(program_name: &str) -> ! {
     std::process::exit(1);
 }
 
-fn main() {
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
     let request = parse_args(std::env::args());
 
     let request = ClientRequest::CreateItemRelationship(request);
-    api::send_client_request(&request);
+    api::send_client_request(&request).map_err(MainError::SendRequest)?;
+
+    Ok(())
 }
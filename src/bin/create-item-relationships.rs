@@ -0,0 +1,178 @@
+use std::{error::Error, fmt, io::BufRead};
+
+use thiserror::Error;
+use todo_fs::fuse::api::{
+    self, ClientRequest, ClientResponse, CreateItemRelationshipRequest,
+    CreateItemRelationshipsRequest, CreateItemRelationshipsResponse, CreatedItemRelationship,
+};
+
+#[derive(Error, Debug)]
+enum ArgParseError {
+    #[error("no relationship provided")]
+    NoRelationshipProvided,
+    #[error("failed to resolve relationship")]
+    ResolveRelationship(#[source] api::ResolveRelationshipError),
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+#[derive(Error, Debug)]
+enum ParseLinkError {
+    #[error("expected \"<from id> <to id>\"")]
+    InvalidFormat,
+    #[error("failed to parse item id")]
+    ParseItemId(#[source] std::num::ParseIntError),
+}
+
+struct Args {
+    relationship_id: i64,
+    abort_on_duplicate: bool,
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> Args {
+    let program_name = it
+        .next()
+        .unwrap_or_else(|| "create-item-relationships".to_string());
+
+    let res = (|| -> Result<Args, ArgParseError> {
+        let mut relationship_id = None;
+        let mut abort_on_duplicate = false;
+        while let Some(arg) = it.next() {
+            match arg.as_ref() {
+                "--relationship" => {
+                    relationship_id = it.next().map(|x| api::resolve_relationship(&x));
+                }
+                "--abort-on-duplicate" => abort_on_duplicate = true,
+                "--help" => {
+                    help(&program_name);
+                }
+                s => return Err(ArgParseError::UnhandledArg(s.to_string())),
+            }
+        }
+
+        let relationship_id = relationship_id
+            .ok_or(ArgParseError::NoRelationshipProvided)?
+            .map_err(ArgParseError::ResolveRelationship)?
+            .0;
+
+        Ok(Args {
+            relationship_id,
+            abort_on_duplicate,
+        })
+    })();
+
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{e}");
+            help(&program_name);
+        }
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args] < links\n\
+        \n\
+        Links the items on every \"<from id> <to id>\" line on stdin in a single batch. Links\n\
+        that already exist are reported and skipped. If any other link fails, none are created\n\
+        \n\
+        Args:\n\
+        --relationship <relationship id or name>\n\
+        --abort-on-duplicate: fail the batch on links that already exist\n"
+    );
+
+    std::process::exit(1);
+}
+
+fn parse_link(line: &str) -> Result<(i64, i64), ParseLinkError> {
+    let mut ids = line.split_whitespace();
+    let (Some(from_id), Some(to_id), None) = (ids.next(), ids.next(), ids.next()) else {
+        return Err(ParseLinkError::InvalidFormat);
+    };
+
+    Ok((
+        from_id.parse().map_err(ParseLinkError::ParseItemId)?,
+        to_id.parse().map_err(ParseLinkError::ParseItemId)?,
+    ))
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let args = parse_args(std::env::args());
+
+    let lines: Vec<String> = std::io::stdin()
+        .lock()
+        .lines()
+        .map(|line| line.expect("failed to read stdin"))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    let mut links = Vec::with_capacity(lines.len());
+    for line in &lines {
+        match parse_link(line) {
+            Ok((from_id, to_id)) => links.push(CreateItemRelationshipRequest {
+                relationship_id: args.relationship_id,
+                from_id,
+                to_id,
+            }),
+            Err(e) => {
+                println!("Invalid link {line:?}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let request = ClientRequest::CreateItemRelationships(CreateItemRelationshipsRequest {
+        links,
+        abort_on_duplicate: args.abort_on_duplicate,
+    });
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::CreateItemRelationships(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    match response {
+        CreateItemRelationshipsResponse::Created(created) => {
+            for (line, created) in lines.iter().zip(created) {
+                if created == CreatedItemRelationship::Duplicate {
+                    println!("Already linked: {line}");
+                }
+            }
+        }
+        CreateItemRelationshipsResponse::Failed { index, reason } => {
+            match index {
+                Some(index) => println!("Failed to link {}: {reason}", lines[index]),
+                None => println!("Failed to create links: {reason}"),
+            }
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::{error::Error, fmt};
+use thiserror::Error;
 use todo_fs::fuse::api::{self, ClientRequest, ClientResponse, CreateItemRequest};
 
 fn get_item_name_from_args<It: Iterator<Item = String>>(mut it: It) -> String {
@@ -35,14 +37,41 @@ fn help(program_name: &str) -> ! {
     std::process::exit(1);
 }
 
-fn main() {
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
     let item_name = get_item_name_from_args(std::env::args());
 
     let request = ClientRequest::CreateItem(CreateItemRequest { name: item_name });
-    let response = api::send_client_request(&request);
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
     let Some(ClientResponse::CreateItem(response)) = response else {
-        panic!("Unexpected response");
+        return Err(MainError::UnexpectedResponse);
     };
 
     println!("{}", response.path.display());
+
+    Ok(())
 }
@@ -0,0 +1,86 @@
+use std::{error::Error, fmt, io::BufRead};
+
+use thiserror::Error;
+use todo_fs::fuse::api::{
+    self, ClientRequest, ClientResponse, CreateItemRequest, CreateItemsResponse,
+};
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} < names\n\
+        \n\
+        Creates an item for every line on stdin in a single batch. If any item fails to be\n\
+        created, none are\n\
+    "
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let mut args = std::env::args();
+    let program_name = args.next().unwrap_or_else(|| "create-items".to_string());
+    if args.next().is_some() {
+        help(&program_name);
+    }
+
+    let names: Vec<String> = std::io::stdin()
+        .lock()
+        .lines()
+        .map(|line| line.expect("failed to read stdin"))
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    let requests = names
+        .iter()
+        .map(|name| CreateItemRequest { name: name.clone() })
+        .collect();
+    let request = ClientRequest::CreateItems(requests);
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::CreateItems(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    match response {
+        CreateItemsResponse::Created(responses) => {
+            for response in responses {
+                println!("{}", response.path.display());
+            }
+        }
+        CreateItemsResponse::Failed { index, reason } => {
+            match index {
+                Some(index) => println!("Failed to create {}: {reason}", names[index]),
+                None => println!("Failed to create items: {reason}"),
+            }
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use std::{error::Error, fmt};
 use thiserror::Error;
 use todo_fs::fuse::api::{self, ClientRequest, ClientResponse, CreateRelationshipRequest};
 
@@ -19,6 +20,7 @@ fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateRelationshipRequ
     let res = (|| -> Result<CreateRelationshipRequest, ArgParseError> {
         let mut from_name = None;
         let mut to_name = None;
+        let mut ensure = false;
         while let Some(arg) = it.next() {
             match arg.as_ref() {
                 "--from" => {
@@ -27,6 +29,9 @@ fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateRelationshipRequ
                 "--to" => {
                     to_name = it.next();
                 }
+                "--ensure" => {
+                    ensure = true;
+                }
                 "--help" => {
                     help(&program_name);
                 }
@@ -38,7 +43,11 @@ fn parse_args<It: Iterator<Item = String>>(mut it: It) -> CreateRelationshipRequ
 
         let to_name = to_name.ok_or(ArgParseError::NoToNameProvided)?;
 
-        Ok(CreateRelationshipRequest { from_name, to_name })
+        Ok(CreateRelationshipRequest {
+            from_name,
+            to_name,
+            ensure,
+        })
     })();
 
     match res {
@@ -57,20 +66,48 @@ fn help(program_name: &str) -> ! {
         \n\
         Args:\n\
         --from <from name>\n\
-        --to <to name>\n"
+        --to <to name>\n\
+        --ensure: Print the existing relationship if one with these names already exists\n"
     );
 
     std::process::exit(1);
 }
 
-fn main() {
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
     let request = parse_args(std::env::args());
 
     let request = ClientRequest::CreateRelationship(request);
-    let response = api::send_client_request(&request);
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
     let Some(ClientResponse::CreateRelationship(response)) = response else {
-        panic!("Unexpected response");
+        return Err(MainError::UnexpectedResponse);
     };
 
     println!("{}", response.path.display());
+
+    Ok(())
 }
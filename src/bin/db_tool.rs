@@ -1,6 +1,8 @@
-use std::{error::Error, fmt, path::PathBuf};
+use std::{error::Error, fmt};
 use thiserror::Error;
-use todo_fs::db::{CreateItemError, Db, ItemId, RelationshipId};
+use todo_fs::db::{
+    CreateItemError, Db, DbLayout, DbLayoutArgError, DbLayoutArgs, ItemId, RelationshipId,
+};
 
 extern crate todo_fs;
 
@@ -8,6 +10,8 @@ extern crate todo_fs;
 enum ArgParseError {
     #[error("db-path not provided")]
     DbPathNotProvided,
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
     #[error("operation name not provided")]
     OperationNotProvided,
     #[error("item name not provided")]
@@ -28,8 +32,24 @@ enum ArgParseError {
     InvalidToId(#[source] std::num::ParseIntError),
     #[error("relationship id invalid")]
     InvalidRelationshipId(#[source] std::num::ParseIntError),
+    #[error("item id not provided")]
+    ItemIdNotProvided,
+    #[error("item id invalid")]
+    InvalidItemId(#[source] std::num::ParseIntError),
+    #[error("id of the item to keep not provided")]
+    KeepIdNotProvided,
+    #[error("id of the item to keep invalid")]
+    InvalidKeepId(#[source] std::num::ParseIntError),
+    #[error("id of the item to merge not provided")]
+    RemoveIdNotProvided,
+    #[error("id of the item to merge invalid")]
+    InvalidRemoveId(#[source] std::num::ParseIntError),
+    #[error("edit mode not provided, expected --swap")]
+    EditModeNotProvided,
     #[error("operation {0} is not a valid operation")]
     InvalidOperation(String),
+    #[error("unknown argument {0}")]
+    UnknownArg(String),
 }
 
 enum Operation {
@@ -47,21 +67,48 @@ enum Operation {
     },
     ListRelationships,
     ListItems,
+    // Swaps the names of a relationship along with the sides of all its links
+    SwapRelationshipSides {
+        relationship_id: i64,
+    },
+    RestoreItem {
+        item_id: i64,
+    },
+    ListTrash,
+    // Moves the links and content of remove over to keep and deletes remove
+    MergeItems {
+        keep_id: i64,
+        remove_id: i64,
+    },
 }
 
 struct Args {
-    db_path: PathBuf,
+    layout: DbLayout,
     operation: Operation,
+    // List operations print one json object per line instead of debug output
+    json: bool,
 }
 
 impl Args {
     fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
         let _program_name = it.next();
-        let db_path = it
-            .next()
-            .map(Into::into)
-            .ok_or(ArgParseError::DbPathNotProvided)?;
-        let operation_name = it.next().ok_or(ArgParseError::OperationNotProvided)?;
+
+        // The database is either the first argument, or given with the usual layout arguments
+        let mut layout_args = DbLayoutArgs::default();
+        let mut arg = it.next().ok_or(ArgParseError::DbPathNotProvided)?;
+        while layout_args
+            .parse_arg(&arg, &mut it)
+            .map_err(ArgParseError::Layout)?
+        {
+            arg = it.next().ok_or(ArgParseError::OperationNotProvided)?;
+        }
+        let operation_name = if layout_args.is_empty() {
+            layout_args.db_path = Some(arg.into());
+            it.next().ok_or(ArgParseError::OperationNotProvided)?
+        } else {
+            arg
+        };
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
 
         let operation = match operation_name.as_ref() {
             "create_item" => {
@@ -97,12 +144,58 @@ impl Args {
                 }
             }
             "list_items" => Operation::ListItems,
+            "restore_item" => {
+                let item_id = it
+                    .next()
+                    .ok_or(ArgParseError::ItemIdNotProvided)?
+                    .parse()
+                    .map_err(ArgParseError::InvalidItemId)?;
+                Operation::RestoreItem { item_id }
+            }
+            "list_trash" => Operation::ListTrash,
+            "merge_items" => {
+                let keep_id = it
+                    .next()
+                    .ok_or(ArgParseError::KeepIdNotProvided)?
+                    .parse()
+                    .map_err(ArgParseError::InvalidKeepId)?;
+                let remove_id = it
+                    .next()
+                    .ok_or(ArgParseError::RemoveIdNotProvided)?
+                    .parse()
+                    .map_err(ArgParseError::InvalidRemoveId)?;
+                Operation::MergeItems { keep_id, remove_id }
+            }
+            "edit_relationship" => {
+                let relationship_id = it
+                    .next()
+                    .ok_or(ArgParseError::RelationshipIdNotProvided)?
+                    .parse()
+                    .map_err(ArgParseError::InvalidRelationshipId)?;
+                match it.next().as_deref() {
+                    Some("--swap") => Operation::SwapRelationshipSides { relationship_id },
+                    Some(arg) => return Err(ArgParseError::UnknownArg(arg.to_string())),
+                    None => return Err(ArgParseError::EditModeNotProvided),
+                }
+            }
             _ => {
                 return Err(ArgParseError::InvalidOperation(operation_name));
             }
         };
 
-        Ok(Args { db_path, operation })
+        let mut json = false;
+        for arg in it {
+            match arg.as_ref() {
+                "--json" => json = true,
+                _ => return Err(ArgParseError::UnknownArg(arg)),
+            }
+        }
+
+        Ok(Args {
+            layout,
+            operation,
+            json,
+        })
     }
 }
 
@@ -122,6 +215,16 @@ enum MainError {
     AddItemRelationship(#[source] todo_fs::db::AddItemRelationshipError),
     #[error("failed to get items")]
     GetItems(#[source] todo_fs::db::GetItemsError),
+    #[error("failed to swap relationship sides")]
+    SwapRelationshipSides(#[source] todo_fs::db::SwapRelationshipSidesError),
+    #[error("failed to restore item")]
+    RestoreItem(#[source] todo_fs::db::RestoreItemError),
+    #[error("failed to get trashed items")]
+    GetTrashedItems(#[source] todo_fs::db::QueryError),
+    #[error("failed to merge items")]
+    MergeItems(#[source] todo_fs::db::MergeItemsError),
+    #[error("failed to write output")]
+    WriteOutput(#[source] std::io::Error),
 }
 
 // main will print the debug implementation, so use that as our user presentable view
@@ -145,7 +248,7 @@ fn main() -> Result<(), MainError> {
     env_logger::init();
 
     let args = Args::parse(std::env::args()).map_err(MainError::ArgParse)?;
-    let mut db = Db::new(args.db_path).map_err(MainError::OpenDb)?;
+    let mut db = Db::with_layout(args.layout).map_err(MainError::OpenDb)?;
     match args.operation {
         Operation::CreateItem { name } => {
             db.create_item(&name).map_err(MainError::CreateItem)?;
@@ -155,11 +258,19 @@ fn main() -> Result<(), MainError> {
                 .map_err(MainError::AddRelationship)?;
         }
         Operation::ListRelationships => {
-            for relationship in db
+            let relationships = db
                 .get_relationships()
-                .map_err(MainError::GetRelationships)?
-            {
-                println!("{:?}", relationship);
+                .map_err(MainError::GetRelationships)?;
+            if args.json {
+                todo_fs::db::write_relationships_json_lines(
+                    std::io::stdout().lock(),
+                    relationships,
+                )
+                .map_err(MainError::WriteOutput)?;
+            } else {
+                for relationship in relationships {
+                    println!("{:?}", relationship);
+                }
             }
         }
         Operation::AddItemRelationship {
@@ -174,10 +285,30 @@ fn main() -> Result<(), MainError> {
             )
             .map_err(MainError::AddItemRelationship)?,
         Operation::ListItems => {
-            for item in db.get_items().map_err(MainError::GetItems)? {
-                println!("{:?}", item);
+            let items = db.get_items().map_err(MainError::GetItems)?;
+            if args.json {
+                todo_fs::db::write_items_json_lines(std::io::stdout().lock(), items)
+                    .map_err(MainError::WriteOutput)?;
+            } else {
+                for item in items {
+                    println!("{:?}", item);
+                }
+            }
+        }
+        Operation::SwapRelationshipSides { relationship_id } => db
+            .swap_relationship_sides(RelationshipId(relationship_id))
+            .map_err(MainError::SwapRelationshipSides)?,
+        Operation::RestoreItem { item_id } => db
+            .restore_item(ItemId(item_id))
+            .map_err(MainError::RestoreItem)?,
+        Operation::ListTrash => {
+            for (id, name) in db.get_trashed_items().map_err(MainError::GetTrashedItems)? {
+                println!("{} {name}", id.0);
             }
         }
+        Operation::MergeItems { keep_id, remove_id } => db
+            .merge_items(ItemId(keep_id), ItemId(remove_id))
+            .map_err(MainError::MergeItems)?,
     }
 
     Ok(())
@@ -1,12 +1,29 @@
+use std::{error::Error, fmt};
+use thiserror::Error;
 use todo_fs::fuse::api::{self, ClientRequest, DeleteItemRequest};
 
-fn get_item_id_from_args<It: Iterator<Item = String>>(mut it: It) -> i64 {
+struct Args {
+    item_id: i64,
+    trash: Option<bool>,
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> Args {
     let program_name = it.next().expect("no program name provided");
 
     let mut item_id = None;
+    let mut trash = None;
     for arg in it {
-        if arg == "--help" {
-            help(&program_name)
+        match arg.as_ref() {
+            "--help" => help(&program_name),
+            "--trash" => {
+                trash = Some(true);
+                continue;
+            }
+            "--purge" => {
+                trash = Some(false);
+                continue;
+            }
+            _ => (),
         }
 
         if item_id.is_some() {
@@ -22,28 +39,62 @@ fn get_item_id_from_args<It: Iterator<Item = String>>(mut it: It) -> i64 {
         help(&program_name)
     };
 
-    match item_id.parse() {
+    let item_id = match item_id.parse() {
         Ok(v) => v,
         Err(e) => {
             println!("Failed to parse item id: {e}");
             help(&program_name);
         }
-    }
+    };
+
+    Args { item_id, trash }
 }
 
 fn help(program_name: &str) -> ! {
     println!(
         "\
-        Usage: {program_name} item_id\n\
+        Usage: {program_name} [--trash | --purge] item_id\n\
+        \n\
+        --trash: Move the item to the trash, where it can be restored from\n\
+        --purge: Delete the item for good\n\
+        Without either the mount's --soft-delete setting decides\n\
     "
     );
 
     std::process::exit(1);
 }
 
-fn main() {
-    let item_id = get_item_id_from_args(std::env::args());
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let args = parse_args(std::env::args());
+
+    let request = ClientRequest::DeleteItem(DeleteItemRequest {
+        id: args.item_id,
+        trash: args.trash,
+    });
+    api::send_client_request(&request).map_err(MainError::SendRequest)?;
 
-    let request = ClientRequest::DeleteItem(DeleteItemRequest { id: item_id });
-    api::send_client_request(&request);
+    Ok(())
 }
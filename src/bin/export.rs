@@ -0,0 +1,96 @@
+use std::{error::Error, fmt, io::Write};
+use thiserror::Error;
+use todo_fs::db::{Db, DbLayout, DbLayoutArgError, DbLayoutArgs, DB_LAYOUT_ARGS_HELP};
+
+#[derive(Debug, Error)]
+enum ArgParseError {
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+struct Args {
+    layout: DbLayout,
+}
+
+impl Args {
+    fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
+        let program_name = it.next().unwrap_or_else(|| "export".to_string());
+
+        let mut layout_args = DbLayoutArgs::default();
+        while let Some(arg) = it.next() {
+            if layout_args
+                .parse_arg(&arg, &mut it)
+                .map_err(ArgParseError::Layout)?
+            {
+                continue;
+            }
+
+            match arg.as_ref() {
+                "--help" => help(&program_name),
+                _ => return Err(ArgParseError::UnhandledArg(arg)),
+            }
+        }
+
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
+
+        Ok(Args { layout })
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Writes the contents of the database to stdout as json\n\
+        \n\
+        Args:\n\
+        {DB_LAYOUT_ARGS_HELP}"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("argument parsing failed")]
+    ArgParse(#[source] ArgParseError),
+    #[error("failed to open database")]
+    OpenDb(#[source] todo_fs::db::OpenDbError),
+    #[error("failed to export database")]
+    Export(#[source] todo_fs::db::ExportError),
+    #[error("failed to write output")]
+    WriteOutput(#[source] std::io::Error),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    env_logger::init();
+
+    let args = Args::parse(std::env::args()).map_err(MainError::ArgParse)?;
+    let mut db = Db::open_readonly_with_layout(args.layout).map_err(MainError::OpenDb)?;
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    db.export_json(&mut output).map_err(MainError::Export)?;
+    writeln!(output).map_err(MainError::WriteOutput)?;
+    output.flush().map_err(MainError::WriteOutput)?;
+
+    Ok(())
+}
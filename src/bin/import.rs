@@ -0,0 +1,105 @@
+use std::{error::Error, fmt};
+use thiserror::Error;
+use todo_fs::db::{Db, DbLayout, DbLayoutArgError, DbLayoutArgs, DB_LAYOUT_ARGS_HELP};
+
+#[derive(Debug, Error)]
+enum ArgParseError {
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+struct Args {
+    layout: DbLayout,
+    force: bool,
+}
+
+impl Args {
+    fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
+        let program_name = it.next().unwrap_or_else(|| "import".to_string());
+
+        let mut layout_args = DbLayoutArgs::default();
+        let mut force = false;
+        while let Some(arg) = it.next() {
+            if layout_args
+                .parse_arg(&arg, &mut it)
+                .map_err(ArgParseError::Layout)?
+            {
+                continue;
+            }
+
+            match arg.as_ref() {
+                "--force" => force = true,
+                "--help" => help(&program_name),
+                _ => return Err(ArgParseError::UnhandledArg(arg)),
+            }
+        }
+
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
+
+        Ok(Args { layout, force })
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Recreates a database from json written by export, read from stdin\n\
+        \n\
+        Args:\n\
+        {DB_LAYOUT_ARGS_HELP}\
+        --force: Import even if the database already has contents\n"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("argument parsing failed")]
+    ArgParse(#[source] ArgParseError),
+    #[error("failed to open database")]
+    OpenDb(#[source] todo_fs::db::OpenDbError),
+    #[error("failed to check if database is empty")]
+    CheckEmpty(#[source] todo_fs::db::QueryError),
+    #[error("database is not empty, pass --force to import anyways")]
+    NotEmpty,
+    #[error("failed to import database")]
+    Import(#[source] todo_fs::db::ImportError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    env_logger::init();
+
+    let args = Args::parse(std::env::args()).map_err(MainError::ArgParse)?;
+    let mut db = Db::with_layout(args.layout).map_err(MainError::OpenDb)?;
+
+    if !args.force && !db.is_empty().map_err(MainError::CheckEmpty)? {
+        return Err(MainError::NotEmpty);
+    }
+
+    db.import_json(std::io::stdin().lock())
+        .map_err(MainError::Import)?;
+
+    Ok(())
+}
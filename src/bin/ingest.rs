@@ -0,0 +1,106 @@
+use std::{error::Error, fmt, path::PathBuf};
+
+use thiserror::Error;
+use todo_fs::fuse::api::{self, ClientRequest, ClientResponse, CreateItemRequest};
+
+struct Args {
+    source: PathBuf,
+    item_name: String,
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> Args {
+    let program_name = it.next().unwrap_or_else(|| "ingest".to_string());
+
+    let mut positional = Vec::new();
+    for arg in it {
+        if arg == "--help" {
+            help(&program_name);
+        }
+        positional.push(arg);
+    }
+
+    let [source, item_name]: [String; 2] = match positional.try_into() {
+        Ok(v) => v,
+        Err(_) => help(&program_name),
+    };
+
+    Args {
+        source: source.into(),
+        item_name,
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} source item_name\n\
+        \n\
+        Creates an item and copies source into its content folder. Directories are copied\n\
+        recursively\n\
+    "
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("{} has no file name", .0.display())]
+    NoFileName(PathBuf),
+    #[error("{} does not exist", .0.display())]
+    SourceMissing(PathBuf),
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+    #[error("failed to find mount root")]
+    FindMountRoot(#[source] std::io::Error),
+    #[error("failed to ingest {}", .0.display())]
+    Ingest(PathBuf, #[source] api::IngestError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let args = parse_args(std::env::args());
+
+    // Checked before the item is created, so that a bad source does not leave an empty item behind
+    if args.source.file_name().is_none() {
+        return Err(MainError::NoFileName(args.source));
+    }
+
+    if !args.source.exists() {
+        return Err(MainError::SourceMissing(args.source));
+    }
+
+    let request = ClientRequest::CreateItem(CreateItemRequest {
+        name: args.item_name,
+    });
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::CreateItem(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    let mount_root = api::mount_root().map_err(MainError::FindMountRoot)?;
+    let dest = api::ingest(&mount_root, &response.path, &args.source)
+        .map_err(|e| MainError::Ingest(args.source, e))?;
+
+    println!("{}", dest.display());
+
+    Ok(())
+}
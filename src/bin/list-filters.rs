@@ -0,0 +1,137 @@
+use std::{error::Error, fmt};
+use thiserror::Error;
+use todo_fs::db::{Db, DbLayout, DbLayoutArgError, DbLayoutArgs, DB_LAYOUT_ARGS_HELP};
+
+#[derive(Debug, Error)]
+enum ArgParseError {
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+struct Args {
+    layout: DbLayout,
+    json: bool,
+}
+
+impl Args {
+    fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
+        let program_name = it.next().unwrap_or_else(|| "list-filters".to_string());
+
+        let mut layout_args = DbLayoutArgs::default();
+        let mut json = false;
+        while let Some(arg) = it.next() {
+            if layout_args
+                .parse_arg(&arg, &mut it)
+                .map_err(ArgParseError::Layout)?
+            {
+                continue;
+            }
+
+            match arg.as_ref() {
+                "--json" => json = true,
+                "--help" => help(&program_name),
+                _ => return Err(ArgParseError::UnhandledArg(arg)),
+            }
+        }
+
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
+
+        Ok(Args { layout, json })
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Lists filters and the rules they are made of\n\
+        \n\
+        Args:\n\
+        {DB_LAYOUT_ARGS_HELP}\
+        --json: Print item filters with their rules and conditions resolved, as json\n"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("argument parsing failed")]
+    ArgParse(#[source] ArgParseError),
+    #[error("failed to open database")]
+    OpenDb(#[source] todo_fs::db::OpenDbError),
+    #[error("failed to get filters")]
+    GetFilters(#[source] todo_fs::db::GetFiltersError),
+    #[error("failed to describe rule")]
+    DescribeRule(#[source] todo_fs::db::QueryError),
+    #[error("failed to get item filter details")]
+    GetItemFilterDetails(#[source] todo_fs::db::GetItemFilterDetailsError),
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    env_logger::init();
+
+    let args = Args::parse(std::env::args()).map_err(MainError::ArgParse)?;
+    let mut db = Db::open_readonly_with_layout(args.layout).map_err(MainError::OpenDb)?;
+
+    if args.json {
+        let details = db
+            .get_item_filter_details()
+            .map_err(MainError::GetItemFilterDetails)?;
+        let output =
+            serde_json::to_string_pretty(&details).expect("filter details are always serializable");
+        println!("{output}");
+        return Ok(());
+    }
+
+    let filters = db.get_filters().map_err(MainError::GetFilters)?;
+
+    for filter in &filters {
+        let kind = if filter.is_item_filter() {
+            "item"
+        } else {
+            "root"
+        };
+        println!("{} (id {}, {kind} filter)", filter.name, filter.id.0);
+
+        if let Some(order) = filter.order {
+            println!("  ordered by {order}");
+        }
+
+        for rule in &filter.rules {
+            let description = db.describe_rule(rule).map_err(MainError::DescribeRule)?;
+            println!("  {description}");
+        }
+
+        for condition in &filter.conditions {
+            let name = filters
+                .iter()
+                .find(|candidate| candidate.id == *condition)
+                .map(|candidate| candidate.name.as_str())
+                .unwrap_or("?");
+            println!("  only for items matching {name} (id {})", condition.0);
+        }
+    }
+
+    Ok(())
+}
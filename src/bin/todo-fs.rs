@@ -1,33 +1,52 @@
-use std::path::PathBuf;
 use thiserror::Error;
-use todo_fs::db::Db;
+use todo_fs::db::{Db, DbLayout, DbLayoutArgError, DbLayoutArgs};
 
 extern crate todo_fs;
 
 #[derive(Debug, Error)]
 enum ArgParseError {
-    #[error("no argument after --db-path")]
-    DbPathArgNotProvided,
-    #[error("--db-path not provided")]
-    DbPathNotProvided,
+    #[error("invalid database location")]
+    Layout(#[source] DbLayoutArgError),
+    #[error("no argument after {0}")]
+    MountOptionArgNotProvided(String),
 }
 
+/// libfuse mount options that have been tried with todo-fs. Anything else is still forwarded, but
+/// may not play well with the file system
+///
+/// * allow_other: let other users access the mount, requires user_allow_other in /etc/fuse.conf
+/// * default_permissions: let the kernel enforce permissions from the reported file modes
+/// * ro: mount read only, this also puts the client in --readonly mode
+const TESTED_MOUNT_OPTIONS: &[&str] = &["allow_other", "default_permissions", "ro"];
+
 struct Args {
-    db_path: PathBuf,
+    layout: DbLayout,
+    mount_options: Vec<String>,
     other_args: Vec<String>,
 }
 
 impl Args {
     fn parse(mut it: impl Iterator<Item = String>) -> Result<Args, ArgParseError> {
-        let mut db_path = None;
+        let mut layout_args = DbLayoutArgs::default();
+        let mut mount_options = Vec::new();
         let mut other_args = Vec::new();
         while let Some(arg) = it.next() {
+            if layout_args
+                .parse_arg(&arg, &mut it)
+                .map_err(ArgParseError::Layout)?
+            {
+                continue;
+            }
+
             match arg.as_ref() {
-                "--db-path" => {
-                    db_path = it
+                "-o" | "--mount-option" => {
+                    let options = it
                         .next()
-                        .map(Into::into)
-                        .ok_or(ArgParseError::DbPathArgNotProvided)?;
+                        .ok_or(ArgParseError::MountOptionArgNotProvided(arg))?;
+                    mount_options.push(options);
+                }
+                _ if arg.starts_with("-o") => {
+                    mount_options.push(arg["-o".len()..].to_string());
                 }
                 _ => {
                     other_args.push(arg);
@@ -35,10 +54,11 @@ impl Args {
             }
         }
 
-        let db_path = db_path.ok_or(ArgParseError::DbPathNotProvided)?.into();
+        let layout = layout_args.into_layout().map_err(ArgParseError::Layout)?;
 
         Ok(Args {
-            db_path,
+            layout,
+            mount_options,
             other_args,
         })
     }
@@ -48,7 +68,38 @@ fn main() {
     env_logger::init();
 
     let args = Args::parse(std::env::args()).expect("failed to parse arguments");
-    let db = Db::new(args.db_path).expect("failed to initialize db");
+    let db = Db::with_layout(args.layout).expect("failed to initialize db");
 
-    todo_fs::fuse::run_fuse_client(db, args.other_args.into_iter());
+    for option in args
+        .mount_options
+        .iter()
+        .flat_map(|options| options.split(','))
+    {
+        let name = option.split('=').next().unwrap_or(option);
+        if !TESTED_MOUNT_OPTIONS.contains(&name) {
+            log::warn!("mount option {name} has not been tested with todo-fs");
+        }
+    }
+
+    // The program name has to stay first for libfuse, options can come before or after the
+    // mountpoint
+    let mut fuse_args = args.other_args.into_iter();
+    let program_name = fuse_args.next();
+    let mount_options = args
+        .mount_options
+        .into_iter()
+        .flat_map(|options| ["-o".to_string(), options]);
+    let fuse_args = program_name
+        .into_iter()
+        .chain(mount_options)
+        .chain(fuse_args);
+
+    let ret = match todo_fs::fuse::run_fuse_client(db, fuse_args) {
+        Ok(ret) => ret,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    };
+    std::process::exit(ret);
 }
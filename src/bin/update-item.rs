@@ -0,0 +1,107 @@
+use std::{error::Error, fmt};
+use todo_fs::fuse::api::{self, ClientRequest, ClientResponse, UpdateItemRequest};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+enum ArgParseError {
+    #[error("no item id provided")]
+    NoIdProvided,
+    #[error("failed to parse item id")]
+    ParseId(#[source] std::num::ParseIntError),
+    #[error("no argument after --name")]
+    NameArgNotProvided,
+    #[error("unhandled argument: {0}")]
+    UnhandledArg(String),
+}
+
+fn parse_args<It: Iterator<Item = String>>(mut it: It) -> UpdateItemRequest {
+    let program_name = it.next().unwrap_or_else(|| "update-item".to_string());
+
+    let res = (|| -> Result<UpdateItemRequest, ArgParseError> {
+        let mut id = None;
+        let mut name = None;
+        while let Some(arg) = it.next() {
+            match arg.as_ref() {
+                "--id" => {
+                    id = it.next().map(|x| x.parse::<i64>());
+                }
+                "--name" => {
+                    name = Some(it.next().ok_or(ArgParseError::NameArgNotProvided)?);
+                }
+                "--help" => {
+                    help(&program_name);
+                }
+                s => return Err(ArgParseError::UnhandledArg(s.to_string())),
+            }
+        }
+
+        let id = id
+            .ok_or(ArgParseError::NoIdProvided)?
+            .map_err(ArgParseError::ParseId)?;
+
+        Ok(UpdateItemRequest { id, name })
+    })();
+
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            println!("{e}");
+            help(&program_name);
+        }
+    }
+}
+
+fn help(program_name: &str) -> ! {
+    println!(
+        "\
+        Usage: {program_name} [args]\n\
+        \n\
+        Updates the provided fields of an item, leaving the rest untouched\n\
+        \n\
+        Args:\n\
+        --id <item id>\n\
+        --name <new name>\n"
+    );
+
+    std::process::exit(1);
+}
+
+#[derive(Error)]
+enum MainError {
+    #[error("failed to send request")]
+    SendRequest(#[source] api::SendRequestError),
+    #[error("unexpected response")]
+    UnexpectedResponse,
+}
+
+// main will print the debug implementation, so use that as our user presentable view
+impl fmt::Debug for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut err: &dyn Error = self;
+
+        writeln!(f, "{}\n", err)?;
+        writeln!(f, "caused by: ")?;
+
+        while let Some(source) = err.source() {
+            err = source;
+            writeln!(f, "{}", source)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), MainError> {
+    let request = parse_args(std::env::args());
+
+    let request = ClientRequest::UpdateItem(request);
+    let response = api::send_client_request(&request).map_err(MainError::SendRequest)?;
+    let Some(ClientResponse::UpdateItem(response)) = response else {
+        return Err(MainError::UnexpectedResponse);
+    };
+
+    println!("{}", response.path.display());
+
+    Ok(())
+}
@@ -1,15 +1,19 @@
 #![allow(unused)]
 
-use rusqlite::Connection;
+use rusqlite::{types::Value, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
     fmt, fs,
     path::{Path, PathBuf},
     str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Hash, Clone, Copy, Debug, Eq, PartialEq)]
 pub struct ItemId(pub i64);
 
 #[derive(Hash, Debug, Clone, Copy, Eq, PartialEq)]
@@ -64,11 +68,127 @@ impl RelationshipSide {
     }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompareOp {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareOp::Less => f.write_str("<"),
+            CompareOp::LessEqual => f.write_str("<="),
+            CompareOp::Equal => f.write_str("="),
+            CompareOp::GreaterEqual => f.write_str(">="),
+            CompareOp::Greater => f.write_str(">"),
+        }
+    }
+}
+
+impl FromStr for CompareOp {
+    type Err = ParseCompareOpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<" => Ok(CompareOp::Less),
+            "<=" => Ok(CompareOp::LessEqual),
+            "=" => Ok(CompareOp::Equal),
+            ">=" => Ok(CompareOp::GreaterEqual),
+            ">" => Ok(CompareOp::Greater),
+            _ => Err(ParseCompareOpError),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("failed to parse comparison operator")]
+pub struct ParseCompareOpError;
+
+/// Order in which a filter lists its items. Filters without one list items in whatever order the
+/// database returns them
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOrder {
+    NameAsc,
+    NameDesc,
+    CreatedAsc,
+    CreatedDesc,
+}
+
+impl FilterOrder {
+    /// Ties, e.g. items created within the same second, are broken by id so the order is stable
+    fn order_by_clause(&self) -> &'static str {
+        match self {
+            FilterOrder::NameAsc => "ORDER BY files.name ASC, files.id ASC",
+            FilterOrder::NameDesc => "ORDER BY files.name DESC, files.id DESC",
+            FilterOrder::CreatedAsc => "ORDER BY files.created_at ASC, files.id ASC",
+            FilterOrder::CreatedDesc => "ORDER BY files.created_at DESC, files.id DESC",
+        }
+    }
+}
+
+impl fmt::Display for FilterOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterOrder::NameAsc => f.write_str("name_asc"),
+            FilterOrder::NameDesc => f.write_str("name_desc"),
+            FilterOrder::CreatedAsc => f.write_str("created_asc"),
+            FilterOrder::CreatedDesc => f.write_str("created_desc"),
+        }
+    }
+}
+
+impl FromStr for FilterOrder {
+    type Err = ParseFilterOrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name_asc" => Ok(FilterOrder::NameAsc),
+            "name_desc" => Ok(FilterOrder::NameDesc),
+            "created_asc" => Ok(FilterOrder::CreatedAsc),
+            "created_desc" => Ok(FilterOrder::CreatedDesc),
+            _ => Err(ParseFilterOrderError),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("failed to parse filter order")]
+pub struct ParseFilterOrderError;
+
 #[derive(Debug)]
 pub struct Relationship {
     pub from_name: String,
     pub to_name: String,
     pub id: RelationshipId,
+    pub description: String,
+    // Unix timestamp, None for relationships created before timestamps were recorded
+    pub created_at: Option<i64>,
+}
+
+/// Names of both ends of a relationship, see [`Db::describe_relationship`]
+#[derive(Debug, Eq, PartialEq)]
+pub struct RelationshipDirection {
+    pub source_name: String,
+    pub dest_name: String,
+}
+
+/// Canonical machine readable form, e.g. "source:parents dest:children"
+impl fmt::Display for RelationshipDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} {}:{}",
+            RelationshipSide::Source,
+            self.source_name,
+            RelationshipSide::Dest,
+            self.dest_name
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -78,8 +198,24 @@ pub struct ItemRelationship {
     pub sibling: ItemId,
 }
 
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum InvalidItemNameError {
+    #[error("item name is empty")]
+    Empty,
+    #[error("item name is . or ..")]
+    DotName,
+    #[error("item name starts or ends with whitespace")]
+    SurroundingWhitespace,
+    #[error("item name contains '/'")]
+    ContainsSlash,
+    #[error("item name contains a nul byte")]
+    ContainsNul,
+}
+
 #[derive(Debug, Error)]
 pub enum CreateItemError {
+    #[error("invalid item name")]
+    InvalidItemName(#[from] InvalidItemNameError),
     #[error("failed to start transaction")]
     StartTransaction(#[source] rusqlite::Error),
     #[error("item already exists")]
@@ -88,25 +224,156 @@ pub enum CreateItemError {
     InsertItem(#[source] rusqlite::Error),
     #[error("failed to create content folder")]
     CreateContentFolder(#[source] std::io::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
     #[error("failed to commit transaction")]
     CommitTransaction(#[source] rusqlite::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum CreateItemsError {
+    #[error("invalid name for item {0}")]
+    InvalidItemName(usize, #[source] InvalidItemNameError),
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("content folder for item {0} already exists")]
+    ItemExists(usize),
+    #[error("failed to insert item {0} into database")]
+    InsertItem(usize, #[source] rusqlite::Error),
+    #[error("failed to create content folder for item {0}")]
+    CreateContentFolder(usize, #[source] std::io::Error),
+    #[error("failed to record event for item {0}")]
+    RecordEvent(usize, #[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+impl CreateItemsError {
+    /// Index of the name that caused the batch to fail, if the failure was specific to one item
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            CreateItemsError::InvalidItemName(index, _)
+            | CreateItemsError::ItemExists(index)
+            | CreateItemsError::InsertItem(index, _)
+            | CreateItemsError::CreateContentFolder(index, _)
+            | CreateItemsError::RecordEvent(index, _) => Some(*index),
+            CreateItemsError::StartTransaction(_) | CreateItemsError::CommitTransaction(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum DeleteItemError {
     #[error("failed to start transaction")]
     StartTransaction(#[source] rusqlite::Error),
     #[error("failed to delete item")]
     DeleteItem(#[source] rusqlite::Error),
-    #[error("failed to delete item relationships")]
-    DeleteItemRelationships(#[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum MergeItemsError {
+    #[error("cannot merge an item into itself")]
+    SameItem,
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to look up items")]
+    FindItems(#[source] rusqlite::Error),
+    #[error("item does not exist")]
+    ItemNotFound,
+    #[error("failed to move item relationships")]
+    RepointItemRelationships(#[source] rusqlite::Error),
+    #[error("failed to delete merged item")]
+    DeleteItem(#[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to merge content folders")]
+    MergeContent(#[source] std::io::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum SetPinnedError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to look up item")]
+    FindItem(#[source] rusqlite::Error),
+    #[error("item does not exist")]
+    ItemNotFound,
+    #[error("failed to update pinned items")]
+    UpdatePinned(#[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum TrashItemError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to mark item as trashed")]
+    UpdateItem(#[source] rusqlite::Error),
+    #[error("item does not exist or is already in the trash")]
+    ItemNotFound,
+    #[error("failed to create trash folder")]
+    CreateTrashFolder(#[source] std::io::Error),
+    #[error("failed to move content folder")]
+    MoveContent(#[source] std::io::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum RestoreItemError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to mark item as restored")]
+    UpdateItem(#[source] rusqlite::Error),
+    #[error("item is not in the trash")]
+    NotInTrash,
+    #[error("failed to move content folder")]
+    MoveContent(#[source] std::io::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum TransactionError<E> {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("operation in transaction failed")]
+    Operation(#[source] E),
+    #[error("failed to roll back transaction")]
+    Rollback(#[source] rusqlite::Error),
     #[error("failed to commit transaction")]
     CommitTransaction(#[source] rusqlite::Error),
 }
+
+#[derive(Debug, Error)]
+pub enum CloseDbError {
+    #[error("failed to checkpoint database")]
+    Checkpoint(#[source] rusqlite::Error),
+    #[error("checkpoint could not complete, the database is still in use")]
+    CheckpointBusy,
+    #[error("failed to close connection")]
+    Close(#[source] rusqlite::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum OpenDbError {
     #[error("failed to create directory for content")]
     CreateFilesDir(#[source] std::io::Error),
+    #[error("failed to create directory for database file")]
+    CreateDbDir(#[source] std::io::Error),
     #[error("failed to open connection with db")]
     OpenConnection(#[source] rusqlite::Error),
     #[error("failed to start transaction")]
@@ -117,14 +384,42 @@ pub enum OpenDbError {
     CreateRelationshipsTable(#[source] rusqlite::Error),
     #[error("failed to create item relationships table")]
     CreateItemRelationshipsTable(#[source] rusqlite::Error),
+    #[error("failed to migrate item relationships table")]
+    MigrateItemRelationshipsTable(#[source] rusqlite::Error),
     #[error("failed to enable foreign key checks")]
     EnableForeignKeys(#[source] rusqlite::Error),
+    #[error("failed to enable write ahead logging")]
+    EnableWal(#[source] rusqlite::Error),
     #[error("failed to commit transactions")]
     CommitTransaction(#[source] rusqlite::Error),
     #[error("failed to create filters table")]
     CreateFiltersTable(#[source] rusqlite::Error),
     #[error("failed to create no relationships filters table")]
     CreateNoRelationshipsFilterTable(#[source] rusqlite::Error),
+    #[error("failed to create variable item filters table")]
+    CreateVariableItemFilterTable(#[source] rusqlite::Error),
+    #[error("failed to create relationship count filters table")]
+    CreateRelationshipCountFilterTable(#[source] rusqlite::Error),
+    #[error("failed to create orphan filters table")]
+    CreateOrphanFilterTable(#[source] rusqlite::Error),
+    #[error("failed to create related to filter filters table")]
+    CreateRelatedToFilterTable(#[source] rusqlite::Error),
+    #[error("failed to create filter conditions table")]
+    CreateFilterConditionsTable(#[source] rusqlite::Error),
+    #[error("failed to create events table")]
+    CreateEventsTable(#[source] rusqlite::Error),
+    #[error("failed to create pinned table")]
+    CreatePinnedTable(#[source] rusqlite::Error),
+    #[error("failed to add column {1} to table {0}")]
+    AddColumn(&'static str, &'static str, #[source] rusqlite::Error),
+    #[error("failed to read schema version")]
+    GetSchemaVersion(#[source] rusqlite::Error),
+    #[error("failed to write schema version")]
+    SetSchemaVersion(#[source] rusqlite::Error),
+    #[error("database schema version {found} is newer than the supported version {supported}")]
+    NewerThanSupported { found: u32, supported: u32 },
+    #[error("database schema version {found} does not match {expected}, open it with write access once to upgrade it")]
+    SchemaVersionMismatch { found: u32, expected: u32 },
 }
 
 #[derive(Debug, Error)]
@@ -133,20 +428,164 @@ pub enum AddRelationshipError {
     FindRelationship(#[source] QueryError),
     #[error("relationship already exists")]
     AlreadyExists(RelationshipId),
+    #[error("relationship name {0} is reserved")]
+    ReservedName(String),
     #[error("failed to start transaction")]
     StartTransaction(#[source] rusqlite::Error),
     #[error("failed to insert relationship")]
     InsertRelationship(#[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum RenameItemError {
+    #[error("invalid item name")]
+    InvalidItemName(#[from] InvalidItemNameError),
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to update item")]
+    UpdateItem(#[source] rusqlite::Error),
+    #[error("item does not exist")]
+    ItemNotFound,
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum RenameRelationshipError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to check if relationship name is in use")]
+    FindRelationship(#[source] QueryError),
+    #[error("relationship name already in use")]
+    AlreadyExists(RelationshipId),
+    #[error("relationship name {0} is reserved")]
+    ReservedName(String),
+    #[error("failed to update relationship")]
+    UpdateRelationship(#[source] rusqlite::Error),
+    #[error("relationship does not exist")]
+    RelationshipNotFound,
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum GetItemFilterDetailsError {
+    #[error("failed to get filters")]
+    GetFilters(#[from] GetFiltersError),
+    #[error("failed to describe rule")]
+    DescribeRule(#[source] QueryError),
+}
+
+#[derive(Debug, Error)]
+pub enum RenameFilterError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to check if filter name is in use")]
+    FindFilter(#[source] rusqlite::Error),
+    #[error("filter name already in use")]
+    AlreadyExists(FilterId),
+    #[error("failed to update filter")]
+    UpdateFilter(#[source] rusqlite::Error),
+    #[error("filter does not exist")]
+    FilterNotFound,
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum SwapRelationshipSidesError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to update relationship")]
+    UpdateRelationship(#[source] rusqlite::Error),
+    #[error("relationship does not exist")]
+    RelationshipNotFound,
+    #[error("failed to get item relationships")]
+    GetItemRelationships(#[source] QueryError),
+    #[error("failed to remove item relationships")]
+    RemoveItemRelationships(#[source] rusqlite::Error),
+    #[error("failed to insert swapped item relationship")]
+    InsertItemRelationship(#[source] rusqlite::Error),
+    #[error("failed to flip sides in {0}")]
+    FlipFilterSides(&'static str, #[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
     #[error("failed to commit transaction")]
     CommitTransaction(#[source] rusqlite::Error),
 }
 
 #[derive(Debug, Error)]
 pub enum AddItemRelationshipError {
+    #[error("item cannot be related to itself")]
+    SelfRelationship,
     #[error("failed to start transaction")]
     StartTransaction(#[source] rusqlite::Error),
     #[error("failed to insert relationship")]
     InsertRelationship(#[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum AddItemRelationshipsError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("link {0} relates an item to itself")]
+    SelfRelationship(usize),
+    #[error("link {0} already exists")]
+    Duplicate(usize),
+    #[error("failed to insert link {0}")]
+    InsertRelationship(usize, #[source] rusqlite::Error),
+    #[error("failed to record event for link {0}")]
+    RecordEvent(usize, #[source] rusqlite::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+impl AddItemRelationshipsError {
+    /// Index of the link that caused the batch to fail, if the failure was specific to one link
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            AddItemRelationshipsError::SelfRelationship(index)
+            | AddItemRelationshipsError::Duplicate(index)
+            | AddItemRelationshipsError::InsertRelationship(index, _)
+            | AddItemRelationshipsError::RecordEvent(index, _) => Some(*index),
+            AddItemRelationshipsError::StartTransaction(_)
+            | AddItemRelationshipsError::CommitTransaction(_) => None,
+        }
+    }
+}
+
+/// What happened to a single link of [`Db::add_item_relationships`]
+#[derive(Debug, Eq, PartialEq)]
+pub enum AddedLink {
+    Added,
+    // The link already existed, either before the batch or earlier in it
+    Duplicate,
+}
+
+#[derive(Debug, Error)]
+pub enum RemoveItemRelationshipError {
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to delete item relationship")]
+    Delete(#[source] rusqlite::Error),
+    #[error("item relationship does not exist")]
+    NotFound,
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
     #[error("failed to commit transaction")]
     CommitTransaction(#[source] rusqlite::Error),
 }
@@ -159,6 +598,14 @@ pub enum AddFilterError {
     InsertFilter(#[source] rusqlite::Error),
     #[error("failed to insert rule")]
     InsertRule(#[source] rusqlite::Error),
+    #[error("failed to insert condition")]
+    InsertCondition(#[source] rusqlite::Error),
+    #[error("failed to load source filter")]
+    LoadFilter(#[source] GetFiltersError),
+    #[error("source filter does not exist")]
+    FilterNotFound,
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
     #[error("failed to commit transaction")]
     CommitTransaction(#[source] rusqlite::Error),
 }
@@ -173,6 +620,22 @@ pub enum QueryError {
     QueryMapFailed(#[source] rusqlite::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum GetSiblingIdError {
+    #[error("failed to query sibling")]
+    Query(#[from] QueryError),
+    #[error("multiple siblings matched the given name")]
+    AmbiguousSibling(Vec<ItemId>),
+}
+
+#[derive(Debug, Error)]
+pub enum GetRelationshipIdByNameError {
+    #[error("failed to query relationship")]
+    Query(#[from] QueryError),
+    #[error("multiple relationships matched the given name")]
+    AmbiguousRelationship(Vec<RelationshipId>),
+}
+
 #[derive(Debug, Error)]
 pub enum GetItemsError {
     #[error("failed to query items")]
@@ -189,57 +652,1098 @@ pub enum GetFiltersError {
     QueryFilters(#[source] QueryError),
     #[error("failed to query rules")]
     QueryRules(#[source] QueryError),
+    #[error("failed to query conditions")]
+    QueryConditions(#[source] QueryError),
     #[error("invalid relationship side")]
     InvalidRelationshipSide(#[source] ParseRelationshipSideError),
+    #[error("invalid comparison operator")]
+    InvalidCompareOp(#[source] ParseCompareOpError),
+    #[error("invalid filter order")]
+    InvalidFilterOrder(#[source] ParseFilterOrderError),
+}
+
+#[derive(Debug, Error)]
+pub enum RunFilterError {
+    #[error("failed to run filter query")]
+    Query(#[from] QueryError),
+    #[error("failed to load filter")]
+    LoadFilter(#[source] GetFiltersError),
+    #[error("failed to load sub filter")]
+    LoadSubFilter(#[source] GetFiltersError),
+    #[error("sub filter {0} does not exist")]
+    SubFilterNotFound(i64),
+    #[error("sub filters nested too deeply at filter {0}, does it reference itself?")]
+    TooDeep(i64),
+}
+
+#[derive(Debug, Error)]
+pub enum RunFiltersIntersectionError {
+    #[error("failed to get filters")]
+    GetFilters(#[source] GetFiltersError),
+    #[error("filter {0} does not exist")]
+    FilterNotFound(i64),
+    #[error("failed to run combined filter")]
+    RunFilter(#[source] RunFilterError),
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("failed to get items")]
+    GetItems(#[source] QueryError),
+    #[error("failed to get relationships")]
+    GetRelationships(#[source] QueryError),
+    #[error("failed to get item relationships")]
+    GetItemRelationships(#[source] QueryError),
+    #[error("failed to get filters")]
+    GetFilters(#[source] GetFiltersError),
+    #[error("failed to serialize export")]
+    Serialize(#[source] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("failed to parse export")]
+    ParseExport(#[source] serde_json::Error),
+    #[error("unsupported export version {0}")]
+    UnsupportedVersion(u32),
+    #[error("failed to start transaction")]
+    StartTransaction(#[source] rusqlite::Error),
+    #[error("failed to insert item {0}")]
+    InsertItem(i64, #[source] rusqlite::Error),
+    #[error("failed to pin item {0}")]
+    PinItem(i64, #[source] rusqlite::Error),
+    #[error("failed to insert relationship {0}")]
+    InsertRelationship(i64, #[source] rusqlite::Error),
+    #[error("failed to insert item relationship {0} -> {1}")]
+    InsertItemRelationship(i64, i64, #[source] rusqlite::Error),
+    #[error("failed to insert filter {0}")]
+    InsertFilter(i64, #[source] rusqlite::Error),
+    #[error("failed to insert rules for filter {0}")]
+    InsertRule(i64, #[source] rusqlite::Error),
+    #[error("failed to insert conditions for filter {0}")]
+    InsertCondition(i64, #[source] rusqlite::Error),
+    #[error("failed to record event")]
+    RecordEvent(#[source] rusqlite::Error),
+    #[error("failed to create content folder")]
+    CreateContentFolder(#[source] std::io::Error),
+    #[error("failed to commit transaction")]
+    CommitTransaction(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("backup destination already contains a database")]
+    DestExists,
+    #[error("failed to create backup directory")]
+    CreateDestDir(#[source] std::io::Error),
+    #[error("failed to back up database")]
+    Backup(#[source] rusqlite::Error),
+    #[error("failed to copy item content")]
+    CopyContent(#[source] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum IntegrityCheckError {
+    #[error("failed to query database")]
+    Query(#[from] QueryError),
+    #[error("failed to list content directories")]
+    ListContentDirs(#[source] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum PruneError {
+    #[error("failed to get item ids")]
+    GetItemIds(#[source] QueryError),
+    #[error("failed to list content directories")]
+    ListContentDirs(#[source] std::io::Error),
+    #[error("failed to remove content directory")]
+    RemoveContentDir(#[source] std::io::Error),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct IntegrityReport {
+    // Problems reported by sqlite's own integrity check
+    pub sqlite_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    // Items in the database that have no content directory
+    pub missing_content_dirs: Vec<ItemId>,
+    // Directories in the content root that do not belong to any item
+    pub orphan_content_dirs: Vec<PathBuf>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        *self == IntegrityReport::default()
+    }
+}
+
+const METADATA_DB_NAME: &str = "metadata.db";
+/// Stored in sqlite's user_version. Bump whenever opening a database would change its schema in a
+/// way that older binaries can not cope with
+pub const SCHEMA_VERSION: u32 = 3;
+const ITEMS_DIR_NAME: &str = "items";
+/// Content folders of trashed items are moved here, inside the content root. The name is not an
+/// id so it is never mistaken for the content folder of an item
+const TRASH_DIR_NAME: &str = ".trash";
+/// Trashed items keep their rows, but are hidden from everything that lists or looks up items
+const TRASHED_ITEM_IDS: &str = "SELECT id FROM files WHERE deleted_at IS NOT NULL";
+/// Every item folder has a fixed entry by this name listing everything the item is related to. A
+/// relationship with the same name would have its folder shadowed by it, so the name is reserved
+pub const ALL_RELATED_FOLDER_NAME: &str = "related";
+/// How many filters deep RelatedToFilter rules are expanded before giving up on a filter that
+/// (indirectly) references itself
+const MAX_SUB_FILTER_DEPTH: usize = 8;
+
+/// Where the pieces of a database live on disk
+#[derive(Debug, Clone)]
+pub struct DbLayout {
+    // sqlite file holding all metadata
+    pub db_path: PathBuf,
+    // directory holding a content folder per item
+    pub content_root: PathBuf,
+}
+
+impl DbLayout {
+    /// Default layout used by [`Db::new`], everything lives in a single directory
+    pub fn in_dir(path: &Path) -> DbLayout {
+        DbLayout {
+            db_path: path.join(METADATA_DB_NAME),
+            content_root: path.join(ITEMS_DIR_NAME),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DbLayoutArgError {
+    #[error("no argument after {0}")]
+    ArgNotProvided(String),
+    #[error("--db-path not provided")]
+    DbPathNotProvided,
+    #[error("--db-file and --content-dir must be provided together")]
+    IncompleteLayout,
+}
+
+/// Help text for the arguments parsed by [`DbLayoutArgs`]
+pub const DB_LAYOUT_ARGS_HELP: &str = "\
+    --db-path <path>\n\
+    --db-file <path> --content-dir <path>: Use a database file and content directory in \
+    separate locations instead of --db-path\n";
+
+/// The --db-path, --db-file and --content-dir arguments of every tool that opens the database
+/// itself
+#[derive(Debug, Default)]
+pub struct DbLayoutArgs {
+    pub db_path: Option<PathBuf>,
+    pub db_file: Option<PathBuf>,
+    pub content_dir: Option<PathBuf>,
+}
+
+impl DbLayoutArgs {
+    /// Consumes arg along with its value from it if arg is one of the layout arguments. Returns
+    /// false for any other argument
+    pub fn parse_arg(
+        &mut self,
+        arg: &str,
+        it: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, DbLayoutArgError> {
+        let value = match arg {
+            "--db-path" => &mut self.db_path,
+            "--db-file" => &mut self.db_file,
+            "--content-dir" => &mut self.content_dir,
+            _ => return Ok(false),
+        };
+
+        let path = it
+            .next()
+            .ok_or_else(|| DbLayoutArgError::ArgNotProvided(arg.to_string()))?;
+        *value = Some(path.into());
+        Ok(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.db_path.is_none() && self.db_file.is_none() && self.content_dir.is_none()
+    }
+
+    /// --db-file and --content-dir override the default layout inside --db-path
+    pub fn into_layout(self) -> Result<DbLayout, DbLayoutArgError> {
+        match (self.db_file, self.content_dir) {
+            (Some(db_path), Some(content_root)) => Ok(DbLayout {
+                db_path,
+                content_root,
+            }),
+            (None, None) => {
+                let path = self.db_path.ok_or(DbLayoutArgError::DbPathNotProvided)?;
+                Ok(DbLayout::in_dir(&path))
+            }
+            _ => Err(DbLayoutArgError::IncompleteLayout),
+        }
+    }
+}
+
+/// A saved filter's query, ready to be run with any context
+#[derive(Debug)]
+struct FilterQuery {
+    sql: String,
 }
 
 #[derive(Debug)]
 pub struct Db {
     item_path: PathBuf,
     connection: Connection,
+    // Queries of saved filters by id, so listing a filter does not reload its rules and rebuild
+    // its sql every time. Anything that changes a filter clears the whole cache, filters can be
+    // built from other filters
+    filter_queries: RefCell<HashMap<FilterId, FilterQuery>>,
+    // How many saved filter queries were built rather than found in filter_queries
+    filter_query_builds: Cell<usize>,
+    // Content folders created in each open Db::with_transaction, innermost last. A rollback
+    // removes them, otherwise the folder would block its id from being handed out again
+    transaction_content_folders: Vec<Vec<PathBuf>>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ItemFilterRule {
     NoRelationship(RelationshipSide, RelationshipId),
+    // Items that are on the given side of a relationship with the item the filter is evaluated
+    // for. e.g. (Dest, children) shows the children of the context item
+    HasRelationshipWithVariableItem(RelationshipSide, RelationshipId),
+    // Items where the number of relationships they are on the given side of compares to the count
+    // with the given operator. e.g. (Source, children, >=, 3) shows items with at least 3 children
+    RelationshipCount(RelationshipSide, RelationshipId, CompareOp, i64),
+    // Items without any relationship to another item, whatever the relationship or side
+    Orphan,
+    // Items that are on the given side of a relationship with any item matched by another filter.
+    // e.g. (Dest, children, todo) shows the children of every item in the todo filter
+    RelatedToFilter(RelationshipSide, RelationshipId, FilterId),
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct FilterId(i64);
+impl ItemFilterRule {
+    pub fn needs_context(&self) -> bool {
+        match self {
+            ItemFilterRule::NoRelationship(_, _)
+            | ItemFilterRule::RelationshipCount(..)
+            | ItemFilterRule::Orphan
+            | ItemFilterRule::RelatedToFilter(..) => false,
+            ItemFilterRule::HasRelationshipWithVariableItem(_, _) => true,
+        }
+    }
+}
+
+/// Human readable rendering of an [`ItemFilterRule`], see [`Db::describe_rule`]
+pub struct RuleDescription<'a> {
+    rule: &'a ItemFilterRule,
+    relationship: Option<Relationship>,
+}
+
+impl fmt::Display for RuleDescription<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Relationships are named as seen from an item on the given side, matching the folder
+        // names in an item's directory
+        let relationship_name =
+            |side: &RelationshipSide, id: &RelationshipId| match (&self.relationship, side) {
+                (Some(relationship), RelationshipSide::Source) => {
+                    format!("'{}'", relationship.to_name)
+                }
+                (Some(relationship), RelationshipSide::Dest) => {
+                    format!("'{}'", relationship.from_name)
+                }
+                (None, _) => format!("'#{}'", id.0),
+            };
+
+        match self.rule {
+            ItemFilterRule::NoRelationship(side, id) => {
+                write!(
+                    f,
+                    "no {} relationship as {side}",
+                    relationship_name(side, id)
+                )
+            }
+            ItemFilterRule::HasRelationshipWithVariableItem(side, id) => {
+                write!(
+                    f,
+                    "{} relationship as {side} with the viewed item",
+                    relationship_name(side, id)
+                )
+            }
+            ItemFilterRule::RelationshipCount(side, id, op, count) => {
+                write!(
+                    f,
+                    "number of {} relationships as {side} {op} {count}",
+                    relationship_name(side, id)
+                )
+            }
+            ItemFilterRule::Orphan => write!(f, "no relationships at all"),
+            ItemFilterRule::RelatedToFilter(side, id, filter_id) => {
+                write!(
+                    f,
+                    "{} relationship as {side} with an item in filter #{}",
+                    relationship_name(side, id),
+                    filter_id.0
+                )
+            }
+        }
+    }
+}
+
+#[derive(Hash, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FilterId(pub i64);
 
 #[derive(Debug)]
 pub struct Filter {
     pub id: FilterId,
     pub name: String,
     pub rules: Vec<ItemFilterRule>,
+    // Filters whose rules an item has to match, with itself as context, for this filter to be
+    // shown in that item's folder
+    pub conditions: Vec<FilterId>,
+    pub order: Option<FilterOrder>,
 }
 
-#[derive(Debug)]
-pub struct DbItem {
-    pub path: PathBuf,
-    pub id: ItemId,
-    pub relationships: Vec<ItemRelationship>,
+impl Filter {
+    /// Filters with rules that reference a variable item can only be evaluated relative to some
+    /// item
+    pub fn needs_context(&self) -> bool {
+        self.rules.iter().any(ItemFilterRule::needs_context)
+    }
+
+    /// Item filters are listed in item folders instead of the root
+    pub fn is_item_filter(&self) -> bool {
+        self.needs_context() || !self.conditions.is_empty()
+    }
+}
+
+/// Version of the format produced by [`Db::export_json`]. Bump whenever the layout of [`DbExport`]
+/// changes in a way that older readers would misinterpret
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ExportedItem {
+    pub id: i64,
     pub name: String,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    // Set for items in the trash
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+    #[serde(default)]
+    pub pinned: bool,
 }
 
-impl Db {
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ExportedRelationship {
+    pub id: i64,
+    pub from_name: String,
+    pub to_name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ExportedItemRelationship {
+    pub from_id: i64,
+    pub to_id: i64,
+    pub relationship_id: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ExportedFilter {
+    pub id: i64,
+    pub name: String,
+    pub rules: Vec<ItemFilterRule>,
+    #[serde(default)]
+    pub conditions: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_by: Option<FilterOrder>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct DbExport {
+    pub version: u32,
+    pub items: Vec<ExportedItem>,
+    pub relationships: Vec<ExportedRelationship>,
+    pub item_relationships: Vec<ExportedItemRelationship>,
+    pub filters: Vec<ExportedFilter>,
+}
+
+#[derive(Serialize)]
+struct JsonItemRelationship {
+    id: i64,
+    side: String,
+    sibling: i64,
+}
+
+#[derive(Serialize)]
+struct JsonItem {
+    id: i64,
+    name: String,
+    relationships: Vec<JsonItemRelationship>,
+}
+
+impl From<DbItem> for JsonItem {
+    fn from(item: DbItem) -> JsonItem {
+        JsonItem {
+            id: item.id.0,
+            name: item.name,
+            relationships: item
+                .relationships
+                .into_iter()
+                .map(|relationship| JsonItemRelationship {
+                    id: relationship.id.0,
+                    side: relationship.side.to_string(),
+                    sibling: relationship.sibling.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRelationship {
+    id: i64,
+    from_name: String,
+    to_name: String,
+}
+
+impl From<Relationship> for JsonRelationship {
+    fn from(relationship: Relationship) -> JsonRelationship {
+        JsonRelationship {
+            id: relationship.id.0,
+            from_name: relationship.from_name,
+            to_name: relationship.to_name,
+        }
+    }
+}
+
+fn write_json_line(
+    writer: &mut impl std::io::Write,
+    value: &impl Serialize,
+) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")
+}
+
+/// One json object per line with the item's id, name and relationships, for scripts that consume
+/// db_tool's output
+pub fn write_items_json_lines(
+    mut writer: impl std::io::Write,
+    items: impl IntoIterator<Item = DbItem>,
+) -> std::io::Result<()> {
+    for item in items {
+        write_json_line(&mut writer, &JsonItem::from(item))?;
+    }
+    Ok(())
+}
+
+/// One json object per line with the relationship's id and names, see [`write_items_json_lines`]
+pub fn write_relationships_json_lines(
+    mut writer: impl std::io::Write,
+    relationships: impl IntoIterator<Item = Relationship>,
+) -> std::io::Result<()> {
+    for relationship in relationships {
+        write_json_line(&mut writer, &JsonRelationship::from(relationship))?;
+    }
+    Ok(())
+}
+
+/// A section of [`Db::export_json`] serialized straight from the rows of a query, so the rows are
+/// never all in memory. Database errors cannot travel through serde, they are stored in error and
+/// replaced with a generic serde error
+struct ExportedRows<'a, F> {
+    connection: &'a Connection,
+    query: &'static str,
+    map_row: F,
+    to_error: fn(QueryError) -> ExportError,
+    error: &'a RefCell<Option<ExportError>>,
+}
+
+impl<T, F> Serialize for ExportedRows<'_, F>
+where
+    T: Serialize,
+    F: Fn(&rusqlite::Row) -> rusqlite::Result<T>,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeSeq};
+
+        let fail = |e| {
+            *self.error.borrow_mut() = Some((self.to_error)(e));
+            S::Error::custom("failed to query database")
+        };
+
+        let mut statement = self
+            .connection
+            .prepare(self.query)
+            .map_err(|e| fail(QueryError::Prepare(e)))?;
+        let rows = statement
+            .query_map([], &self.map_row)
+            .map_err(|e| fail(QueryError::Execute(e)))?;
+
+        let mut seq = serializer.serialize_seq(None)?;
+        for row in rows {
+            let row = row.map_err(|e| fail(QueryError::QueryMapFailed(e)))?;
+            seq.serialize_element(&row)?;
+        }
+        seq.end()
+    }
+}
+
+/// An item filter with the relationships and filters it references resolved to names, for tools
+/// that display filters
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ItemFilterDetails {
+    pub id: i64,
+    pub name: String,
+    pub rules: Vec<ItemFilterRuleDetails>,
+    pub conditions: Vec<FilterConditionDetails>,
+    pub order_by: Option<FilterOrder>,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ItemFilterRuleDetails {
+    pub rule: ItemFilterRule,
+    pub description: String,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct FilterConditionDetails {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct DbItem {
+    pub path: PathBuf,
+    pub id: ItemId,
+    pub relationships: Vec<ItemRelationship>,
+    pub name: String,
+    // Unix timestamp, None for items created before timestamps were recorded
+    pub created_at: Option<i64>,
+}
+
+fn insert_filter_rules(
+    transaction: &Connection,
+    filter_id: i64,
+    rules: &[ItemFilterRule],
+) -> Result<(), rusqlite::Error> {
+    for rule in rules {
+        match rule {
+            ItemFilterRule::NoRelationship(side, relationship_id) => {
+                transaction.execute("INSERT INTO no_relationship_filters(filter_id, side, relationship_id) VALUES (?1, ?2, ?3)", [filter_id, side.as_i64(), relationship_id.0])?;
+            }
+            ItemFilterRule::HasRelationshipWithVariableItem(side, relationship_id) => {
+                transaction.execute("INSERT INTO variable_item_filters(filter_id, side, relationship_id) VALUES (?1, ?2, ?3)", [filter_id, side.as_i64(), relationship_id.0])?;
+            }
+            ItemFilterRule::RelationshipCount(side, relationship_id, op, count) => {
+                transaction.execute("INSERT INTO relationship_count_filters(filter_id, side, relationship_id, op, count) VALUES (?1, ?2, ?3, ?4, ?5)", rusqlite::params![filter_id, side.as_i64(), relationship_id.0, op.to_string(), count])?;
+            }
+            ItemFilterRule::Orphan => {
+                transaction.execute(
+                    "INSERT INTO orphan_filters(filter_id) VALUES (?1)",
+                    [filter_id],
+                )?;
+            }
+            ItemFilterRule::RelatedToFilter(side, relationship_id, sub_filter_id) => {
+                transaction.execute("INSERT INTO related_to_filter_filters(filter_id, side, relationship_id, sub_filter_id) VALUES (?1, ?2, ?3, ?4)", [filter_id, side.as_i64(), relationship_id.0, sub_filter_id.0])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Values to bind to a filter query's statement, the context is ?1. Queries without variable item
+/// rules never reference ?1, and a statement only takes as many parameters as it references
+fn filter_query_params(
+    context: Option<ItemId>,
+    parameter_count: usize,
+) -> impl Iterator<Item = Value> {
+    let context = context.map_or(Value::Null, |context| Value::Integer(context.0));
+    std::iter::once(context).take(parameter_count)
+}
+
+/// Reads the rules, conditions and order of a filter whose id and name have already been loaded
+fn load_filter_rules(
+    transaction: &rusqlite::Connection,
+    filter: &mut Filter,
+) -> Result<(), GetFiltersError> {
+    type MakeRule = fn(RelationshipSide, RelationshipId) -> ItemFilterRule;
+    let rule_tables: [(&str, MakeRule); 2] = [
+        ("no_relationship_filters", ItemFilterRule::NoRelationship),
+        (
+            "variable_item_filters",
+            ItemFilterRule::HasRelationshipWithVariableItem,
+        ),
+    ];
+
+    let mut rules = Vec::new();
+
+    for (table, make_rule) in rule_tables {
+        let mut statement = transaction
+            .prepare(&format!(
+                "SELECT side, relationship_id FROM {table} WHERE filter_id = ?1"
+            ))
+            .map_err(QueryError::Prepare)
+            .map_err(GetFiltersError::QueryRules)?;
+
+        let mut query = statement
+            .query([filter.id.0])
+            .map_err(QueryError::Execute)
+            .map_err(GetFiltersError::QueryRules)?;
+
+        while let Some(row) = query
+            .next()
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?
+        {
+            let side: i64 = row
+                .get(0)
+                .map_err(QueryError::QueryMapFailed)
+                .map_err(GetFiltersError::QueryRules)?;
+            let side = RelationshipSide::from_i64(side)
+                .map_err(GetFiltersError::InvalidRelationshipSide)?;
+
+            let relationship_id: i64 = row
+                .get(1)
+                .map_err(QueryError::QueryMapFailed)
+                .map_err(GetFiltersError::QueryRules)?;
+            let relationship_id = RelationshipId(relationship_id);
+            rules.push(make_rule(side, relationship_id));
+        }
+    }
+
+    let mut statement = transaction
+        .prepare("SELECT side, relationship_id, op, count FROM relationship_count_filters WHERE filter_id = ?1")
+        .map_err(QueryError::Prepare)
+        .map_err(GetFiltersError::QueryRules)?;
+
+    let mut query = statement
+        .query([filter.id.0])
+        .map_err(QueryError::Execute)
+        .map_err(GetFiltersError::QueryRules)?;
+
+    while let Some(row) = query
+        .next()
+        .map_err(QueryError::QueryMapFailed)
+        .map_err(GetFiltersError::QueryRules)?
+    {
+        let side: i64 = row
+            .get(0)
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?;
+        let side =
+            RelationshipSide::from_i64(side).map_err(GetFiltersError::InvalidRelationshipSide)?;
+
+        let relationship_id: i64 = row
+            .get(1)
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?;
+
+        let op: String = row
+            .get(2)
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?;
+        let op = op.parse().map_err(GetFiltersError::InvalidCompareOp)?;
+
+        let count: i64 = row
+            .get(3)
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?;
+
+        rules.push(ItemFilterRule::RelationshipCount(
+            side,
+            RelationshipId(relationship_id),
+            op,
+            count,
+        ));
+    }
+
+    let is_orphan_filter = transaction
+        .prepare("SELECT 1 FROM orphan_filters WHERE filter_id = ?1")
+        .map_err(QueryError::Prepare)
+        .map_err(GetFiltersError::QueryRules)?
+        .exists([filter.id.0])
+        .map_err(QueryError::Execute)
+        .map_err(GetFiltersError::QueryRules)?;
+
+    if is_orphan_filter {
+        rules.push(ItemFilterRule::Orphan);
+    }
+
+    let mut statement = transaction
+        .prepare("SELECT side, relationship_id, sub_filter_id FROM related_to_filter_filters WHERE filter_id = ?1")
+        .map_err(QueryError::Prepare)
+        .map_err(GetFiltersError::QueryRules)?;
+
+    let related_to_filter_rows = statement
+        .query_map([filter.id.0], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .map_err(QueryError::Execute)
+        .map_err(GetFiltersError::QueryRules)?;
+
+    for row in related_to_filter_rows {
+        let (side, relationship_id, sub_filter_id) = row
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryRules)?;
+        let side =
+            RelationshipSide::from_i64(side).map_err(GetFiltersError::InvalidRelationshipSide)?;
+        rules.push(ItemFilterRule::RelatedToFilter(
+            side,
+            RelationshipId(relationship_id),
+            FilterId(sub_filter_id),
+        ));
+    }
+
+    filter.rules = rules;
+
+    let order: Option<String> = transaction
+        .query_row(
+            "SELECT order_by FROM filters WHERE id = ?1",
+            [filter.id.0],
+            |row| row.get(0),
+        )
+        .map_err(QueryError::Execute)
+        .map_err(GetFiltersError::QueryFilters)?;
+    filter.order = order
+        .map(|order| order.parse())
+        .transpose()
+        .map_err(GetFiltersError::InvalidFilterOrder)?;
+
+    let mut statement = transaction
+        .prepare("SELECT condition_filter_id FROM filter_conditions WHERE filter_id = ?1")
+        .map_err(QueryError::Prepare)
+        .map_err(GetFiltersError::QueryConditions)?;
+
+    let conditions = statement
+        .query_map([filter.id.0], |row| row.get::<_, i64>(0))
+        .map_err(QueryError::Execute)
+        .map_err(GetFiltersError::QueryConditions)?;
+
+    for condition in conditions {
+        let condition = condition
+            .map_err(QueryError::QueryMapFailed)
+            .map_err(GetFiltersError::QueryConditions)?;
+        filter.conditions.push(FilterId(condition));
+    }
+
+    Ok(())
+}
+
+fn insert_filter_conditions(
+    transaction: &Connection,
+    filter_id: i64,
+    conditions: &[i64],
+) -> Result<(), rusqlite::Error> {
+    for condition_id in conditions {
+        transaction.execute(
+            "INSERT INTO filter_conditions(filter_id, condition_filter_id) VALUES (?1, ?2)",
+            [filter_id, *condition_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn add_column_if_missing(
+    transaction: &rusqlite::Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), rusqlite::Error> {
+    let exists = transaction
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))?
+        .exists([column])?;
+
+    if !exists {
+        transaction.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+            (),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks that a name can be shown as a file name in the items-by-name folder. Surrounding
+/// whitespace is rejected rather than trimmed, so the name that is stored is always the one that
+/// was asked for. Names do not have to be unique, items that share a name are told apart by id
+fn validate_item_name(name: &str) -> Result<(), InvalidItemNameError> {
+    if name.is_empty() {
+        return Err(InvalidItemNameError::Empty);
+    }
+
+    if name == "." || name == ".." {
+        return Err(InvalidItemNameError::DotName);
+    }
+
+    if name.trim() != name {
+        return Err(InvalidItemNameError::SurroundingWhitespace);
+    }
+
+    if name.contains('/') {
+        return Err(InvalidItemNameError::ContainsSlash);
+    }
+
+    if name.contains('\0') {
+        return Err(InvalidItemNameError::ContainsNul);
+    }
+
+    Ok(())
+}
+
+fn unix_timestamp_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let dest_path = dest.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir_recursive(&entry.path(), &dest_path)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, dest_path)?;
+        } else {
+            fs::copy(entry.path(), dest_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Entry of the audit log, see [`Db::get_events`]
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub id: i64,
+    pub ts: i64,
+    pub kind: String,
+    pub detail: serde_json::Value,
+}
+
+/// Appends to the audit log. Callers pass the transaction of the change being recorded so that
+/// the event is rolled back along with it
+fn record_event(
+    connection: &Connection,
+    kind: &str,
+    detail: serde_json::Value,
+) -> Result<(), rusqlite::Error> {
+    connection.execute(
+        "INSERT INTO events(ts, kind, detail) VALUES (?1, ?2, ?3)",
+        rusqlite::params![unix_timestamp_now(), kind, detail.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Moves a content folder in or out of the trash. A folder that is already missing has nothing to
+/// move, restore_content_folder recreates it when needed
+fn move_content_folder(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        ret => ret,
+    }
+}
+
+/// Picks a path in dir for an entry called name that does not clobber anything already there.
+/// Conflicting names get a number appended to the stem, notes.txt becomes notes-1.txt
+fn free_merge_target(dir: &Path, name: &OsStr) -> PathBuf {
+    let candidate = dir.join(name);
+    if candidate.symlink_metadata().is_err() {
+        return candidate;
+    }
+
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or(name.as_os_str());
+    (1..)
+        .map(|n| {
+            let mut new_name = stem.to_os_string();
+            new_name.push(format!("-{n}"));
+            if let Some(extension) = name.extension() {
+                new_name.push(".");
+                new_name.push(extension);
+            }
+            dir.join(new_name)
+        })
+        .find(|path| path.symlink_metadata().is_err())
+        .expect("ran out of suffixes")
+}
+
+/// Moves everything in from into into, renaming entries that would overwrite existing ones, then
+/// removes from. Returns the moves made so they can be undone with [`undo_content_merge`], on
+/// failure the moves made so far are undone already
+fn merge_content_folder(
+    from: &Path,
+    into: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, std::io::Error> {
+    let entries = match fs::read_dir(from) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        ret => ret?,
+    };
+
+    let mut moves = Vec::new();
+    let ret = (|| {
+        fs::create_dir_all(into)?;
+        for entry in entries {
+            let entry = entry?;
+            let target = free_merge_target(into, &entry.file_name());
+            fs::rename(entry.path(), &target)?;
+            moves.push((entry.path(), target));
+        }
+
+        fs::remove_dir(from)
+    })();
+
+    if let Err(e) = ret {
+        undo_content_merge(from, &moves);
+        return Err(e);
+    }
+
+    Ok(moves)
+}
+
+/// Puts everything moved by [`merge_content_folder`] back where it came from. Failures are only
+/// logged, as this already runs on an error path
+fn undo_content_merge(from: &Path, moves: &[(PathBuf, PathBuf)]) {
+    if let Err(e) = fs::create_dir_all(from) {
+        log::error!("failed to recreate {}: {e}", from.display());
+        return;
+    }
+
+    for (source, target) in moves.iter().rev() {
+        if let Err(e) = fs::rename(target, source) {
+            log::error!(
+                "failed to move {} back to {}: {e}",
+                target.display(),
+                source.display()
+            );
+        }
+    }
+}
+
+/// Escapes the LIKE wildcards in s for use with ESCAPE '\\'
+fn escape_like(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+fn escape_dot_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Db {
     pub fn new(path: PathBuf) -> Result<Db, OpenDbError> {
         if !path.exists() {
             fs::create_dir_all(&path).map_err(OpenDbError::CreateFilesDir)?;
         }
 
-        let sqlite_path = path.join("metadata.db");
-        let mut connection = Connection::open(sqlite_path).map_err(OpenDbError::OpenConnection)?;
+        Db::with_layout(DbLayout::in_dir(&path))
+    }
+
+    /// Opens an existing database without write access. Nothing is created or upgraded, so the
+    /// database has to already be at [`SCHEMA_VERSION`]. Every method that modifies the database
+    /// fails
+    pub fn open_readonly(path: PathBuf) -> Result<Db, OpenDbError> {
+        Db::open_readonly_with_layout(DbLayout::in_dir(&path))
+    }
+
+    /// [`Db::open_readonly`] for a database that is not laid out in a single directory
+    pub fn open_readonly_with_layout(layout: DbLayout) -> Result<Db, OpenDbError> {
+        let connection = Connection::open_with_flags(
+            &layout.db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(OpenDbError::OpenConnection)?;
+
+        let found: u32 = connection
+            .query_row("PRAGMA user_version", (), |row| row.get(0))
+            .map_err(OpenDbError::GetSchemaVersion)?;
+        if found != SCHEMA_VERSION {
+            return Err(OpenDbError::SchemaVersionMismatch {
+                found,
+                expected: SCHEMA_VERSION,
+            });
+        }
+
+        Ok(Db {
+            item_path: layout.content_root,
+            connection,
+            filter_queries: Default::default(),
+            filter_query_builds: Default::default(),
+            transaction_content_folders: Vec::new(),
+        })
+    }
+
+    pub fn with_layout(layout: DbLayout) -> Result<Db, OpenDbError> {
+        if let Some(parent) = layout.db_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent).map_err(OpenDbError::CreateDbDir)?;
+            }
+        }
+
+        let mut connection =
+            Connection::open(&layout.db_path).map_err(OpenDbError::OpenConnection)?;
 
         // NOTE: cannot enable foreign keys on transaction
         connection
             .execute("PRAGMA foreign_keys = ON", ())
             .map_err(OpenDbError::EnableForeignKeys)?;
 
+        // Persists in the database file, Db::close checkpoints the log back into it. Like foreign
+        // keys this cannot be changed inside a transaction
+        connection
+            .query_row("PRAGMA journal_mode = WAL", (), |_| Ok(()))
+            .map_err(OpenDbError::EnableWal)?;
+
         let transaction = connection
             .transaction()
             .map_err(OpenDbError::StartTransaction)?;
 
+        // A database written by a newer binary may rely on schema changes we know nothing about,
+        // refuse it before touching anything
+        let found: u32 = transaction
+            .query_row("PRAGMA user_version", (), |row| row.get(0))
+            .map_err(OpenDbError::GetSchemaVersion)?;
+        if found > SCHEMA_VERSION {
+            return Err(OpenDbError::NewerThanSupported {
+                found,
+                supported: SCHEMA_VERSION,
+            });
+        }
+
         transaction
             .execute(
                 "CREATE TABLE IF NOT EXISTS files(id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL)",
@@ -254,6 +1758,21 @@ impl Db {
             )
             .map_err(OpenDbError::CreateRelationshipsTable)?;
 
+        // Columns added after the initial schema, databases created before them need the
+        // columns added
+        let added_columns = [
+            ("files", "created_at", "INTEGER"),
+            // Set while an item is in the trash, see Db::trash_item
+            ("files", "deleted_at", "INTEGER"),
+            ("relationships", "description", "TEXT NOT NULL DEFAULT ''"),
+            ("relationships", "created_at", "INTEGER"),
+        ];
+
+        for (table, column, definition) in added_columns {
+            add_column_if_missing(&transaction, table, column, definition)
+                .map_err(|e| OpenDbError::AddColumn(table, column, e))?;
+        }
+
         transaction
             .execute(
                 "CREATE TABLE IF NOT EXISTS filters(id INTEGER PRIMARY KEY, name TEXT_NOT_NULL)",
@@ -261,6 +1780,9 @@ impl Db {
             )
             .map_err(OpenDbError::CreateFiltersTable)?;
 
+        add_column_if_missing(&transaction, "filters", "order_by", "TEXT")
+            .map_err(|e| OpenDbError::AddColumn("filters", "order_by", e))?;
+
         transaction
             .execute(
                 "CREATE TABLE IF NOT EXISTS no_relationship_filters(filter_id INTEGER, side INTEGER, relationship_id INTEGER,
@@ -271,81 +1793,520 @@ impl Db {
             )
             .map_err(OpenDbError::CreateNoRelationshipsFilterTable)?;
 
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS variable_item_filters(filter_id INTEGER, side INTEGER, relationship_id INTEGER,
+                FOREIGN KEY(filter_id) REFERENCES filters(id),
+                FOREIGN KEY(relationship_id) REFERENCES relationships(id),
+                UNIQUE(filter_id, side, relationship_id))",
+                (),
+            )
+            .map_err(OpenDbError::CreateVariableItemFilterTable)?;
+
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS relationship_count_filters(filter_id INTEGER, side INTEGER, relationship_id INTEGER, op TEXT NOT NULL, count INTEGER NOT NULL,
+                FOREIGN KEY(filter_id) REFERENCES filters(id),
+                FOREIGN KEY(relationship_id) REFERENCES relationships(id),
+                UNIQUE(filter_id, side, relationship_id, op, count))",
+                (),
+            )
+            .map_err(OpenDbError::CreateRelationshipCountFilterTable)?;
+
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS orphan_filters(filter_id INTEGER,
+                FOREIGN KEY(filter_id) REFERENCES filters(id))",
+                (),
+            )
+            .map_err(OpenDbError::CreateOrphanFilterTable)?;
+
+        // The sub filter reference is deferred so an import can insert filters in any order
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS related_to_filter_filters(filter_id INTEGER, side INTEGER, relationship_id INTEGER, sub_filter_id INTEGER,
+                FOREIGN KEY(filter_id) REFERENCES filters(id),
+                FOREIGN KEY(relationship_id) REFERENCES relationships(id),
+                FOREIGN KEY(sub_filter_id) REFERENCES filters(id) DEFERRABLE INITIALLY DEFERRED)",
+                (),
+            )
+            .map_err(OpenDbError::CreateRelatedToFilterTable)?;
+
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS filter_conditions(filter_id INTEGER, condition_filter_id INTEGER,
+                FOREIGN KEY(filter_id) REFERENCES filters(id),
+                FOREIGN KEY(condition_filter_id) REFERENCES filters(id),
+                UNIQUE(filter_id, condition_filter_id))",
+                (),
+            )
+            .map_err(OpenDbError::CreateFilterConditionsTable)?;
+
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS events(id INTEGER PRIMARY KEY, ts INTEGER, kind TEXT, detail TEXT)",
+                (),
+            )
+            .map_err(OpenDbError::CreateEventsTable)?;
+
+        transaction
+            .execute(
+                "CREATE TABLE IF NOT EXISTS pinned(item_id INTEGER PRIMARY KEY,
+                FOREIGN KEY(item_id) REFERENCES files(id) ON DELETE CASCADE)",
+                (),
+            )
+            .map_err(OpenDbError::CreatePinnedTable)?;
+
+        // Older databases did not cascade item deletion to item relationships. SQLite cannot alter
+        // foreign keys in place, so the table is recreated with its existing rows
+        let needs_cascade = transaction
+            .prepare("SELECT 1 FROM pragma_foreign_key_list('item_relationships') WHERE \"table\" = 'files' AND on_delete != 'CASCADE'")
+            .and_then(|mut statement| statement.exists(()))
+            .map_err(OpenDbError::MigrateItemRelationshipsTable)?;
+
+        if needs_cascade {
+            transaction
+                .execute(
+                    "ALTER TABLE item_relationships RENAME TO item_relationships_old",
+                    (),
+                )
+                .map_err(OpenDbError::MigrateItemRelationshipsTable)?;
+        }
+
         transaction
             .execute(
                 "CREATE TABLE IF NOT EXISTS item_relationships(from_id INTEGER, to_id INTEGER, relationship_id INTEGER,
-                FOREIGN KEY(from_id) REFERENCES files(id),
-                FOREIGN KEY(to_id) REFERENCES files(id),
+                FOREIGN KEY(from_id) REFERENCES files(id) ON DELETE CASCADE,
+                FOREIGN KEY(to_id) REFERENCES files(id) ON DELETE CASCADE,
                 FOREIGN KEY(relationship_id) REFERENCES relationships(id),
                 UNIQUE(from_id, to_id, relationship_id))",
                 (),
             )
             .map_err(OpenDbError::CreateItemRelationshipsTable)?;
 
+        if needs_cascade {
+            transaction
+                .execute(
+                    "INSERT INTO item_relationships(from_id, to_id, relationship_id) SELECT from_id, to_id, relationship_id FROM item_relationships_old",
+                    (),
+                )
+                .map_err(OpenDbError::MigrateItemRelationshipsTable)?;
+            transaction
+                .execute("DROP TABLE item_relationships_old", ())
+                .map_err(OpenDbError::MigrateItemRelationshipsTable)?;
+        }
+
+        transaction
+            .pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(OpenDbError::SetSchemaVersion)?;
+
         transaction
             .commit()
             .map_err(OpenDbError::CommitTransaction)?;
-        let item_path = path.join("items");
         Ok(Db {
-            item_path,
+            item_path: layout.content_root,
             connection,
+            filter_queries: Default::default(),
+            filter_query_builds: Default::default(),
+            transaction_content_folders: Vec::new(),
         })
     }
 
     pub fn create_item(&mut self, name: &str) -> Result<ItemId, CreateItemError> {
+        validate_item_name(name)?;
         let transaction = self
             .connection
-            .transaction()
+            .savepoint()
             .map_err(CreateItemError::StartTransaction)?;
         transaction
-            .execute("INSERT INTO files(name) VALUES (?1)", [name])
-            .map_err(CreateItemError::InsertItem);
+            .execute(
+                "INSERT INTO files(name, created_at) VALUES (?1, ?2)",
+                rusqlite::params![name, unix_timestamp_now()],
+            )
+            .map_err(CreateItemError::InsertItem)?;
         let id = transaction.last_insert_rowid();
+        record_event(
+            &transaction,
+            "item_created",
+            serde_json::json!({ "id": id, "name": name }),
+        )
+        .map_err(CreateItemError::RecordEvent)?;
 
         let item_path = self.item_path.join(id.to_string());
         if item_path.exists() {
             return Err(CreateItemError::ItemExists);
         }
 
-        fs::create_dir_all(item_path).map_err(CreateItemError::CreateContentFolder);
+        fs::create_dir_all(&item_path).map_err(CreateItemError::CreateContentFolder)?;
 
         transaction
             .commit()
             .map_err(CreateItemError::CommitTransaction)?;
+        self.track_content_folders([item_path]);
         Ok(ItemId(id))
     }
 
+    /// Creates every item in a single transaction. If any item fails, nothing is created
+    pub fn create_items(&mut self, names: &[String]) -> Result<Vec<ItemId>, CreateItemsError> {
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(CreateItemsError::StartTransaction)?;
+
+        let mut ids = Vec::with_capacity(names.len());
+        let mut created_folders = Vec::with_capacity(names.len());
+        let mut insert_items = || -> Result<(), CreateItemsError> {
+            let created_at = unix_timestamp_now();
+            for (index, name) in names.iter().enumerate() {
+                validate_item_name(name)
+                    .map_err(|e| CreateItemsError::InvalidItemName(index, e))?;
+                transaction
+                    .execute(
+                        "INSERT INTO files(name, created_at) VALUES (?1, ?2)",
+                        rusqlite::params![name, created_at],
+                    )
+                    .map_err(|e| CreateItemsError::InsertItem(index, e))?;
+                let id = transaction.last_insert_rowid();
+                record_event(
+                    &transaction,
+                    "item_created",
+                    serde_json::json!({ "id": id, "name": name }),
+                )
+                .map_err(|e| CreateItemsError::RecordEvent(index, e))?;
+
+                let item_path = self.item_path.join(id.to_string());
+                if item_path.exists() {
+                    return Err(CreateItemsError::ItemExists(index));
+                }
+
+                fs::create_dir_all(&item_path)
+                    .map_err(|e| CreateItemsError::CreateContentFolder(index, e))?;
+                created_folders.push(item_path);
+                ids.push(ItemId(id));
+            }
+
+            Ok(())
+        };
+
+        // The transaction rolls back on drop, but folders have to be cleaned up by hand
+        let ret = insert_items().and_then(|()| {
+            transaction
+                .commit()
+                .map_err(CreateItemsError::CommitTransaction)
+        });
+
+        if let Err(e) = ret {
+            for folder in created_folders {
+                if let Err(e) = fs::remove_dir_all(&folder) {
+                    log::error!("failed to remove {}: {e}", folder.display());
+                }
+            }
+            return Err(e);
+        }
+
+        self.track_content_folders(created_folders);
+        Ok(ids)
+    }
+
     pub fn delete_item(&mut self, id: ItemId) -> Result<(), DeleteItemError> {
+        let trash_folder = self.trash_folder_for_id(id);
         let transaction = self
             .connection
-            .transaction()
+            .savepoint()
             .map_err(DeleteItemError::StartTransaction)?;
 
-        transaction
-            .execute(
-                "DELETE FROM item_relationships WHERE from_id = ?1 OR to_id = ?1",
-                [id.0],
-            )
-            .map_err(DeleteItemError::DeleteItemRelationships)?;
-
+        // Item relationships are removed by ON DELETE CASCADE
         transaction
             .execute("DELETE FROM files WHERE id = ?1", [id.0])
             .map_err(DeleteItemError::DeleteItem)?;
-
-        let item_path = self.item_path.join(id.0.to_string());
-        fs::remove_dir_all(item_path);
+        record_event(
+            &transaction,
+            "item_deleted",
+            serde_json::json!({ "id": id.0 }),
+        )
+        .map_err(DeleteItemError::RecordEvent)?;
 
         transaction
             .commit()
             .map_err(DeleteItemError::CommitTransaction)?;
+
+        // Content is only removed once the item is gone for sure. Trashed items are deleted for
+        // good from the trash
+        for folder in [self.item_path.join(id.0.to_string()), trash_folder] {
+            match fs::remove_dir_all(&folder) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    log::error!("failed to remove {}: {e}", folder.display());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Soft deletes an item. It disappears from listings, filters and relationship folders, and its
+    /// content folder moves to the trash, until [`Db::restore_item`] brings it back.
+    /// [`Db::delete_item`] removes it for good
+    pub fn trash_item(&mut self, id: ItemId) -> Result<(), TrashItemError> {
+        let trash_folder = self.trash_folder_for_id(id);
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(TrashItemError::StartTransaction)?;
+
+        let num_updated = transaction
+            .execute(
+                "UPDATE files SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                [unix_timestamp_now(), id.0],
+            )
+            .map_err(TrashItemError::UpdateItem)?;
+        if num_updated == 0 {
+            return Err(TrashItemError::ItemNotFound);
+        }
+
+        record_event(
+            &transaction,
+            "item_trashed",
+            serde_json::json!({ "id": id.0 }),
+        )
+        .map_err(TrashItemError::RecordEvent)?;
+
+        fs::create_dir_all(self.item_path.join(TRASH_DIR_NAME))
+            .map_err(TrashItemError::CreateTrashFolder)?;
+        let content_folder = self.item_path.join(id.0.to_string());
+        move_content_folder(&content_folder, &trash_folder).map_err(TrashItemError::MoveContent)?;
+
+        // The transaction rolls back on drop, but the content has to be moved back by hand
+        if let Err(e) = transaction.commit() {
+            if let Err(e) = move_content_folder(&trash_folder, &content_folder) {
+                log::error!("failed to restore {}: {e}", content_folder.display());
+            }
+            return Err(TrashItemError::CommitTransaction(e));
+        }
+
+        Ok(())
+    }
+
+    /// Brings an item trashed with [`Db::trash_item`] back, along with its content
+    pub fn restore_item(&mut self, id: ItemId) -> Result<(), RestoreItemError> {
+        let trash_folder = self.trash_folder_for_id(id);
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(RestoreItemError::StartTransaction)?;
+
+        let num_updated = transaction
+            .execute(
+                "UPDATE files SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [id.0],
+            )
+            .map_err(RestoreItemError::UpdateItem)?;
+        if num_updated == 0 {
+            return Err(RestoreItemError::NotInTrash);
+        }
+
+        record_event(
+            &transaction,
+            "item_restored",
+            serde_json::json!({ "id": id.0 }),
+        )
+        .map_err(RestoreItemError::RecordEvent)?;
+
+        let content_folder = self.item_path.join(id.0.to_string());
+        move_content_folder(&trash_folder, &content_folder)
+            .map_err(RestoreItemError::MoveContent)?;
+
+        // The transaction rolls back on drop, but the content has to be moved back by hand
+        if let Err(e) = transaction.commit() {
+            if let Err(e) = move_content_folder(&content_folder, &trash_folder) {
+                log::error!(
+                    "failed to move {} back to the trash: {e}",
+                    content_folder.display()
+                );
+            }
+            return Err(RestoreItemError::CommitTransaction(e));
+        }
+
+        Ok(())
+    }
+
+    /// Folds remove into keep. Every link remove had is moved over to keep, links keep already
+    /// has are not duplicated and links between the two are dropped. remove's content is moved
+    /// into keep's content folder and remove is deleted
+    pub fn merge_items(&mut self, keep: ItemId, remove: ItemId) -> Result<(), MergeItemsError> {
+        if keep == remove {
+            return Err(MergeItemsError::SameItem);
+        }
+
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(MergeItemsError::StartTransaction)?;
+
+        let num_found: i64 = transaction
+            .query_row(
+                "SELECT COUNT(*) FROM files WHERE id IN (?1, ?2) AND deleted_at IS NULL",
+                [keep.0, remove.0],
+                |row| row.get(0),
+            )
+            .map_err(MergeItemsError::FindItems)?;
+        if num_found != 2 {
+            return Err(MergeItemsError::ItemNotFound);
+        }
+
+        // Links between the two would turn into links from keep to itself
+        transaction
+            .execute(
+                "DELETE FROM item_relationships WHERE (from_id = ?1 AND to_id = ?2) OR (from_id = ?2 AND to_id = ?1)",
+                [keep.0, remove.0],
+            )
+            .map_err(MergeItemsError::RepointItemRelationships)?;
+
+        // Links keep already has are left pointing at remove and are cleaned up by ON DELETE
+        // CASCADE below
+        for statement in [
+            "UPDATE OR IGNORE item_relationships SET from_id = ?1 WHERE from_id = ?2",
+            "UPDATE OR IGNORE item_relationships SET to_id = ?1 WHERE to_id = ?2",
+        ] {
+            transaction
+                .execute(statement, [keep.0, remove.0])
+                .map_err(MergeItemsError::RepointItemRelationships)?;
+        }
+
+        transaction
+            .execute("DELETE FROM files WHERE id = ?1", [remove.0])
+            .map_err(MergeItemsError::DeleteItem)?;
+
+        record_event(
+            &transaction,
+            "items_merged",
+            serde_json::json!({ "keep": keep.0, "remove": remove.0 }),
+        )
+        .map_err(MergeItemsError::RecordEvent)?;
+
+        let remove_folder = self.item_path.join(remove.0.to_string());
+        let moves = merge_content_folder(&remove_folder, &self.item_path.join(keep.0.to_string()))
+            .map_err(MergeItemsError::MergeContent)?;
+
+        // The transaction rolls back on drop, but the content has to be moved back by hand
+        if let Err(e) = transaction.commit() {
+            undo_content_merge(&remove_folder, &moves);
+            return Err(MergeItemsError::CommitTransaction(e));
+        }
+
+        Ok(())
+    }
+
+    /// Pinning an item that is already pinned, or unpinning one that is not, changes nothing
+    pub fn set_pinned(&mut self, id: ItemId, pinned: bool) -> Result<(), SetPinnedError> {
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(SetPinnedError::StartTransaction)?;
+
+        let exists = transaction
+            .prepare("SELECT 1 FROM files WHERE id = ?1 AND deleted_at IS NULL")
+            .and_then(|mut statement| statement.exists([id.0]))
+            .map_err(SetPinnedError::FindItem)?;
+        if !exists {
+            return Err(SetPinnedError::ItemNotFound);
+        }
+
+        let (statement, kind) = if pinned {
+            (
+                "INSERT OR IGNORE INTO pinned(item_id) VALUES (?1)",
+                "item_pinned",
+            )
+        } else {
+            ("DELETE FROM pinned WHERE item_id = ?1", "item_unpinned")
+        };
+        let num_changed = transaction
+            .execute(statement, [id.0])
+            .map_err(SetPinnedError::UpdatePinned)?;
+
+        if num_changed > 0 {
+            record_event(&transaction, kind, serde_json::json!({ "id": id.0 }))
+                .map_err(SetPinnedError::RecordEvent)?;
+        }
+
+        transaction
+            .commit()
+            .map_err(SetPinnedError::CommitTransaction)?;
         Ok(())
     }
 
+    /// Ids and names of pinned items, in id order. Trashed items stay pinned but are not listed
+    /// until they are restored
+    pub fn get_pinned_items(&self) -> Result<Vec<(ItemId, String)>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT files.id, files.name FROM pinned JOIN files ON files.id = pinned.item_id \
+                WHERE files.deleted_at IS NULL ORDER BY files.id",
+            )
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                Ok((ItemId(id), name))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Ids and names of the items in the trash, in id order
+    pub fn get_trashed_items(&self) -> Result<Vec<(ItemId, String)>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, name FROM files WHERE deleted_at IS NOT NULL ORDER BY id")
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                Ok((ItemId(id), name))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Where the content of a trashed item is kept. The folder only exists while the item is in
+    /// the trash
+    pub fn trash_folder_for_id(&self, id: ItemId) -> PathBuf {
+        self.item_path.join(TRASH_DIR_NAME).join(id.0.to_string())
+    }
+
     pub fn add_relationship(
         &mut self,
         from_name: &str,
         to_name: &str,
     ) -> Result<RelationshipId, AddRelationshipError> {
+        self.add_relationship_with_description(from_name, to_name, "")
+    }
+
+    pub fn add_relationship_with_description(
+        &mut self,
+        from_name: &str,
+        to_name: &str,
+        description: &str,
+    ) -> Result<RelationshipId, AddRelationshipError> {
+        for name in [from_name, to_name] {
+            if name == ALL_RELATED_FOLDER_NAME {
+                return Err(AddRelationshipError::ReservedName(name.to_string()));
+            }
+        }
+
         if let Some(id) = self
             .find_relationship(from_name, to_name)
             .map_err(AddRelationshipError::FindRelationship)?
@@ -355,15 +2316,21 @@ impl Db {
 
         let transaction = self
             .connection
-            .transaction()
+            .savepoint()
             .map_err(AddRelationshipError::StartTransaction)?;
         transaction
             .execute(
-                "INSERT INTO relationships(from_name, to_name) VALUES (?1, ?2)",
-                [from_name, to_name],
+                "INSERT INTO relationships(from_name, to_name, description, created_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![from_name, to_name, description, unix_timestamp_now()],
             )
             .map_err(AddRelationshipError::InsertRelationship)?;
         let id = transaction.last_insert_rowid();
+        record_event(
+            &transaction,
+            "relationship_added",
+            serde_json::json!({ "id": id, "from_name": from_name, "to_name": to_name }),
+        )
+        .map_err(AddRelationshipError::RecordEvent)?;
 
         transaction
             .commit()
@@ -372,6 +2339,37 @@ impl Db {
         Ok(RelationshipId(id))
     }
 
+    /// Same as [`Db::add_relationship`], but an existing relationship with the same pair of names,
+    /// in either orientation, is returned instead of being an error. A relationship that only
+    /// shares one of the names is still reported as [`AddRelationshipError::AlreadyExists`]
+    pub fn get_or_create_relationship(
+        &mut self,
+        from_name: &str,
+        to_name: &str,
+    ) -> Result<RelationshipId, AddRelationshipError> {
+        let id = match self.add_relationship(from_name, to_name) {
+            Err(AddRelationshipError::AlreadyExists(id)) => id,
+            ret => return ret,
+        };
+
+        let relationship = self
+            .get_relationship(id)
+            .map_err(AddRelationshipError::FindRelationship)?;
+        let same_pair = relationship.is_some_and(|relationship| {
+            let names = (
+                relationship.from_name.as_str(),
+                relationship.to_name.as_str(),
+            );
+            names == (from_name, to_name) || names == (to_name, from_name)
+        });
+
+        if same_pair {
+            Ok(id)
+        } else {
+            Err(AddRelationshipError::AlreadyExists(id))
+        }
+    }
+
     fn find_relationship(
         &mut self,
         from_name: &str,
@@ -393,10 +2391,43 @@ impl Db {
         item.transpose().map_err(QueryError::QueryMapFailed)
     }
 
+    pub fn rename_item(&mut self, id: ItemId, name: &str) -> Result<(), RenameItemError> {
+        validate_item_name(name)?;
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(RenameItemError::StartTransaction)?;
+        let num_updated = transaction
+            .execute(
+                "UPDATE files SET name = ?1 WHERE id = ?2",
+                rusqlite::params![name, id.0],
+            )
+            .map_err(RenameItemError::UpdateItem)?;
+
+        if num_updated == 0 {
+            return Err(RenameItemError::ItemNotFound);
+        }
+
+        record_event(
+            &transaction,
+            "item_renamed",
+            serde_json::json!({ "id": id.0, "name": name }),
+        )
+        .map_err(RenameItemError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(RenameItemError::CommitTransaction)?;
+
+        Ok(())
+    }
+
     pub fn get_relationship(&self, id: RelationshipId) -> Result<Option<Relationship>, QueryError> {
         let mut statement = self
             .connection
-            .prepare("SELECT id, from_name, to_name FROM relationships WHERE id = ?1")
+            .prepare(
+                "SELECT id, from_name, to_name, description, created_at FROM relationships WHERE id = ?1",
+            )
             .map_err(QueryError::Prepare)?;
 
         let item = statement
@@ -404,10 +2435,14 @@ impl Db {
                 let id: i64 = row.get(0)?;
                 let from_name: String = row.get(1)?;
                 let to_name: String = row.get(2)?;
+                let description: String = row.get(3)?;
+                let created_at: Option<i64> = row.get(4)?;
                 Ok(Relationship {
                     id: RelationshipId(id),
                     from_name,
                     to_name,
+                    description,
+                    created_at,
                 })
             })
             .map_err(QueryError::Execute)?
@@ -417,10 +2452,245 @@ impl Db {
         item.transpose().map_err(QueryError::QueryMapFailed)
     }
 
+    /// Cheaper than [`Db::get_relationship`] when the caller only needs to know that the
+    /// relationship is there
+    pub fn relationship_exists(&self, id: RelationshipId) -> Result<bool, QueryError> {
+        self.connection
+            .prepare("SELECT 1 FROM relationships WHERE id = ?1 LIMIT 1")
+            .map_err(QueryError::Prepare)?
+            .exists([id.0])
+            .map_err(QueryError::Execute)
+    }
+
+    /// Finds the relationship with name on either side
+    pub fn get_relationship_id_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<RelationshipId>, GetRelationshipIdByNameError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM relationships WHERE from_name = ?1 OR to_name = ?1")
+            .map_err(QueryError::Prepare)?;
+
+        let mut ids = statement
+            .query_map([name], |row| Ok(RelationshipId(row.get(0)?)))
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ids.len() > 1 {
+            return Err(GetRelationshipIdByNameError::AmbiguousRelationship(ids));
+        }
+
+        Ok(ids.pop())
+    }
+
+    pub fn describe_relationship(
+        &self,
+        id: RelationshipId,
+    ) -> Result<Option<RelationshipDirection>, QueryError> {
+        let ret = self
+            .get_relationship(id)?
+            .map(|relationship| RelationshipDirection {
+                source_name: relationship.from_name,
+                dest_name: relationship.to_name,
+            });
+        Ok(ret)
+    }
+
+    /// Resolves the relationship referenced by rule so it can be displayed by name
+    pub fn describe_rule<'a>(
+        &self,
+        rule: &'a ItemFilterRule,
+    ) -> Result<RuleDescription<'a>, QueryError> {
+        let relationship_id = match rule {
+            ItemFilterRule::NoRelationship(_, id)
+            | ItemFilterRule::HasRelationshipWithVariableItem(_, id)
+            | ItemFilterRule::RelationshipCount(_, id, _, _)
+            | ItemFilterRule::RelatedToFilter(_, id, _) => Some(*id),
+            ItemFilterRule::Orphan => None,
+        };
+
+        let relationship = match relationship_id {
+            Some(id) => self.get_relationship(id)?,
+            None => None,
+        };
+
+        Ok(RuleDescription { rule, relationship })
+    }
+
+    pub fn rename_relationship(
+        &mut self,
+        id: RelationshipId,
+        from_name: Option<&str>,
+        to_name: Option<&str>,
+    ) -> Result<(), RenameRelationshipError> {
+        for name in [from_name, to_name].into_iter().flatten() {
+            if name == ALL_RELATED_FOLDER_NAME {
+                return Err(RenameRelationshipError::ReservedName(name.to_string()));
+            }
+        }
+
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(RenameRelationshipError::StartTransaction)?;
+
+        {
+            // Same uniqueness rule as add_relationship, a name may only be used by one
+            // relationship
+            let mut statement = transaction
+                .prepare("SELECT id FROM relationships WHERE (from_name = ?1 OR to_name = ?1) AND id != ?2")
+                .map_err(QueryError::Prepare)
+                .map_err(RenameRelationshipError::FindRelationship)?;
+
+            for name in [from_name, to_name].into_iter().flatten() {
+                let existing = statement
+                    .query_map(rusqlite::params![name, id.0], |row| {
+                        let id: i64 = row.get(0)?;
+                        Ok(RelationshipId(id))
+                    })
+                    .map_err(QueryError::Execute)
+                    .map_err(RenameRelationshipError::FindRelationship)?
+                    .next()
+                    .transpose()
+                    .map_err(QueryError::QueryMapFailed)
+                    .map_err(RenameRelationshipError::FindRelationship)?;
+
+                if let Some(existing) = existing {
+                    return Err(RenameRelationshipError::AlreadyExists(existing));
+                }
+            }
+        }
+
+        let updates = [("from_name", from_name), ("to_name", to_name)];
+        for (column, name) in updates {
+            let Some(name) = name else {
+                continue;
+            };
+
+            let num_updated = transaction
+                .execute(
+                    &format!("UPDATE relationships SET {column} = ?1 WHERE id = ?2"),
+                    rusqlite::params![name, id.0],
+                )
+                .map_err(RenameRelationshipError::UpdateRelationship)?;
+
+            if num_updated == 0 {
+                return Err(RenameRelationshipError::RelationshipNotFound);
+            }
+        }
+
+        record_event(
+            &transaction,
+            "relationship_renamed",
+            serde_json::json!({ "id": id.0, "from_name": from_name, "to_name": to_name }),
+        )
+        .map_err(RenameRelationshipError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(RenameRelationshipError::CommitTransaction)?;
+
+        Ok(())
+    }
+
+    /// Flips the orientation of a relationship. The names swap places and so does every link, so
+    /// an item that was a parent of another is still its parent afterwards. Filter rules flip
+    /// their side along with the links, so saved filters keep matching the same items
+    pub fn swap_relationship_sides(
+        &mut self,
+        id: RelationshipId,
+    ) -> Result<(), SwapRelationshipSidesError> {
+        self.invalidate_filter_queries();
+
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(SwapRelationshipSidesError::StartTransaction)?;
+
+        let num_updated = transaction
+            .execute(
+                "UPDATE relationships SET from_name = to_name, to_name = from_name WHERE id = ?1",
+                [id.0],
+            )
+            .map_err(SwapRelationshipSidesError::UpdateRelationship)?;
+
+        if num_updated == 0 {
+            return Err(SwapRelationshipSidesError::RelationshipNotFound);
+        }
+
+        let links = {
+            let mut statement = transaction
+                .prepare("SELECT from_id, to_id FROM item_relationships WHERE relationship_id = ?1")
+                .map_err(QueryError::Prepare)
+                .map_err(SwapRelationshipSidesError::GetItemRelationships)?;
+
+            let links: Result<Vec<(i64, i64)>, _> = statement
+                .query_map([id.0], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(QueryError::Execute)
+                .map_err(SwapRelationshipSidesError::GetItemRelationships)?
+                .collect();
+
+            links
+                .map_err(QueryError::QueryMapFailed)
+                .map_err(SwapRelationshipSidesError::GetItemRelationships)?
+        };
+
+        // Swapping the columns in place would trip the uniqueness constraint part way through if
+        // two items are linked both ways, so the links are reinserted instead
+        transaction
+            .execute(
+                "DELETE FROM item_relationships WHERE relationship_id = ?1",
+                [id.0],
+            )
+            .map_err(SwapRelationshipSidesError::RemoveItemRelationships)?;
+
+        for (from_id, to_id) in links {
+            transaction
+                .execute(
+                    "INSERT INTO item_relationships(from_id, to_id, relationship_id) VALUES (?1, ?2, ?3)",
+                    [to_id, from_id, id.0],
+                )
+                .map_err(SwapRelationshipSidesError::InsertItemRelationship)?;
+        }
+
+        // Sides are 0 and 1. They are moved out of the way first, a rule that exists for both
+        // sides would otherwise collide with itself part way through the flip
+        for table in [
+            "no_relationship_filters",
+            "variable_item_filters",
+            "relationship_count_filters",
+            "related_to_filter_filters",
+        ] {
+            for update in ["side = side + 2", "side = 3 - side"] {
+                transaction
+                    .execute(
+                        &format!("UPDATE {table} SET {update} WHERE relationship_id = ?1"),
+                        [id.0],
+                    )
+                    .map_err(|e| SwapRelationshipSidesError::FlipFilterSides(table, e))?;
+            }
+        }
+
+        record_event(
+            &transaction,
+            "relationship_sides_swapped",
+            serde_json::json!({ "id": id.0 }),
+        )
+        .map_err(SwapRelationshipSidesError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(SwapRelationshipSidesError::CommitTransaction)?;
+
+        Ok(())
+    }
+
     pub fn get_relationships(&self) -> Result<Vec<Relationship>, QueryError> {
         let mut statement = self
             .connection
-            .prepare("SELECT id, from_name, to_name FROM relationships")
+            .prepare("SELECT id, from_name, to_name, description, created_at FROM relationships")
             .map_err(QueryError::Prepare)?;
 
         let ret = statement
@@ -428,11 +2698,15 @@ impl Db {
                 let id: i64 = row.get(0)?;
                 let from_name: String = row.get(1)?;
                 let to_name: String = row.get(2)?;
+                let description: String = row.get(3)?;
+                let created_at: Option<i64> = row.get(4)?;
                 let id = RelationshipId(id);
                 Ok(Relationship {
                     id,
                     from_name,
                     to_name,
+                    description,
+                    created_at,
                 })
             })
             .map_err(QueryError::Execute)?
@@ -449,14 +2723,39 @@ impl Db {
         from_id: ItemId,
         to_id: ItemId,
         relationship_id: RelationshipId,
+    ) -> Result<(), AddItemRelationshipError> {
+        if from_id == to_id {
+            return Err(AddItemRelationshipError::SelfRelationship);
+        }
+
+        self.add_item_relationship_allow_self(from_id, to_id, relationship_id)
+    }
+
+    /// Like [`Db::add_item_relationship`], but allows an item to be related to itself for graphs
+    /// that want self loops
+    pub fn add_item_relationship_allow_self(
+        &mut self,
+        from_id: ItemId,
+        to_id: ItemId,
+        relationship_id: RelationshipId,
     ) -> Result<(), AddItemRelationshipError> {
         let transaction = self
             .connection
-            .transaction()
+            .savepoint()
             .map_err(AddItemRelationshipError::StartTransaction)?;
         transaction
             .execute("INSERT INTO item_relationships(from_id, to_id, relationship_id) VALUES (?1, ?2, ?3)", [from_id.0, to_id.0, relationship_id.0])
             .map_err(AddItemRelationshipError::InsertRelationship)?;
+        record_event(
+            &transaction,
+            "item_relationship_added",
+            serde_json::json!({
+                "from_id": from_id.0,
+                "to_id": to_id.0,
+                "relationship_id": relationship_id.0,
+            }),
+        )
+        .map_err(AddItemRelationshipError::RecordEvent)?;
 
         transaction
             .commit()
@@ -464,47 +2763,303 @@ impl Db {
         Ok(())
     }
 
-    pub fn fs_root(&self) -> &Path {
-        &self.item_path
-    }
-
-    pub fn add_filter(
+    /// Adds every link in a single transaction, returning what happened to each in order. Links
+    /// that already exist are reported as [`AddedLink::Duplicate`] unless abort_on_duplicate is
+    /// set, in which case they fail the batch like any other bad link. Nothing is added if the
+    /// batch fails
+    pub fn add_item_relationships(
         &mut self,
-        name: &str,
-        filters: &[ItemFilterRule],
-    ) -> Result<(), AddFilterError> {
+        links: &[(ItemId, ItemId, RelationshipId)],
+        abort_on_duplicate: bool,
+    ) -> Result<Vec<AddedLink>, AddItemRelationshipsError> {
         let transaction = self
             .connection
-            .transaction()
-            .map_err(AddFilterError::StartTransaction)?;
+            .savepoint()
+            .map_err(AddItemRelationshipsError::StartTransaction)?;
 
-        transaction
-            .execute("INSERT INTO filters(name) VALUES (?1)", [name])
-            .map_err(AddFilterError::InsertFilter)?;
+        let mut ret = Vec::with_capacity(links.len());
+        for (index, (from_id, to_id, relationship_id)) in links.iter().enumerate() {
+            if from_id == to_id {
+                return Err(AddItemRelationshipsError::SelfRelationship(index));
+            }
 
-        let filter_id = transaction.last_insert_rowid();
+            let num_inserted = transaction
+                .execute(
+                    "INSERT OR IGNORE INTO item_relationships(from_id, to_id, relationship_id) VALUES (?1, ?2, ?3)",
+                    [from_id.0, to_id.0, relationship_id.0],
+                )
+                .map_err(|e| AddItemRelationshipsError::InsertRelationship(index, e))?;
 
-        for filter in filters {
-            match filter {
-                ItemFilterRule::NoRelationship(side, relationship_id) => {
-                    transaction.execute("INSERT INTO no_relationship_filters(filter_id, side, relationship_id) VALUES (?1, ?2, ?3)", [filter_id, side.as_i64(), relationship_id.0]).map_err(AddFilterError::InsertRule)?;
+            if num_inserted == 0 {
+                if abort_on_duplicate {
+                    return Err(AddItemRelationshipsError::Duplicate(index));
                 }
+                ret.push(AddedLink::Duplicate);
+                continue;
             }
+
+            record_event(
+                &transaction,
+                "item_relationship_added",
+                serde_json::json!({
+                    "from_id": from_id.0,
+                    "to_id": to_id.0,
+                    "relationship_id": relationship_id.0,
+                }),
+            )
+            .map_err(|e| AddItemRelationshipsError::RecordEvent(index, e))?;
+            ret.push(AddedLink::Added);
         }
 
         transaction
             .commit()
-            .map_err(AddFilterError::CommitTransaction)?;
-
-        Ok(())
+            .map_err(AddItemRelationshipsError::CommitTransaction)?;
+        Ok(ret)
     }
 
-    pub fn get_filters(&mut self) -> Result<Vec<Filter>, GetFiltersError> {
-        let mut transaction = self
-            .connection
-            .transaction()
-            .map_err(GetFiltersError::StartTransaction)?;
-
+    pub fn remove_item_relationship(
+        &mut self,
+        from_id: ItemId,
+        to_id: ItemId,
+        relationship_id: RelationshipId,
+    ) -> Result<(), RemoveItemRelationshipError> {
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(RemoveItemRelationshipError::StartTransaction)?;
+        let num_deleted = transaction
+            .execute(
+                "DELETE FROM item_relationships WHERE from_id = ?1 AND to_id = ?2 AND relationship_id = ?3",
+                [from_id.0, to_id.0, relationship_id.0],
+            )
+            .map_err(RemoveItemRelationshipError::Delete)?;
+
+        if num_deleted == 0 {
+            return Err(RemoveItemRelationshipError::NotFound);
+        }
+
+        record_event(
+            &transaction,
+            "item_relationship_removed",
+            serde_json::json!({
+                "from_id": from_id.0,
+                "to_id": to_id.0,
+                "relationship_id": relationship_id.0,
+            }),
+        )
+        .map_err(RemoveItemRelationshipError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(RemoveItemRelationshipError::CommitTransaction)?;
+        Ok(())
+    }
+
+    /// Audit log of changes made through [`Db`], oldest first
+    pub fn get_events(&self) -> Result<Vec<Event>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, ts, kind, detail FROM events ORDER BY id")
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([], |row| {
+                let detail: String = row.get(3)?;
+                Ok(Event {
+                    id: row.get(0)?,
+                    ts: row.get(1)?,
+                    kind: row.get(2)?,
+                    detail: serde_json::from_str(&detail)
+                        .unwrap_or(serde_json::Value::String(detail)),
+                })
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        ret
+    }
+
+    /// Runs f in a single transaction, everything it changes in the db is committed together or
+    /// not at all. Db methods start savepoints rather than transactions, so they nest inside.
+    /// Content folders of items created by a failed f are removed again. Other changes to the file
+    /// system, e.g. the content of deleted or trashed items, are not undone
+    pub fn with_transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Db) -> Result<T, E>,
+    ) -> Result<T, TransactionError<E>> {
+        self.begin_transaction()
+            .map_err(TransactionError::StartTransaction)?;
+
+        match f(self) {
+            Ok(ret) => {
+                self.commit_transaction()
+                    .map_err(TransactionError::CommitTransaction)?;
+                Ok(ret)
+            }
+            Err(e) => {
+                self.rollback_transaction()
+                    .map_err(TransactionError::Rollback)?;
+                Err(TransactionError::Operation(e))
+            }
+        }
+    }
+
+    /// Building blocks of [`Db::with_transaction`] for callers that cannot hand the work over as
+    /// a closure of the db. Every begin has to be followed by exactly one commit or rollback
+    pub(crate) fn begin_transaction(&mut self) -> Result<(), rusqlite::Error> {
+        self.connection
+            .execute_batch("SAVEPOINT with_transaction")?;
+        self.transaction_content_folders.push(Vec::new());
+        Ok(())
+    }
+
+    /// A transaction that fails to commit is rolled back, so that it is never left open
+    pub(crate) fn commit_transaction(&mut self) -> Result<(), rusqlite::Error> {
+        if let Err(e) = self.connection.execute_batch("RELEASE with_transaction") {
+            if let Err(e) = self.rollback_transaction() {
+                log::error!("failed to roll back transaction: {e}");
+            }
+            return Err(e);
+        }
+
+        let folders = self
+            .transaction_content_folders
+            .pop()
+            .expect("commit without a transaction");
+        // Still removed if an enclosing transaction rolls back
+        self.track_content_folders(folders);
+        Ok(())
+    }
+
+    pub(crate) fn rollback_transaction(&mut self) -> Result<(), rusqlite::Error> {
+        // Filters created in the transaction are gone, and their ids may be handed out again
+        self.invalidate_filter_queries();
+        self.connection
+            .execute_batch("ROLLBACK TO with_transaction; RELEASE with_transaction")?;
+
+        let folders = self
+            .transaction_content_folders
+            .pop()
+            .expect("rollback without a transaction");
+        for folder in folders {
+            if let Err(e) = fs::remove_dir_all(&folder) {
+                log::error!("failed to remove {}: {e}", folder.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn track_content_folders(&mut self, folders: impl IntoIterator<Item = PathBuf>) {
+        if let Some(transaction_folders) = self.transaction_content_folders.last_mut() {
+            transaction_folders.extend(folders);
+        }
+    }
+
+    /// Flushes any write ahead log back into the database file and closes the connection,
+    /// reporting errors that would otherwise be swallowed on drop
+    pub fn close(self) -> Result<(), CloseDbError> {
+        // The first column is set if another connection kept the checkpoint from finishing
+        let busy: bool = self
+            .connection
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", (), |row| row.get(0))
+            .map_err(CloseDbError::Checkpoint)?;
+        if busy {
+            return Err(CloseDbError::CheckpointBusy);
+        }
+
+        self.connection
+            .close()
+            .map_err(|(_, e)| CloseDbError::Close(e))
+    }
+
+    pub fn fs_root(&self) -> &Path {
+        &self.item_path
+    }
+
+    /// Location of the sqlite file, None for databases that do not live on disk
+    pub fn db_path(&self) -> Option<&Path> {
+        self.connection
+            .path()
+            .filter(|path| !path.is_empty())
+            .map(Path::new)
+    }
+
+    /// A filter without rules is not an error, it matches every item. See [`Db::run_filter`]
+    pub fn add_filter(
+        &mut self,
+        name: &str,
+        filters: &[ItemFilterRule],
+    ) -> Result<FilterId, AddFilterError> {
+        self.add_ordered_filter(name, filters, &[], None)
+    }
+
+    /// Same as [`Db::add_filter`], with the filters an item has to match for this one to show up
+    /// in its folder, and the order the filter lists its items in
+    pub fn add_ordered_filter(
+        &mut self,
+        name: &str,
+        filters: &[ItemFilterRule],
+        conditions: &[FilterId],
+        order: Option<FilterOrder>,
+    ) -> Result<FilterId, AddFilterError> {
+        self.invalidate_filter_queries();
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(AddFilterError::StartTransaction)?;
+
+        transaction
+            .execute(
+                "INSERT INTO filters(name, order_by) VALUES (?1, ?2)",
+                rusqlite::params![name, order.map(|order| order.to_string())],
+            )
+            .map_err(AddFilterError::InsertFilter)?;
+
+        let filter_id = transaction.last_insert_rowid();
+
+        insert_filter_rules(&transaction, filter_id, filters)
+            .map_err(AddFilterError::InsertRule)?;
+
+        let conditions: Vec<i64> = conditions.iter().map(|id| id.0).collect();
+        insert_filter_conditions(&transaction, filter_id, &conditions)
+            .map_err(AddFilterError::InsertCondition)?;
+
+        record_event(
+            &transaction,
+            "filter_added",
+            serde_json::json!({ "id": filter_id, "name": name }),
+        )
+        .map_err(AddFilterError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(AddFilterError::CommitTransaction)?;
+
+        Ok(FilterId(filter_id))
+    }
+
+    /// Adds a new filter with the same rules, conditions and order as an existing one. The copy
+    /// is independent of the source, later edits to either do not affect the other
+    pub fn clone_filter(
+        &mut self,
+        id: FilterId,
+        new_name: &str,
+    ) -> Result<FilterId, AddFilterError> {
+        let source = self
+            .get_filter_by_id(id)
+            .map_err(AddFilterError::LoadFilter)?
+            .ok_or(AddFilterError::FilterNotFound)?;
+
+        self.add_ordered_filter(new_name, &source.rules, &source.conditions, source.order)
+    }
+
+    pub fn get_filters(&mut self) -> Result<Vec<Filter>, GetFiltersError> {
+        let mut transaction = self
+            .connection
+            .savepoint()
+            .map_err(GetFiltersError::StartTransaction)?;
+
         let mut statement = transaction
             .prepare("SELECT id, name FROM filters")
             .map_err(QueryError::Prepare)
@@ -519,6 +3074,8 @@ impl Db {
                     id: FilterId(id),
                     name,
                     rules: Vec::new(),
+                    conditions: Vec::new(),
+                    order: None,
                 })
             })
             .map_err(QueryError::Execute)
@@ -529,848 +3086,4627 @@ impl Db {
         let mut ret = ret.map_err(GetFiltersError::QueryFilters)?;
 
         for item in &mut ret {
-            let mut statement = transaction.prepare("SELECT side, relationship_id FROM no_relationship_filters WHERE filter_id = ?1").map_err(QueryError::Prepare)
-                .map_err(GetFiltersError::QueryRules)?;
+            load_filter_rules(&transaction, item)?;
+        }
 
-            let mut rules = Vec::new();
+        Ok(ret)
+    }
 
-            let mut query = statement
-                .query([item.id.0])
-                .map_err(QueryError::Execute)
-                .map_err(GetFiltersError::QueryRules)?;
+    /// Every item filter with its rules described and conditions named, see
+    /// [`Filter::is_item_filter`]
+    pub fn get_item_filter_details(
+        &mut self,
+    ) -> Result<Vec<ItemFilterDetails>, GetItemFilterDetailsError> {
+        let filters = self.get_filters()?;
 
-            while let Some(row) = query
-                .next()
-                .map_err(QueryError::QueryMapFailed)
-                .map_err(GetFiltersError::QueryRules)?
-            {
-                let side: i64 = row
-                    .get(0)
-                    .map_err(QueryError::QueryMapFailed)
-                    .map_err(GetFiltersError::QueryRules)?;
-                let side = RelationshipSide::from_i64(side)
-                    .map_err(GetFiltersError::InvalidRelationshipSide)?;
+        let mut ret = Vec::new();
+        for filter in &filters {
+            if !filter.is_item_filter() {
+                continue;
+            }
 
-                let relationship_id: i64 = row
-                    .get(1)
-                    .map_err(QueryError::QueryMapFailed)
-                    .map_err(GetFiltersError::QueryRules)?;
-                let relationship_id = RelationshipId(relationship_id);
-                rules.push(ItemFilterRule::NoRelationship(side, relationship_id));
+            let mut rules = Vec::new();
+            for rule in &filter.rules {
+                let description = self
+                    .describe_rule(rule)
+                    .map_err(GetItemFilterDetailsError::DescribeRule)?
+                    .to_string();
+                rules.push(ItemFilterRuleDetails {
+                    rule: rule.clone(),
+                    description,
+                });
             }
 
-            item.rules = rules;
+            // Conditions reference filters by foreign key, they always resolve
+            let conditions = filter
+                .conditions
+                .iter()
+                .filter_map(|condition| filters.iter().find(|f| f.id == *condition))
+                .map(|condition| FilterConditionDetails {
+                    id: condition.id.0,
+                    name: condition.name.clone(),
+                })
+                .collect();
+
+            ret.push(ItemFilterDetails {
+                id: filter.id.0,
+                name: filter.name.clone(),
+                rules,
+                conditions,
+                order_by: filter.order,
+            });
         }
 
         Ok(ret)
     }
 
-    pub fn run_filter(&self, filters: &[ItemFilterRule]) -> Result<Vec<ItemId>, QueryError> {
-        let mut query_string = "SELECT files.id FROM files ".to_string();
-
-        if !filters.is_empty() {
-            query_string += "WHERE ";
+    /// Filters are listed side by side at the root of the mount, so the new name has to be
+    /// unused by other filters
+    pub fn rename_filter(&mut self, id: FilterId, name: &str) -> Result<(), RenameFilterError> {
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(RenameFilterError::StartTransaction)?;
+
+        let existing: Option<i64> = transaction
+            .query_row(
+                "SELECT id FROM filters WHERE name = ?1 AND id != ?2",
+                rusqlite::params![name, id.0],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(RenameFilterError::FindFilter)?;
+        if let Some(existing) = existing {
+            return Err(RenameFilterError::AlreadyExists(FilterId(existing)));
         }
 
-        for filter in filters {
-            match filter {
-                ItemFilterRule::NoRelationship(side, id) => {
-                    let side_filter_str = match side {
-                        RelationshipSide::Dest => "item_relationships.to_id = files.id",
-                        RelationshipSide::Source => "item_relationships.from_id = files.id",
-                    };
+        let num_updated = transaction
+            .execute(
+                "UPDATE filters SET name = ?1 WHERE id = ?2",
+                rusqlite::params![name, id.0],
+            )
+            .map_err(RenameFilterError::UpdateFilter)?;
+        if num_updated == 0 {
+            return Err(RenameFilterError::FilterNotFound);
+        }
 
-                    let id_i64 = id.0;
+        record_event(
+            &transaction,
+            "filter_renamed",
+            serde_json::json!({ "id": id.0, "name": name }),
+        )
+        .map_err(RenameFilterError::RecordEvent)?;
 
-                    let filter_str = format!("files.id not in (SELECT files.id FROM files JOIN item_relationships ON {side_filter_str} AND relationship_id = {id_i64}) ");
-                    query_string.push_str(&filter_str);
-                }
-            }
-        }
+        transaction
+            .commit()
+            .map_err(RenameFilterError::CommitTransaction)?;
+        Ok(())
+    }
 
-        let mut statement = self
+    /// Loads a single filter, or None if no filter has the given id
+    pub fn get_filter_by_id(&mut self, id: FilterId) -> Result<Option<Filter>, GetFiltersError> {
+        let transaction = self
             .connection
-            .prepare(&query_string)
-            .map_err(QueryError::Prepare)?;
+            .savepoint()
+            .map_err(GetFiltersError::StartTransaction)?;
 
-        let ret: Result<Vec<_>, QueryError> = statement
-            .query_map([], |row| {
-                let id: i64 = row.get(0)?;
-                Ok(ItemId(id))
+        let name: Option<String> = transaction
+            .query_row("SELECT name FROM filters WHERE id = ?1", [id.0], |row| {
+                row.get(0)
             })
-            .map_err(QueryError::Execute)?
-            .map(|x| x.map_err(QueryError::QueryMapFailed))
-            .collect();
+            .optional()
+            .map_err(QueryError::Execute)
+            .map_err(GetFiltersError::QueryFilters)?;
 
-        ret
-    }
+        let Some(name) = name else {
+            return Ok(None);
+        };
 
-    pub fn content_folder_for_id(&self, id: ItemId) -> Result<PathBuf, std::io::Error> {
-        self.item_path.join(id.0.to_string()).canonicalize()
+        let mut filter = Filter {
+            id,
+            name,
+            rules: Vec::new(),
+            conditions: Vec::new(),
+            order: None,
+        };
+        load_filter_rules(&transaction, &mut filter)?;
+
+        Ok(Some(filter))
     }
 
-    pub fn get_sibling_id(
+    /// Whether item matches every rule, evaluating variable item rules with the item itself as
+    /// context
+    pub fn item_matches(
         &self,
-        id: ItemId,
-        side: RelationshipSide,
-        relationship_id: RelationshipId,
-        sibling_name: &str,
-    ) -> Result<Option<ItemId>, QueryError> {
-        let join_str = match side {
-            RelationshipSide::Dest => {
-                "INNER JOIN item_relationships ON us_files.id = item_relationships.to_id LEFT JOIN files them_files ON them_files.id = item_relationships.from_id"
-            }
-            RelationshipSide::Source => {
-                "INNER JOIN item_relationships ON us_files.id = item_relationships.from_id LEFT JOIN files them_files ON them_files.id = item_relationships.to_id"
-            }
-        };
-
-        let query = format!("SELECT them_files.id FROM files us_files {join_str} LEFT JOIN relationships ON item_relationships.relationship_id = relationships.id WHERE us_files.id = ?1 AND them_files.name = ?2 AND relationships.id = ?3");
+        item_id: ItemId,
+        rules: &[ItemFilterRule],
+    ) -> Result<bool, RunFilterError> {
+        let query = self.filter_query(rules, None)?;
+        let sql = format!(
+            "SELECT EXISTS(SELECT 1 FROM ({}) AS matches WHERE matches.id = ?1)",
+            query.sql
+        );
 
         let mut statement = self
             .connection
-            .prepare(&query)
+            .prepare_cached(&sql)
             .map_err(QueryError::Prepare)?;
-        let mut query = statement
-            .query_map(
-                rusqlite::params![id.0, sibling_name, relationship_id.0],
-                |row| {
-                    let id: i64 = row.get(0)?;
-                    Ok(ItemId(id))
-                },
-            )
-            .map_err(QueryError::Execute)?;
+        let params = filter_query_params(Some(item_id), statement.parameter_count());
 
-        // Option<Result<..>> -> Result<Option<...>>
-        let first = query
-            .next()
-            .transpose()
-            .map_err(QueryError::QueryMapFailed)?;
-        let second = query
-            .next()
-            .transpose()
-            .map_err(QueryError::QueryMapFailed)?;
+        let ret = statement
+            .query_row(rusqlite::params_from_iter(params), |row| row.get(0))
+            .map_err(QueryError::Execute)?;
+        Ok(ret)
+    }
 
-        if second.is_some() {
-            panic!("Multiple items matched :(");
+    /// Items matched by every one of the given filters. The rules of all filters are combined into
+    /// a single query rather than intersecting the individual results
+    pub fn run_filters_intersection(
+        &mut self,
+        filter_ids: &[FilterId],
+    ) -> Result<Vec<ItemId>, RunFiltersIntersectionError> {
+        let mut filters = self
+            .get_filters()
+            .map_err(RunFiltersIntersectionError::GetFilters)?;
+
+        let mut rules = Vec::new();
+        for filter_id in filter_ids {
+            let filter = filters
+                .iter_mut()
+                .find(|filter| filter.id == *filter_id)
+                .ok_or(RunFiltersIntersectionError::FilterNotFound(filter_id.0))?;
+            // A filter listed twice has nothing left to add the second time around
+            rules.append(&mut filter.rules);
         }
 
-        Ok(first)
+        self.run_filter(&rules, None)
+            .map_err(RunFiltersIntersectionError::RunFilter)
     }
 
-    pub fn get_item_by_id(&self, id: ItemId) -> Option<DbItem> {
-        // FIXME: Don't query the whole database for every item lookup idiot
-        self.get_items()
-            .into_iter()
-            .flatten()
-            .find(|item| item.id == id)
+    /// Finds all items matching every rule in filters. Rules that reference a variable item are
+    /// evaluated against context, and match nothing if no context is given. Without any rules
+    /// every item matches, the same as an empty AND. Trashed items never match
+    pub fn run_filter(
+        &self,
+        filters: &[ItemFilterRule],
+        context: Option<ItemId>,
+    ) -> Result<Vec<ItemId>, RunFilterError> {
+        self.run_ordered_filter(filters, None, context)
     }
 
-    pub fn get_items(&self) -> Result<Vec<DbItem>, GetItemsError> {
+    /// Number of items [`Db::run_filter`] would return, counted by sqlite instead of collecting
+    /// the ids
+    pub fn count_filter_matches(
+        &self,
+        filters: &[ItemFilterRule],
+        context: Option<ItemId>,
+    ) -> Result<i64, RunFilterError> {
+        let query = self.filter_query(filters, None)?;
+        let sql = format!("SELECT COUNT(*) FROM ({})", query.sql);
+
         let mut statement = self
             .connection
-            .prepare("SELECT id, name FROM files")
-            .map_err(QueryError::Prepare)
-            .map_err(GetItemsError::QueryItems)?;
+            .prepare_cached(&sql)
+            .map_err(QueryError::Prepare)?;
+        let params = filter_query_params(context, statement.parameter_count());
 
-        struct Item {
-            id: ItemId,
-            name: String,
+        let ret = statement
+            .query_row(rusqlite::params_from_iter(params), |row| row.get(0))
+            .map_err(QueryError::Execute)?;
+        Ok(ret)
+    }
+
+    /// Same as [`Db::run_filter`], with the matched items sorted by order
+    pub fn run_ordered_filter(
+        &self,
+        filters: &[ItemFilterRule],
+        order: Option<FilterOrder>,
+        context: Option<ItemId>,
+    ) -> Result<Vec<ItemId>, RunFilterError> {
+        let query = self.filter_query(filters, order)?;
+        Ok(self.run_filter_query(&query, context)?)
+    }
+
+    /// Runs the saved filter with the given id, or returns None if there is no such filter. The
+    /// filter's query is built once and reused until a filter changes
+    pub fn run_saved_filter(
+        &self,
+        id: FilterId,
+        context: Option<ItemId>,
+    ) -> Result<Option<Vec<ItemId>>, RunFilterError> {
+        if let Some(query) = self.filter_queries.borrow().get(&id) {
+            return Ok(Some(self.run_filter_query(query, context)?));
         }
-        let items: Vec<Item> = statement
-            .query_map([], |row| {
-                let id: i64 = row.get(0)?;
-                let id = ItemId(id);
-                Ok(Item {
-                    id,
-                    name: row.get(1)?,
-                })
-            })
-            .map_err(QueryError::Execute)
-            .map_err(GetItemsError::QueryItems)?
-            .map(|x| {
-                x.map_err(QueryError::QueryMapFailed)
-                    .map_err(GetItemsError::QueryItems)
-            })
-            .collect::<Result<Vec<Item>, GetItemsError>>()?;
 
-        let mut statement = self
+        let exists = self
             .connection
-            .prepare("SELECT from_id, to_id, relationship_id FROM item_relationships")
-            .map_err(QueryError::Prepare)
-            .map_err(GetItemsError::GetRelationships)?;
-
-        struct DbRelationship {
-            from_id: ItemId,
-            to_id: ItemId,
-            relationship_id: RelationshipId,
+            .prepare_cached("SELECT 1 FROM filters WHERE id = ?1")
+            .map_err(QueryError::Prepare)?
+            .exists([id.0])
+            .map_err(QueryError::Execute)?;
+        if !exists {
+            return Ok(None);
         }
 
-        let item_relationships: Vec<DbRelationship> = statement
-            .query_map([], |row| {
-                let from_id: i64 = row.get(0)?;
-                let to_id: i64 = row.get(1)?;
-                let relationship_id: i64 = row.get(2)?;
-                Ok(DbRelationship {
-                    from_id: ItemId(from_id),
-                    to_id: ItemId(to_id),
-                    relationship_id: RelationshipId(relationship_id),
-                })
-            })
-            .map_err(QueryError::Execute)
-            .map_err(GetItemsError::GetRelationships)?
-            .map(|x| {
-                x.map_err(QueryError::QueryMapFailed)
-                    .map_err(GetItemsError::GetRelationships)
-            })
-            .collect::<Result<Vec<DbRelationship>, GetItemsError>>()?;
+        let mut filter = Filter {
+            id,
+            name: String::new(),
+            rules: Vec::new(),
+            conditions: Vec::new(),
+            order: None,
+        };
+        load_filter_rules(&self.connection, &mut filter).map_err(RunFilterError::LoadFilter)?;
+
+        let query = self.filter_query(&filter.rules, filter.order)?;
+        self.filter_query_builds
+            .set(self.filter_query_builds.get() + 1);
+        let ret = self.run_filter_query(&query, context)?;
+        self.filter_queries.borrow_mut().insert(id, query);
+        Ok(Some(ret))
+    }
 
-        let mut ret = Vec::new();
-        for item in items {
-            let mut relationships = Vec::new();
-            for relationship in &item_relationships {
-                if relationship.from_id == item.id {
-                    relationships.push(ItemRelationship {
-                        id: relationship.relationship_id,
-                        sibling: relationship.to_id,
-                        side: RelationshipSide::Source,
-                    });
-                }
-                if relationship.to_id == item.id {
-                    relationships.push(ItemRelationship {
-                        id: relationship.relationship_id,
-                        sibling: relationship.from_id,
-                        side: RelationshipSide::Dest,
-                    });
-                }
-            }
+    fn invalidate_filter_queries(&mut self) {
+        self.filter_queries.get_mut().clear();
+    }
 
-            ret.push(DbItem {
-                path: self.item_path.join(item.id.0.to_string()),
-                id: item.id,
-                relationships,
-                name: item.name,
-            })
+    fn filter_query(
+        &self,
+        filters: &[ItemFilterRule],
+        order: Option<FilterOrder>,
+    ) -> Result<FilterQuery, RunFilterError> {
+        let mut sql = self.filter_query_string(filters, 0)?;
+        if let Some(order) = order {
+            sql += order.order_by_clause();
         }
-        Ok(ret)
+
+        Ok(FilterQuery { sql })
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use tempfile::TempDir;
+    /// The context is bound to ?1. Without a context ?1 is NULL, which no variable item rule
+    /// matches
+    fn run_filter_query(
+        &self,
+        query: &FilterQuery,
+        context: Option<ItemId>,
+    ) -> Result<Vec<ItemId>, QueryError> {
+        log::trace!(
+            "running filter query {:?} with context {context:?}",
+            query.sql
+        );
+
+        let mut statement = self
+            .connection
+            .prepare_cached(&query.sql)
+            .map_err(QueryError::Prepare)?;
+
+        let params = filter_query_params(context, statement.parameter_count());
+
+        let ret = statement
+            .query_map(rusqlite::params_from_iter(params), |row| {
+                let id: i64 = row.get(0)?;
+                Ok(ItemId(id))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Builds a query selecting the ids of items matching filters. Sub filters referenced by
+    /// filters are expanded into nested queries, depth counts how far down that expansion is
+    fn filter_query_string(
+        &self,
+        filters: &[ItemFilterRule],
+        depth: usize,
+    ) -> Result<String, RunFilterError> {
+        let mut query_string =
+            "SELECT files.id FROM files WHERE files.deleted_at IS NULL ".to_string();
+
+        for filter in filters {
+            query_string += "AND ";
+
+            match filter {
+                ItemFilterRule::NoRelationship(side, id) => {
+                    let (side_filter_str, other_column) = match side {
+                        RelationshipSide::Dest => {
+                            ("item_relationships.to_id = files.id", "from_id")
+                        }
+                        RelationshipSide::Source => {
+                            ("item_relationships.from_id = files.id", "to_id")
+                        }
+                    };
+
+                    let id_i64 = id.0;
+
+                    // Links to trashed items do not count
+                    let filter_str = format!("files.id not in (SELECT files.id FROM files JOIN item_relationships ON {side_filter_str} AND relationship_id = {id_i64} JOIN files other_files ON other_files.id = item_relationships.{other_column} AND other_files.deleted_at IS NULL) ");
+                    query_string.push_str(&filter_str);
+                }
+                ItemFilterRule::HasRelationshipWithVariableItem(side, id) => {
+                    let (item_column, context_column) = match side {
+                        RelationshipSide::Dest => ("to_id", "from_id"),
+                        RelationshipSide::Source => ("from_id", "to_id"),
+                    };
+
+                    let id_i64 = id.0;
+
+                    let filter_str = format!("files.id in (SELECT {item_column} FROM item_relationships WHERE relationship_id = {id_i64} AND {context_column} = ?1) ");
+                    query_string.push_str(&filter_str);
+                }
+                ItemFilterRule::RelationshipCount(side, id, op, count) => {
+                    let (item_column, other_column) = match side {
+                        RelationshipSide::Dest => ("to_id", "from_id"),
+                        RelationshipSide::Source => ("from_id", "to_id"),
+                    };
+
+                    let id_i64 = id.0;
+
+                    // Counting per item rather than grouping item_relationships keeps items
+                    // without any relationships in the result with a count of 0. Links to trashed
+                    // items do not count
+                    let filter_str = format!("(SELECT COUNT(*) FROM item_relationships JOIN files other_files ON other_files.id = item_relationships.{other_column} AND other_files.deleted_at IS NULL WHERE relationship_id = {id_i64} AND {item_column} = files.id) {op} {count} ");
+                    query_string.push_str(&filter_str);
+                }
+                ItemFilterRule::Orphan => {
+                    // Links to trashed items do not count
+                    query_string += "files.id NOT IN (SELECT from_id FROM item_relationships JOIN files other_files ON other_files.id = to_id AND other_files.deleted_at IS NULL UNION SELECT to_id FROM item_relationships JOIN files other_files ON other_files.id = from_id AND other_files.deleted_at IS NULL) ";
+                }
+                ItemFilterRule::RelatedToFilter(side, id, sub_filter_id) => {
+                    if depth >= MAX_SUB_FILTER_DEPTH {
+                        return Err(RunFilterError::TooDeep(sub_filter_id.0));
+                    }
+
+                    let mut sub_filter = Filter {
+                        id: *sub_filter_id,
+                        name: String::new(),
+                        rules: Vec::new(),
+                        conditions: Vec::new(),
+                        order: None,
+                    };
+                    let exists = self
+                        .connection
+                        .prepare("SELECT 1 FROM filters WHERE id = ?1")
+                        .map_err(QueryError::Prepare)?
+                        .exists([sub_filter_id.0])
+                        .map_err(QueryError::Execute)?;
+                    if !exists {
+                        return Err(RunFilterError::SubFilterNotFound(sub_filter_id.0));
+                    }
+                    load_filter_rules(&self.connection, &mut sub_filter)
+                        .map_err(RunFilterError::LoadSubFilter)?;
+
+                    let (item_column, other_column) = match side {
+                        RelationshipSide::Dest => ("to_id", "from_id"),
+                        RelationshipSide::Source => ("from_id", "to_id"),
+                    };
+
+                    let id_i64 = id.0;
+                    // The sub filter sees the same context, its own files alias shadows ours
+                    let sub_query = self.filter_query_string(&sub_filter.rules, depth + 1)?;
+
+                    let filter_str = format!("files.id in (SELECT {item_column} FROM item_relationships WHERE relationship_id = {id_i64} AND {other_column} IN ({sub_query})) ");
+                    query_string.push_str(&filter_str);
+                }
+            }
+        }
+
+        Ok(query_string)
+    }
+
+    /// Never touches the filesystem. A folder that was removed behind our back still resolves,
+    /// see [`Db::restore_content_folder`]
+    pub fn content_folder_for_id(&self, id: ItemId) -> Result<PathBuf, std::io::Error> {
+        let path = self.item_path.join(id.0.to_string());
+        match path.canonicalize() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(self.item_path.canonicalize()?.join(id.0.to_string()))
+            }
+            ret => ret,
+        }
+    }
+
+    /// The item whose content folder is exactly path. Expects a canonical path, as handed out by
+    /// [`Db::content_folder_for_id`]
+    pub fn content_folder_owner(&self, path: &Path) -> Option<ItemId> {
+        let item_path = self.item_path.canonicalize().ok()?;
+        if path.parent()? != item_path {
+            return None;
+        }
+
+        path.file_name()?.to_str()?.parse().ok().map(ItemId)
+    }
+
+    /// Recreates a content folder that was removed behind our back, the item row means it is
+    /// supposed to exist. Only needed right before creating something inside of it
+    pub fn restore_content_folder(&self, id: ItemId) -> Result<(), std::io::Error> {
+        let path = self.item_path.join(id.0.to_string());
+        if !path.exists() {
+            log::warn!("content folder for item {} is missing, recreating", id.0);
+            fs::create_dir_all(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the database and all item content into dest, which can then be opened with
+    /// [`Db::new`]. Uses sqlite's online backup so that the database stays consistent even if it is
+    /// being modified while the backup runs
+    pub fn backup_to(&self, dest: &Path) -> Result<(), BackupError> {
+        let dest_db_path = dest.join(METADATA_DB_NAME);
+        if dest_db_path.exists() {
+            return Err(BackupError::DestExists);
+        }
+
+        fs::create_dir_all(dest).map_err(BackupError::CreateDestDir)?;
+
+        self.connection
+            .backup(rusqlite::DatabaseName::Main, dest_db_path, None)
+            .map_err(BackupError::Backup)?;
+
+        if self.item_path.exists() {
+            copy_dir_recursive(&self.item_path, &dest.join(ITEMS_DIR_NAME))
+                .map_err(BackupError::CopyContent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ids of items in id order, without loading any of their relationships. A limit of None
+    /// returns every item after offset
+    pub fn get_item_ids(&self, offset: i64, limit: Option<i64>) -> Result<Vec<ItemId>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM files WHERE deleted_at IS NULL ORDER BY id LIMIT ?1 OFFSET ?2")
+            .map_err(QueryError::Prepare)?;
+
+        // sqlite treats a negative limit as no limit
+        let ret = statement
+            .query_map([limit.unwrap_or(-1), offset], |row| {
+                let id: i64 = row.get(0)?;
+                Ok(ItemId(id))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Up to limit ids of items with an id greater than after, in id order. Unlike an offset into
+    /// [`Db::get_item_ids`], this picks up where a previous page ended even if items were created
+    /// or deleted in between
+    pub fn get_item_ids_after(&self, after: ItemId, limit: i64) -> Result<Vec<ItemId>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare_cached(
+                "SELECT id FROM files WHERE deleted_at IS NULL AND id > ?1 ORDER BY id LIMIT ?2",
+            )
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([after.0, limit], |row| {
+                let id: i64 = row.get(0)?;
+                Ok(ItemId(id))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// All directories in the content root, whether or not they belong to an item. The trash is
+    /// not a content folder and is left out
+    fn list_content_dirs(&self) -> Result<Vec<PathBuf>, std::io::Error> {
+        if !self.item_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut ret = Vec::new();
+        for entry in fs::read_dir(&self.item_path)? {
+            let entry = entry?;
+            if entry.file_name() != TRASH_DIR_NAME {
+                ret.push(entry.path());
+            }
+        }
+        ret.sort();
+        Ok(ret)
+    }
+
+    pub fn check_integrity(&self) -> Result<IntegrityReport, IntegrityCheckError> {
+        let mut report = IntegrityReport::default();
+
+        let mut statement = self
+            .connection
+            .prepare("PRAGMA integrity_check")
+            .map_err(QueryError::Prepare)?;
+
+        let results = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(QueryError::Execute)?;
+
+        for result in results {
+            let result = result.map_err(QueryError::QueryMapFailed)?;
+            if result != "ok" {
+                report.sqlite_errors.push(result);
+            }
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("PRAGMA foreign_key_check")
+            .map_err(QueryError::Prepare)?;
+
+        let violations = statement
+            .query_map([], |row| {
+                Ok(ForeignKeyViolation {
+                    table: row.get(0)?,
+                    rowid: row.get(1)?,
+                    parent: row.get(2)?,
+                })
+            })
+            .map_err(QueryError::Execute)?;
+
+        for violation in violations {
+            report
+                .foreign_key_violations
+                .push(violation.map_err(QueryError::QueryMapFailed)?);
+        }
+
+        let item_ids = self.get_item_ids(0, None)?;
+        let content_dirs = self
+            .list_content_dirs()
+            .map_err(IntegrityCheckError::ListContentDirs)?;
+
+        for id in &item_ids {
+            if !self.item_path.join(id.0.to_string()).is_dir() {
+                report.missing_content_dirs.push(*id);
+            }
+        }
+
+        for dir in content_dirs {
+            let id = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<i64>().ok());
+
+            if !id.is_some_and(|id| item_ids.contains(&ItemId(id))) {
+                report.orphan_content_dirs.push(dir);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Ids of content directories that have no matching item. Directories that are not named
+    /// after an id are not considered, as they were not created by us
+    pub fn find_orphans(&self) -> Result<Vec<ItemId>, PruneError> {
+        let item_ids = self.get_item_ids(0, None).map_err(PruneError::GetItemIds)?;
+        let content_dirs = self
+            .list_content_dirs()
+            .map_err(PruneError::ListContentDirs)?;
+
+        let mut ret: Vec<ItemId> = content_dirs
+            .iter()
+            .filter_map(|dir| dir.file_name()?.to_str()?.parse::<i64>().ok())
+            .map(ItemId)
+            .filter(|id| !item_ids.contains(id))
+            .collect();
+        ret.sort_by_key(|id| id.0);
+        Ok(ret)
+    }
+
+    /// Removes content directories left behind by items that no longer exist
+    pub fn prune_orphans(&mut self) -> Result<Vec<ItemId>, PruneError> {
+        let orphans = self.find_orphans()?;
+        for id in &orphans {
+            fs::remove_dir_all(self.item_path.join(id.0.to_string()))
+                .map_err(PruneError::RemoveContentDir)?;
+        }
+
+        Ok(orphans)
+    }
+
+    /// Refreshes query planner statistics and rebuilds the database file to reclaim space left by
+    /// deletes. VACUUM needs exclusive access to the database, so this should not be run while
+    /// the database is mounted. It also refuses to run inside a transaction, taking &mut self
+    /// ensures none of ours is open
+    pub fn optimize(&mut self) -> Result<(), QueryError> {
+        self.connection
+            .execute_batch("PRAGMA optimize; VACUUM;")
+            .map_err(QueryError::Execute)
+    }
+
+    /// Whether path is somewhere within an item's content folder. Expects a canonical path, as
+    /// handed out by [`Db::content_folder_for_id`]
+    pub fn is_content_path(&self, path: &Path) -> bool {
+        self.item_path
+            .canonicalize()
+            .is_ok_and(|item_path| path.starts_with(item_path))
+    }
+
+    /// Whether path is the content of a trashed item, or somewhere inside it
+    pub fn is_trash_path(&self, path: &Path) -> bool {
+        let trash_root = self.item_path.join(TRASH_DIR_NAME);
+        path.starts_with(&trash_root)
+            || trash_root
+                .canonicalize()
+                .is_ok_and(|trash_root| path.starts_with(trash_root))
+    }
+
+    pub fn get_sibling_id(
+        &self,
+        id: ItemId,
+        side: RelationshipSide,
+        relationship_id: RelationshipId,
+        sibling_name: &str,
+    ) -> Result<Option<ItemId>, GetSiblingIdError> {
+        let join_str = match side {
+            RelationshipSide::Dest => {
+                "INNER JOIN item_relationships ON us_files.id = item_relationships.to_id LEFT JOIN files them_files ON them_files.id = item_relationships.from_id"
+            }
+            RelationshipSide::Source => {
+                "INNER JOIN item_relationships ON us_files.id = item_relationships.from_id LEFT JOIN files them_files ON them_files.id = item_relationships.to_id"
+            }
+        };
+
+        let query = format!("SELECT them_files.id FROM files us_files {join_str} LEFT JOIN relationships ON item_relationships.relationship_id = relationships.id WHERE us_files.id = ?1 AND them_files.name = ?2 AND relationships.id = ?3 AND them_files.deleted_at IS NULL");
+
+        let mut statement = self
+            .connection
+            .prepare(&query)
+            .map_err(QueryError::Prepare)?;
+        let mut ids = statement
+            .query_map(
+                rusqlite::params![id.0, sibling_name, relationship_id.0],
+                |row| {
+                    let id: i64 = row.get(0)?;
+                    Ok(ItemId(id))
+                },
+            )
+            .map_err(QueryError::Execute)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(QueryError::QueryMapFailed)?;
+
+        // Item names are not unique, so there may be several siblings with the same name
+        if ids.len() > 1 {
+            return Err(GetSiblingIdError::AmbiguousSibling(ids));
+        }
+
+        Ok(ids.pop())
+    }
+
+    pub fn get_item_by_id(&self, id: ItemId) -> Option<DbItem> {
+        // FIXME: Don't query the whole database for every item lookup idiot
+        self.get_items()
+            .into_iter()
+            .flatten()
+            .find(|item| item.id == id)
+    }
+
+    /// Every (from, to) pair linked by the relationship, ordered by from and then to
+    pub fn get_links_for_relationship(
+        &self,
+        id: RelationshipId,
+    ) -> Result<Vec<(ItemId, ItemId)>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT from_id, to_id FROM item_relationships WHERE relationship_id = ?1 \
+                AND from_id NOT IN ({TRASHED_ITEM_IDS}) AND to_id NOT IN ({TRASHED_ITEM_IDS}) \
+                ORDER BY from_id, to_id"
+            ))
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([id.0], |row| {
+                let from_id: i64 = row.get(0)?;
+                let to_id: i64 = row.get(1)?;
+                Ok((ItemId(from_id), ItemId(to_id)))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Ids and names of the items related to id, where id is on the given side of the
+    /// relationship
+    pub fn get_siblings(
+        &self,
+        id: ItemId,
+        relationship_id: RelationshipId,
+        side: RelationshipSide,
+    ) -> Result<Vec<(ItemId, String)>, QueryError> {
+        let (item_column, sibling_column) = match side {
+            RelationshipSide::Source => ("from_id", "to_id"),
+            RelationshipSide::Dest => ("to_id", "from_id"),
+        };
+
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT files.id, files.name FROM item_relationships \
+                JOIN files ON files.id = item_relationships.{sibling_column} \
+                WHERE item_relationships.{item_column} = ?1 AND item_relationships.relationship_id = ?2 \
+                AND files.deleted_at IS NULL \
+                ORDER BY files.id"
+            ))
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([id.0, relationship_id.0], |row| {
+                Ok((ItemId(row.get(0)?), row.get(1)?))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        ret
+    }
+
+    /// Unix timestamp of when the item was created, None for items from before creation times
+    /// were recorded
+    pub fn get_item_created_at(&self, id: ItemId) -> Result<Option<i64>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT created_at FROM files WHERE id = ?1")
+            .map_err(QueryError::Prepare)?;
+
+        let created_at = statement
+            .query_map([id.0], |row| row.get::<_, Option<i64>>(0))
+            .map_err(QueryError::Execute)?
+            .next()
+            .transpose()
+            .map_err(QueryError::QueryMapFailed)?;
+
+        Ok(created_at.flatten())
+    }
+
+    /// Unix timestamp of when the relationship was created, see [`Db::get_item_created_at`]
+    pub fn get_relationship_created_at(
+        &self,
+        id: RelationshipId,
+    ) -> Result<Option<i64>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT created_at FROM relationships WHERE id = ?1")
+            .map_err(QueryError::Prepare)?;
+
+        let created_at = statement
+            .query_map([id.0], |row| row.get::<_, Option<i64>>(0))
+            .map_err(QueryError::Execute)?
+            .next()
+            .transpose()
+            .map_err(QueryError::QueryMapFailed)?;
+
+        Ok(created_at.flatten())
+    }
+
+    /// Item names are not unique, so every item with the given name is returned
+    pub fn get_item_by_name(&self, name: &str) -> Result<Vec<ItemId>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM files WHERE name = ?1 AND deleted_at IS NULL")
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([name], |row| {
+                let id: i64 = row.get(0)?;
+                Ok(ItemId(id))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Up to limit items whose name starts with prefix, ordered by name. Like LIKE itself the
+    /// match ignores ASCII case. A negative limit returns every match
+    pub fn find_items_by_name_prefix(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<(ItemId, String)>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, name FROM files WHERE name LIKE ?1 || '%' ESCAPE '\\' \
+                AND deleted_at IS NULL ORDER BY name, id LIMIT ?2",
+            )
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map(rusqlite::params![escape_like(prefix), limit], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                Ok((ItemId(id), name))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    /// Returns up to limit items, newest first. Imported items keep their ids, so ids only break
+    /// ties between items created in the same second
+    pub fn get_recent_items(&self, limit: usize) -> Result<Vec<(ItemId, String)>, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, name FROM files WHERE deleted_at IS NULL \
+                 ORDER BY created_at DESC, id DESC LIMIT ?1",
+            )
+            .map_err(QueryError::Prepare)?;
+
+        let ret = statement
+            .query_map([limit], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                Ok((ItemId(id), name))
+            })
+            .map_err(QueryError::Execute)?
+            .map(|x| x.map_err(QueryError::QueryMapFailed))
+            .collect();
+
+        #[allow(clippy::let_and_return)]
+        ret
+    }
+
+    pub fn count_items(&self) -> Result<i64, QueryError> {
+        self.count_rows("SELECT COUNT(*) FROM files WHERE deleted_at IS NULL")
+    }
+
+    pub fn count_relationships(&self) -> Result<i64, QueryError> {
+        self.count_rows("SELECT COUNT(*) FROM relationships")
+    }
+
+    pub fn count_filters(&self) -> Result<i64, QueryError> {
+        self.count_rows("SELECT COUNT(*) FROM filters")
+    }
+
+    fn count_rows(&self, query: &str) -> Result<i64, QueryError> {
+        self.connection
+            .prepare(query)
+            .map_err(QueryError::Prepare)?
+            .query_row([], |row| row.get(0))
+            .map_err(QueryError::Execute)
+    }
+
+    /// True if the database has no items, relationships or filters
+    pub fn is_empty(&self) -> Result<bool, QueryError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT NOT EXISTS (SELECT 1 FROM files) \
+                AND NOT EXISTS (SELECT 1 FROM relationships) \
+                AND NOT EXISTS (SELECT 1 FROM filters)",
+            )
+            .map_err(QueryError::Prepare)?;
+
+        statement
+            .query_row([], |row| row.get(0))
+            .map_err(QueryError::Execute)
+    }
+
+    pub fn get_items(&self) -> Result<Vec<DbItem>, GetItemsError> {
+        let mut ret = Vec::new();
+        self.for_each_item(|item| {
+            ret.push(item);
+            Ok::<_, GetItemsError>(())
+        })?;
+        Ok(ret)
+    }
+
+    /// Calls f with every item, one at a time, in id order. Items are read from the database as
+    /// they are visited rather than all up front, only the relationships of all items are held in
+    /// memory at once. Stops at the first error returned by f
+    pub fn for_each_item<E: From<GetItemsError>>(
+        &self,
+        mut f: impl FnMut(DbItem) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT from_id, to_id, relationship_id FROM item_relationships \
+                WHERE from_id NOT IN ({TRASHED_ITEM_IDS}) AND to_id NOT IN ({TRASHED_ITEM_IDS})"
+            ))
+            .map_err(QueryError::Prepare)
+            .map_err(GetItemsError::GetRelationships)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                let from_id: i64 = row.get(0)?;
+                let to_id: i64 = row.get(1)?;
+                let relationship_id: i64 = row.get(2)?;
+                Ok((from_id, to_id, relationship_id))
+            })
+            .map_err(QueryError::Execute)
+            .map_err(GetItemsError::GetRelationships)?;
+
+        let mut item_relationships: HashMap<i64, Vec<ItemRelationship>> = HashMap::new();
+        for row in rows {
+            let (from_id, to_id, relationship_id) = row
+                .map_err(QueryError::QueryMapFailed)
+                .map_err(GetItemsError::GetRelationships)?;
+
+            item_relationships
+                .entry(from_id)
+                .or_default()
+                .push(ItemRelationship {
+                    id: RelationshipId(relationship_id),
+                    sibling: ItemId(to_id),
+                    side: RelationshipSide::Source,
+                });
+            item_relationships
+                .entry(to_id)
+                .or_default()
+                .push(ItemRelationship {
+                    id: RelationshipId(relationship_id),
+                    sibling: ItemId(from_id),
+                    side: RelationshipSide::Dest,
+                });
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, name, created_at FROM files WHERE deleted_at IS NULL ORDER BY id")
+            .map_err(QueryError::Prepare)
+            .map_err(GetItemsError::QueryItems)?;
+
+        // query_map steps through the rows as they are consumed, items are never all in memory
+        let rows = statement
+            .query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                let created_at: Option<i64> = row.get(2)?;
+                Ok((id, name, created_at))
+            })
+            .map_err(QueryError::Execute)
+            .map_err(GetItemsError::QueryItems)?;
+
+        for row in rows {
+            let (id, name, created_at) = row
+                .map_err(QueryError::QueryMapFailed)
+                .map_err(GetItemsError::QueryItems)?;
+
+            f(DbItem {
+                path: self.item_path.join(id.to_string()),
+                id: ItemId(id),
+                relationships: item_relationships.remove(&id).unwrap_or_default(),
+                name,
+                created_at,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the item graph as a graphviz digraph. Edges point from the source of a
+    /// relationship to its destination, and are labeled with the name the source uses to refer to
+    /// the destination
+    pub fn export_dot(&self) -> Result<String, QueryError> {
+        let mut ret = "digraph todo_fs {\n".to_string();
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, name FROM files WHERE deleted_at IS NULL ORDER BY id")
+            .map_err(QueryError::Prepare)?;
+
+        let nodes = statement
+            .query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                let name: String = row.get(1)?;
+                Ok((id, name))
+            })
+            .map_err(QueryError::Execute)?;
+
+        for node in nodes {
+            let (id, name) = node.map_err(QueryError::QueryMapFailed)?;
+            ret.push_str(&format!(
+                "    {id} [label=\"{}\"];\n",
+                escape_dot_string(&name)
+            ));
+        }
+
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT item_relationships.from_id, item_relationships.to_id, relationships.to_name \
+                FROM item_relationships \
+                INNER JOIN relationships ON item_relationships.relationship_id = relationships.id \
+                WHERE item_relationships.from_id NOT IN ({TRASHED_ITEM_IDS}) \
+                AND item_relationships.to_id NOT IN ({TRASHED_ITEM_IDS}) \
+                ORDER BY item_relationships.from_id, item_relationships.to_id, relationships.id",
+            ))
+            .map_err(QueryError::Prepare)?;
+
+        let edges = statement
+            .query_map([], |row| {
+                let from_id: i64 = row.get(0)?;
+                let to_id: i64 = row.get(1)?;
+                let to_name: String = row.get(2)?;
+                Ok((from_id, to_id, to_name))
+            })
+            .map_err(QueryError::Execute)?;
+
+        for edge in edges {
+            let (from_id, to_id, to_name) = edge.map_err(QueryError::QueryMapFailed)?;
+            ret.push_str(&format!(
+                "    {from_id} -> {to_id} [label=\"{}\"];\n",
+                escape_dot_string(&to_name)
+            ));
+        }
+
+        ret.push_str("}\n");
+        Ok(ret)
+    }
+
+    /// Writes everything needed to recreate the database as pretty printed json, in the layout of
+    /// [`DbExport`]. Items and their links are serialized as they are read from the database
+    /// instead of being collected first
+    pub fn export_json(&mut self, writer: impl std::io::Write) -> Result<(), ExportError> {
+        let mut relationships: Vec<ExportedRelationship> = self
+            .get_relationships()
+            .map_err(ExportError::GetRelationships)?
+            .into_iter()
+            .map(|relationship| ExportedRelationship {
+                id: relationship.id.0,
+                from_name: relationship.from_name,
+                to_name: relationship.to_name,
+                description: relationship.description,
+                created_at: relationship.created_at,
+            })
+            .collect();
+        relationships.sort_by_key(|relationship| relationship.id);
+
+        let mut filters: Vec<ExportedFilter> = self
+            .get_filters()
+            .map_err(ExportError::GetFilters)?
+            .into_iter()
+            .map(|filter| ExportedFilter {
+                id: filter.id.0,
+                name: filter.name,
+                rules: filter.rules,
+                conditions: filter.conditions.into_iter().map(|id| id.0).collect(),
+                order_by: filter.order,
+            })
+            .collect();
+        filters.sort_by_key(|filter| filter.id);
+
+        // Trashed items are exported too, along with their links, so nothing is lost by a round
+        // trip through an export
+        let query_error = RefCell::new(None);
+        let items = ExportedRows {
+            connection: &self.connection,
+            query: "SELECT files.id, files.name, files.created_at, files.deleted_at, \
+                pinned.item_id IS NOT NULL FROM files \
+                LEFT JOIN pinned ON pinned.item_id = files.id ORDER BY files.id",
+            map_row: |row: &rusqlite::Row| {
+                Ok(ExportedItem {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                    deleted_at: row.get(3)?,
+                    pinned: row.get(4)?,
+                })
+            },
+            to_error: ExportError::GetItems,
+            error: &query_error,
+        };
+        let item_relationships = ExportedRows {
+            connection: &self.connection,
+            query: "SELECT from_id, to_id, relationship_id FROM item_relationships \
+                ORDER BY from_id, to_id, relationship_id",
+            map_row: |row: &rusqlite::Row| {
+                Ok(ExportedItemRelationship {
+                    from_id: row.get(0)?,
+                    to_id: row.get(1)?,
+                    relationship_id: row.get(2)?,
+                })
+            },
+            to_error: ExportError::GetItemRelationships,
+            error: &query_error,
+        };
+
+        use serde::ser::{SerializeMap, Serializer};
+        let mut serializer = serde_json::Serializer::pretty(writer);
+        let ret = (|| {
+            let mut map = serializer.serialize_map(Some(5))?;
+            map.serialize_entry("version", &EXPORT_VERSION)?;
+            map.serialize_entry("items", &items)?;
+            map.serialize_entry("relationships", &relationships)?;
+            map.serialize_entry("item_relationships", &item_relationships)?;
+            map.serialize_entry("filters", &filters)?;
+            map.end()
+        })();
+
+        if let Some(e) = query_error.into_inner() {
+            return Err(e);
+        }
+
+        ret.map_err(ExportError::Serialize)
+    }
+
+    /// Recreates the contents of an export produced by [`Db::export_json`]. Ids are preserved as
+    /// filters reference relationships by id. Nothing is imported if any part of the export is
+    /// inconsistent
+    pub fn import_json(&mut self, reader: impl std::io::Read) -> Result<(), ImportError> {
+        let export: DbExport = serde_json::from_reader(reader).map_err(ImportError::ParseExport)?;
+        if export.version != EXPORT_VERSION {
+            return Err(ImportError::UnsupportedVersion(export.version));
+        }
+
+        self.invalidate_filter_queries();
+
+        let transaction = self
+            .connection
+            .savepoint()
+            .map_err(ImportError::StartTransaction)?;
+
+        for item in &export.items {
+            transaction
+                .execute(
+                    "INSERT INTO files(id, name, created_at, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+                    rusqlite::params![item.id, item.name, item.created_at, item.deleted_at],
+                )
+                .map_err(|e| ImportError::InsertItem(item.id, e))?;
+
+            if item.pinned {
+                transaction
+                    .execute("INSERT INTO pinned(item_id) VALUES (?1)", [item.id])
+                    .map_err(|e| ImportError::PinItem(item.id, e))?;
+            }
+        }
+
+        for relationship in &export.relationships {
+            transaction
+                .execute(
+                    "INSERT INTO relationships(id, from_name, to_name, description, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![
+                        relationship.id,
+                        relationship.from_name,
+                        relationship.to_name,
+                        relationship.description,
+                        relationship.created_at
+                    ],
+                )
+                .map_err(|e| ImportError::InsertRelationship(relationship.id, e))?;
+        }
+
+        for item_relationship in &export.item_relationships {
+            transaction
+                .execute(
+                    "INSERT INTO item_relationships(from_id, to_id, relationship_id) VALUES (?1, ?2, ?3)",
+                    [
+                        item_relationship.from_id,
+                        item_relationship.to_id,
+                        item_relationship.relationship_id,
+                    ],
+                )
+                .map_err(|e| {
+                    ImportError::InsertItemRelationship(
+                        item_relationship.from_id,
+                        item_relationship.to_id,
+                        e,
+                    )
+                })?;
+        }
+
+        for filter in &export.filters {
+            transaction
+                .execute(
+                    "INSERT INTO filters(id, name, order_by) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![
+                        filter.id,
+                        filter.name,
+                        filter.order_by.map(|order| order.to_string())
+                    ],
+                )
+                .map_err(|e| ImportError::InsertFilter(filter.id, e))?;
+
+            insert_filter_rules(&transaction, filter.id, &filter.rules)
+                .map_err(|e| ImportError::InsertRule(filter.id, e))?;
+        }
+
+        // Conditions reference other filters, so they can only be inserted once all filters exist
+        for filter in &export.filters {
+            insert_filter_conditions(&transaction, filter.id, &filter.conditions)
+                .map_err(|e| ImportError::InsertCondition(filter.id, e))?;
+        }
+
+        record_event(
+            &transaction,
+            "imported",
+            serde_json::json!({
+                "items": export.items.len(),
+                "relationships": export.relationships.len(),
+                "item_relationships": export.item_relationships.len(),
+                "filters": export.filters.len(),
+            }),
+        )
+        .map_err(ImportError::RecordEvent)?;
+
+        transaction
+            .commit()
+            .map_err(ImportError::CommitTransaction)?;
+
+        // Only once the items are committed, a failed import should not leave folders behind
+        for item in &export.items {
+            fs::create_dir_all(self.item_path.join(item.id.to_string()))
+                .map_err(ImportError::CreateContentFolder)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct Fixture {
+        temp_dir: TempDir,
+        db: Db,
+    }
+
+    fn create_fixture() -> Fixture {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        Fixture { temp_dir, db }
+    }
+
+    #[test]
+    fn split_layout() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let layout = DbLayout {
+            db_path: temp_dir.path().join("fast/todo.db"),
+            content_root: temp_dir.path().join("bulk/content"),
+        };
+
+        let mut db = Db::with_layout(layout.clone()).expect("failed to create db");
+        let id = db.create_item("test").expect("failed to create item");
+        let content_folder = db
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+
+        assert!(layout.db_path.is_file());
+        assert_eq!(
+            content_folder,
+            layout
+                .content_root
+                .join(id.0.to_string())
+                .canonicalize()
+                .expect("content folder should exist")
+        );
+        assert!(!temp_dir.path().join("fast/items").exists());
+
+        drop(db);
+        let db = Db::with_layout(layout.clone()).expect("failed to reopen db");
+        assert_eq!(
+            db.get_item_by_id(id).expect("item should exist").name,
+            "test"
+        );
+
+        drop(db);
+        let db = Db::open_readonly_with_layout(layout).expect("failed to open db read only");
+        assert_eq!(
+            db.get_item_by_id(id).expect("item should exist").name,
+            "test"
+        );
+    }
+
+    #[test]
+    fn db_layout_args() {
+        let parse = |args: &[&str]| {
+            let mut it = args.iter().map(|arg| arg.to_string());
+            let mut layout_args = DbLayoutArgs::default();
+            let mut others = Vec::new();
+            while let Some(arg) = it.next() {
+                if !layout_args.parse_arg(&arg, &mut it)? {
+                    others.push(arg);
+                }
+            }
+            layout_args
+                .into_layout()
+                .map(|layout| (layout.db_path, layout.content_root, others))
+        };
+
+        let (db_path, content_root, others) =
+            parse(&["--db-path", "/db", "--json"]).expect("failed to parse args");
+        assert_eq!(db_path, Path::new("/db").join(METADATA_DB_NAME));
+        assert_eq!(content_root, Path::new("/db").join(ITEMS_DIR_NAME));
+        assert_eq!(others, ["--json"]);
+
+        let (db_path, content_root, _) =
+            parse(&["--db-file", "/fast/todo.db", "--content-dir", "/bulk"])
+                .expect("failed to parse args");
+        assert_eq!(db_path, Path::new("/fast/todo.db"));
+        assert_eq!(content_root, Path::new("/bulk"));
+
+        assert!(matches!(
+            parse(&["--db-file", "/fast/todo.db"]),
+            Err(DbLayoutArgError::IncompleteLayout)
+        ));
+        assert!(matches!(
+            parse(&["--db-path"]),
+            Err(DbLayoutArgError::ArgNotProvided(arg)) if arg == "--db-path"
+        ));
+        assert!(matches!(
+            parse(&[]),
+            Err(DbLayoutArgError::DbPathNotProvided)
+        ));
+    }
+
+    #[test]
+    fn close_and_reopen() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let mut db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        let id = db.create_item("test").expect("failed to create item");
+
+        // Writes land in the log until close checkpoints them into the database file
+        let wal_path = temp_dir.path().join(format!("{METADATA_DB_NAME}-wal"));
+        let wal_len = || fs::metadata(&wal_path).map_or(0, |metadata| metadata.len());
+        assert!(wal_len() > 0);
+        db.close().expect("failed to close db");
+        assert_eq!(wal_len(), 0);
+
+        let db = Db::new(temp_dir.path().into()).expect("failed to reopen db");
+        assert_eq!(
+            db.get_item_by_id(id).expect("item should exist").name,
+            "test"
+        );
+    }
+
+    #[test]
+    fn newer_schema_version() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        db.connection
+            .pragma_update(None, "user_version", 99)
+            .expect("failed to set user version");
+        db.close().expect("failed to close db");
+
+        let Err(OpenDbError::NewerThanSupported { found, supported }) =
+            Db::new(temp_dir.path().into())
+        else {
+            panic!("expected newer than supported error");
+        };
+        assert_eq!(found, 99);
+        assert_eq!(supported, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn open_readonly() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let mut db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        let id = db.create_item("test").expect("failed to create item");
+        db.close().expect("failed to close db");
+
+        let mut db = Db::open_readonly(temp_dir.path().into()).expect("failed to open db");
+        assert_eq!(
+            db.get_item_by_id(id).expect("item should exist").name,
+            "test"
+        );
+        let Err(CreateItemError::InsertItem(_)) = db.create_item("new") else {
+            panic!("expected write to fail");
+        };
+        assert_eq!(db.get_item_ids(0, None).expect("failed to get items"), [id]);
+
+        let Err(OpenDbError::OpenConnection(_)) =
+            Db::open_readonly(temp_dir.path().join("missing"))
+        else {
+            panic!("expected missing db to fail");
+        };
+    }
+
+    #[test]
+    fn events() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+
+        let events = fixture.db.get_events().expect("failed to get events");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "item_created");
+        assert_eq!(
+            events[0].detail,
+            serde_json::json!({ "id": id.0, "name": "test" })
+        );
+
+        // A folder in the way of the next item makes the create roll back
+        fs::create_dir_all(
+            fixture
+                .temp_dir
+                .path()
+                .join("items")
+                .join((id.0 + 1).to_string()),
+        )
+        .expect("failed to create folder");
+        let Err(CreateItemError::ItemExists) = fixture.db.create_item("blocked") else {
+            panic!("expected create to fail");
+        };
+
+        let events = fixture.db.get_events().expect("failed to get events");
+        assert_eq!(events.len(), 1);
+
+        let filter_id = fixture
+            .db
+            .add_filter("filter", &[])
+            .expect("failed to add filter");
+        fixture
+            .db
+            .rename_filter(filter_id, "renamed")
+            .expect("failed to rename filter");
+        let mut export = Vec::new();
+        fixture
+            .db
+            .export_json(&mut export)
+            .expect("failed to export");
+
+        let import_dir = tempfile::tempdir().expect("failed to create db dir");
+        let mut imported = Db::new(import_dir.path().into()).expect("failed to create db");
+        imported
+            .import_json(export.as_slice())
+            .expect("failed to import");
+
+        let events = fixture.db.get_events().expect("failed to get events");
+        assert_eq!(
+            events.last().map(|event| &*event.kind),
+            Some("filter_renamed")
+        );
+        assert_eq!(
+            events.last().map(|event| &event.detail),
+            Some(&serde_json::json!({ "id": filter_id.0, "name": "renamed" }))
+        );
+        let events = imported.get_events().expect("failed to get events");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "imported");
+        assert_eq!(events[0].detail["items"], 1);
+        assert_eq!(events[0].detail["filters"], 1);
+    }
+
+    #[test]
+    fn invalid_item_names() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .db
+            .create_item("padded")
+            .expect("failed to create item");
+
+        let cases = [
+            ("", InvalidItemNameError::Empty),
+            (".", InvalidItemNameError::DotName),
+            ("..", InvalidItemNameError::DotName),
+            (" \t", InvalidItemNameError::SurroundingWhitespace),
+            ("  padded\n", InvalidItemNameError::SurroundingWhitespace),
+            ("a/b", InvalidItemNameError::ContainsSlash),
+            ("a\0b", InvalidItemNameError::ContainsNul),
+        ];
+        for (name, expected) in cases {
+            let ret = fixture.db.create_item(name);
+            assert!(
+                matches!(&ret, Err(CreateItemError::InvalidItemName(e)) if *e == expected),
+                "create {name:?} returned {ret:?}"
+            );
+
+            let ret = fixture.db.rename_item(id, name);
+            assert!(
+                matches!(&ret, Err(RenameItemError::InvalidItemName(e)) if *e == expected),
+                "rename to {name:?} returned {ret:?}"
+            );
+        }
+
+        let ret = fixture
+            .db
+            .create_items(&["fine".to_string(), "a/b".to_string()]);
+        assert!(matches!(
+            ret,
+            Err(CreateItemsError::InvalidItemName(
+                1,
+                InvalidItemNameError::ContainsSlash
+            ))
+        ));
+
+        // Duplicate names are allowed
+        fixture
+            .db
+            .create_item("padded")
+            .expect("failed to create duplicate item");
+        assert_eq!(fixture.db.count_items().expect("failed to count items"), 2);
+    }
+
+    #[test]
+    fn create_items() {
+        let mut fixture = create_fixture();
+        let names: Vec<String> = (0..1000).map(|i| format!("item {i}")).collect();
+        let ids = fixture
+            .db
+            .create_items(&names)
+            .expect("failed to create items");
+
+        assert_eq!(ids.len(), names.len());
+        for (id, name) in ids.iter().zip(&names) {
+            let item = fixture.db.get_item_by_id(*id).expect("item should exist");
+            assert_eq!(&item.name, name);
+            assert!(item.path.exists());
+        }
+    }
+
+    #[test]
+    fn create_items_rolls_back() {
+        let mut fixture = create_fixture();
+        let existing = fixture
+            .db
+            .create_item("existing")
+            .expect("failed to create item");
+
+        // Occupy the content folder the third item would get
+        let blocked_path = fixture
+            .temp_dir
+            .path()
+            .join("items")
+            .join((existing.0 + 3).to_string());
+        fs::create_dir_all(&blocked_path).expect("failed to create folder");
+
+        let names: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        let Err(e) = fixture.db.create_items(&names) else {
+            panic!("expected batch to fail");
+        };
+        assert_eq!(e.index(), Some(2));
+
+        let items = fixture.db.get_items().expect("failed to get items");
+        assert_eq!(items.len(), 1);
+        assert!(!fixture
+            .temp_dir
+            .path()
+            .join("items")
+            .join((existing.0 + 1).to_string())
+            .exists());
+    }
+
+    #[test]
+    fn open_empty_db() {
+        create_fixture();
+    }
+
+    #[test]
+    fn open_populated_db() {
+        let fixture = create_fixture();
+        let db = Db::new(fixture.temp_dir.path().into()).expect("failed to create db");
+    }
+
+    #[test]
+    fn create_new_item() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+
+        let retrieved_item = fixture.db.get_item_by_id(id).expect("item should be in db");
+
+        assert!(retrieved_item.path.exists());
+        assert!(retrieved_item.path.is_dir());
+        assert_eq!(retrieved_item.id, id);
+        assert!(retrieved_item.relationships.is_empty());
+        assert_eq!(retrieved_item.name, "test");
+    }
+
+    #[test]
+    fn create_new_item_already_exists_on_disk() {
+        let mut fixture = create_fixture();
+
+        std::fs::create_dir_all(fixture.temp_dir.path().join("items/1"))
+            .expect("failed to create conflicting dir");
+
+        match fixture.db.create_item("test") {
+            Err(CreateItemError::ItemExists) => (),
+            _ => panic!("Unexpected response to creating existing item"),
+        };
+    }
+
+    #[test]
+    fn add_relationship_success() {
+        let mut fixture = create_fixture();
+        fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+    }
+
+    #[test]
+    fn add_relationship_already_exists() {
+        let mut fixture = create_fixture();
+        fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let Err(AddRelationshipError::AlreadyExists(_)) =
+            fixture.db.add_relationship("parents", "new_key")
+        else {
+            panic!("expected already exists");
+        };
+
+        let Err(AddRelationshipError::AlreadyExists(_)) =
+            fixture.db.add_relationship("new_key", "parents")
+        else {
+            panic!("expected already exists");
+        };
+
+        let Err(AddRelationshipError::AlreadyExists(_)) =
+            fixture.db.add_relationship("children", "new_key")
+        else {
+            panic!("expected already exists");
+        };
+
+        let Err(AddRelationshipError::AlreadyExists(_)) =
+            fixture.db.add_relationship("new_key", "children")
+        else {
+            panic!("expected already exists");
+        };
+
+        fixture
+            .db
+            .add_relationship("new_key", "new_key_2")
+            .expect("failed to create releationship with new key");
+    }
+
+    #[test]
+    fn with_transaction() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+
+        let ret = fixture.db.with_transaction(|db| {
+            let child = db.create_item("child").map_err(|_| "create item")?;
+            db.add_item_relationship(parent, ItemId(child.0 + 1), relationship_id)
+                .map_err(|_| "add item relationship")?;
+            Ok::<_, &str>(child)
+        });
+        let Err(TransactionError::Operation("add item relationship")) = ret else {
+            panic!("expected the second operation to fail");
+        };
+        assert_eq!(fixture.db.count_items().expect("failed to count items"), 1);
+
+        let child = fixture
+            .db
+            .with_transaction(|db| {
+                let child = db.create_item("child").map_err(|_| "create item")?;
+                db.add_item_relationship(parent, child, relationship_id)
+                    .map_err(|_| "add item relationship")?;
+                Ok::<_, &str>(child)
+            })
+            .expect("failed to run transaction");
+        assert_eq!(fixture.db.count_items().expect("failed to count items"), 2);
+        assert_eq!(
+            fixture
+                .db
+                .get_links_for_relationship(relationship_id)
+                .expect("failed to get links"),
+            [(parent, child)]
+        );
+    }
+
+    #[test]
+    fn get_or_create_relationship() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .db
+            .get_or_create_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let again = fixture
+            .db
+            .get_or_create_relationship("parents", "children")
+            .expect("failed to get relationship");
+        assert_eq!(id, again);
+
+        let flipped = fixture
+            .db
+            .get_or_create_relationship("children", "parents")
+            .expect("failed to get relationship");
+        assert_eq!(id, flipped);
+
+        // Sharing a single name with another relationship is still a conflict
+        let Err(AddRelationshipError::AlreadyExists(existing)) =
+            fixture.db.get_or_create_relationship("parents", "new_key")
+        else {
+            panic!("expected already exists");
+        };
+        assert_eq!(existing, id);
+
+        assert_eq!(
+            fixture.db.count_relationships().expect("failed to count"),
+            1
+        );
+    }
+
+    #[test]
+    fn get_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let relationship_id_2 = fixture
+            .db
+            .add_relationship("parents2", "children2")
+            .expect("failed to create relationship");
+
+        let relationship_1 = fixture
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship_1.from_name, "parents");
+        assert_eq!(relationship_1.to_name, "children");
+    }
+
+    #[test]
+    fn relationship_exists() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+
+        assert!(fixture
+            .db
+            .relationship_exists(relationship_id)
+            .expect("failed to check relationship"));
+        assert!(!fixture
+            .db
+            .relationship_exists(RelationshipId(relationship_id.0 + 1))
+            .expect("failed to check relationship"));
+    }
+
+    #[test]
+    fn count_items_and_relationships() {
+        let mut fixture = create_fixture();
+        assert_eq!(fixture.db.count_items().expect("failed to count items"), 0);
+        assert_eq!(
+            fixture
+                .db
+                .count_relationships()
+                .expect("failed to count relationships"),
+            0
+        );
+
+        fixture.db.create_item("a").expect("failed to create item");
+        fixture.db.create_item("b").expect("failed to create item");
+        fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+
+        assert_eq!(fixture.db.count_items().expect("failed to count items"), 2);
+        assert_eq!(
+            fixture
+                .db
+                .count_relationships()
+                .expect("failed to count relationships"),
+            1
+        );
+    }
+
+    #[test]
+    fn get_relationship_id_by_name() {
+        let mut fixture = create_fixture();
+        let parents = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let related = fixture
+            .db
+            .add_relationship("linked", "linked")
+            .expect("failed to create relationship");
+        let blocks = fixture
+            .db
+            .add_relationship("blocks", "blocked by")
+            .expect("failed to create relationship");
+        // add_relationship refuses names that are already in use, but databases from before that
+        // check can still contain duplicates
+        fixture
+            .db
+            .connection
+            .execute(
+                "INSERT INTO relationships(from_name, to_name) VALUES ('depends on', 'blocks')",
+                (),
+            )
+            .expect("failed to insert relationship");
+        let depends = RelationshipId(fixture.db.connection.last_insert_rowid());
+
+        let lookup = |name| {
+            fixture
+                .db
+                .get_relationship_id_by_name(name)
+                .expect("failed to look up relationship")
+        };
+        assert_eq!(lookup("parents"), Some(parents));
+        assert_eq!(lookup("children"), Some(parents));
+        assert_eq!(lookup("linked"), Some(related));
+        assert_eq!(lookup("missing"), None);
+
+        let Err(GetRelationshipIdByNameError::AmbiguousRelationship(mut ids)) =
+            fixture.db.get_relationship_id_by_name("blocks")
+        else {
+            panic!("expected ambiguous relationship");
+        };
+        ids.sort_by_key(|id| id.0);
+        assert_eq!(ids, [blocks, depends]);
+    }
+
+    #[test]
+    fn relationship_description() {
+        let mut fixture = create_fixture();
+        let described = fixture
+            .db
+            .add_relationship_with_description("blocked by", "blocks", "work ordering")
+            .expect("failed to create relationship");
+        let undescribed = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+
+        let relationship = fixture
+            .db
+            .get_relationship(described)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.description, "work ordering");
+
+        let relationship = fixture
+            .db
+            .get_relationship(undescribed)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.description, "");
+    }
+
+    #[test]
+    fn relationship_description_added_to_old_db() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let layout = DbLayout::in_dir(temp_dir.path());
+
+        let connection = Connection::open(&layout.db_path).expect("failed to open connection");
+        connection
+            .execute(
+                "CREATE TABLE relationships(id INTEGER PRIMARY KEY, from_name TEXT NOT NULL, to_name TEXT_NOT_NULL)",
+                (),
+            )
+            .expect("failed to create old relationships table");
+        connection
+            .execute(
+                "INSERT INTO relationships(from_name, to_name) VALUES ('parents', 'children')",
+                (),
+            )
+            .expect("failed to insert relationship");
+        drop(connection);
+
+        let db = Db::with_layout(layout).expect("failed to open db");
+        let relationships = db.get_relationships().expect("failed to get relationships");
+        assert_eq!(relationships.len(), 1);
+        assert_eq!(relationships[0].from_name, "parents");
+        assert_eq!(relationships[0].description, "");
+    }
+
+    #[test]
+    fn get_all_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let relationship_id_2 = fixture
+            .db
+            .add_relationship("parents2", "children2")
+            .expect("failed to create relationship");
+
+        use std::collections::HashMap;
+
+        let items: HashMap<String, String> = fixture
+            .db
+            .get_relationships()
+            .expect("failed to get relationships")
+            .into_iter()
+            .map(|item| (item.from_name, item.to_name))
+            .collect();
+
+        assert_eq!(items.get("parents").map(|x| x.as_ref()), Some("children"));
+        assert_eq!(items.get("parents2").map(|x| x.as_ref()), Some("children2"));
+    }
+
+    #[test]
+    fn rename_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parnets", "children")
+            .expect("failed to create relationship");
+
+        fixture
+            .db
+            .rename_relationship(relationship_id, Some("parents"), None)
+            .expect("failed to rename relationship");
+
+        let relationship = fixture
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.from_name, "parents");
+        assert_eq!(relationship.to_name, "children");
+
+        fixture
+            .db
+            .rename_relationship(relationship_id, Some("blockers"), Some("blocked"))
+            .expect("failed to rename relationship");
+
+        let relationship = fixture
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.from_name, "blockers");
+        assert_eq!(relationship.to_name, "blocked");
+    }
+
+    #[test]
+    fn swap_relationship_sides() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        let other = fixture
+            .db
+            .create_item("other")
+            .expect("failed to create item");
+        // Linked both ways to make sure reinserting the links does not collide
+        for (from_id, to_id) in [(parent, child), (parent, other), (other, parent)] {
+            fixture
+                .db
+                .add_item_relationship(from_id, to_id, relationship_id)
+                .expect("failed to add item relationship");
+        }
+
+        fixture
+            .db
+            .swap_relationship_sides(relationship_id)
+            .expect("failed to swap relationship sides");
+
+        let relationship = fixture
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.from_name, "parents");
+        assert_eq!(relationship.to_name, "children");
+
+        let child_item = fixture
+            .db
+            .get_item_by_id(child)
+            .expect("child does not exist");
+        assert_eq!(child_item.relationships.len(), 1);
+        assert_eq!(child_item.relationships[0].side, RelationshipSide::Source);
+        assert_eq!(child_item.relationships[0].sibling, parent);
+
+        let other_item = fixture
+            .db
+            .get_item_by_id(other)
+            .expect("other does not exist");
+        assert_eq!(other_item.relationships.len(), 2);
+
+        let Err(SwapRelationshipSidesError::RelationshipNotFound) = fixture
+            .db
+            .swap_relationship_sides(RelationshipId(relationship_id.0 + 1))
+        else {
+            panic!("swapping a missing relationship should fail");
+        };
+    }
+
+    #[test]
+    fn swap_relationship_sides_keeps_filters() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let [parent, child, loner] = ["parent", "child", "loner"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        fixture
+            .db
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let filters = [
+            vec![ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+            // Both sides of the same relationship, flipping one must not collide with the other
+            vec![
+                ItemFilterRule::NoRelationship(RelationshipSide::Source, relationship_id),
+                ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id),
+            ],
+            vec![ItemFilterRule::RelationshipCount(
+                RelationshipSide::Source,
+                relationship_id,
+                CompareOp::GreaterEqual,
+                1,
+            )],
+        ];
+        let filter_ids = filters
+            .iter()
+            .enumerate()
+            .map(|(i, rules)| {
+                fixture
+                    .db
+                    .add_filter(&format!("filter {i}"), rules)
+                    .expect("failed to add filter")
+            })
+            .collect::<Vec<_>>();
+
+        let run_saved_filters = |db: &mut Db| {
+            filter_ids
+                .iter()
+                .map(|id| {
+                    let filter = db
+                        .get_filter_by_id(*id)
+                        .expect("failed to get filter")
+                        .expect("filter does not exist");
+                    let mut items = db
+                        .run_filter(&filter.rules, None)
+                        .expect("failed to run filter");
+                    items.sort_by_key(|id| id.0);
+                    items
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let before = run_saved_filters(&mut fixture.db);
+        assert_eq!(before[0], [parent, loner]);
+        assert_eq!(before[1], [loner]);
+        assert_eq!(before[2], [parent]);
+
+        fixture
+            .db
+            .swap_relationship_sides(relationship_id)
+            .expect("failed to swap relationship sides");
+        assert_eq!(run_saved_filters(&mut fixture.db), before);
+    }
+
+    #[test]
+    fn pinned_items() {
+        let mut fixture = create_fixture();
+        let [first, second, trashed] = ["first", "second", "trashed"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        for id in [second, first, second, trashed] {
+            fixture.db.set_pinned(id, true).expect("failed to pin item");
+        }
+        fixture
+            .db
+            .trash_item(trashed)
+            .expect("failed to trash item");
+
+        let Err(SetPinnedError::ItemNotFound) = fixture.db.set_pinned(ItemId(trashed.0 + 1), true)
+        else {
+            panic!("pinning a missing item should fail");
+        };
+
+        // Pins are stored in the db, not in the mount
+        let Fixture { temp_dir, db } = fixture;
+        db.close().expect("failed to close db");
+        let mut db = Db::new(temp_dir.path().into()).expect("failed to reopen db");
+        assert_eq!(
+            db.get_pinned_items().expect("failed to get pinned items"),
+            [(first, "first".to_string()), (second, "second".to_string())]
+        );
+
+        db.set_pinned(first, false).expect("failed to unpin item");
+        db.set_pinned(first, false).expect("failed to unpin item");
+        db.restore_item(trashed).expect("failed to restore item");
+        assert_eq!(
+            db.get_pinned_items().expect("failed to get pinned items"),
+            [
+                (second, "second".to_string()),
+                (trashed, "trashed".to_string())
+            ]
+        );
+
+        // Repeated pins and unpins are not recorded
+        let kinds: Vec<String> = db
+            .get_events()
+            .expect("failed to get events")
+            .into_iter()
+            .map(|event| event.kind)
+            .filter(|kind| kind.contains("pinned"))
+            .collect();
+        assert_eq!(
+            kinds,
+            ["item_pinned", "item_pinned", "item_pinned", "item_unpinned"]
+        );
+    }
+
+    #[test]
+    fn saved_filter_query_cache() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let roots = fixture
+            .db
+            .add_filter(
+                "roots",
+                &[ItemFilterRule::NoRelationship(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
+        let children = fixture
+            .db
+            .add_filter(
+                "children",
+                &[ItemFilterRule::HasRelationshipWithVariableItem(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
+
+        for _ in 0..3 {
+            assert_eq!(
+                fixture
+                    .db
+                    .run_saved_filter(roots, None)
+                    .expect("failed to run filter"),
+                Some(vec![parent])
+            );
+        }
+        assert_eq!(fixture.db.filter_query_builds.get(), 1);
+
+        // The context is bound, so one query serves every item
+        for (context, expected) in [(parent, vec![child]), (child, vec![])] {
+            assert_eq!(
+                fixture
+                    .db
+                    .run_saved_filter(children, Some(context))
+                    .expect("failed to run filter"),
+                Some(expected)
+            );
+        }
+        assert_eq!(
+            fixture
+                .db
+                .run_saved_filter(children, None)
+                .expect("failed to run filter"),
+            Some(vec![])
+        );
+        assert_eq!(fixture.db.filter_query_builds.get(), 2);
+
+        assert_eq!(
+            fixture
+                .db
+                .run_saved_filter(FilterId(children.0 + 1), None)
+                .expect("failed to run filter"),
+            None
+        );
+
+        // Filters can be built from other filters, so any change drops every cached query
+        fixture
+            .db
+            .add_filter("everything", &[])
+            .expect("failed to add filter");
+        assert!(fixture.db.filter_queries.borrow().is_empty());
+        fixture
+            .db
+            .run_saved_filter(roots, None)
+            .expect("failed to run filter");
+        assert_eq!(fixture.db.filter_query_builds.get(), 3);
+    }
+
+    #[test]
+    fn find_items_by_name_prefix() {
+        let mut fixture = create_fixture();
+        let names = [
+            "groceries",
+            "Grocery list",
+            "garden",
+            "100% done",
+            "100 things",
+            "a_b",
+            "axb",
+            "c\\d",
+        ];
+        let ids: Vec<ItemId> = names
+            .iter()
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"))
+            .collect();
+
+        let find = |db: &Db, prefix, limit| {
+            db.find_items_by_name_prefix(prefix, limit)
+                .expect("failed to search items")
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(find(&fixture.db, "groc", 10), ["Grocery list", "groceries"]);
+        assert_eq!(find(&fixture.db, "groc", 1), ["Grocery list"]);
+        assert_eq!(find(&fixture.db, "g", -1).len(), 3);
+        assert!(find(&fixture.db, "nothing", 10).is_empty());
+
+        // Wildcards in the prefix only match themselves
+        assert_eq!(find(&fixture.db, "100%", 10), ["100% done"]);
+        assert_eq!(find(&fixture.db, "a_", 10), ["a_b"]);
+        assert_eq!(find(&fixture.db, "%", 10), Vec::<String>::new());
+        assert_eq!(find(&fixture.db, "c\\", 10), ["c\\d"]);
+
+        fixture.db.trash_item(ids[0]).expect("failed to trash item");
+        assert_eq!(find(&fixture.db, "groc", 10), ["Grocery list"]);
+    }
+
+    #[test]
+    fn add_item_relationships() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let children: Vec<ItemId> = (0..5)
+            .map(|i| {
+                fixture
+                    .db
+                    .create_item(&format!("child {i}"))
+                    .expect("failed to create item")
+            })
+            .collect();
+        fixture
+            .db
+            .add_item_relationship(parent, children[0], relationship_id)
+            .expect("failed to add item relationship");
+
+        let mut links: Vec<_> = children
+            .iter()
+            .map(|child| (parent, *child, relationship_id))
+            .collect();
+        links.push((parent, children[1], relationship_id));
+
+        let Err(AddItemRelationshipsError::Duplicate(0)) =
+            fixture.db.add_item_relationships(&links, true)
+        else {
+            panic!("existing link should abort the batch");
+        };
+        assert_eq!(
+            fixture
+                .db
+                .get_siblings(parent, relationship_id, RelationshipSide::Source)
+                .expect("failed to get siblings")
+                .len(),
+            1
+        );
+
+        let added = fixture
+            .db
+            .add_item_relationships(&links, false)
+            .expect("failed to add item relationships");
+        assert_eq!(
+            added,
+            [
+                AddedLink::Duplicate,
+                AddedLink::Added,
+                AddedLink::Added,
+                AddedLink::Added,
+                AddedLink::Added,
+                AddedLink::Duplicate,
+            ]
+        );
+        assert_eq!(
+            fixture
+                .db
+                .get_siblings(parent, relationship_id, RelationshipSide::Source)
+                .expect("failed to get siblings")
+                .len(),
+            5
+        );
+
+        // A bad link rolls back the links before it
+        let other = fixture
+            .db
+            .create_item("other")
+            .expect("failed to create item");
+        let err = fixture
+            .db
+            .add_item_relationships(
+                &[
+                    (other, parent, relationship_id),
+                    (other, ItemId(other.0 + 1), relationship_id),
+                ],
+                false,
+            )
+            .expect_err("linking a missing item should fail");
+        assert_eq!(err.index(), Some(1));
+        assert!(fixture
+            .db
+            .get_item_by_id(other)
+            .expect("item missing")
+            .relationships
+            .is_empty());
+    }
+
+    #[test]
+    fn links_for_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let other_relationship_id = fixture
+            .db
+            .add_relationship("blocks", "blocked by")
+            .expect("failed to create relationship");
+        let [a, b, c] = ["a", "b", "c"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        for (from_id, to_id, relationship_id) in [
+            (b, c, relationship_id),
+            (a, c, relationship_id),
+            (a, b, relationship_id),
+            (a, c, other_relationship_id),
+        ] {
+            fixture
+                .db
+                .add_item_relationship(from_id, to_id, relationship_id)
+                .expect("failed to add item relationship");
+        }
+
+        assert_eq!(
+            fixture
+                .db
+                .get_links_for_relationship(relationship_id)
+                .expect("failed to get links"),
+            [(a, b), (a, c), (b, c)]
+        );
+
+        fixture.db.trash_item(c).expect("failed to trash item");
+        assert_eq!(
+            fixture
+                .db
+                .get_links_for_relationship(relationship_id)
+                .expect("failed to get links"),
+            [(a, b)]
+        );
+    }
+
+    #[test]
+    fn merge_items() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("children", "parents")
+            .expect("failed to create relationship");
+        let [project, keep, remove, child, tag] = ["project", "keep", "remove", "child", "tag"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        for (from_id, to_id) in [
+            (project, keep),
+            (project, remove),
+            (remove, child),
+            (tag, remove),
+            (keep, remove),
+        ] {
+            fixture
+                .db
+                .add_item_relationship(from_id, to_id, relationship_id)
+                .expect("failed to add item relationship");
+        }
+
+        let keep_content = fixture
+            .db
+            .content_folder_for_id(keep)
+            .expect("failed to get content folder");
+        let remove_content = fixture
+            .db
+            .content_folder_for_id(remove)
+            .expect("failed to get content folder");
+        fs::write(keep_content.join("notes.txt"), "keep").expect("failed to write content");
+        fs::write(remove_content.join("notes.txt"), "remove").expect("failed to write content");
+        fs::write(remove_content.join("other"), "other").expect("failed to write content");
+
+        let Err(MergeItemsError::SameItem) = fixture.db.merge_items(keep, keep) else {
+            panic!("merging an item into itself should fail");
+        };
+
+        fixture
+            .db
+            .merge_items(keep, remove)
+            .expect("failed to merge items");
+
+        assert!(fixture.db.get_item_by_id(remove).is_none());
+        let sibling_ids = |fixture: &Fixture, id, side| {
+            let mut ids: Vec<ItemId> = fixture
+                .db
+                .get_siblings(id, relationship_id, side)
+                .expect("failed to get siblings")
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            ids.sort_by_key(|id| id.0);
+            ids
+        };
+        assert_eq!(
+            sibling_ids(&fixture, keep, RelationshipSide::Source),
+            [child]
+        );
+        assert_eq!(
+            sibling_ids(&fixture, keep, RelationshipSide::Dest),
+            [project, tag]
+        );
+        assert_eq!(
+            sibling_ids(&fixture, project, RelationshipSide::Source),
+            [keep]
+        );
+
+        assert_eq!(
+            fs::read_to_string(keep_content.join("notes.txt")).expect("failed to read content"),
+            "keep"
+        );
+        assert_eq!(
+            fs::read_to_string(keep_content.join("notes-1.txt")).expect("failed to read content"),
+            "remove"
+        );
+        assert!(keep_content.join("other").exists());
+        assert!(!remove_content.exists());
+
+        let Err(MergeItemsError::ItemNotFound) = fixture.db.merge_items(keep, remove) else {
+            panic!("merging a deleted item should fail");
+        };
+    }
+
+    #[test]
+    fn undo_content_merge() {
+        let temp_dir = tempfile::tempdir().expect("failed to create dir");
+        let from = temp_dir.path().join("from");
+        let into = temp_dir.path().join("into");
+        fs::create_dir_all(from.join("dir")).expect("failed to create dir");
+        fs::create_dir_all(&into).expect("failed to create dir");
+        fs::write(from.join("notes.txt"), "from").expect("failed to write content");
+        fs::write(into.join("notes.txt"), "into").expect("failed to write content");
+
+        let moves = merge_content_folder(&from, &into).expect("failed to merge content");
+        assert_eq!(moves.len(), 2);
+        assert!(!from.exists());
+
+        super::undo_content_merge(&from, &moves);
+
+        let list = |path: &Path| {
+            let mut names: Vec<_> = fs::read_dir(path)
+                .expect("failed to read dir")
+                .map(|entry| entry.expect("failed to read entry").file_name())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(list(&from), ["dir", "notes.txt"]);
+        assert_eq!(list(&into), ["notes.txt"]);
+        assert_eq!(
+            fs::read_to_string(from.join("notes.txt")).expect("failed to read content"),
+            "from"
+        );
+        assert_eq!(
+            fs::read_to_string(into.join("notes.txt")).expect("failed to read content"),
+            "into"
+        );
+    }
+
+    #[test]
+    fn rename_filter() {
+        let mut fixture = create_fixture();
+        let filter_id = fixture
+            .db
+            .add_filter("typo", &[])
+            .expect("failed to add filter");
+        let other_id = fixture
+            .db
+            .add_filter("other", &[])
+            .expect("failed to add filter");
+
+        fixture
+            .db
+            .rename_filter(filter_id, "fixed")
+            .expect("failed to rename filter");
+        let filter = fixture
+            .db
+            .get_filter_by_id(filter_id)
+            .expect("failed to get filter")
+            .expect("filter missing");
+        assert_eq!(filter.name, "fixed");
+
+        // Renaming to its own name is not a collision
+        fixture
+            .db
+            .rename_filter(filter_id, "fixed")
+            .expect("failed to rename filter");
+
+        let Err(RenameFilterError::AlreadyExists(existing)) =
+            fixture.db.rename_filter(filter_id, "other")
+        else {
+            panic!("expected name collision");
+        };
+        assert_eq!(existing, other_id);
+
+        let Err(RenameFilterError::FilterNotFound) = fixture
+            .db
+            .rename_filter(FilterId(other_id.0 + 1), "missing")
+        else {
+            panic!("expected missing filter");
+        };
+    }
+
+    #[test]
+    fn clone_filter() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        let condition_id = fixture
+            .db
+            .add_filter("condition", &[ItemFilterRule::Orphan])
+            .expect("failed to add filter");
+        let source_id = fixture
+            .db
+            .add_ordered_filter(
+                "source",
+                &[
+                    ItemFilterRule::NoRelationship(RelationshipSide::Source, relationship_id),
+                    ItemFilterRule::Orphan,
+                    ItemFilterRule::RelationshipCount(
+                        RelationshipSide::Dest,
+                        relationship_id,
+                        CompareOp::GreaterEqual,
+                        2,
+                    ),
+                ],
+                &[condition_id],
+                Some(FilterOrder::NameDesc),
+            )
+            .expect("failed to add filter");
+
+        let clone_id = fixture
+            .db
+            .clone_filter(source_id, "clone")
+            .expect("failed to clone filter");
+        assert_ne!(clone_id, source_id);
+
+        let source = fixture
+            .db
+            .get_filter_by_id(source_id)
+            .expect("failed to get filter")
+            .expect("filter missing");
+        let clone = fixture
+            .db
+            .get_filter_by_id(clone_id)
+            .expect("failed to get filter")
+            .expect("filter missing");
+        assert_eq!(clone.name, "clone");
+        assert_eq!(clone.rules, source.rules);
+        assert_eq!(clone.conditions, source.conditions);
+        assert_eq!(clone.order, source.order);
+
+        // Editing the source leaves the clone untouched
+        fixture
+            .db
+            .rename_filter(source_id, "renamed")
+            .expect("failed to rename filter");
+        fixture
+            .db
+            .connection
+            .execute(
+                "DELETE FROM orphan_filters WHERE filter_id = ?1",
+                [source_id.0],
+            )
+            .expect("failed to delete rule");
+        let clone = fixture
+            .db
+            .get_filter_by_id(clone_id)
+            .expect("failed to get filter")
+            .expect("filter missing");
+        assert_eq!(clone.name, "clone");
+        assert_eq!(clone.rules, source.rules);
+
+        let Err(AddFilterError::FilterNotFound) =
+            fixture.db.clone_filter(FilterId(clone_id.0 + 1), "missing")
+        else {
+            panic!("expected missing filter");
+        };
+    }
+
+    #[test]
+    fn item_filter_details() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        let condition_id = fixture
+            .db
+            .add_filter("top level", &[ItemFilterRule::Orphan])
+            .expect("failed to add filter");
+        let rules = [
+            ItemFilterRule::HasRelationshipWithVariableItem(
+                RelationshipSide::Dest,
+                relationship_id,
+            ),
+            ItemFilterRule::RelationshipCount(
+                RelationshipSide::Source,
+                relationship_id,
+                CompareOp::Less,
+                3,
+            ),
+        ];
+        let filter_id = fixture
+            .db
+            .add_ordered_filter(
+                "todo children",
+                &rules,
+                &[condition_id],
+                Some(FilterOrder::CreatedAsc),
+            )
+            .expect("failed to add filter");
+
+        let details = fixture
+            .db
+            .get_item_filter_details()
+            .expect("failed to get item filter details");
+
+        // The condition itself has no context and no conditions, so it is a root filter
+        let [details] = &details[..] else {
+            panic!("expected a single item filter, got {details:?}");
+        };
+        let descriptions: Vec<String> = rules
+            .iter()
+            .map(|rule| {
+                fixture
+                    .db
+                    .describe_rule(rule)
+                    .expect("failed to describe rule")
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            *details,
+            ItemFilterDetails {
+                id: filter_id.0,
+                name: "todo children".to_string(),
+                rules: rules
+                    .into_iter()
+                    .zip(descriptions)
+                    .map(|(rule, description)| ItemFilterRuleDetails { rule, description })
+                    .collect(),
+                conditions: vec![FilterConditionDetails {
+                    id: condition_id.0,
+                    name: "top level".to_string(),
+                }],
+                order_by: Some(FilterOrder::CreatedAsc),
+            }
+        );
+        assert!(details.rules[0]
+            .description
+            .ends_with("relationship as dest with the viewed item"));
+    }
+
+    #[test]
+    fn rename_relationship_collision() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let other_relationship_id = fixture
+            .db
+            .add_relationship("blockers", "blocked")
+            .expect("failed to create relationship");
+
+        let Err(RenameRelationshipError::AlreadyExists(id)) =
+            fixture
+                .db
+                .rename_relationship(relationship_id, Some("parents2"), Some("blocked"))
+        else {
+            panic!("expected already exists");
+        };
+        assert_eq!(id, other_relationship_id);
+
+        // Failed rename should not partially apply
+        let relationship = fixture
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.from_name, "parents");
+        assert_eq!(relationship.to_name, "children");
+
+        let Err(RenameRelationshipError::RelationshipNotFound) =
+            fixture
+                .db
+                .rename_relationship(RelationshipId(99), Some("new_name"), None)
+        else {
+            panic!("expected relationship not found");
+        };
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let mut output = Vec::new();
+        write_items_json_lines(
+            &mut output,
+            fixture.db.get_items().expect("failed to get items"),
+        )
+        .expect("failed to write items");
+        let items: Vec<serde_json::Value> = std::str::from_utf8(&output)
+            .expect("output should be utf8")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("line should be json"))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                serde_json::json!({
+                    "id": parent.0,
+                    "name": "parent",
+                    "relationships": [
+                        { "id": relationship_id.0, "side": "source", "sibling": child.0 },
+                    ],
+                }),
+                serde_json::json!({
+                    "id": child.0,
+                    "name": "child",
+                    "relationships": [
+                        { "id": relationship_id.0, "side": "dest", "sibling": parent.0 },
+                    ],
+                }),
+            ]
+        );
+
+        let mut output = Vec::new();
+        write_relationships_json_lines(
+            &mut output,
+            fixture
+                .db
+                .get_relationships()
+                .expect("failed to get relationships"),
+        )
+        .expect("failed to write relationships");
+        let relationship: serde_json::Value =
+            serde_json::from_slice(&output).expect("output should be json");
+        assert_eq!(
+            relationship,
+            serde_json::json!({ "id": relationship_id.0, "from_name": "parents", "to_name": "children" })
+        );
+        assert_eq!(output.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn reserved_relationship_name() {
+        let mut fixture = create_fixture();
+        let Err(AddRelationshipError::ReservedName(name)) = fixture
+            .db
+            .add_relationship(ALL_RELATED_FOLDER_NAME, "unrelated")
+        else {
+            panic!("expected reserved name");
+        };
+        assert_eq!(name, ALL_RELATED_FOLDER_NAME);
+
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let Err(RenameRelationshipError::ReservedName(_)) =
+            fixture
+                .db
+                .rename_relationship(relationship_id, None, Some(ALL_RELATED_FOLDER_NAME))
+        else {
+            panic!("expected reserved name");
+        };
+    }
+
+    #[test]
+    fn add_item_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let retrieved_1 = fixture
+            .db
+            .get_item_by_id(item_1)
+            .expect("failed to retrieve relationship");
+        let retrieved_2 = fixture
+            .db
+            .get_item_by_id(item_2)
+            .expect("failed to retrieve relationship");
+
+        assert_eq!(retrieved_1.relationships.len(), 1);
+        assert_eq!(retrieved_1.relationships[0].id, relationship_id);
+        assert_eq!(retrieved_1.relationships[0].side, RelationshipSide::Source);
+        assert_eq!(retrieved_1.relationships[0].sibling, item_2);
+
+        assert_eq!(retrieved_2.relationships.len(), 1);
+        assert_eq!(retrieved_2.relationships[0].id, relationship_id);
+        assert_eq!(retrieved_2.relationships[0].side, RelationshipSide::Dest);
+        assert_eq!(retrieved_2.relationships[0].sibling, item_1);
+    }
+
+    #[test]
+    fn add_item_relationship_to_self() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("blocked by", "blocks")
+            .expect("failed to create relationship");
+        let item_1 = fixture.db.create_item("test").expect("failed to add item");
+        let item_2 = fixture.db.create_item("test2").expect("failed to add item");
+
+        let Err(AddItemRelationshipError::SelfRelationship) =
+            fixture
+                .db
+                .add_item_relationship(item_1, item_1, relationship_id)
+        else {
+            panic!("self relationship should be rejected");
+        };
+        assert!(fixture
+            .db
+            .get_item_by_id(item_1)
+            .expect("item should exist")
+            .relationships
+            .is_empty());
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to add item relationship");
+
+        fixture
+            .db
+            .add_item_relationship_allow_self(item_2, item_2, relationship_id)
+            .expect("failed to add self relationship");
+        let relationships = fixture
+            .db
+            .get_item_by_id(item_2)
+            .expect("item should exist")
+            .relationships;
+        assert!(relationships
+            .iter()
+            .any(|relationship| relationship.sibling == item_2));
+    }
+
+    #[test]
+    fn remove_item_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture.db.create_item("test").expect("failed to add item");
+        let item_2 = fixture.db.create_item("test2").expect("failed to add item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to add item relationship");
+
+        let Err(RemoveItemRelationshipError::NotFound) =
+            fixture
+                .db
+                .remove_item_relationship(item_2, item_1, relationship_id)
+        else {
+            panic!("reversed item relationship should not exist");
+        };
+
+        fixture
+            .db
+            .remove_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to remove item relationship");
+        assert!(fixture
+            .db
+            .get_item_by_id(item_1)
+            .expect("item should exist")
+            .relationships
+            .is_empty());
+    }
+
+    #[test]
+    fn get_siblings() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let other_relationship_id = fixture
+            .db
+            .add_relationship("blocked by", "blocks")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to add item");
 
-    struct Fixture {
-        temp_dir: TempDir,
-        db: Db,
+        let mut children = Vec::new();
+        for i in 0..50 {
+            let name = format!("child {i}");
+            let child = fixture.db.create_item(&name).expect("failed to add item");
+            fixture
+                .db
+                .add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+            children.push((child, name));
+        }
+
+        let unrelated = fixture
+            .db
+            .create_item("unrelated")
+            .expect("failed to add item");
+        fixture
+            .db
+            .add_item_relationship(parent, unrelated, other_relationship_id)
+            .expect("failed to add item relationship");
+
+        let siblings = fixture
+            .db
+            .get_siblings(parent, relationship_id, RelationshipSide::Source)
+            .expect("failed to get siblings");
+        assert_eq!(siblings, children);
+
+        let siblings = fixture
+            .db
+            .get_siblings(children[3].0, relationship_id, RelationshipSide::Dest)
+            .expect("failed to get siblings");
+        assert_eq!(siblings, [(parent, "parent".to_string())]);
+
+        let siblings = fixture
+            .db
+            .get_siblings(parent, relationship_id, RelationshipSide::Dest)
+            .expect("failed to get siblings");
+        assert!(siblings.is_empty());
+    }
+
+    #[test]
+    fn add_item_relationship_already_exists() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+        else {
+            panic!("expected insertion error");
+        };
+    }
+
+    #[test]
+    fn item_relationships_from_id_foreign_key() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+            .db
+            .add_item_relationship(ItemId(99), item_2, relationship_id)
+        else {
+            panic!("expected insertion error");
+        };
+    }
+
+    #[test]
+    fn item_relationships_to_id_foreign_key() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+            .db
+            .add_item_relationship(item_1, ItemId(99), relationship_id)
+        else {
+            panic!("expected insertion error");
+        };
+    }
+
+    #[test]
+    fn item_relationships_relationship_id_foreign_key() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+            .db
+            .add_item_relationship(item_1, item_2, RelationshipId(99))
+        else {
+            panic!("expected insertion error");
+        };
+    }
+
+    #[test]
+    fn lookup_present_item_id_from_dest_sibling() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let item_id = fixture
+            .db
+            .get_sibling_id(item_1, RelationshipSide::Source, relationship_id, "test2")
+            .expect("failed to find item id");
+        assert_eq!(item_id, Some(item_2));
+    }
+
+    #[test]
+    fn lookup_missing_item_id_from_dest_sibling_no_sibling_name() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(item_1, RelationshipSide::Source, relationship_id, "invalid")
+        else {
+            panic!("did not expect to find sibling");
+        };
+    }
+
+    #[test]
+    fn lookup_missing_item_id_from_dest_sibling_no_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) = fixture.db.get_sibling_id(
+            item_1,
+            RelationshipSide::Source,
+            RelationshipId(99),
+            "test2",
+        ) else {
+            panic!("did not expect to find sibling");
+        };
+    }
+
+    #[test]
+    fn lookup_missing_item_id_from_dest_sibling_no_source_id() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) = fixture.db.get_sibling_id(
+            ItemId(99),
+            RelationshipSide::Source,
+            relationship_id,
+            "test2",
+        ) else {
+            panic!("did not expect to find sibling");
+        };
+    }
+
+    #[test]
+    fn lookup_missing_item_id_from_dest_sibling_wrong_side() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(item_1, RelationshipSide::Dest, relationship_id, "test2")
+        else {
+            panic!("did not expect to find sibling");
+        };
     }
 
-    fn create_fixture() -> Fixture {
-        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
-        let db = Db::new(temp_dir.path().into()).expect("failed to create db");
-        Fixture { temp_dir, db }
+    #[test]
+    fn lookup_present_item_id_from_source_sibling() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        fixture
+            .db
+            .get_sibling_id(item_2, RelationshipSide::Dest, relationship_id, "test")
+            .expect("failed to find sibling");
     }
 
     #[test]
-    fn open_empty_db() {
-        create_fixture();
+    fn lookup_missing_item_id_from_source_sibling_no_sibling_name() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(item_2, RelationshipSide::Dest, relationship_id, "invalid")
+        else {
+            panic!("did not expect to find sibling");
+        };
     }
 
     #[test]
-    fn open_populated_db() {
-        let fixture = create_fixture();
-        let db = Db::new(fixture.temp_dir.path().into()).expect("failed to create db");
+    fn lookup_missing_item_id_from_source_sibling_no_relationship() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(item_2, RelationshipSide::Dest, RelationshipId(99), "test")
+        else {
+            panic!("did not expect to find sibling");
+        };
     }
 
     #[test]
-    fn create_new_item() {
+    fn lookup_missing_item_id_from_source_sibling_no_source_id() {
         let mut fixture = create_fixture();
-        let id = fixture
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
             .db
             .create_item("test")
             .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
 
-        let retrieved_item = fixture.db.get_item_by_id(id).expect("item should be in db");
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(ItemId(99), RelationshipSide::Dest, relationship_id, "test")
+        else {
+            panic!("did not expect to find sibling");
+        };
+    }
 
-        assert!(retrieved_item.path.exists());
-        assert!(retrieved_item.path.is_dir());
-        assert_eq!(retrieved_item.id, id);
-        assert!(retrieved_item.relationships.is_empty());
-        assert_eq!(retrieved_item.name, "test");
+    #[test]
+    fn lookup_missing_item_id_from_source_sibling_wrong_side() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        fixture
+            .db
+            .add_item_relationship(item_1, item_2, relationship_id)
+            .expect("failed to create relationship");
+        let Ok(None) =
+            fixture
+                .db
+                .get_sibling_id(item_2, RelationshipSide::Source, relationship_id, "test")
+        else {
+            panic!("did not expect to find sibling");
+        };
     }
 
     #[test]
-    fn create_new_item_already_exists_on_disk() {
+    fn lookup_ambiguous_sibling() {
         let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child_1 = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        let child_2 = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
 
-        std::fs::create_dir_all(fixture.temp_dir.path().join("items/1"))
-            .expect("failed to create conflicting dir");
+        fixture
+            .db
+            .add_item_relationship(parent, child_1, relationship_id)
+            .expect("failed to create relationship");
+        fixture
+            .db
+            .add_item_relationship(parent, child_2, relationship_id)
+            .expect("failed to create relationship");
 
-        match fixture.db.create_item("test") {
-            Err(CreateItemError::ItemExists) => (),
-            _ => panic!("Unexpected response to creating existing item"),
+        let Err(GetSiblingIdError::AmbiguousSibling(ids)) =
+            fixture
+                .db
+                .get_sibling_id(parent, RelationshipSide::Source, relationship_id, "child")
+        else {
+            panic!("expected ambiguous sibling");
         };
+        assert_eq!(ids, vec![child_1, child_2]);
+    }
+
+    #[test]
+    fn get_item_by_id_success() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let item_id = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item = fixture
+            .db
+            .get_item_by_id(item_id)
+            .expect("failed to get item by id");
+    }
+
+    #[test]
+    fn get_item_by_id_missing_id() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        assert!(fixture.db.get_item_by_id(ItemId(99)).is_none());
+    }
+
+    #[test]
+    fn get_item_by_name() {
+        let mut fixture = create_fixture();
+        let item_1 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_2 = fixture
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let item_3 = fixture
+            .db
+            .create_item("test2")
+            .expect("failed to create item");
+
+        let ids = fixture
+            .db
+            .get_item_by_name("test")
+            .expect("failed to get items by name");
+        assert_eq!(ids, vec![item_1, item_2]);
+
+        let ids = fixture
+            .db
+            .get_item_by_name("test2")
+            .expect("failed to get items by name");
+        assert_eq!(ids, vec![item_3]);
+
+        let ids = fixture
+            .db
+            .get_item_by_name("missing")
+            .expect("failed to get items by name");
+        assert!(ids.is_empty());
+    }
+
+    #[test]
+    fn describe_rules() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        let missing_id = RelationshipId(relationship_id.0 + 1);
+
+        let rules = [
+            (
+                ItemFilterRule::NoRelationship(RelationshipSide::Source, relationship_id),
+                "no 'children' relationship as source",
+            ),
+            (
+                ItemFilterRule::HasRelationshipWithVariableItem(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                ),
+                "'parents' relationship as dest with the viewed item",
+            ),
+            (
+                ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Source,
+                    relationship_id,
+                    CompareOp::GreaterEqual,
+                    3,
+                ),
+                "number of 'children' relationships as source >= 3",
+            ),
+            (
+                ItemFilterRule::NoRelationship(RelationshipSide::Dest, missing_id),
+                &format!("no '#{}' relationship as dest", missing_id.0),
+            ),
+        ];
+
+        for (rule, expected) in &rules {
+            let description = fixture
+                .db
+                .describe_rule(rule)
+                .expect("failed to describe rule");
+            assert_eq!(description.to_string(), *expected);
+        }
     }
 
-    #[test]
-    fn add_relationship_success() {
-        let mut fixture = create_fixture();
-        fixture
-            .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
+    #[test]
+    fn add_filter_to_db() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+
+        fixture
+            .db
+            .add_filter(
+                "my_filter",
+                &[ItemFilterRule::NoRelationship(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
+
+        let filters = fixture.db.get_filters().expect("failed to get filters");
+
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].name, "my_filter");
+        assert_eq!(filters[0].rules.len(), 1);
+        assert_eq!(
+            filters[0].rules[0],
+            ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id)
+        );
     }
 
     #[test]
-    fn add_relationship_already_exists() {
+    fn add_filter_rolls_back() {
         let mut fixture = create_fixture();
-        fixture
+        let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let Err(AddRelationshipError::AlreadyExists(_)) =
-            fixture.db.add_relationship("parents", "new_key")
-        else {
-            panic!("expected already exists");
-        };
 
-        let Err(AddRelationshipError::AlreadyExists(_)) =
-            fixture.db.add_relationship("new_key", "parents")
-        else {
-            panic!("expected already exists");
+        // First rule is fine, second references a relationship that does not exist
+        let Err(AddFilterError::InsertRule(_)) = fixture.db.add_filter(
+            "broken",
+            &[
+                ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id),
+                ItemFilterRule::NoRelationship(
+                    RelationshipSide::Source,
+                    RelationshipId(relationship_id.0 + 1),
+                ),
+            ],
+        ) else {
+            panic!("expected rule insertion to fail");
         };
 
-        let Err(AddRelationshipError::AlreadyExists(_)) =
-            fixture.db.add_relationship("children", "new_key")
-        else {
-            panic!("expected already exists");
+        let Err(AddFilterError::InsertCondition(_)) = fixture.db.add_ordered_filter(
+            "broken",
+            &[ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+            &[FilterId(100)],
+            None,
+        ) else {
+            panic!("expected condition insertion to fail");
         };
 
-        let Err(AddRelationshipError::AlreadyExists(_)) =
-            fixture.db.add_relationship("new_key", "children")
-        else {
-            panic!("expected already exists");
-        };
+        let filters = fixture.db.get_filters().expect("failed to get filters");
+        assert!(filters.is_empty());
 
-        fixture
+        let num_rules: i64 = fixture
             .db
-            .add_relationship("new_key", "new_key_2")
-            .expect("failed to create releationship with new key");
+            .connection
+            .query_row("SELECT COUNT(*) FROM no_relationship_filters", (), |row| {
+                row.get(0)
+            })
+            .expect("failed to count rules");
+        assert_eq!(num_rules, 0);
     }
 
     #[test]
-    fn get_relationship() {
+    fn get_filter_by_id() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let relationship_id_2 = fixture
+        fixture
             .db
-            .add_relationship("parents2", "children2")
-            .expect("failed to create relationship");
+            .add_filter("orphans", &[ItemFilterRule::Orphan])
+            .expect("failed to add filter");
+        let rules = [
+            ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id),
+            ItemFilterRule::HasRelationshipWithVariableItem(
+                RelationshipSide::Source,
+                relationship_id,
+            ),
+        ];
+        let condition = fixture
+            .db
+            .add_filter("condition", &[ItemFilterRule::Orphan])
+            .expect("failed to add filter");
+        let filter_id = fixture
+            .db
+            .add_ordered_filter("roots", &rules, &[condition], None)
+            .expect("failed to add filter");
 
-        let relationship_1 = fixture
+        let filter = fixture
             .db
-            .get_relationship(relationship_id)
-            .expect("failed to get relationship")
-            .expect("relationship does not exist");
-        assert_eq!(relationship_1.from_name, "parents");
-        assert_eq!(relationship_1.to_name, "children");
+            .get_filter_by_id(filter_id)
+            .expect("failed to get filter")
+            .expect("filter should exist");
+        assert_eq!(filter.id, filter_id);
+        assert_eq!(filter.name, "roots");
+        assert_eq!(filter.rules, rules);
+        assert_eq!(filter.conditions, [condition]);
+
+        let missing = fixture
+            .db
+            .get_filter_by_id(FilterId(filter_id.0 + 1))
+            .expect("failed to get filter");
+        assert!(missing.is_none());
     }
 
     #[test]
-    fn get_all_relationship() {
+    fn orphan_filter() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let relationship_id_2 = fixture
+        let parent = fixture
             .db
-            .add_relationship("parents2", "children2")
-            .expect("failed to create relationship");
-
-        use std::collections::HashMap;
+            .create_item("parent")
+            .expect("failed to create item");
+        let child = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        let orphan_1 = fixture
+            .db
+            .create_item("orphan 1")
+            .expect("failed to create item");
+        let orphan_2 = fixture
+            .db
+            .create_item("orphan 2")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
 
-        let items: HashMap<String, String> = fixture
+        fixture
             .db
-            .get_relationships()
-            .expect("failed to get relationships")
-            .into_iter()
-            .map(|item| (item.from_name, item.to_name))
-            .collect();
+            .add_filter("orphans", &[ItemFilterRule::Orphan])
+            .expect("failed to add filter");
+        let filters = fixture.db.get_filters().expect("failed to get filters");
+        assert_eq!(filters[0].rules, vec![ItemFilterRule::Orphan]);
 
-        assert_eq!(items.get("parents").map(|x| x.as_ref()), Some("children"));
-        assert_eq!(items.get("parents2").map(|x| x.as_ref()), Some("children2"));
+        let ids = fixture
+            .db
+            .run_filter(&filters[0].rules, None)
+            .expect("failed to run filter");
+        assert_eq!(ids, vec![orphan_1, orphan_2]);
     }
 
     #[test]
-    fn add_item_relationship() {
+    fn related_to_filter() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let mut create_item = |name| fixture.db.create_item(name).expect("failed to create item");
+        let alpha = create_item("alpha");
+        let beta = create_item("beta");
+        let alpha_task = create_item("task 1");
+        let beta_task = create_item("task 2");
+        let subtask = create_item("subtask");
+        for (parent, child) in [
+            (alpha, alpha_task),
+            (beta, beta_task),
+            (alpha_task, subtask),
+        ] {
+            fixture
+                .db
+                .add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+        }
+        let starred_id = fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+            .add_relationship("starred", "starred by")
+            .expect("failed to create relationship");
+        let star = fixture
             .db
-            .create_item("test2")
+            .create_item("star")
             .expect("failed to create item");
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let retrieved_1 = fixture
+            .add_item_relationship(alpha, star, starred_id)
+            .expect("failed to add item relationship");
+
+        let projects = fixture
             .db
-            .get_item_by_id(item_1)
-            .expect("failed to retrieve relationship");
-        let retrieved_2 = fixture
+            .add_filter(
+                "projects",
+                &[ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Source,
+                    starred_id,
+                    CompareOp::GreaterEqual,
+                    1,
+                )],
+            )
+            .expect("failed to add filter");
+        let tasks = fixture
             .db
-            .get_item_by_id(item_2)
-            .expect("failed to retrieve relationship");
+            .add_filter(
+                "tasks",
+                &[ItemFilterRule::RelatedToFilter(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                    projects,
+                )],
+            )
+            .expect("failed to add filter");
+        let subtasks = fixture
+            .db
+            .add_filter(
+                "subtasks",
+                &[ItemFilterRule::RelatedToFilter(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                    tasks,
+                )],
+            )
+            .expect("failed to add filter");
 
-        assert_eq!(retrieved_1.relationships.len(), 1);
-        assert_eq!(retrieved_1.relationships[0].id, relationship_id);
-        assert_eq!(retrieved_1.relationships[0].side, RelationshipSide::Source);
-        assert_eq!(retrieved_1.relationships[0].sibling, item_2);
+        let filters = fixture.db.get_filters().expect("failed to get filters");
+        let rules_for = |id| {
+            &filters
+                .iter()
+                .find(|filter| filter.id == id)
+                .expect("filter missing")
+                .rules
+        };
+        assert_eq!(
+            rules_for(tasks),
+            &vec![ItemFilterRule::RelatedToFilter(
+                RelationshipSide::Dest,
+                relationship_id,
+                projects
+            )]
+        );
 
-        assert_eq!(retrieved_2.relationships.len(), 1);
-        assert_eq!(retrieved_2.relationships[0].id, relationship_id);
-        assert_eq!(retrieved_2.relationships[0].side, RelationshipSide::Dest);
-        assert_eq!(retrieved_2.relationships[0].sibling, item_1);
+        let ids = fixture
+            .db
+            .run_filter(rules_for(tasks), None)
+            .expect("failed to run filter");
+        assert_eq!(ids, vec![alpha_task]);
+
+        let ids = fixture
+            .db
+            .run_filter(rules_for(subtasks), None)
+            .expect("failed to run filter");
+        assert_eq!(ids, vec![subtask]);
+
+        let ids = fixture
+            .db
+            .run_filter(
+                &[ItemFilterRule::RelatedToFilter(
+                    RelationshipSide::Source,
+                    relationship_id,
+                    subtasks,
+                )],
+                None,
+            )
+            .expect("failed to run filter");
+        assert_eq!(ids, vec![alpha_task]);
+
+        // Only an import can make a filter reference itself, the id is unknown before it is added
+        fixture
+            .db
+            .connection
+            .execute(
+                "INSERT INTO related_to_filter_filters(filter_id, side, relationship_id, sub_filter_id) VALUES (?1, ?2, ?3, ?1)",
+                [projects.0, RelationshipSide::Dest.as_i64(), relationship_id.0],
+            )
+            .expect("failed to insert rule");
+        let Err(RunFilterError::TooDeep(_)) = fixture.db.run_filter(
+            &[ItemFilterRule::RelatedToFilter(
+                RelationshipSide::Dest,
+                relationship_id,
+                projects,
+            )],
+            None,
+        ) else {
+            panic!("self referencing filter should fail");
+        };
+
+        let Err(RunFilterError::SubFilterNotFound(_)) = fixture.db.run_filter(
+            &[ItemFilterRule::RelatedToFilter(
+                RelationshipSide::Dest,
+                relationship_id,
+                FilterId(subtasks.0 + 1),
+            )],
+            None,
+        ) else {
+            panic!("missing sub filter should fail");
+        };
     }
 
     #[test]
-    fn add_item_relationship_already_exists() {
+    fn filters_intersection() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let mut ids = Vec::new();
+        for name in ["lone", "parent", "middle", "child"] {
+            ids.push(fixture.db.create_item(name).expect("failed to create item"));
+        }
+        for (from, to) in [(ids[1], ids[2]), (ids[2], ids[3])] {
+            fixture
+                .db
+                .add_item_relationship(from, to, relationship_id)
+                .expect("failed to add item relationship");
+        }
+
+        let no_parent = fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+            .add_filter(
+                "no parent",
+                &[ItemFilterRule::NoRelationship(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
+        let no_children = fixture
             .db
-            .create_item("test2")
-            .expect("failed to create item");
+            .add_filter(
+                "no children",
+                &[ItemFilterRule::NoRelationship(
+                    RelationshipSide::Source,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
 
-        fixture
+        let mut expected = Vec::new();
+        let individual: Vec<Vec<ItemId>> = [no_parent, no_children]
+            .iter()
+            .map(|id| {
+                fixture
+                    .db
+                    .run_filters_intersection(&[*id])
+                    .expect("failed to run filter")
+            })
+            .collect();
+        for id in &individual[0] {
+            if individual[1].contains(id) {
+                expected.push(*id);
+            }
+        }
+
+        let intersection = fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+            .run_filters_intersection(&[no_parent, no_children])
+            .expect("failed to run intersection");
+        assert_eq!(intersection, expected);
+        assert_eq!(intersection, vec![ids[0]]);
+
+        let Err(RunFiltersIntersectionError::FilterNotFound(_)) = fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
+            .run_filters_intersection(&[no_parent, FilterId(no_children.0 + 1)])
         else {
-            panic!("expected insertion error");
+            panic!("expected missing filter");
         };
     }
 
     #[test]
-    fn item_relationships_from_id_foreign_key() {
+    fn relationship_count_filter() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let parent = fixture
             .db
-            .create_item("test")
+            .create_item("parent")
             .expect("failed to create item");
-        let item_2 = fixture
+        let lonely = fixture
             .db
-            .create_item("test2")
+            .create_item("lonely")
             .expect("failed to create item");
+        let mut children = Vec::new();
+        for _ in 0..2 {
+            let child = fixture
+                .db
+                .create_item("child")
+                .expect("failed to create item");
+            fixture
+                .db
+                .add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+            children.push(child);
+        }
 
-        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+        let no_children = ItemFilterRule::RelationshipCount(
+            RelationshipSide::Source,
+            relationship_id,
+            CompareOp::Equal,
+            0,
+        );
+        fixture
             .db
-            .add_item_relationship(ItemId(99), item_2, relationship_id)
+            .add_filter("no_children", std::slice::from_ref(&no_children))
+            .expect("failed to add filter");
+        let filters = fixture.db.get_filters().expect("failed to get filters");
+        let ids = fixture
+            .db
+            .run_filter(&filters[0].rules, None)
+            .expect("failed to run filter");
+        assert_eq!(ids, [lonely, children[0], children[1]]);
+
+        let ids = fixture
+            .db
+            .run_filter(
+                &[ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Source,
+                    relationship_id,
+                    CompareOp::GreaterEqual,
+                    2,
+                )],
+                None,
+            )
+            .expect("failed to run filter");
+        assert_eq!(ids, [parent]);
+
+        let ids = fixture
+            .db
+            .run_filter(
+                &[ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                    CompareOp::Equal,
+                    1,
+                )],
+                None,
+            )
+            .expect("failed to run filter");
+        assert_eq!(ids, children);
+
+        let Err(AddFilterError::InsertRule(_)) = fixture
+            .db
+            .add_filter("duplicate", &[no_children.clone(), no_children])
         else {
-            panic!("expected insertion error");
+            panic!("expected duplicate rule to be rejected");
         };
     }
 
     #[test]
-    fn item_relationships_to_id_foreign_key() {
+    fn filter_without_rules_matches_every_item() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
-            .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        let item_1 = fixture
-            .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+        let [a, b, trashed] = ["a", "b", "trashed"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        fixture
             .db
-            .create_item("test2")
-            .expect("failed to create item");
+            .trash_item(trashed)
+            .expect("failed to trash item");
 
-        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+        let filter_id = fixture
             .db
-            .add_item_relationship(item_1, ItemId(99), relationship_id)
-        else {
-            panic!("expected insertion error");
-        };
+            .add_filter("everything", &[])
+            .expect("filters without rules should be accepted");
+        assert_eq!(
+            fixture
+                .db
+                .run_saved_filter(filter_id, None)
+                .expect("failed to run filter"),
+            Some(vec![a, b])
+        );
+        assert_eq!(
+            fixture
+                .db
+                .run_filter(&[], Some(a))
+                .expect("failed to run filter"),
+            [a, b]
+        );
     }
 
     #[test]
-    fn item_relationships_relationship_id_foreign_key() {
+    fn item_matches() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
-            .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+        let [parent, child, other] = ["parent", "child", "other"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
+        fixture
             .db
-            .create_item("test2")
-            .expect("failed to create item");
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
 
-        let Err(AddItemRelationshipError::InsertRelationship(_)) = fixture
+        // Variable item rules use the checked item as context
+        let is_child_of_self = [ItemFilterRule::HasRelationshipWithVariableItem(
+            RelationshipSide::Dest,
+            relationship_id,
+        )];
+        let has_children = [ItemFilterRule::RelationshipCount(
+            RelationshipSide::Source,
+            relationship_id,
+            CompareOp::GreaterEqual,
+            1,
+        )];
+        for (item, rules, expected) in [
+            (parent, &has_children[..], true),
+            (child, &has_children[..], false),
+            (parent, &is_child_of_self[..], false),
+            (other, &[][..], true),
+        ] {
+            assert_eq!(
+                fixture
+                    .db
+                    .item_matches(item, rules)
+                    .expect("failed to check item"),
+                expected,
+                "{item:?} {rules:?}"
+            );
+        }
+
+        fixture.db.trash_item(other).expect("failed to trash item");
+        assert!(!fixture
             .db
-            .add_item_relationship(item_1, item_2, RelationshipId(99))
-        else {
-            panic!("expected insertion error");
-        };
+            .item_matches(other, &[])
+            .expect("failed to check item"));
     }
 
     #[test]
-    fn lookup_present_item_id_from_dest_sibling() {
+    fn count_filter_matches() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
-            .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
-            .db
-            .create_item("test2")
-            .expect("failed to create item");
-
+        let [parent, child, _other] = ["parent", "child todo", "other todo"]
+            .map(|name| fixture.db.create_item(name).expect("failed to create item"));
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let item_id = fixture
-            .db
-            .get_sibling_id(item_1, RelationshipSide::Source, relationship_id, "test2")
-            .expect("failed to find item id");
-        assert_eq!(item_id, Some(item_2));
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let rule_sets = [
+            vec![],
+            vec![ItemFilterRule::Orphan],
+            vec![ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+            vec![
+                ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id),
+                ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Source,
+                    relationship_id,
+                    CompareOp::Equal,
+                    0,
+                ),
+            ],
+            vec![ItemFilterRule::HasRelationshipWithVariableItem(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+        ];
+        for rules in &rule_sets {
+            for context in [None, Some(parent)] {
+                let ids = fixture
+                    .db
+                    .run_filter(rules, context)
+                    .expect("failed to run filter");
+                let count = fixture
+                    .db
+                    .count_filter_matches(rules, context)
+                    .expect("failed to count filter matches");
+                assert_eq!(count as usize, ids.len(), "{rules:?} {context:?}");
+            }
+        }
+
+        assert_eq!(
+            fixture
+                .db
+                .count_filter_matches(&[], None)
+                .expect("failed to count filter matches"),
+            3
+        );
     }
 
     #[test]
-    fn lookup_missing_item_id_from_dest_sibling_no_sibling_name() {
+    fn export_json() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let parent_id = fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+            .create_item("parent")
+            .expect("failed to create parent");
+        let child_id = fixture
             .db
-            .create_item("test2")
+            .create_item("child")
+            .expect("failed to create child");
+        fixture
+            .db
+            .create_item("unrelated")
             .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
+        fixture
+            .db
+            .add_filter(
+                "no_parents",
+                &[ItemFilterRule::NoRelationship(
+                    RelationshipSide::Dest,
+                    relationship_id,
+                )],
+            )
+            .expect("failed to add filter");
 
+        let mut exported = Vec::new();
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
-            fixture
-                .db
-                .get_sibling_id(item_1, RelationshipSide::Source, relationship_id, "invalid")
-        else {
-            panic!("did not expect to find sibling");
-        };
+            .export_json(&mut exported)
+            .expect("failed to export db");
+        let exported: serde_json::Value =
+            serde_json::from_slice(&exported).expect("failed to parse export");
+
+        assert_eq!(exported["version"], EXPORT_VERSION);
+        assert_eq!(exported["items"].as_array().map(Vec::len), Some(3));
+        assert_eq!(exported["relationships"].as_array().map(Vec::len), Some(1));
+        assert_eq!(
+            exported["item_relationships"].as_array().map(Vec::len),
+            Some(1)
+        );
+        assert_eq!(exported["filters"].as_array().map(Vec::len), Some(1));
+
+        let export: DbExport = serde_json::from_value(exported).expect("failed to parse export");
+        assert_eq!(
+            export.item_relationships[0],
+            ExportedItemRelationship {
+                from_id: parent_id.0,
+                to_id: child_id.0,
+                relationship_id: relationship_id.0,
+            }
+        );
     }
 
     #[test]
-    fn lookup_missing_item_id_from_dest_sibling_no_relationship() {
+    fn is_content_path() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
-            .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        let item_1 = fixture
+        assert!(!fixture.db.is_content_path(fixture.temp_dir.path()));
+
+        let id = fixture
             .db
             .create_item("test")
             .expect("failed to create item");
-        let item_2 = fixture
-            .db
-            .create_item("test2")
-            .expect("failed to create item");
-
-        fixture
+        let content_folder = fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) = fixture.db.get_sibling_id(
-            item_1,
-            RelationshipSide::Source,
-            RelationshipId(99),
-            "test2",
-        ) else {
-            panic!("did not expect to find sibling");
-        };
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+        assert!(fixture.db.is_content_path(&content_folder.join("file")));
+        assert!(!fixture.db.is_content_path(Path::new("/usr/bin/env")));
     }
 
     #[test]
-    fn lookup_missing_item_id_from_dest_sibling_no_source_id() {
+    fn backup() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let parent = fixture
             .db
-            .create_item("test")
+            .create_item("parent")
             .expect("failed to create item");
-        let item_2 = fixture
+        let child = fixture
             .db
-            .create_item("test2")
+            .create_item("child")
             .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+        let content_folder = fixture
+            .db
+            .content_folder_for_id(parent)
+            .expect("failed to get content folder");
+        fs::write(content_folder.join("notes"), "hello").expect("failed to write content");
 
+        let backup_dir = fixture.temp_dir.path().join("backup");
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) = fixture.db.get_sibling_id(
-            ItemId(99),
-            RelationshipSide::Source,
-            relationship_id,
-            "test2",
-        ) else {
-            panic!("did not expect to find sibling");
+            .backup_to(&backup_dir)
+            .expect("failed to back up db");
+
+        let mut backup = Db::new(backup_dir.clone()).expect("failed to open backup");
+        let mut backup_export = Vec::new();
+        backup
+            .export_json(&mut backup_export)
+            .expect("failed to export backup");
+        let mut db_export = Vec::new();
+        fixture
+            .db
+            .export_json(&mut db_export)
+            .expect("failed to export db");
+        assert_eq!(backup_export, db_export);
+
+        let backup_content_folder = backup
+            .content_folder_for_id(parent)
+            .expect("failed to get backup content folder");
+        let content = fs::read_to_string(backup_content_folder.join("notes"))
+            .expect("failed to read backed up content");
+        assert_eq!(content, "hello");
+
+        let Err(BackupError::DestExists) = fixture.db.backup_to(&backup_dir) else {
+            panic!("backing up over an existing db should fail");
         };
     }
 
     #[test]
-    fn lookup_missing_item_id_from_dest_sibling_wrong_side() {
+    fn for_each_item() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c"] {
+            ids.push(fixture.db.create_item(name).expect("failed to create item"));
+        }
+        fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+            .add_item_relationship(ids[0], ids[1], relationship_id)
+            .expect("failed to add item relationship");
+        fixture
             .db
-            .create_item("test2")
-            .expect("failed to create item");
+            .add_item_relationship(ids[0], ids[2], relationship_id)
+            .expect("failed to add item relationship");
 
+        let describe = |item: &DbItem| {
+            let relationships: Vec<_> = item
+                .relationships
+                .iter()
+                .map(|relationship| (relationship.id, relationship.side, relationship.sibling))
+                .collect();
+            (item.id, item.name.clone(), item.path.clone(), relationships)
+        };
+
+        let expected: Vec<_> = fixture
+            .db
+            .get_items()
+            .expect("failed to get items")
+            .iter()
+            .map(describe)
+            .collect();
+
+        let mut visited = Vec::new();
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
-            fixture
-                .db
-                .get_sibling_id(item_1, RelationshipSide::Dest, relationship_id, "test2")
-        else {
-            panic!("did not expect to find sibling");
+            .for_each_item(|item| {
+                visited.push(describe(&item));
+                Ok::<_, GetItemsError>(())
+            })
+            .expect("failed to visit items");
+        assert_eq!(visited, expected);
+        assert_eq!(visited[0].3.len(), 2);
+
+        #[derive(Debug)]
+        enum VisitError {
+            GetItems,
+            Stop,
+        }
+        impl From<GetItemsError> for VisitError {
+            fn from(_: GetItemsError) -> VisitError {
+                VisitError::GetItems
+            }
+        }
+
+        let mut num_visited = 0;
+        let Err(VisitError::Stop) = fixture.db.for_each_item(|_| {
+            num_visited += 1;
+            Err(VisitError::Stop)
+        }) else {
+            panic!("visiting should stop at the first error");
         };
+        assert_eq!(num_visited, 1);
     }
 
     #[test]
-    fn lookup_present_item_id_from_source_sibling() {
+    fn get_item_ids() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
-            .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        let item_1 = fixture
+        let ids = (0..3000)
+            .map(|i| fixture.db.create_item(&format!("item {i}")))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to create items");
+
+        let all_ids = fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
+            .get_item_ids(0, None)
+            .expect("failed to get item ids");
+        assert_eq!(all_ids, ids);
+
+        let page = fixture
             .db
-            .create_item("test2")
-            .expect("failed to create item");
+            .get_item_ids(1000, Some(500))
+            .expect("failed to get item ids");
+        assert_eq!(page, ids[1000..1500]);
 
-        fixture
+        let past_end = fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        fixture
+            .get_item_ids(2900, Some(500))
+            .expect("failed to get item ids");
+        assert_eq!(past_end, ids[2900..]);
+
+        let after = fixture
             .db
-            .get_sibling_id(item_2, RelationshipSide::Dest, relationship_id, "test")
-            .expect("failed to find sibling");
+            .get_item_ids_after(ids[999], 500)
+            .expect("failed to get item ids");
+        assert_eq!(after, ids[1000..1500]);
     }
 
     #[test]
-    fn lookup_missing_item_id_from_source_sibling_no_sibling_name() {
+    fn recent_items_by_creation_time() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
+        let old = fixture
             .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        let item_1 = fixture
+            .create_item("old")
+            .expect("failed to create item");
+        let new = fixture
             .db
-            .create_item("test")
+            .create_item("new")
             .expect("failed to create item");
-        let item_2 = fixture
+        let same_second = fixture
             .db
-            .create_item("test2")
+            .create_item("same second")
             .expect("failed to create item");
 
+        // Imported items can have a low id but a late creation time
         fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
-            fixture
-                .db
-                .get_sibling_id(item_2, RelationshipSide::Dest, relationship_id, "invalid")
-        else {
-            panic!("did not expect to find sibling");
-        };
+            .connection
+            .execute(
+                "UPDATE files SET created_at = created_at + 10 WHERE id = ?1",
+                [old.0],
+            )
+            .expect("failed to update created_at");
+
+        let recent = fixture
+            .db
+            .get_recent_items(3)
+            .expect("failed to get recent items");
+        let ids: Vec<_> = recent.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [old, same_second, new]);
     }
 
     #[test]
-    fn lookup_missing_item_id_from_source_sibling_no_relationship() {
+    fn check_integrity() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
+        let report = fixture
             .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
+            .check_integrity()
+            .expect("failed to check integrity");
+        assert!(report.is_ok());
+
         let item_1 = fixture
             .db
             .create_item("test")
             .expect("failed to create item");
-        let item_2 = fixture
+        fixture
             .db
             .create_item("test2")
             .expect("failed to create item");
 
-        fixture
+        let content_folder = fixture
             .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
-            fixture
-                .db
-                .get_sibling_id(item_2, RelationshipSide::Dest, RelationshipId(99), "test")
-        else {
-            panic!("did not expect to find sibling");
-        };
+            .content_folder_for_id(item_1)
+            .expect("failed to get content folder");
+        fs::remove_dir_all(content_folder).expect("failed to remove content folder");
+
+        let orphan_path = fixture.temp_dir.path().join("items/1000");
+        fs::create_dir(&orphan_path).expect("failed to create orphan dir");
+
+        let report = fixture
+            .db
+            .check_integrity()
+            .expect("failed to check integrity");
+        assert!(!report.is_ok());
+        assert!(report.sqlite_errors.is_empty());
+        assert!(report.foreign_key_violations.is_empty());
+        assert_eq!(report.missing_content_dirs, [item_1]);
+        assert_eq!(report.orphan_content_dirs, [orphan_path]);
     }
 
     #[test]
-    fn lookup_missing_item_id_from_source_sibling_no_source_id() {
+    fn prune_orphans() {
         let mut fixture = create_fixture();
-        let relationship_id = fixture
-            .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        let item_1 = fixture
+        let item = fixture
             .db
             .create_item("test")
             .expect("failed to create item");
-        let item_2 = fixture
-            .db
-            .create_item("test2")
-            .expect("failed to create item");
 
-        fixture
-            .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
-            fixture
-                .db
-                .get_sibling_id(ItemId(99), RelationshipSide::Dest, relationship_id, "test")
-        else {
-            panic!("did not expect to find sibling");
-        };
+        let items_dir = fixture.temp_dir.path().join("items");
+        fs::create_dir_all(items_dir.join("1000/nested")).expect("failed to create orphan dir");
+        fs::create_dir(items_dir.join("not_an_item")).expect("failed to create unrelated dir");
+
+        let orphans = fixture.db.find_orphans().expect("failed to find orphans");
+        assert_eq!(orphans, [ItemId(1000)]);
+        assert!(items_dir.join("1000").exists());
+
+        let pruned = fixture.db.prune_orphans().expect("failed to prune orphans");
+        assert_eq!(pruned, [ItemId(1000)]);
+        assert!(!items_dir.join("1000").exists());
+        assert!(items_dir.join("not_an_item").exists());
+        assert!(items_dir.join(item.0.to_string()).exists());
     }
 
     #[test]
-    fn lookup_missing_item_id_from_source_sibling_wrong_side() {
+    fn optimize() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_1 = fixture
-            .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item_2 = fixture
-            .db
-            .create_item("test2")
-            .expect("failed to create item");
-
-        fixture
-            .db
-            .add_item_relationship(item_1, item_2, relationship_id)
-            .expect("failed to create relationship");
-        let Ok(None) =
+        let mut ids = Vec::new();
+        for i in 0..100 {
+            let id = fixture
+                .db
+                .create_item(&format!("item {i}"))
+                .expect("failed to create item");
+            ids.push(id);
+        }
+        for pair in ids.windows(2) {
             fixture
                 .db
-                .get_sibling_id(item_2, RelationshipSide::Source, relationship_id, "test")
-        else {
-            panic!("did not expect to find sibling");
-        };
+                .add_item_relationship(pair[0], pair[1], relationship_id)
+                .expect("failed to add item relationship");
+        }
+        for id in ids.drain(50..) {
+            fixture.db.delete_item(id).expect("failed to delete item");
+        }
+
+        fixture.db.optimize().expect("failed to optimize");
+
+        let items = fixture.db.get_items().expect("failed to get items");
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), ids);
+        assert_eq!(items[0].name, "item 0");
+        assert_eq!(items[1].relationships.len(), 2);
+        assert_eq!(items[49].relationships.len(), 1);
     }
 
     #[test]
-    fn get_item_by_id_success() {
+    fn export_dot() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-        let item_id = fixture
+        let parent_id = fixture
             .db
-            .create_item("test")
-            .expect("failed to create item");
-        let item = fixture
+            .create_item("parent")
+            .expect("failed to create parent");
+        let child_id = fixture
             .db
-            .get_item_by_id(item_id)
-            .expect("failed to get item by id");
-    }
-
-    #[test]
-    fn get_item_by_id_missing_id() {
-        let mut fixture = create_fixture();
-        let relationship_id = fixture
+            .create_item("child \"quoted\"")
+            .expect("failed to create child");
+        fixture
             .db
-            .add_relationship("parents", "children")
-            .expect("failed to create relationship");
-        assert!(fixture.db.get_item_by_id(ItemId(99)).is_none());
+            .create_item("isolated")
+            .expect("failed to create isolated item");
+        fixture
+            .db
+            .add_item_relationship(parent_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
+
+        let dot = fixture.db.export_dot().expect("failed to export dot");
+
+        let (edges, nodes): (Vec<&str>, Vec<&str>) = dot
+            .lines()
+            .filter(|line| line.contains("[label="))
+            .partition(|line| line.contains("->"));
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(edges.len(), 1);
+        assert!(dot.contains(&format!(
+            "{} -> {} [label=\"children\"]",
+            parent_id.0, child_id.0
+        )));
+        assert!(dot.contains(r#"label="child \"quoted\"""#));
+        assert!(dot.contains(r#"label="isolated""#));
     }
 
     #[test]
-    fn add_filter_to_db() {
+    fn import_json_round_trip() {
         let mut fixture = create_fixture();
         let relationship_id = fixture
             .db
             .add_relationship("parents", "children")
             .expect("failed to create relationship");
-
+        let parent_id = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create parent");
+        let child_id = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create child");
+        fixture
+            .db
+            .delete_item(parent_id)
+            .expect("failed to delete item");
+        let parent_id = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create parent");
+        fixture
+            .db
+            .add_item_relationship(parent_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
         fixture
             .db
             .add_filter(
-                "my_filter",
+                "no_parents",
                 &[ItemFilterRule::NoRelationship(
                     RelationshipSide::Dest,
                     relationship_id,
@@ -1378,15 +7714,150 @@ mod test {
             )
             .expect("failed to add filter");
 
-        let filters = fixture.db.get_filters().expect("failed to get filters");
+        // Timestamps far from now, so the import cannot pass by stamping the current time
+        fixture
+            .db
+            .connection
+            .execute("UPDATE files SET created_at = 1000 + id", ())
+            .expect("failed to set item timestamps");
+        fixture
+            .db
+            .connection
+            .execute("UPDATE relationships SET created_at = 2000", ())
+            .expect("failed to set relationship timestamps");
 
-        assert_eq!(filters.len(), 1);
-        assert_eq!(filters[0].name, "my_filter");
-        assert_eq!(filters[0].rules.len(), 1);
+        let trashed_id = fixture
+            .db
+            .create_item("trashed")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(trashed_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
+        fixture
+            .db
+            .trash_item(trashed_id)
+            .expect("failed to trash item");
+        fixture
+            .db
+            .set_pinned(child_id, true)
+            .expect("failed to pin item");
+
+        let mut exported = Vec::new();
+        fixture
+            .db
+            .export_json(&mut exported)
+            .expect("failed to export db");
+
+        let mut imported_fixture = create_fixture();
+        assert!(imported_fixture.db.is_empty().expect("failed to check db"));
+        imported_fixture
+            .db
+            .import_json(exported.as_slice())
+            .expect("failed to import db");
+
+        let mut reexported = Vec::new();
+        imported_fixture
+            .db
+            .export_json(&mut reexported)
+            .expect("failed to export db");
+        assert_eq!(exported, reexported);
         assert_eq!(
-            filters[0].rules[0],
-            ItemFilterRule::NoRelationship(RelationshipSide::Dest, relationship_id)
+            imported_fixture
+                .db
+                .get_item_created_at(child_id)
+                .expect("failed to get timestamp"),
+            Some(1000 + child_id.0)
+        );
+        assert_eq!(
+            imported_fixture
+                .db
+                .get_relationship_created_at(relationship_id)
+                .expect("failed to get timestamp"),
+            Some(2000)
+        );
+        assert_eq!(
+            imported_fixture
+                .db
+                .get_trashed_items()
+                .expect("failed to get trash"),
+            [(trashed_id, "trashed".to_string())]
+        );
+        assert_eq!(
+            imported_fixture
+                .db
+                .get_pinned_items()
+                .expect("failed to get pinned items"),
+            [(child_id, "child".to_string())]
+        );
+
+        // The trashed item comes back with its links
+        imported_fixture
+            .db
+            .restore_item(trashed_id)
+            .expect("failed to restore item");
+        assert_eq!(
+            imported_fixture
+                .db
+                .get_item_by_id(trashed_id)
+                .expect("restored item missing")
+                .relationships
+                .len(),
+            1
         );
+
+        let parent = imported_fixture
+            .db
+            .get_item_by_id(parent_id)
+            .expect("parent should keep its id");
+        assert!(parent.path.is_dir());
+    }
+
+    #[test]
+    fn import_json_inconsistent() {
+        let mut fixture = create_fixture();
+        let export = DbExport {
+            version: EXPORT_VERSION,
+            items: vec![ExportedItem {
+                id: 1,
+                name: "test".to_string(),
+                created_at: None,
+                deleted_at: None,
+                pinned: false,
+            }],
+            relationships: vec![ExportedRelationship {
+                id: 1,
+                from_name: "parents".to_string(),
+                to_name: "children".to_string(),
+                description: String::new(),
+                created_at: None,
+            }],
+            item_relationships: vec![ExportedItemRelationship {
+                from_id: 1,
+                to_id: 2,
+                relationship_id: 1,
+            }],
+            filters: vec![],
+        };
+
+        let Err(ImportError::InsertItemRelationship(1, 2, _)) = fixture.db.import_json(
+            serde_json::to_vec(&export)
+                .expect("failed to serialize export")
+                .as_slice(),
+        ) else {
+            panic!("expected item relationship insertion failure");
+        };
+
+        assert!(fixture
+            .db
+            .get_items()
+            .expect("failed to get items")
+            .is_empty());
+        assert!(fixture
+            .db
+            .get_relationships()
+            .expect("failed to get relationships")
+            .is_empty());
     }
 
     #[test]
@@ -1440,5 +7911,312 @@ mod test {
             .get_item_by_id(parent_id)
             .expect("failed to get parent");
         assert_eq!(parent.relationships.len(), 0);
+
+        let num_item_relationships: i64 = fixture
+            .db
+            .connection
+            .query_row("SELECT COUNT(*) FROM item_relationships", (), |row| {
+                row.get(0)
+            })
+            .expect("failed to count item relationships");
+        assert_eq!(num_item_relationships, 0);
+    }
+
+    #[test]
+    fn trash_and_restore_item() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent_id = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child_id = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
+
+        let content_path = fixture
+            .db
+            .content_folder_for_id(child_id)
+            .expect("failed to get content folder");
+        fs::write(content_path.join("notes"), "keep me").expect("failed to write content");
+
+        fixture
+            .db
+            .trash_item(child_id)
+            .expect("failed to trash item");
+        let Err(TrashItemError::ItemNotFound) = fixture.db.trash_item(child_id) else {
+            panic!("trashing twice should fail");
+        };
+
+        assert!(fixture.db.get_item_by_id(child_id).is_none());
+        assert!(fixture
+            .db
+            .get_item_by_name("child")
+            .expect("failed to look up name")
+            .is_empty());
+        assert_eq!(
+            fixture
+                .db
+                .get_siblings(parent_id, relationship_id, RelationshipSide::Source)
+                .expect("failed to get siblings"),
+            []
+        );
+        assert_eq!(
+            fixture
+                .db
+                .run_filter(&[], None)
+                .expect("failed to run filter"),
+            [parent_id]
+        );
+        assert_eq!(
+            fixture
+                .db
+                .get_item_by_id(parent_id)
+                .expect("parent missing")
+                .relationships
+                .len(),
+            0
+        );
+        assert_eq!(
+            fixture.db.get_trashed_items().expect("failed to get trash"),
+            [(child_id, "child".to_string())]
+        );
+        assert!(!content_path.exists());
+        assert!(fixture
+            .db
+            .trash_folder_for_id(child_id)
+            .join("notes")
+            .exists());
+        assert!(fixture
+            .db
+            .check_integrity()
+            .expect("failed to check integrity")
+            .is_ok());
+
+        fixture
+            .db
+            .restore_item(child_id)
+            .expect("failed to restore item");
+        let Err(RestoreItemError::NotInTrash) = fixture.db.restore_item(child_id) else {
+            panic!("restoring twice should fail");
+        };
+
+        let parent = fixture
+            .db
+            .get_item_by_id(parent_id)
+            .expect("parent missing");
+        assert_eq!(parent.relationships.len(), 1);
+        assert_eq!(
+            fs::read_to_string(content_path.join("notes")).expect("failed to read content"),
+            "keep me"
+        );
+        assert!(fixture
+            .db
+            .get_trashed_items()
+            .expect("failed to get trash")
+            .is_empty());
+    }
+
+    #[test]
+    fn purge_trashed_item() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        fixture.db.trash_item(id).expect("failed to trash item");
+        let trash_folder = fixture.db.trash_folder_for_id(id);
+        assert!(trash_folder.exists());
+
+        fixture.db.delete_item(id).expect("failed to delete item");
+
+        assert!(!trash_folder.exists());
+        assert!(fixture
+            .db
+            .get_trashed_items()
+            .expect("failed to get trash")
+            .is_empty());
+        let Err(RestoreItemError::NotInTrash) = fixture.db.restore_item(id) else {
+            panic!("purged item should not be restorable");
+        };
+    }
+
+    #[test]
+    fn trashed_items_do_not_count_as_links() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent_id = fixture
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let child_id = fixture
+            .db
+            .create_item("child")
+            .expect("failed to create item");
+        fixture
+            .db
+            .add_item_relationship(parent_id, child_id, relationship_id)
+            .expect("failed to add item relationship");
+
+        let rule_sets = [
+            vec![ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+            vec![ItemFilterRule::RelationshipCount(
+                RelationshipSide::Dest,
+                relationship_id,
+                CompareOp::Equal,
+                0,
+            )],
+            vec![ItemFilterRule::Orphan],
+        ];
+
+        for rules in &rule_sets {
+            let matches = fixture
+                .db
+                .run_filter(rules, None)
+                .expect("failed to run filter");
+            assert!(!matches.contains(&child_id), "{rules:?}");
+        }
+
+        // With its parent in the trash the child is a root again
+        fixture
+            .db
+            .trash_item(parent_id)
+            .expect("failed to trash item");
+
+        for rules in &rule_sets {
+            assert_eq!(
+                fixture
+                    .db
+                    .run_filter(rules, None)
+                    .expect("failed to run filter"),
+                [child_id],
+                "{rules:?}"
+            );
+        }
+    }
+
+    /// Schema written by the first release, before the database was versioned. Upgrades from it
+    /// exercise every migration in [`Db::with_layout`]
+    const UNVERSIONED_SCHEMA: &str = "
+        CREATE TABLE files(id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL);
+        CREATE TABLE relationships(id INTEGER PRIMARY KEY, from_name TEXT NOT NULL, to_name TEXT_NOT_NULL);
+        CREATE TABLE item_relationships(from_id INTEGER, to_id INTEGER, relationship_id INTEGER,
+            FOREIGN KEY(from_id) REFERENCES files(id),
+            FOREIGN KEY(to_id) REFERENCES files(id),
+            FOREIGN KEY(relationship_id) REFERENCES relationships(id),
+            UNIQUE(from_id, to_id, relationship_id));
+        CREATE TABLE filters(id INTEGER PRIMARY KEY, name TEXT_NOT_NULL);
+        CREATE TABLE no_relationship_filters(filter_id INTEGER, side INTEGER, relationship_id INTEGER,
+            FOREIGN KEY(filter_id) REFERENCES filters(id),
+            FOREIGN KEY(relationship_id) REFERENCES relationships(id),
+            UNIQUE(filter_id, side, relationship_id));";
+
+    /// Writes a database with the given schema and data, without going through [`Db`]
+    fn create_old_db(sql: &str) -> (TempDir, DbLayout) {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let layout = DbLayout::in_dir(temp_dir.path());
+
+        let connection = Connection::open(&layout.db_path).expect("failed to open connection");
+        connection
+            .execute_batch(sql)
+            .expect("failed to create old schema");
+        drop(connection);
+
+        std::fs::create_dir_all(&layout.content_root).expect("failed to create content root");
+        (temp_dir, layout)
+    }
+
+    #[test]
+    fn upgrade_unversioned_db() {
+        let (temp_dir, layout) = create_old_db(&format!(
+            "{UNVERSIONED_SCHEMA}
+            INSERT INTO files(id, name) VALUES (1, 'parent'), (2, 'child');
+            INSERT INTO relationships(id, from_name, to_name) VALUES (1, 'parents', 'children');
+            INSERT INTO item_relationships(from_id, to_id, relationship_id) VALUES (1, 2, 1);
+            INSERT INTO filters(id, name) VALUES (1, 'roots');
+            INSERT INTO no_relationship_filters(filter_id, side, relationship_id) VALUES (1, 1, 1);"
+        ));
+
+        let mut db = Db::with_layout(layout).expect("failed to upgrade db");
+        let version: u32 = db
+            .connection
+            .query_row("PRAGMA user_version", (), |row| row.get(0))
+            .expect("failed to get schema version");
+        assert_eq!(version, SCHEMA_VERSION);
+
+        let relationships = db.get_relationships().expect("failed to get relationships");
+        assert_eq!(relationships.len(), 1);
+        assert_eq!(relationships[0].from_name, "parents");
+        assert_eq!(relationships[0].to_name, "children");
+        assert_eq!(relationships[0].description, "");
+
+        let parent = db.get_item_by_id(ItemId(1)).expect("parent should exist");
+        assert_eq!(parent.name, "parent");
+        assert_eq!(parent.relationships.len(), 1);
+        assert_eq!(parent.relationships[0].sibling, ItemId(2));
+
+        let filters = db.get_filters().expect("failed to get filters");
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].name, "roots");
+        assert_eq!(
+            filters[0].rules,
+            [ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                RelationshipId(1)
+            )]
+        );
+        let matches = db
+            .run_filter(&filters[0].rules, None)
+            .expect("failed to run filter");
+        assert_eq!(matches, [ItemId(1)]);
+
+        // Upgrading again has to leave the already migrated data alone
+        drop(db);
+        let mut db =
+            Db::with_layout(DbLayout::in_dir(temp_dir.path())).expect("failed to reopen db");
+        assert_eq!(
+            db.get_relationships()
+                .expect("failed to get relationships")
+                .len(),
+            1
+        );
+        assert_eq!(db.get_filters().expect("failed to get filters").len(), 1);
+        let parent = db.get_item_by_id(ItemId(1)).expect("parent should exist");
+        assert_eq!(parent.relationships.len(), 1);
+    }
+
+    #[test]
+    fn delete_item_cascades_in_old_db() {
+        let (_temp_dir, layout) = create_old_db(&format!(
+            "{UNVERSIONED_SCHEMA}
+            INSERT INTO files(id, name) VALUES (1, 'parent'), (2, 'child'), (3, 'other');
+            INSERT INTO relationships(id, from_name, to_name) VALUES (1, 'parents', 'children');
+            INSERT INTO item_relationships(from_id, to_id, relationship_id) VALUES (1, 2, 1), (1, 3, 1);"
+        ));
+
+        let mut db = Db::with_layout(layout).expect("failed to open db");
+
+        let parent = db.get_item_by_id(ItemId(1)).expect("parent should exist");
+        assert_eq!(parent.relationships.len(), 2);
+
+        db.delete_item(ItemId(2)).expect("failed to delete item");
+
+        let parent = db.get_item_by_id(ItemId(1)).expect("parent should exist");
+        assert_eq!(parent.relationships.len(), 1);
+        assert_eq!(parent.relationships[0].sibling, ItemId(3));
     }
 }
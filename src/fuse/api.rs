@@ -3,25 +3,55 @@ use serde::{
     Deserialize, Serialize,
 };
 use std::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
+use thiserror::Error;
 
-use crate::db::{ItemFilterRule, RelationshipId};
+use crate::db::{FilterId, FilterOrder, ItemFilterRule, RelationshipId};
 
 pub const API_HANDLE_PATH: &str = "/.api_handle";
 
-/// Assuming the binary that is calling this is mapped to /bin/, we can resolve the file handle by
-/// going up a dir and looking at the API_HANDLE_PATH
-fn open_api_handle_for_file() -> Result<File, std::io::Error> {
+/// Version of the socket protocol. Bump whenever requests or responses change in a way that an
+/// older tool or mount would misinterpret
+///
+/// 2: requests end with a newline
+/// 3: DeleteItemRequest::trash, older mounts would delete for good
+/// 4: CreateItemRelationships requests
+/// 5: CreateRelationshipRequest::ensure, older mounts would fail on existing relationships
+/// 6: CountFilterMatches requests
+/// 7: Batch requests
+pub const API_VERSION: u32 = 7;
+
+#[derive(Debug, Error)]
+pub enum HandshakeError {
+    #[error("failed to send hello")]
+    Write(#[source] std::io::Error),
+    #[error("failed to read hello response")]
+    Read(#[source] std::io::Error),
+    #[error("failed to parse hello response")]
+    ParseResponse(#[source] serde_json::Error),
+    #[error("unexpected response to hello")]
+    UnexpectedResponse,
+    #[error("version mismatch: tool speaks API version {client}, file system speaks {server}")]
+    VersionMismatch { client: u32, server: u32 },
+}
+
+/// Assuming the binary that is calling this is mapped to /bin/, the root of the mount is one dir
+/// up from the executable
+pub fn mount_root() -> Result<PathBuf, std::io::Error> {
     let current_exe = std::env::current_exe()?;
     let fs_root = current_exe
         .parent()
         .expect("exe should have a parent")
         .parent()
         .expect("exe is expected to be in /bin/");
-    let socket_path = fs_root.join(&API_HANDLE_PATH[1..]);
+    Ok(fs_root.to_path_buf())
+}
+
+fn open_api_handle_for_file() -> Result<File, std::io::Error> {
+    let socket_path = mount_root()?.join(&API_HANDLE_PATH[1..]);
     OpenOptions::new()
         .read(true)
         .write(true)
@@ -29,32 +59,192 @@ fn open_api_handle_for_file() -> Result<File, std::io::Error> {
         .open(socket_path)
 }
 
-pub fn send_client_request(request: &ClientRequest) -> Option<ClientResponse> {
-    let serialized = serde_json::to_vec(&request).expect("failed to serialize request");
-
-    let mut api_handle = open_api_handle_for_file().expect("failed to open api handle");
+/// Requests are sent as a single line of json, the mount waits for the newline before parsing.
+/// Compact json never contains a raw newline, so it cannot show up inside a request
+pub fn serialize_request(request: &ClientRequest) -> Vec<u8> {
+    let mut ret = serde_json::to_vec(request).expect("failed to serialize request");
+    ret.push(b'\n');
+    ret
+}
 
+/// Exchanges API versions with the mount so that mismatched tools fail up front instead of
+/// misinterpreting responses
+fn handshake(api_handle: &mut File) -> Result<(), HandshakeError> {
+    let request = ClientRequest::Hello(HelloRequest {
+        version: API_VERSION,
+    });
+    let serialized = serialize_request(&request);
     api_handle
         .write_all(&serialized)
-        .expect("failed to write request");
+        .map_err(HandshakeError::Write)?;
 
-    let mut response_buf = vec![0; 4096];
+    let mut response_buf = Vec::new();
+    api_handle
+        .read_to_end(&mut response_buf)
+        .map_err(HandshakeError::Read)?;
+
+    let response: ClientResponse =
+        serde_json::from_slice(&response_buf).map_err(HandshakeError::ParseResponse)?;
+
+    let ClientResponse::Hello(response) = response else {
+        return Err(HandshakeError::UnexpectedResponse);
+    };
+
+    if response.version != API_VERSION {
+        return Err(HandshakeError::VersionMismatch {
+            client: API_VERSION,
+            server: response.version,
+        });
+    }
+
+    Ok(())
+}
 
-    let num_bytes_read = api_handle
-        .read(&mut response_buf)
-        .expect("failed to read response");
+#[derive(Debug, Error)]
+pub enum SendRequestError {
+    #[error("failed to open api handle")]
+    OpenHandle(#[source] std::io::Error),
+    #[error("handshake with the mount failed")]
+    Handshake(#[source] HandshakeError),
+    #[error("failed to write request")]
+    Write(#[source] std::io::Error),
+    #[error("failed to read response")]
+    Read(#[source] std::io::Error),
+    #[error("failed to parse response")]
+    ParseResponse(#[source] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum ResolveRelationshipError {
+    #[error("no relationship named {0}")]
+    NotFound(String),
+    #[error("multiple relationships named {0}: {1:?}")]
+    Ambiguous(String, Vec<i64>),
+    #[error("failed to look up relationship")]
+    SendRequest(#[source] SendRequestError),
+    #[error("unexpected response to relationship lookup")]
+    UnexpectedResponse,
+}
+
+/// Tools accept either a relationship id or one of its names, names are looked up through the
+/// mount
+pub fn resolve_relationship(name_or_id: &str) -> Result<RelationshipId, ResolveRelationshipError> {
+    if let Ok(id) = name_or_id.parse() {
+        return Ok(RelationshipId(id));
+    }
+
+    let request = ClientRequest::GetRelationshipId(GetRelationshipIdRequest {
+        name: name_or_id.to_string(),
+    });
+    let response = send_client_request(&request).map_err(ResolveRelationshipError::SendRequest)?;
+    let Some(ClientResponse::GetRelationshipId(response)) = response else {
+        return Err(ResolveRelationshipError::UnexpectedResponse);
+    };
+
+    match response {
+        GetRelationshipIdResponse::Found { id } => Ok(RelationshipId(id)),
+        GetRelationshipIdResponse::NotFound => {
+            Err(ResolveRelationshipError::NotFound(name_or_id.to_string()))
+        }
+        GetRelationshipIdResponse::Ambiguous { candidates } => Err(
+            ResolveRelationshipError::Ambiguous(name_or_id.to_string(), candidates),
+        ),
+    }
+}
+
+/// Sends request through the mount's api handle. Requests that have no response return None
+pub fn send_client_request(
+    request: &ClientRequest,
+) -> Result<Option<ClientResponse>, SendRequestError> {
+    let serialized = serialize_request(request);
+
+    let mut api_handle = open_api_handle_for_file().map_err(SendRequestError::OpenHandle)?;
+
+    handshake(&mut api_handle).map_err(SendRequestError::Handshake)?;
+
+    api_handle
+        .write_all(&serialized)
+        .map_err(SendRequestError::Write)?;
+
+    // Responses are read until the buffered response is drained, large ones span several reads
+    let mut response_buf = Vec::new();
+    api_handle
+        .read_to_end(&mut response_buf)
+        .map_err(SendRequestError::Read)?;
 
     match request {
         ClientRequest::CreateItemRelationship(_)
         | ClientRequest::CreateFilter(_)
-        | ClientRequest::DeleteItem(_) => return None,
-        ClientRequest::CreateItem(_) | ClientRequest::CreateRelationship(_) => (),
+        | ClientRequest::DeleteItem(_) => return Ok(None),
+        ClientRequest::CreateItem(_)
+        | ClientRequest::CreateRelationship(_)
+        | ClientRequest::CreateItemRelationships(_)
+        | ClientRequest::CreateItemRelationshipByName(_)
+        | ClientRequest::UpdateItem(_)
+        | ClientRequest::CreateItems(_)
+        | ClientRequest::GetContentPath(_)
+        | ClientRequest::PreviewFilter(_)
+        | ClientRequest::CountFilterMatches(_)
+        | ClientRequest::GetRelationshipId(_)
+        | ClientRequest::SearchItems(_)
+        | ClientRequest::Batch(_)
+        | ClientRequest::Hello(_) => (),
     }
 
     let response: ClientResponse =
-        serde_json::from_slice(&response_buf[0..num_bytes_read]).expect("failed to parse response");
+        serde_json::from_slice(&response_buf).map_err(SendRequestError::ParseResponse)?;
+
+    Ok(Some(response))
+}
+
+#[derive(Debug, Error)]
+pub enum IngestError {
+    #[error("{} has no file name", .0.display())]
+    NoFileName(PathBuf),
+    #[error("failed to copy to {}", .0.display())]
+    Copy(PathBuf, #[source] std::io::Error),
+}
+
+/// Copies source into the content folder of the item at item_path, as handed out when the item
+/// was created. Directories are copied recursively. Returns where source ended up
+pub fn ingest(mount_root: &Path, item_path: &Path, source: &Path) -> Result<PathBuf, IngestError> {
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| IngestError::NoFileName(source.to_path_buf()))?;
+
+    // Item paths are absolute within the mount
+    let item_path = item_path.strip_prefix("/").unwrap_or(item_path);
+    let dest = mount_root.join(item_path).join("content").join(file_name);
+
+    copy_recursive(source, &dest).map_err(|e| IngestError::Copy(dest.clone(), e))?;
+    Ok(dest)
+}
+
+fn copy_recursive(source: &Path, dest: &Path) -> Result<(), std::io::Error> {
+    if !source.is_dir() {
+        fs::copy(source, dest)?;
+        return Ok(());
+    }
+
+    fs::create_dir(dest)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
 
-    Some(response)
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct HelloRequest {
+    pub version: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct HelloResponse {
+    pub version: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -63,10 +253,42 @@ pub struct CreateItemRequest {
     pub name: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateItemsResponse {
+    // Paths of the created items, in request order
+    Created(Vec<CreateItemResponse>),
+    // Nothing is created if any item fails. index is the failing item, if one was at fault
+    Failed {
+        index: Option<usize>,
+        reason: String,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct DeleteItemRequest {
     pub id: i64,
+    /// Move the item to the trash instead of deleting it for good. Left as None the mount's
+    /// --soft-delete setting decides
+    #[serde(default)]
+    pub trash: Option<bool>,
+}
+
+/// Fields left as None are not modified. New fields should also be optional so that older
+/// clients keep working
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct UpdateItemRequest {
+    pub id: i64,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct UpdateItemResponse {
+    pub path: PathBuf,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -75,11 +297,28 @@ pub struct CreateItemResponse {
     pub path: PathBuf,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct GetContentPathRequest {
+    pub id: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GetContentPathResponse {
+    // Canonical path of the content folder on the underlying file system, not within the mount
+    Found { path: PathBuf },
+    ItemNotFound,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct CreateRelationshipRequest {
     pub from_name: String,
     pub to_name: String,
+    /// Return the existing relationship with the same names instead of failing
+    #[serde(default)]
+    pub ensure: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -96,10 +335,100 @@ pub struct CreateItemRelationshipRequest {
     pub to_id: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct CreateItemRelationshipsRequest {
+    pub links: Vec<CreateItemRelationshipRequest>,
+    /// Fail the whole batch on a link that already exists instead of reporting it and moving on
+    #[serde(default)]
+    pub abort_on_duplicate: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CreatedItemRelationship {
+    Added,
+    // The link already existed, either before the request or earlier in it
+    Duplicate,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateItemRelationshipsResponse {
+    // What happened to each link, in request order
+    Created(Vec<CreatedItemRelationship>),
+    // Nothing is linked if any link fails. index is the failing link, if one was at fault
+    Failed {
+        index: Option<usize>,
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct CreateItemRelationshipByNameRequest {
+    /// Either a relationship id, or one of the relationship's names
+    pub relationship: String,
+    pub from_name: String,
+    pub to_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateItemRelationshipByNameResponse {
+    Created { from_id: i64, to_id: i64 },
+    RelationshipNotFound,
+    ItemNotFound { name: String },
+    // Item names are not unique, we refuse to guess which item was meant
+    AmbiguousItemName { name: String, candidates: Vec<i64> },
+    AmbiguousRelationshipName { candidates: Vec<i64> },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchItemsRequest {
+    pub prefix: String,
+    pub limit: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct GetRelationshipIdRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GetRelationshipIdResponse {
+    Found { id: i64 },
+    NotFound,
+    // Several relationships use the name on one of their sides
+    Ambiguous { candidates: Vec<i64> },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 enum ItemFilterRuleSerializeProxy {
-    NoRelationship { side: String, id: i64 },
+    NoRelationship {
+        side: String,
+        id: i64,
+    },
+    HasRelationshipWithVariableItem {
+        side: String,
+        id: i64,
+    },
+    RelationshipCount {
+        side: String,
+        id: i64,
+        op: String,
+        count: i64,
+    },
+    Orphan,
+    RelatedToFilter {
+        side: String,
+        id: i64,
+        filter: i64,
+    },
 }
 
 impl ItemFilterRuleSerializeProxy {
@@ -110,6 +439,26 @@ impl ItemFilterRuleSerializeProxy {
                 side: side.to_string(),
                 id: id.0,
             },
+            HasRelationshipWithVariableItem(side, id) => {
+                ItemFilterRuleSerializeProxy::HasRelationshipWithVariableItem {
+                    side: side.to_string(),
+                    id: id.0,
+                }
+            }
+            RelationshipCount(side, id, op, count) => {
+                ItemFilterRuleSerializeProxy::RelationshipCount {
+                    side: side.to_string(),
+                    id: id.0,
+                    op: op.to_string(),
+                    count: *count,
+                }
+            }
+            Orphan => ItemFilterRuleSerializeProxy::Orphan,
+            RelatedToFilter(side, id, filter) => ItemFilterRuleSerializeProxy::RelatedToFilter {
+                side: side.to_string(),
+                id: id.0,
+                filter: filter.0,
+            },
         }
     }
 }
@@ -136,15 +485,39 @@ impl<'de> serde::Deserialize<'de> for ItemFilterRule {
                 formatter.write_str("expected one of \"source\" or \"dest\"")
             }
         }
+        let parse_side = |side: String| {
+            side.parse().map_err(|_| {
+                serde::de::Error::invalid_value(Unexpected::Other("invalid side"), &ExpectedSize)
+            })
+        };
         let ret = match proxy {
             ItemFilterRuleSerializeProxy::NoRelationship { side, id } => {
-                let side = side.parse().map_err(|_| {
-                    serde::de::Error::invalid_value(
-                        Unexpected::Other("invalid side"),
-                        &ExpectedSize,
-                    )
+                ItemFilterRule::NoRelationship(parse_side(side)?, RelationshipId(id))
+            }
+            ItemFilterRuleSerializeProxy::HasRelationshipWithVariableItem { side, id } => {
+                ItemFilterRule::HasRelationshipWithVariableItem(
+                    parse_side(side)?,
+                    RelationshipId(id),
+                )
+            }
+            ItemFilterRuleSerializeProxy::RelationshipCount {
+                side,
+                id,
+                op,
+                count,
+            } => {
+                let op = op.parse().map_err(|_| {
+                    serde::de::Error::invalid_value(Unexpected::Str(&op), &"one of <, <=, =, >=, >")
                 })?;
-                ItemFilterRule::NoRelationship(side, RelationshipId(id))
+                ItemFilterRule::RelationshipCount(parse_side(side)?, RelationshipId(id), op, count)
+            }
+            ItemFilterRuleSerializeProxy::Orphan => ItemFilterRule::Orphan,
+            ItemFilterRuleSerializeProxy::RelatedToFilter { side, id, filter } => {
+                ItemFilterRule::RelatedToFilter(
+                    parse_side(side)?,
+                    RelationshipId(id),
+                    FilterId(filter),
+                )
             }
         };
         Ok(ret)
@@ -156,23 +529,70 @@ impl<'de> serde::Deserialize<'de> for ItemFilterRule {
 pub struct CreateFilterRequest {
     pub name: String,
     pub filters: Vec<ItemFilterRule>,
+    /// Ids of filters an item has to match for this filter to show up in its folder
+    #[serde(default)]
+    pub conditions: Vec<i64>,
+    /// Order the filter lists its items in, unspecified if not given
+    #[serde(default)]
+    pub order_by: Option<FilterOrder>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct PreviewFilterRequest {
+    pub filters: Vec<ItemFilterRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchResponse {
+    // Response of each request in order, None for requests that have no response
+    Completed(Vec<Option<ClientResponse>>),
+    // Nothing is changed if a request fails. index is the failing request
+    Failed { index: usize, reason: String },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type", content = "data")]
 #[serde(rename_all = "snake_case")]
 pub enum ClientRequest {
+    Hello(HelloRequest),
     CreateItem(CreateItemRequest),
+    CreateItems(Vec<CreateItemRequest>),
     DeleteItem(DeleteItemRequest),
+    UpdateItem(UpdateItemRequest),
     CreateRelationship(CreateRelationshipRequest),
     CreateItemRelationship(CreateItemRelationshipRequest),
+    CreateItemRelationships(CreateItemRelationshipsRequest),
+    CreateItemRelationshipByName(CreateItemRelationshipByNameRequest),
     CreateFilter(CreateFilterRequest),
+    GetContentPath(GetContentPathRequest),
+    PreviewFilter(PreviewFilterRequest),
+    CountFilterMatches(PreviewFilterRequest),
+    GetRelationshipId(GetRelationshipIdRequest),
+    SearchItems(SearchItemsRequest),
+    // Runs the requests in order, in a single transaction. Items cannot be deleted in a batch
+    Batch(Vec<ClientRequest>),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type", content = "data")]
 #[serde(rename_all = "snake_case")]
 pub enum ClientResponse {
+    Hello(HelloResponse),
     CreateItem(CreateItemResponse),
+    CreateItems(CreateItemsResponse),
+    UpdateItem(UpdateItemResponse),
     CreateRelationship(CreateRelationshipResponse),
+    CreateItemRelationships(CreateItemRelationshipsResponse),
+    CreateItemRelationshipByName(CreateItemRelationshipByNameResponse),
+    GetContentPath(GetContentPathResponse),
+    // Id and name of every item the filter would match
+    PreviewFilter(Vec<(i64, String)>),
+    // Number of items the filter would match
+    CountFilterMatches(i64),
+    GetRelationshipId(GetRelationshipIdResponse),
+    // Id and name of the items whose name starts with the prefix, ordered by name
+    SearchItems(Vec<(i64, String)>),
+    Batch(BatchResponse),
 }
@@ -1,18 +1,26 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     ffi::OsString,
     fs,
     io::Read,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use crate::db::{
-    Db, FilterId, GetItemsError, ItemId, ItemRelationship, QueryError, RelationshipId,
-    RelationshipSide,
+    self, AddedLink, CompareOp, Db, Filter, FilterId, GetItemsError, GetRelationshipIdByNameError,
+    ItemFilterRule, ItemId, ItemRelationship, QueryError, Relationship, RelationshipId,
+    RelationshipSide, ALL_RELATED_FOLDER_NAME,
 };
+use serde::Serialize;
 use thiserror::Error;
 
-use super::api::{ClientRequest, ClientResponse, CreateItemResponse, CreateRelationshipResponse};
+use super::api::{
+    BatchResponse, ClientRequest, ClientResponse, CreateItemRelationshipByNameRequest,
+    CreateItemRelationshipByNameResponse, CreateItemRelationshipsResponse, CreateItemResponse,
+    CreateItemsResponse, CreateRelationshipResponse, CreatedItemRelationship,
+    GetContentPathResponse, GetRelationshipIdResponse, HelloResponse, UpdateItemResponse,
+    API_VERSION,
+};
 
 #[derive(Debug, Error)]
 pub enum CategorizeRelationshipsError {
@@ -28,6 +36,8 @@ pub enum ParsePathError {
     ReadDir(#[from] ReadDirError),
     #[error("failed to parse path name")]
     ParsePath,
+    #[error("failed to resolve item name")]
+    ResolveItemName(#[source] QueryError),
 }
 
 #[derive(Debug, Error)]
@@ -36,6 +46,8 @@ pub enum ReadDirError {
     ParsePath(#[source] Box<ParsePathError>),
     #[error("failed to get items")]
     GetItems(#[source] GetItemsError),
+    #[error("failed to get item ids")]
+    GetItemIds(#[source] crate::db::QueryError),
     #[error("failed to get relationships")]
     GetRelationships(#[source] crate::db::QueryError),
     #[error("failed to read db dir")]
@@ -49,9 +61,25 @@ pub enum ReadDirError {
     #[error("failed to find filter for given ID")]
     FindFilter,
     #[error("failed to run filter")]
-    RunFilter(#[source] crate::db::QueryError),
+    RunFilter(#[source] crate::db::RunFilterError),
+    #[error("failed to get recent items")]
+    GetRecentItems(#[source] crate::db::QueryError),
+    #[error("failed to get pinned items")]
+    GetPinnedItems(#[source] crate::db::QueryError),
+    #[error("failed to get links for relationship")]
+    GetRelationshipLinks(#[source] crate::db::QueryError),
+    #[error("failed to get trashed items")]
+    GetTrashedItems(#[source] crate::db::QueryError),
+    #[error("invalid search query")]
+    InvalidSearchQuery,
+    #[error("failed to run filter intersection")]
+    RunFiltersIntersection(#[source] crate::db::RunFiltersIntersectionError),
+    #[error("failed to get siblings")]
+    GetSiblings(#[source] crate::db::QueryError),
     #[error("failed to get content folder for item")]
     GetContentFolder(#[source] std::io::Error),
+    #[error("failed to read content folder for item")]
+    ReadContentFolder(#[source] std::io::Error),
     #[error("failed to get filetype for path")]
     GetFiletype(#[source] PathPurposeToFiletypeError),
     #[error("read dir called on non directory")]
@@ -66,12 +94,56 @@ pub enum GetFiletypeError {
     GetFileType(#[source] PathPurposeToFiletypeError),
 }
 
+#[derive(Debug, Error)]
+pub enum OpenError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("failed to get file type")]
+    GetFiletype(#[source] PathPurposeToFiletypeError),
+    #[error("path does not exist")]
+    NotFound,
+    #[error("path is not a directory")]
+    NotADirectory,
+    #[error("directories cannot be opened for writing")]
+    IsADirectory,
+    #[error("file is read only")]
+    ReadOnly,
+    #[error("failed to get events")]
+    Events(#[source] QueryError),
+    #[error("failed to get stats")]
+    Stats(#[source] GetStatsError),
+    #[error("failed to measure item content size")]
+    ItemSize(#[source] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum GetTimestampError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("failed to get creation time")]
+    GetCreatedAt(#[from] QueryError),
+}
+
+#[derive(Debug, Error)]
+pub enum GetContentSizeError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("failed to get content folder")]
+    GetContentFolder(#[source] std::io::Error),
+    #[error("failed to measure content size")]
+    Measure(#[source] std::io::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum ReadLinkError {
     #[error("failed to parse path")]
     ParsePath(#[source] ParsePathError),
     #[error("item is not a link")]
     NotALink,
+    #[error("failed to resolve item name")]
+    ResolveItemName(#[source] QueryError),
+    #[error("no item with name {0}")]
+    ItemNameNotFound(String),
 }
 
 #[derive(Debug, Error)]
@@ -82,16 +154,150 @@ pub enum WriteError {
     CreateItem(#[source] crate::db::CreateItemError),
     #[error("failed to delete item")]
     DeleteItem(#[from] crate::db::DeleteItemError),
+    #[error("failed to trash item")]
+    TrashItem(#[from] crate::db::TrashItemError),
+    #[error("failed to rename item")]
+    RenameItem(#[from] crate::db::RenameItemError),
     #[error("failed to create relationship")]
     CreateRelationship(#[from] crate::db::AddRelationshipError),
     #[error("failed to create item relationship")]
     CreateItemRelationship(#[from] crate::db::AddItemRelationshipError),
     #[error("failed to add filter")]
     AddFilter(#[from] crate::db::AddFilterError),
+    #[error("failed to look up relationship")]
+    FindRelationship(#[source] QueryError),
+    #[error("failed to look up item by name")]
+    FindItemByName(#[source] QueryError),
+    #[error("failed to get content folder for item")]
+    GetContentFolder(#[source] std::io::Error),
+    #[error("failed to run filter")]
+    RunFilter(#[source] crate::db::RunFilterError),
+    #[error("item id not in database")]
+    ItemIdNotInDatabase,
     #[error("failed to find response handle")]
     FindResponseHandle,
     #[error("failed to serialise response")]
     SerializeResponse(#[source] serde_json::Error),
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("unhandled path")]
+    UnhandledPath,
+    #[error("written data is not valid utf8")]
+    InvalidUtf8(#[source] std::str::Utf8Error),
+    #[error("failed to rename relationship")]
+    RenameRelationship(#[from] crate::db::RenameRelationshipError),
+    #[error("filesystem is mounted read only")]
+    ReadOnly,
+    #[error("handle negotiated incompatible API version {0}")]
+    IncompatibleVersion(u32),
+    #[error("failed to start batch transaction")]
+    StartBatch(#[source] rusqlite::Error),
+    #[error("failed to roll back batch")]
+    RollbackBatch(#[source] rusqlite::Error),
+    #[error("failed to commit batch")]
+    CommitBatch(#[source] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum SymlinkError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("symlink target is not an item")]
+    InvalidTarget,
+    #[error("failed to get relationships")]
+    GetRelationships(#[source] QueryError),
+    #[error("failed to add item relationship")]
+    AddItemRelationship(#[from] crate::db::AddItemRelationshipError),
+    #[error("failed to pin item")]
+    SetPinned(#[from] crate::db::SetPinnedError),
+    #[error("symlinks cannot be created here")]
+    Unhandled,
+}
+
+#[derive(Debug, Error)]
+pub enum UnlinkError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("failed to remove item relationship")]
+    RemoveItemRelationship(#[from] crate::db::RemoveItemRelationshipError),
+    #[error("failed to unpin item")]
+    SetPinned(#[from] crate::db::SetPinnedError),
+    #[error("path does not exist")]
+    NotFound,
+    #[error("links in computed folders cannot be removed")]
+    Computed,
+    #[error("path cannot be removed")]
+    Unhandled,
+}
+
+#[derive(Debug, Error)]
+pub enum RenameError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("path does not exist")]
+    NotFound,
+    #[error("rename crosses between virtual and real files")]
+    CrossesBoundary,
+    #[error("virtual paths cannot be renamed")]
+    Synthetic,
+    #[error("trashed items cannot be modified")]
+    InTrash,
+    #[error("failed to rename")]
+    Rename(#[source] std::io::Error),
+    #[error("failed to rename filter")]
+    RenameFilter(#[from] crate::db::RenameFilterError),
+}
+
+#[derive(Debug, Error)]
+pub enum GetWritablePassthroughPathError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("trashed items cannot be modified")]
+    InTrash,
+}
+
+#[derive(Debug, Error)]
+pub enum MkdirError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("invalid search query")]
+    InvalidQuery,
+    #[error("directories cannot be created here")]
+    Unhandled,
+}
+
+#[derive(Debug, Error)]
+pub enum CreateError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("item names must be valid utf8")]
+    InvalidName,
+    #[error("item {} already exists", .0 .0)]
+    ItemExists(ItemId),
+    #[error("numeric names would not resolve to the created item")]
+    NumericName,
+    #[error("failed to create item")]
+    CreateItem(#[source] crate::db::CreateItemError),
+    #[error("files cannot be created here")]
+    Unhandled,
+}
+
+#[derive(Debug, Error)]
+pub enum RmdirError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("directory does not exist")]
+    NotFound,
+    #[error("directories cannot be removed here")]
+    Unhandled,
+}
+
+#[derive(Debug, Error)]
+pub enum GetXattrsError {
+    #[error("failed to parse path")]
+    ParsePath(#[from] ParsePathError),
+    #[error("item id not in db")]
+    ItemIdNotInDatabase,
 }
 
 #[derive(Debug, Error)]
@@ -108,6 +314,12 @@ pub enum ReadError {
     RelationshipFromName(#[source] QueryError),
     #[error("failed to get to_name for relationship")]
     RelationshipToName(#[source] QueryError),
+    #[error("failed to get description for relationship")]
+    RelationshipDescription(#[source] QueryError),
+    #[error("failed to get direction for relationship")]
+    RelationshipDirection(#[source] QueryError),
+    #[error("failed to find snapshot for handle")]
+    FindSnapshot,
 }
 
 #[derive(Debug, Error)]
@@ -118,6 +330,37 @@ pub enum PathPurposeToFiletypeError {
     RelationshipFromName(#[source] QueryError),
     #[error("failed to get to_name for relationship")]
     RelationshipToName(#[source] QueryError),
+    #[error("failed to get description for relationship")]
+    RelationshipDescription(#[source] QueryError),
+    #[error("failed to get direction for relationship")]
+    RelationshipDirection(#[source] QueryError),
+}
+
+#[derive(Debug, Error)]
+pub enum GetStatsError {
+    #[error("failed to count items")]
+    CountItems(#[source] QueryError),
+    #[error("failed to count relationships")]
+    CountRelationships(#[source] QueryError),
+    #[error("failed to count filters")]
+    CountFilters(#[source] QueryError),
+    #[error("failed to measure content size")]
+    ContentSize(#[source] std::io::Error),
+}
+
+/// Name of the folder in an item's directory that holds the items on the other side of the
+/// relationship. A relationship that uses the same name for both sides (e.g. related/related) would
+/// otherwise produce two folders with the same name, so the direction is added as a suffix. Links
+/// are still directed, the out folder holds items this item points to and the in folder the ones
+/// pointing at it
+fn relationship_folder_name(relationship: &Relationship, side: RelationshipSide) -> String {
+    let symmetric = relationship.from_name == relationship.to_name;
+    match (side, symmetric) {
+        (RelationshipSide::Source, false) => relationship.to_name.clone(),
+        (RelationshipSide::Dest, false) => relationship.from_name.clone(),
+        (RelationshipSide::Source, true) => format!("{}(out)", relationship.to_name),
+        (RelationshipSide::Dest, true) => format!("{}(in)", relationship.from_name),
+    }
 }
 
 fn categorize_relationships(
@@ -134,10 +377,7 @@ fn categorize_relationships(
                 CategorizeRelationshipsError::RelationshipNonExistent(item_relationship.id.0)
             })?;
 
-        let name = match item_relationship.side {
-            RelationshipSide::Dest => relationship.from_name,
-            RelationshipSide::Source => relationship.to_name,
-        };
+        let name = relationship_folder_name(&relationship, item_relationship.side);
 
         ret.insert((item_relationship.id, item_relationship.side, name));
     }
@@ -145,10 +385,174 @@ fn categorize_relationships(
     Ok(ret.into_iter().collect())
 }
 
-pub enum DirEntry {
-    Dir(OsString),
-    File(OsString),
-    Link(OsString),
+/// Item names are not unique. Items that share their name with another entry of the same listing
+/// are presented as name#id so that every entry can still be resolved
+fn disambiguate_item_names(items: Vec<(ItemId, String)>) -> impl Iterator<Item = (ItemId, String)> {
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for (_, name) in &items {
+        *name_counts.entry(name.clone()).or_default() += 1;
+    }
+
+    items.into_iter().map(move |(id, name)| {
+        if name_counts[&name] > 1 {
+            let name = format!("{name}#{}", id.0);
+            (id, name)
+        } else {
+            (id, name)
+        }
+    })
+}
+
+/// Target of an item link at link_path. Links are relative so the mount can be moved, they climb
+/// out of the link's folder to the root and point at the item folder from there, e.g.
+/// /filter/item -> ../items/5 and /items/3/children/item -> ../../../items/5. The item id is
+/// always the last component of the target
+fn item_link_target(link_path: &Path, item_id: ItemId) -> PathBuf {
+    // Everything but the root and the link itself is a folder to climb out of. Links are never
+    // shallower than /filter/item, but a malformed path must not underflow into billions of ..
+    let num_components = link_path.iter().count().saturating_sub(2);
+
+    let mut output_path = PathBuf::new();
+    for _ in 0..num_components {
+        output_path.push("..")
+    }
+    output_path.push(&ITEMS_FOLDER[1..]);
+    output_path.push(item_id.0.to_string());
+    output_path
+}
+
+/// Intersections are named by the ids of the filters involved, e.g. 1+3
+fn parse_filter_intersection(name: &str) -> Option<Vec<FilterId>> {
+    name.split('+')
+        .map(|id| id.parse().ok().map(FilterId))
+        .collect()
+}
+
+/// Search queries are whitespace separated terms that all have to match
+/// * name~<substring>: name contains substring
+/// * has:<relationship id>:<side>: item is on the given side of at least one such relationship
+/// * no:<relationship id>:<side>: item is on the given side of no such relationship
+struct SearchQuery {
+    // Checked against item names in memory, search terms are never stored in the database
+    name_contains: Vec<String>,
+    rules: Vec<ItemFilterRule>,
+}
+
+impl SearchQuery {
+    fn matches_name(&self, name: &str) -> bool {
+        self.name_contains
+            .iter()
+            .all(|substring| name.contains(substring.as_str()))
+    }
+}
+
+fn parse_search_query(query: &str) -> Option<SearchQuery> {
+    let parse_relationship = |term: &str| -> Option<(RelationshipId, RelationshipSide)> {
+        let (id, side) = term.split_once(':')?;
+        Some((RelationshipId(id.parse().ok()?), side.parse().ok()?))
+    };
+
+    let mut name_contains = Vec::new();
+    let mut rules = Vec::new();
+    for term in query.split_whitespace() {
+        if let Some(substring) = term.strip_prefix("name~") {
+            name_contains.push(substring.to_string());
+        } else if let Some(term) = term.strip_prefix("has:") {
+            let (id, side) = parse_relationship(term)?;
+            rules.push(ItemFilterRule::RelationshipCount(
+                side,
+                id,
+                CompareOp::GreaterEqual,
+                1,
+            ));
+        } else if let Some(term) = term.strip_prefix("no:") {
+            let (id, side) = parse_relationship(term)?;
+            rules.push(ItemFilterRule::NoRelationship(side, id));
+        } else {
+            return None;
+        }
+    }
+
+    if name_contains.is_empty() && rules.is_empty() {
+        return None;
+    }
+
+    Some(SearchQuery {
+        name_contains,
+        rules,
+    })
+}
+
+const READDIR_PAGE_SIZE: usize = 256;
+
+/// Walks item ids in id order starting after the item with id after, only querying the next page
+/// once the previous one is consumed
+fn paginate_item_ids(
+    db: &Db,
+    after: ItemId,
+) -> impl Iterator<Item = Result<ItemId, QueryError>> + '_ {
+    let mut last_id = after;
+    let mut page = Vec::new().into_iter();
+    let mut done = false;
+
+    std::iter::from_fn(move || loop {
+        if let Some(id) = page.next() {
+            return Some(Ok(id));
+        }
+
+        if done {
+            return None;
+        }
+
+        let limit = READDIR_PAGE_SIZE
+            .try_into()
+            .expect("page size does not fit in i64");
+        match db.get_item_ids_after(last_id, limit) {
+            Ok(ids) => {
+                done = ids.len() < READDIR_PAGE_SIZE;
+                last_id = ids.last().copied().unwrap_or(last_id);
+                page = ids.into_iter();
+            }
+            Err(e) => {
+                done = true;
+                return Some(Err(e));
+            }
+        }
+    })
+}
+
+fn to_dir_entry(
+    purpose: PathPurpose,
+    name: String,
+    offset: usize,
+    db: &Db,
+) -> Result<DirEntry, ReadDirError> {
+    let filetype = path_purpose_to_filetype(&purpose, db).map_err(ReadDirError::GetFiletype)?;
+    let passthrough_path = match purpose {
+        PathPurpose::PassthroughPath(p) => Some(p),
+        _ => None,
+    };
+    Ok(DirEntry {
+        name: name.into(),
+        offset,
+        filetype,
+        passthrough_path,
+    })
+}
+
+pub struct DirEntry {
+    pub name: OsString,
+    // Passed back to readdir to continue listing after this entry
+    pub offset: usize,
+    pub filetype: Filetype,
+    // Location on the real filesystem for entries that are passed through
+    pub passthrough_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Xattr {
+    pub name: String,
+    pub value: Vec<u8>,
 }
 
 pub enum Filetype {
@@ -157,9 +561,22 @@ pub enum Filetype {
     Link,
 }
 
+/// The parts of the flags passed to open that decide whether a path may be opened at all
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenFlags {
+    // Opened for writing or truncation
+    pub write: bool,
+    // O_DIRECTORY, the caller expects a directory
+    pub directory: bool,
+}
+
 pub enum OpenRet {
     Socket(u64),
-    Noop,
+    // Reads are served from contents captured at open, see FuseClient::snapshots
+    Snapshot(u64),
+    // Nothing to track for the file. direct_io bypasses the kernel page cache so that reads
+    // always reflect the current state of the database
+    Noop { direct_io: bool },
     Unhandled,
 }
 
@@ -171,6 +588,17 @@ enum PathPurpose {
     ToolBins,
     // listing of all items by id
     Items,
+    // listing of all items by name
+    ItemsByName,
+    // listing of the most recently created items, newest first
+    Recent,
+    // listing of pinned items. Linking an item here pins it, removing the link unpins it
+    Pinned,
+    // listing of items with nothing in their content folder. Every content folder is read on
+    // each listing, so this gets slower as the number of items grows
+    EmptyContent,
+    // listing of items in the trash, each entry is the trashed item's content folder
+    Trash,
     // listing of all relationships by id
     Relationships,
     // "socket" file that allows sending/receiving messages out of band to the fuse filesystem
@@ -181,28 +609,78 @@ enum PathPurpose {
     ItemId(ItemId),
     // metadata file that shows name of current item
     ItemName(ItemId),
+    // metadata file that shows the total size of the files in the item's content folder
+    ItemSize(ItemId),
     // Directory associated with a given relationship
     Relationship(RelationshipId),
     RelationshipId(RelationshipId),
     RelationshipFromName(RelationshipId),
     RelationshipToName(RelationshipId),
+    RelationshipDescription(RelationshipId),
+    RelationshipDirection(RelationshipId),
+    // Folder with an entry for every pair of items linked by the relationship, named
+    // <from id>-<to id>
+    RelationshipLinks(RelationshipId),
+    // File naming the two items of a link in a RelationshipLinks folder
+    RelationshipLink(RelationshipId, ItemId, ItemId),
     // Folder showing all items associated with ItemId by relationship RelationshipId
     // e.g. in a parents <-> children relationship, this is a "parents" or "children" directory
     ItemRelationships(ItemId, RelationshipId, RelationshipSide),
+    // Folder showing every item related to ItemId, regardless of relationship or side. Entries
+    // are named <relationship folder name>#<sibling id>
+    ItemAllRelated(ItemId),
     // A link to a specific item by id (presented by name)
     ItemLink(ItemId),
+    // A link to a specific item by name. Names that are shared by multiple items are suffixed
+    // with #<id> to disambiguate
+    ItemByName(String),
     // a path that is passed through to the real filesystem
     PassthroughPath(PathBuf),
     // Named filter that shows items filtered in some way
     Filter(FilterId),
+    // Named filter that references a variable item, evaluated with the given item as context
+    ItemContextFilter(ItemId, FilterId),
+    // Directory holding searches created with mkdir
+    Search,
+    // Items matching a search query, see parse_search_query
+    SearchQuery(String),
+    // Directory in which any <filter id>+<filter id>+... name can be looked up
+    Intersect,
+    // Items matched by all of the given filters
+    FilterIntersection(Vec<FilterId>),
+    // Audit log of changes to the db, one json object per line
+    Events,
+    // Summary of the db contents
+    Stats(StatsFormat),
+    // Which mount this is, as json
+    Info(MountInfo),
+    // Item created with create() in the items folder, shown as the requested empty file until
+    // the creating handle is released. Writes to it are discarded
+    CreatedItem,
     // Unknown
     Unknown,
 }
 
 const ITEMS_FOLDER: &str = "/items";
 const RELATIONSHIPS_FOLDER: &str = "/relationships";
+const ITEMS_BY_NAME_FOLDER: &str = "/items-by-name";
+const RECENT_FOLDER: &str = "/recent";
+const PINNED_FOLDER: &str = "/pinned";
+const EMPTY_CONTENT_FOLDER: &str = "/empty-content";
+const TRASH_FOLDER: &str = "/trash";
+const SEARCH_FOLDER: &str = "/search";
+const INTERSECT_FOLDER: &str = "/intersect";
+pub const DEFAULT_RECENT_COUNT: usize = 20;
+const XATTR_PREFIX: &str = "user.todofs.";
+
+/// Metadata files hold a single value. Trailing whitespace is not part of it, so writes with or
+/// without a final newline store the same thing and reads end in exactly one newline
+fn normalize_metadata_value(s: &str) -> &str {
+    s.trim_end()
+}
 
-fn with_newline_as_vec(mut s: String) -> Vec<u8> {
+fn with_newline_as_vec(s: String) -> Vec<u8> {
+    let mut s = normalize_metadata_value(&s).to_string();
     s += "\n";
     s.into_bytes()
 }
@@ -222,6 +700,20 @@ fn get_item_name_file_contents(id: &ItemId, db: &Db) -> Vec<u8> {
     with_newline_as_vec(item.name)
 }
 
+fn get_item_size_file_contents(id: &ItemId, db: &Db) -> Result<Vec<u8>, std::io::Error> {
+    let size = content_size(&db.content_folder_for_id(*id)?)?;
+    Ok(format!("{size}\n").into_bytes())
+}
+
+/// Walked on every call, content is written through the passthrough folder without us noticing.
+/// A content folder that was removed behind our back is empty
+fn content_size(content_folder: &Path) -> Result<u64, std::io::Error> {
+    match dir_size(content_folder) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        ret => ret,
+    }
+}
+
 fn get_relationship_from_name_file_contents(
     id: &RelationshipId,
     db: &Db,
@@ -242,6 +734,145 @@ fn get_relationship_to_name_file_contents(
     Ok(with_newline_as_vec(relationship.to_name))
 }
 
+fn get_relationship_description_file_contents(
+    id: &RelationshipId,
+    db: &Db,
+) -> Result<Vec<u8>, QueryError> {
+    let Some(relationship) = db.get_relationship(*id)? else {
+        return Ok(Default::default());
+    };
+    Ok(with_newline_as_vec(relationship.description))
+}
+
+fn get_relationship_direction_file_contents(
+    id: &RelationshipId,
+    db: &Db,
+) -> Result<Vec<u8>, QueryError> {
+    let Some(direction) = db.describe_relationship(*id)? else {
+        return Ok(Default::default());
+    };
+    Ok(with_newline_as_vec(direction.to_string()))
+}
+
+fn get_relationship_link_file_contents(from_id: &ItemId, to_id: &ItemId, db: &Db) -> Vec<u8> {
+    let (Some(from), Some(to)) = (db.get_item_by_id(*from_id), db.get_item_by_id(*to_id)) else {
+        return Default::default();
+    };
+    with_newline_as_vec(format!("{} -> {}", from.name, to.name))
+}
+
+fn get_events_file_contents(db: &Db) -> Result<Vec<u8>, QueryError> {
+    let mut ret = Vec::new();
+    for event in db.get_events()? {
+        serde_json::to_writer(&mut ret, &event).expect("events are always serializable");
+        ret.push(b'\n');
+    }
+    Ok(ret)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    items: i64,
+    relationships: i64,
+    filters: i64,
+    content_bytes: u64,
+}
+
+/// Total size of the regular files under path. Symlinks are not followed so links out of a
+/// content folder are not counted
+fn dir_size(path: &Path) -> Result<u64, std::io::Error> {
+    let mut ret = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            ret += dir_size(&entry.path())?;
+        } else if metadata.is_file() {
+            ret += metadata.len();
+        }
+    }
+    Ok(ret)
+}
+
+/// Identifies a running mount, so scripts can check they are talking to the right one before
+/// sending requests through its socket
+#[derive(Debug, Clone, Serialize)]
+struct MountInfo {
+    db_path: Option<PathBuf>,
+    mountpoint: Option<PathBuf>,
+    pid: u32,
+    schema_version: u32,
+    api_version: u32,
+}
+
+fn get_info_file_contents(info: &MountInfo) -> Vec<u8> {
+    let mut ret = serde_json::to_vec(info).expect("mount info is always serializable");
+    ret.push(b'\n');
+    ret
+}
+
+fn get_stats_file_contents(db: &Db, format: StatsFormat) -> Result<Vec<u8>, GetStatsError> {
+    let stats = Stats {
+        items: db.count_items().map_err(GetStatsError::CountItems)?,
+        relationships: db
+            .count_relationships()
+            .map_err(GetStatsError::CountRelationships)?,
+        filters: db.count_filters().map_err(GetStatsError::CountFilters)?,
+        content_bytes: dir_size(db.fs_root()).map_err(GetStatsError::ContentSize)?,
+    };
+
+    let ret = match format {
+        StatsFormat::Text => format!(
+            "items: {}\nrelationships: {}\nfilters: {}\ncontent bytes: {}\n",
+            stats.items, stats.relationships, stats.filters, stats.content_bytes
+        )
+        .into_bytes(),
+        StatsFormat::Json => {
+            let mut ret = serde_json::to_vec(&stats).expect("stats are always serializable");
+            ret.push(b'\n');
+            ret
+        }
+    };
+    Ok(ret)
+}
+
+/// Whether a listed entry is a directory, without evaluating anything for entries that are files.
+/// Covers the same purposes path_purpose_to_filetype reports as directories, except unknown paths
+fn is_subdir(purpose: &PathPurpose) -> bool {
+    match purpose {
+        PathPurpose::PassthroughPath(p) => {
+            p.symlink_metadata().is_ok_and(|metadata| metadata.is_dir())
+        }
+        PathPurpose::Root
+        | PathPurpose::ToolBins
+        | PathPurpose::Items
+        | PathPurpose::ItemsByName
+        | PathPurpose::Recent
+        | PathPurpose::Pinned
+        | PathPurpose::EmptyContent
+        | PathPurpose::Trash
+        | PathPurpose::Relationships
+        | PathPurpose::Item(_)
+        | PathPurpose::Relationship(_)
+        | PathPurpose::RelationshipLinks(_)
+        | PathPurpose::Filter(_)
+        | PathPurpose::ItemContextFilter(_, _)
+        | PathPurpose::Search
+        | PathPurpose::SearchQuery(_)
+        | PathPurpose::Intersect
+        | PathPurpose::FilterIntersection(_)
+        | PathPurpose::ItemRelationships(_, _, _)
+        | PathPurpose::ItemAllRelated(_) => true,
+        _ => false,
+    }
+}
+
 fn path_purpose_to_filetype(
     purpose: &PathPurpose,
     db: &Db,
@@ -250,14 +881,32 @@ fn path_purpose_to_filetype(
         PathPurpose::Root
         | PathPurpose::ToolBins
         | PathPurpose::Items
+        | PathPurpose::ItemsByName
+        | PathPurpose::Recent
+        | PathPurpose::Pinned
+        | PathPurpose::EmptyContent
+        | PathPurpose::Trash
         | PathPurpose::Relationships
         | PathPurpose::Item(_)
         | PathPurpose::Relationship(_)
+        | PathPurpose::RelationshipLinks(_)
         | PathPurpose::Filter(_)
+        | PathPurpose::ItemContextFilter(_, _)
+        | PathPurpose::Search
+        | PathPurpose::SearchQuery(_)
+        | PathPurpose::Intersect
+        | PathPurpose::FilterIntersection(_)
         | PathPurpose::ItemRelationships(_, _, _)
+        | PathPurpose::ItemAllRelated(_)
         | PathPurpose::Unknown => Filetype::Dir,
-        PathPurpose::ItemLink(_) => Filetype::Link,
-        PathPurpose::Socket => Filetype::File(0),
+        PathPurpose::ItemLink(_) | PathPurpose::ItemByName(_) => Filetype::Link,
+        // Sockets have no contents of their own, snapshotted files are only rendered when
+        // opened, see FuseClient::snapshots
+        PathPurpose::Socket
+        | PathPurpose::CreatedItem
+        | PathPurpose::ItemSize(_)
+        | PathPurpose::Events
+        | PathPurpose::Stats(_) => Filetype::File(0),
         PathPurpose::ItemId(id) => {
             let content_length = get_item_id_file_contents(id).len();
             Filetype::File(content_length)
@@ -282,10 +931,35 @@ fn path_purpose_to_filetype(
                 .len();
             Filetype::File(content_length)
         }
+        PathPurpose::RelationshipDescription(id) => {
+            let content_length = get_relationship_description_file_contents(id, db)
+                .map_err(PathPurposeToFiletypeError::RelationshipDescription)?
+                .len();
+            Filetype::File(content_length)
+        }
+        PathPurpose::RelationshipDirection(id) => {
+            let content_length = get_relationship_direction_file_contents(id, db)
+                .map_err(PathPurposeToFiletypeError::RelationshipDirection)?
+                .len();
+            Filetype::File(content_length)
+        }
+        PathPurpose::RelationshipLink(_, from_id, to_id) => {
+            let content_length = get_relationship_link_file_contents(from_id, to_id, db).len();
+            Filetype::File(content_length)
+        }
+        PathPurpose::Info(info) => Filetype::File(get_info_file_contents(info).len()),
         PathPurpose::PassthroughPath(p) => {
-            let metadata = p
-                .metadata()
-                .map_err(PathPurposeToFiletypeError::GetMetadata)?;
+            let metadata = match p.metadata() {
+                Ok(v) => v,
+                // Listed as an empty directory until something is created inside it
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::NotFound
+                        && db.content_folder_owner(p).is_some() =>
+                {
+                    return Ok(Filetype::Dir);
+                }
+                Err(e) => return Err(PathPurposeToFiletypeError::GetMetadata(e)),
+            };
             if metadata.is_dir() {
                 Filetype::Dir
             } else if metadata.is_symlink() {
@@ -299,22 +973,112 @@ fn path_purpose_to_filetype(
     Ok(ret)
 }
 
+/// Why a request failed, for requests that report failure in their response rather than as an
+/// error. A batch has to roll back for those as well
+fn response_failure(response: &ClientResponse) -> Option<String> {
+    let reason = match response {
+        ClientResponse::CreateItems(CreateItemsResponse::Failed { reason, .. })
+        | ClientResponse::CreateItemRelationships(CreateItemRelationshipsResponse::Failed {
+            reason,
+            ..
+        })
+        | ClientResponse::Batch(BatchResponse::Failed { reason, .. }) => reason.clone(),
+        ClientResponse::CreateItemRelationshipByName(response) => match response {
+            CreateItemRelationshipByNameResponse::Created { .. } => return None,
+            CreateItemRelationshipByNameResponse::RelationshipNotFound => {
+                "relationship not found".to_string()
+            }
+            CreateItemRelationshipByNameResponse::ItemNotFound { name } => {
+                format!("no item named {name}")
+            }
+            CreateItemRelationshipByNameResponse::AmbiguousItemName { name, .. } => {
+                format!("several items are named {name}")
+            }
+            CreateItemRelationshipByNameResponse::AmbiguousRelationshipName { .. } => {
+                "several relationships have the given name".to_string()
+            }
+        },
+        _ => return None,
+    };
+
+    Some(reason)
+}
+
 #[derive(Debug)]
 pub struct FuseClient {
     pub db: Db,
     latest_open_id: u64,
+    // Responses waiting to be read from socket handles. A handle carries one request at a time,
+    // the response to a request replaces anything left unread from the one before
     open_files: HashMap<u64, VecDeque<u8>>,
+    // API versions sent by clients that said hello on a handle
+    handle_versions: HashMap<u64, u32>,
+    // Contents of generated files that are expensive to render, taken once when the handle is
+    // opened so that reading a large file in pieces does not render it again for every piece
+    snapshots: HashMap<u64, Vec<u8>>,
+    // Requests larger than a single write arrive in pieces, they are buffered here until the
+    // newline ending them arrives
+    pending_requests: HashMap<u64, Vec<u8>>,
+    readonly: bool,
+    recent_count: usize,
+    // Whether deleting an item moves it to the trash by default instead of removing it for good
+    soft_delete: bool,
+    // Queries created under the search folder. These only live as long as the mount
+    searches: BTreeSet<String>,
+    // Paths and items of handles returned by create, see PathPurpose::CreatedItem
+    created_items: HashMap<u64, (PathBuf, ItemId)>,
+    // Items created with create() by the path they were created at. Names are not unique, so
+    // the path has to keep linking to the item that was created rather than resolving the name
+    created_paths: HashMap<PathBuf, ItemId>,
+    // Files in content folders opened on the underlying file system, by handle. The fd is not
+    // used as the handle itself, it could collide with a socket handle or be 0
+    passthrough_fds: HashMap<u64, i32>,
+    // Where the file system is mounted, reported in /.info
+    mountpoint: Option<PathBuf>,
 }
 
 impl FuseClient {
-    pub fn new(db: Db) -> FuseClient {
+    pub fn new(db: Db, readonly: bool, recent_count: usize, soft_delete: bool) -> FuseClient {
         FuseClient {
             db,
             latest_open_id: 0,
             open_files: HashMap::new(),
+            handle_versions: HashMap::new(),
+            snapshots: HashMap::new(),
+            pending_requests: HashMap::new(),
+            readonly,
+            recent_count,
+            soft_delete,
+            searches: BTreeSet::new(),
+            created_items: HashMap::new(),
+            created_paths: HashMap::new(),
+            passthrough_fds: HashMap::new(),
+            mountpoint: None,
+        }
+    }
+
+    pub fn set_mountpoint(&mut self, mountpoint: PathBuf) {
+        self.mountpoint = Some(mountpoint);
+    }
+
+    fn mount_info(&self) -> MountInfo {
+        MountInfo {
+            db_path: self.db.db_path().map(Path::to_path_buf),
+            mountpoint: self.mountpoint.clone(),
+            pid: std::process::id(),
+            schema_version: db::SCHEMA_VERSION,
+            api_version: API_VERSION,
         }
     }
 
+    pub fn into_db(self) -> Db {
+        self.db
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
     pub fn get_passthrough_path(&mut self, path: &Path) -> Result<Option<PathBuf>, ParsePathError> {
         if let PathPurpose::PassthroughPath(p) = self.parse_path(path)? {
             return Ok(Some(p));
@@ -323,62 +1087,585 @@ impl FuseClient {
         Ok(None)
     }
 
-    pub fn get_filetype(&mut self, path: &Path) -> Result<Filetype, GetFiletypeError> {
-        path_purpose_to_filetype(
-            &self.parse_path(path).map_err(GetFiletypeError::ParsePath)?,
-            &self.db,
-        )
-        .map_err(GetFiletypeError::GetFileType)
+    /// Like [`FuseClient::get_passthrough_path`], for operations that change what is at the path.
+    /// The content of trashed items is read only, otherwise restoring an item could bring back
+    /// something other than what was trashed
+    pub fn get_writable_passthrough_path(
+        &mut self,
+        path: &Path,
+    ) -> Result<Option<PathBuf>, GetWritablePassthroughPathError> {
+        let passthrough_path = self.get_passthrough_path(path)?;
+        if passthrough_path
+            .as_ref()
+            .is_some_and(|p| self.db.is_trash_path(p))
+        {
+            return Err(GetWritablePassthroughPathError::InTrash);
+        }
+
+        Ok(passthrough_path)
     }
 
-    pub fn open(&mut self, path: &Path) -> Result<OpenRet, ParsePathError> {
-        match self.parse_path(path)? {
-            PathPurpose::Socket => (),
-            PathPurpose::ItemId(_)
-            | PathPurpose::ItemName(_)
-            | PathPurpose::RelationshipId(_)
-            | PathPurpose::RelationshipToName(_)
-            | PathPurpose::RelationshipFromName(_) => {
-                return Ok(OpenRet::Noop);
+    /// Called before creating passthrough_path. A content folder that was removed behind our back
+    /// is listed as empty, and has to come back before anything can be created inside of it
+    pub fn prepare_passthrough_create(
+        &self,
+        passthrough_path: &Path,
+    ) -> Result<(), std::io::Error> {
+        let owner = passthrough_path
+            .parent()
+            .and_then(|parent| self.db.content_folder_owner(parent));
+        match owner {
+            Some(id) => self.db.restore_content_folder(id),
+            None => Ok(()),
+        }
+    }
+
+    /// Symlinks created in an item relationship folder relate the folder's item to the target
+    /// item, symlinks created in the pinned folder pin the target item. The link name is ignored,
+    /// and the target has to resolve to items/<id> either relative to the link, e.g. ../../5 from
+    /// items/3/children, or as an absolute path into the mount
+    pub fn symlink(&mut self, target: &Path, linkpath: &Path) -> Result<(), SymlinkError> {
+        let parent = linkpath.parent().ok_or(SymlinkError::Unhandled)?;
+        let (item_id, relationship_id, side) = match self.parse_path(parent)? {
+            PathPurpose::ItemRelationships(item_id, relationship_id, side) => {
+                (item_id, relationship_id, side)
+            }
+            PathPurpose::Pinned => {
+                let target_id = self.resolve_symlink_target(parent, target)?;
+                self.db.set_pinned(target_id, true)?;
+                return Ok(());
             }
-            _ => return Ok(OpenRet::Unhandled),
+            // Relationship folders are only listed once an item has such a relationship, so the
+            // first link has to be resolved by relationship name
+            PathPurpose::Unknown => self
+                .find_unlisted_relationship_folder(parent)?
+                .ok_or(SymlinkError::Unhandled)?,
+            _ => return Err(SymlinkError::Unhandled),
         };
 
-        self.open_files.insert(self.latest_open_id, VecDeque::new());
-        let id = self.latest_open_id;
-        self.latest_open_id += 1;
+        let target_id = self.resolve_symlink_target(parent, target)?;
+        match side {
+            RelationshipSide::Source => {
+                self.db
+                    .add_item_relationship(item_id, target_id, relationship_id)?
+            }
+            RelationshipSide::Dest => {
+                self.db
+                    .add_item_relationship(target_id, item_id, relationship_id)?
+            }
+        }
 
-        Ok(OpenRet::Socket(id))
+        Ok(())
     }
 
-    pub fn write(&mut self, id: u64, buf: &[u8]) -> Result<(), WriteError> {
-        let req = serde_json::from_slice::<ClientRequest>(buf).map_err(WriteError::ParseJson)?;
+    fn resolve_symlink_target(&self, parent: &Path, target: &Path) -> Result<ItemId, SymlinkError> {
+        let mut resolved = PathBuf::new();
+        for component in parent.join(target).components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::CurDir => (),
+                _ => resolved.push(component),
+            }
+        }
 
-        match req {
-            ClientRequest::CreateItem(create_item_req) => {
-                let item_id = self
-                    .db
-                    .create_item(&create_item_req.name)
-                    .map_err(WriteError::CreateItem)?;
-                let new_item_path = Path::new(ITEMS_FOLDER).join(item_id.0.to_string());
+        let mut components = resolved.iter().rev();
+        let (Some(id), Some(items_folder)) = (components.next(), components.next()) else {
+            return Err(SymlinkError::InvalidTarget);
+        };
+
+        if items_folder != &ITEMS_FOLDER[1..] {
+            return Err(SymlinkError::InvalidTarget);
+        }
+
+        let target_id = id
+            .to_str()
+            .and_then(|id| id.parse().ok())
+            .map(ItemId)
+            .ok_or(SymlinkError::InvalidTarget)?;
+
+        if self.db.get_item_by_id(target_id).is_none() {
+            return Err(SymlinkError::InvalidTarget);
+        }
+
+        Ok(target_id)
+    }
+
+    /// Removing an item link from an item relationship folder removes that relationship, and
+    /// removing one from the pinned folder unpins the item. Links in other folders are the result
+    /// of a filter or query and cannot be removed
+    pub fn unlink(&mut self, path: &Path) -> Result<(), UnlinkError> {
+        let parent = path.parent().ok_or(UnlinkError::Unhandled)?;
+        let sibling_id = match self.parse_path(path)? {
+            PathPurpose::ItemLink(sibling_id) => sibling_id,
+            PathPurpose::Unknown => return Err(UnlinkError::NotFound),
+            _ => return Err(UnlinkError::Unhandled),
+        };
+
+        let (item_id, relationship_id, side) = match self.parse_path(parent)? {
+            PathPurpose::ItemRelationships(item_id, relationship_id, side) => {
+                (item_id, relationship_id, side)
+            }
+            PathPurpose::Pinned => {
+                self.db.set_pinned(sibling_id, false)?;
+                return Ok(());
+            }
+            _ => return Err(UnlinkError::Computed),
+        };
+
+        match side {
+            RelationshipSide::Source => {
+                self.db
+                    .remove_item_relationship(item_id, sibling_id, relationship_id)?
+            }
+            RelationshipSide::Dest => {
+                self.db
+                    .remove_item_relationship(sibling_id, item_id, relationship_id)?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renames within the real file system. Virtual files only exist as long as the db says so,
+    /// so they can neither be moved nor be the destination of a real file. The exception are
+    /// filters, which can be given a new name that is free at the root
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), RenameError> {
+        let to_purpose = self.parse_path(to)?;
+        let from = match self.parse_path(from)? {
+            PathPurpose::Filter(filter_id) => {
+                let name = to.file_name().and_then(|name| name.to_str());
+                return match (to.parent(), name, to_purpose) {
+                    (Some(parent), Some(name), PathPurpose::Unknown)
+                        if parent == Path::new("/") =>
+                    {
+                        Ok(self.db.rename_filter(filter_id, name)?)
+                    }
+                    _ => Err(RenameError::Synthetic),
+                };
+            }
+            PathPurpose::PassthroughPath(p) => Some(p),
+            PathPurpose::Unknown => return Err(RenameError::NotFound),
+            _ => None,
+        };
+
+        let to = match to_purpose {
+            PathPurpose::PassthroughPath(p) => Some(p),
+            _ => None,
+        };
+
+        match (from, to) {
+            (Some(from), Some(to)) => {
+                if self.db.is_trash_path(&from) || self.db.is_trash_path(&to) {
+                    return Err(RenameError::InTrash);
+                }
+                log::debug!("Renaming {} -> {}", from.display(), to.display());
+                fs::rename(from, to).map_err(RenameError::Rename)
+            }
+            (None, None) => Err(RenameError::Synthetic),
+            _ => Err(RenameError::CrossesBoundary),
+        }
+    }
+
+    fn find_unlisted_relationship_folder(
+        &mut self,
+        path: &Path,
+    ) -> Result<Option<(ItemId, RelationshipId, RelationshipSide)>, SymlinkError> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(None);
+        };
+
+        let PathPurpose::Item(item_id) = self.parse_path(parent)? else {
+            return Ok(None);
+        };
+
+        let relationships = self
+            .db
+            .get_relationships()
+            .map_err(SymlinkError::GetRelationships)?;
+
+        for relationship in relationships {
+            for side in [RelationshipSide::Source, RelationshipSide::Dest] {
+                if name == relationship_folder_name(&relationship, side).as_str() {
+                    return Ok(Some((item_id, relationship.id, side)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn mkdir(&mut self, path: &Path) -> Result<(), MkdirError> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(MkdirError::Unhandled);
+        };
+
+        let PathPurpose::Search = self.parse_path(parent)? else {
+            return Err(MkdirError::Unhandled);
+        };
+
+        let query = name.to_str().ok_or(MkdirError::InvalidQuery)?;
+        if parse_search_query(query).is_none() {
+            return Err(MkdirError::InvalidQuery);
+        }
+
+        self.searches.insert(query.to_string());
+        Ok(())
+    }
+
+    /// Creates an item named after the last component of a path in the items folder, returning a
+    /// handle for the new file. Once the handle is released the path links to the item
+    pub fn create(&mut self, path: &Path) -> Result<u64, CreateError> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(CreateError::Unhandled);
+        };
+
+        let PathPurpose::Items = self.parse_path(parent)? else {
+            return Err(CreateError::Unhandled);
+        };
+
+        let name = name.to_str().ok_or(CreateError::InvalidName)?;
+        if let Ok(id) = name.parse::<i64>() {
+            let id = ItemId(id);
+            if self.db.get_item_by_id(id).is_some() {
+                return Err(CreateError::ItemExists(id));
+            }
+            return Err(CreateError::NumericName);
+        }
+
+        let item_id = self.db.create_item(name).map_err(CreateError::CreateItem)?;
+
+        let id = self.latest_open_id;
+        self.latest_open_id += 1;
+        self.created_items.insert(id, (path.to_path_buf(), item_id));
+        Ok(id)
+    }
+
+    pub fn rmdir(&mut self, path: &Path) -> Result<(), RmdirError> {
+        match self.parse_path(path)? {
+            PathPurpose::SearchQuery(query) => {
+                self.searches.remove(&query);
+                Ok(())
+            }
+            PathPurpose::Unknown => Err(RmdirError::NotFound),
+            _ => Err(RmdirError::Unhandled),
+        }
+    }
+
+    /// Extended attributes of a non-passthrough path, or None if the path does not support them
+    pub fn get_xattrs(&mut self, path: &Path) -> Result<Option<Vec<Xattr>>, GetXattrsError> {
+        let PathPurpose::Item(id) = self.parse_path(path)? else {
+            return Ok(None);
+        };
+
+        let item = self
+            .db
+            .get_item_by_id(id)
+            .ok_or(GetXattrsError::ItemIdNotInDatabase)?;
+
+        let mut ret = vec![
+            Xattr {
+                name: format!("{XATTR_PREFIX}id"),
+                value: item.id.0.to_string().into_bytes(),
+            },
+            Xattr {
+                name: format!("{XATTR_PREFIX}name"),
+                value: item.name.into_bytes(),
+            },
+        ];
+
+        // Items from before timestamps were recorded have nothing to report
+        if let Some(created_at) = item.created_at {
+            ret.push(Xattr {
+                name: format!("{XATTR_PREFIX}created_at"),
+                value: created_at.to_string().into_bytes(),
+            });
+        }
+
+        Ok(Some(ret))
+    }
+
+    pub fn get_filetype(&mut self, path: &Path) -> Result<Filetype, GetFiletypeError> {
+        path_purpose_to_filetype(
+            &self.parse_path(path).map_err(GetFiletypeError::ParsePath)?,
+            &self.db,
+        )
+        .map_err(GetFiletypeError::GetFileType)
+    }
+
+    /// Unix timestamp to report as the modification time of a path backed by an item or
+    /// relationship. Other synthetic paths have no meaningful time
+    pub fn get_timestamp(&mut self, path: &Path) -> Result<Option<i64>, GetTimestampError> {
+        let ret = match self.parse_path(path)? {
+            PathPurpose::Item(id)
+            | PathPurpose::ItemId(id)
+            | PathPurpose::ItemName(id)
+            | PathPurpose::ItemSize(id)
+            | PathPurpose::ItemLink(id) => self.db.get_item_created_at(id)?,
+            PathPurpose::Relationship(id)
+            | PathPurpose::RelationshipId(id)
+            | PathPurpose::RelationshipFromName(id)
+            | PathPurpose::RelationshipToName(id)
+            | PathPurpose::RelationshipDescription(id)
+            | PathPurpose::RelationshipDirection(id)
+            | PathPurpose::RelationshipLinks(id)
+            | PathPurpose::RelationshipLink(id, _, _) => self.db.get_relationship_created_at(id)?,
+            _ => None,
+        };
+
+        Ok(ret)
+    }
+
+    /// Total size of the files in an item's content, reported as the size of the item folder and
+    /// of its content folder. None for every other path
+    pub fn get_content_size(&mut self, path: &Path) -> Result<Option<u64>, GetContentSizeError> {
+        let content_folder = match self.parse_path(path)? {
+            PathPurpose::Item(id) => self
+                .db
+                .content_folder_for_id(id)
+                .map_err(GetContentSizeError::GetContentFolder)?,
+            PathPurpose::PassthroughPath(p) if self.db.content_folder_owner(&p).is_some() => p,
+            _ => return Ok(None),
+        };
+
+        let size = content_size(&content_folder).map_err(GetContentSizeError::Measure)?;
+        Ok(Some(size))
+    }
+
+    /// Virtual files are checked against flags the way a real file system would. Only the files
+    /// that write handles accept write access, the rest are generated from the db
+    pub fn open(&mut self, path: &Path, flags: OpenFlags) -> Result<OpenRet, OpenError> {
+        let purpose = self.parse_path(path)?;
+        let writable = match purpose {
+            PathPurpose::Socket
+            | PathPurpose::RelationshipToName(_)
+            | PathPurpose::RelationshipFromName(_) => true,
+            PathPurpose::ItemId(_)
+            | PathPurpose::ItemName(_)
+            | PathPurpose::ItemSize(_)
+            | PathPurpose::RelationshipId(_)
+            | PathPurpose::RelationshipDescription(_)
+            | PathPurpose::RelationshipDirection(_)
+            | PathPurpose::RelationshipLink(_, _, _)
+            | PathPurpose::Events
+            | PathPurpose::Stats(_)
+            | PathPurpose::Info(_) => false,
+            PathPurpose::Unknown => return Err(OpenError::NotFound),
+            _ => {
+                let filetype =
+                    path_purpose_to_filetype(&purpose, &self.db).map_err(OpenError::GetFiletype)?;
+                return match filetype {
+                    Filetype::Dir if flags.write => Err(OpenError::IsADirectory),
+                    Filetype::Dir => Ok(OpenRet::Noop { direct_io: false }),
+                    _ => Ok(OpenRet::Unhandled),
+                };
+            }
+        };
+
+        if flags.directory {
+            return Err(OpenError::NotADirectory);
+        }
+
+        if flags.write && !writable {
+            return Err(OpenError::ReadOnly);
+        }
+
+        let snapshot = match purpose {
+            PathPurpose::Socket => None,
+            PathPurpose::Events => {
+                Some(get_events_file_contents(&self.db).map_err(OpenError::Events)?)
+            }
+            PathPurpose::Stats(format) => {
+                Some(get_stats_file_contents(&self.db, format).map_err(OpenError::Stats)?)
+            }
+            PathPurpose::ItemSize(id) => {
+                Some(get_item_size_file_contents(&id, &self.db).map_err(OpenError::ItemSize)?)
+            }
+            // Metadata is generated from the db on every read, a cached copy goes stale as soon
+            // as e.g. the item is renamed
+            _ => return Ok(OpenRet::Noop { direct_io: true }),
+        };
+
+        if let Some(snapshot) = snapshot {
+            let id = self.latest_open_id;
+            self.latest_open_id += 1;
+            self.snapshots.insert(id, snapshot);
+            return Ok(OpenRet::Snapshot(id));
+        }
+
+        self.open_files.insert(self.latest_open_id, VecDeque::new());
+        let id = self.latest_open_id;
+        self.latest_open_id += 1;
+
+        Ok(OpenRet::Socket(id))
+    }
+
+    pub fn write(&mut self, path: &Path, id: u64, buf: &[u8]) -> Result<(), WriteError> {
+        if self.readonly {
+            return Err(WriteError::ReadOnly);
+        }
+
+        match self.parse_path(path)? {
+            PathPurpose::Socket => self.handle_socket_request(id, buf),
+            PathPurpose::RelationshipFromName(relationship_id) => {
+                let name = std::str::from_utf8(buf).map_err(WriteError::InvalidUtf8)?;
+                let name = normalize_metadata_value(name);
+                self.db
+                    .rename_relationship(relationship_id, Some(name), None)?;
+                Ok(())
+            }
+            PathPurpose::RelationshipToName(relationship_id) => {
+                let name = std::str::from_utf8(buf).map_err(WriteError::InvalidUtf8)?;
+                let name = normalize_metadata_value(name);
+                self.db
+                    .rename_relationship(relationship_id, None, Some(name))?;
+                Ok(())
+            }
+            // Shells write to files they create, e.g. with echo x > /items/new. The item is
+            // created either way, so the contents are dropped rather than failing the write
+            PathPurpose::CreatedItem => Ok(()),
+            _ => Err(WriteError::UnhandledPath),
+        }
+    }
+
+    /// Requests end in a newline, see [`crate::fuse::api::serialize_request`]. Bytes are buffered
+    /// until one arrives, and only the newly written bytes are searched for it
+    fn handle_socket_request(&mut self, id: u64, buf: &[u8]) -> Result<(), WriteError> {
+        let pending = self.pending_requests.entry(id).or_default();
+        let mut searched = pending.len();
+        pending.extend_from_slice(buf);
+
+        loop {
+            let Some(pending) = self.pending_requests.get_mut(&id) else {
+                return Ok(());
+            };
+            let Some(end) = pending[searched..].iter().position(|&b| b == b'\n') else {
+                return Ok(());
+            };
+            let line: Vec<u8> = pending.drain(..=searched + end).collect();
+            if pending.is_empty() {
+                self.pending_requests.remove(&id);
+            }
+            searched = 0;
+
+            let req =
+                serde_json::from_slice::<ClientRequest>(&line).map_err(WriteError::ParseJson)?;
+            self.respond_to_socket_request(id, req)?;
+        }
+    }
+
+    fn respond_to_socket_request(&mut self, id: u64, req: ClientRequest) -> Result<(), WriteError> {
+        // Appending behind undrained bytes would leave the client parsing two responses as one
+        if let Some(response) = self.open_files.get_mut(&id) {
+            response.clear();
+        }
+
+        // Clients that never said hello predate the handshake and are trusted to be compatible
+        if let Some(&version) = self.handle_versions.get(&id) {
+            if version != API_VERSION && !matches!(req, ClientRequest::Hello(_)) {
+                return Err(WriteError::IncompatibleVersion(version));
+            }
+        }
+
+        if let Some(response) = self.handle_request(id, req)? {
+            let response_file = self
+                .open_files
+                .get_mut(&id)
+                .ok_or(WriteError::FindResponseHandle)?;
+            serde_json::to_writer(response_file, &response)
+                .map_err(WriteError::SerializeResponse)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(
+        &mut self,
+        id: u64,
+        req: ClientRequest,
+    ) -> Result<Option<ClientResponse>, WriteError> {
+        let response = match req {
+            ClientRequest::Hello(req) => {
+                self.handle_versions.insert(id, req.version);
+                if req.version != API_VERSION {
+                    log::warn!(
+                        "client speaks API version {}, expected {API_VERSION}",
+                        req.version
+                    );
+                }
+
+                let response = ClientResponse::Hello(HelloResponse {
+                    version: API_VERSION,
+                });
+
+                Some(response)
+            }
+            ClientRequest::CreateItem(create_item_req) => {
+                let item_id = self
+                    .db
+                    .create_item(&create_item_req.name)
+                    .map_err(WriteError::CreateItem)?;
+                let new_item_path = Path::new(ITEMS_FOLDER).join(item_id.0.to_string());
                 let response = CreateItemResponse {
                     path: new_item_path,
                 };
 
                 let response = ClientResponse::CreateItem(response);
 
-                let response_file = self
-                    .open_files
-                    .get_mut(&id)
-                    .ok_or(WriteError::FindResponseHandle)?;
-                serde_json::to_writer(response_file, &response)
-                    .map_err(WriteError::SerializeResponse)?;
+                Some(response)
+            }
+            ClientRequest::CreateItems(reqs) => {
+                let names: Vec<String> = reqs.into_iter().map(|req| req.name).collect();
+                let response = match self.db.create_items(&names) {
+                    Ok(item_ids) => CreateItemsResponse::Created(
+                        item_ids
+                            .into_iter()
+                            .map(|item_id| CreateItemResponse {
+                                path: Path::new(ITEMS_FOLDER).join(item_id.0.to_string()),
+                            })
+                            .collect(),
+                    ),
+                    Err(e) => {
+                        log::error!("failed to create items: {e}");
+                        CreateItemsResponse::Failed {
+                            index: e.index(),
+                            reason: e.to_string(),
+                        }
+                    }
+                };
+                let response = ClientResponse::CreateItems(response);
+
+                Some(response)
             }
             ClientRequest::DeleteItem(req) => {
-                self.db.delete_item(ItemId(req.id))?;
+                if req.trash.unwrap_or(self.soft_delete) {
+                    self.db.trash_item(ItemId(req.id))?;
+                } else {
+                    self.db.delete_item(ItemId(req.id))?;
+                }
+                None
+            }
+            ClientRequest::UpdateItem(req) => {
+                let item_id = ItemId(req.id);
+                if let Some(name) = &req.name {
+                    self.db.rename_item(item_id, name)?;
+                }
+
+                let response = UpdateItemResponse {
+                    path: Path::new(ITEMS_FOLDER).join(item_id.0.to_string()),
+                };
+                let response = ClientResponse::UpdateItem(response);
+
+                Some(response)
             }
             ClientRequest::CreateRelationship(req) => {
-                let item_id = self.db.add_relationship(&req.from_name, &req.to_name)?;
+                let item_id = if req.ensure {
+                    self.db
+                        .get_or_create_relationship(&req.from_name, &req.to_name)?
+                } else {
+                    self.db.add_relationship(&req.from_name, &req.to_name)?
+                };
                 let new_item_path = Path::new(RELATIONSHIPS_FOLDER).join(item_id.0.to_string());
 
                 let response = CreateRelationshipResponse {
@@ -386,82 +1673,405 @@ impl FuseClient {
                 };
                 let response = ClientResponse::CreateRelationship(response);
 
-                let response_file = self
-                    .open_files
-                    .get_mut(&id)
-                    .ok_or(WriteError::FindResponseHandle)?;
-                serde_json::to_writer(response_file, &response)
-                    .map_err(WriteError::SerializeResponse)?;
+                Some(response)
             }
             ClientRequest::CreateFilter(req) => {
-                self.db.add_filter(&req.name, &req.filters)?;
+                let conditions: Vec<FilterId> =
+                    req.conditions.iter().copied().map(FilterId).collect();
+                self.db
+                    .add_ordered_filter(&req.name, &req.filters, &conditions, req.order_by)?;
+                None
+            }
+            ClientRequest::GetContentPath(req) => {
+                let item_id = ItemId(req.id);
+                let response = match self.db.get_item_by_id(item_id) {
+                    Some(_) => GetContentPathResponse::Found {
+                        path: self
+                            .db
+                            .content_folder_for_id(item_id)
+                            .map_err(WriteError::GetContentFolder)?,
+                    },
+                    None => GetContentPathResponse::ItemNotFound,
+                };
+                let response = ClientResponse::GetContentPath(response);
+
+                Some(response)
+            }
+            ClientRequest::GetRelationshipId(req) => {
+                let response = match self.db.get_relationship_id_by_name(&req.name) {
+                    Ok(Some(id)) => GetRelationshipIdResponse::Found { id: id.0 },
+                    Ok(None) => GetRelationshipIdResponse::NotFound,
+                    Err(GetRelationshipIdByNameError::AmbiguousRelationship(ids)) => {
+                        GetRelationshipIdResponse::Ambiguous {
+                            candidates: ids.into_iter().map(|id| id.0).collect(),
+                        }
+                    }
+                    Err(GetRelationshipIdByNameError::Query(e)) => {
+                        return Err(WriteError::FindRelationship(e))
+                    }
+                };
+                let response = ClientResponse::GetRelationshipId(response);
+
+                Some(response)
+            }
+            ClientRequest::PreviewFilter(req) => {
+                // Filters with variable item rules match nothing without an item to view them
+                // from, the same as when they are listed
+                let items = self
+                    .db
+                    .run_filter(&req.filters, None)
+                    .map_err(WriteError::RunFilter)?
+                    .into_iter()
+                    .map(|item_id| {
+                        let item = self
+                            .db
+                            .get_item_by_id(item_id)
+                            .ok_or(WriteError::ItemIdNotInDatabase)?;
+                        Ok((item.id.0, item.name))
+                    })
+                    .collect::<Result<Vec<_>, WriteError>>()?;
+                let response = ClientResponse::PreviewFilter(items);
+
+                Some(response)
+            }
+            ClientRequest::CountFilterMatches(req) => {
+                let count = self
+                    .db
+                    .count_filter_matches(&req.filters, None)
+                    .map_err(WriteError::RunFilter)?;
+                let response = ClientResponse::CountFilterMatches(count);
+
+                Some(response)
+            }
+            ClientRequest::SearchItems(req) => {
+                let items = self
+                    .db
+                    .find_items_by_name_prefix(&req.prefix, req.limit)
+                    .map_err(WriteError::FindItemByName)?
+                    .into_iter()
+                    .map(|(id, name)| (id.0, name))
+                    .collect();
+                let response = ClientResponse::SearchItems(items);
+
+                Some(response)
             }
             ClientRequest::CreateItemRelationship(req) => {
-                println!("Adding item relationship");
+                log::debug!(
+                    "adding item relationship {} from {} to {}",
+                    req.relationship_id,
+                    req.from_id,
+                    req.to_id
+                );
                 self.db.add_item_relationship(
                     ItemId(req.from_id),
                     ItemId(req.to_id),
                     RelationshipId(req.relationship_id),
                 )?;
+                None
+            }
+            ClientRequest::CreateItemRelationships(req) => {
+                let links: Vec<_> = req
+                    .links
+                    .iter()
+                    .map(|link| {
+                        (
+                            ItemId(link.from_id),
+                            ItemId(link.to_id),
+                            RelationshipId(link.relationship_id),
+                        )
+                    })
+                    .collect();
+                let response = match self
+                    .db
+                    .add_item_relationships(&links, req.abort_on_duplicate)
+                {
+                    Ok(added) => CreateItemRelationshipsResponse::Created(
+                        added
+                            .into_iter()
+                            .map(|added| match added {
+                                AddedLink::Added => CreatedItemRelationship::Added,
+                                AddedLink::Duplicate => CreatedItemRelationship::Duplicate,
+                            })
+                            .collect(),
+                    ),
+                    Err(e) => {
+                        log::error!("failed to create item relationships: {e}");
+                        CreateItemRelationshipsResponse::Failed {
+                            index: e.index(),
+                            reason: e.to_string(),
+                        }
+                    }
+                };
+                let response = ClientResponse::CreateItemRelationships(response);
+
+                Some(response)
+            }
+            ClientRequest::Batch(requests) => {
+                Some(ClientResponse::Batch(self.handle_batch(id, requests)?))
+            }
+            ClientRequest::CreateItemRelationshipByName(req) => {
+                let response = self.create_item_relationship_by_name(&req)?;
+                let response = ClientResponse::CreateItemRelationshipByName(response);
+
+                Some(response)
             }
+        };
+
+        Ok(response)
+    }
+
+    /// Runs every request in a single transaction. Any request that fails, with an error or with a
+    /// response that reports a failure, rolls back the whole batch. Batches containing a
+    /// DeleteItem are rejected before anything runs
+    fn handle_batch(
+        &mut self,
+        id: u64,
+        requests: Vec<ClientRequest>,
+    ) -> Result<BatchResponse, WriteError> {
+        // Deleting removes content from disk right away, rolling the batch back could only bring
+        // back the item without its content
+        let delete_index = requests
+            .iter()
+            .position(|request| matches!(request, ClientRequest::DeleteItem(_)));
+        if let Some(index) = delete_index {
+            return Ok(BatchResponse::Failed {
+                index,
+                reason: "items cannot be deleted in a batch".to_string(),
+            });
         }
 
-        Ok(())
+        self.db
+            .begin_transaction()
+            .map_err(WriteError::StartBatch)?;
+
+        let mut responses = Vec::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            let reason = match self.handle_request(id, request) {
+                Ok(response) => match response.as_ref().and_then(response_failure) {
+                    Some(reason) => reason,
+                    None => {
+                        responses.push(response);
+                        continue;
+                    }
+                },
+                Err(e) => e.to_string(),
+            };
+
+            log::error!("batched request {index} failed: {reason}");
+            self.db
+                .rollback_transaction()
+                .map_err(WriteError::RollbackBatch)?;
+            return Ok(BatchResponse::Failed { index, reason });
+        }
+
+        self.db
+            .commit_transaction()
+            .map_err(WriteError::CommitBatch)?;
+        Ok(BatchResponse::Completed(responses))
+    }
+
+    fn create_item_relationship_by_name(
+        &mut self,
+        req: &CreateItemRelationshipByNameRequest,
+    ) -> Result<CreateItemRelationshipByNameResponse, WriteError> {
+        let relationship_id = match req.relationship.parse().map(RelationshipId) {
+            // Ids are the common case for scripts, no need to pull every relationship
+            Ok(id)
+                if self
+                    .db
+                    .relationship_exists(id)
+                    .map_err(WriteError::FindRelationship)? =>
+            {
+                Some(id)
+            }
+            _ => match self.db.get_relationship_id_by_name(&req.relationship) {
+                Ok(id) => id,
+                Err(GetRelationshipIdByNameError::AmbiguousRelationship(ids)) => {
+                    return Ok(
+                        CreateItemRelationshipByNameResponse::AmbiguousRelationshipName {
+                            candidates: ids.into_iter().map(|id| id.0).collect(),
+                        },
+                    )
+                }
+                Err(GetRelationshipIdByNameError::Query(e)) => {
+                    return Err(WriteError::FindRelationship(e))
+                }
+            },
+        };
+        let Some(relationship_id) = relationship_id else {
+            return Ok(CreateItemRelationshipByNameResponse::RelationshipNotFound);
+        };
+
+        let mut ids = Vec::new();
+        for name in [&req.from_name, &req.to_name] {
+            let candidates = self
+                .db
+                .get_item_by_name(name)
+                .map_err(WriteError::FindItemByName)?;
+            match candidates[..] {
+                [] => {
+                    return Ok(CreateItemRelationshipByNameResponse::ItemNotFound {
+                        name: name.clone(),
+                    })
+                }
+                [id] => ids.push(id),
+                _ => {
+                    return Ok(CreateItemRelationshipByNameResponse::AmbiguousItemName {
+                        name: name.clone(),
+                        candidates: candidates.into_iter().map(|id| id.0).collect(),
+                    })
+                }
+            }
+        }
+
+        self.db
+            .add_item_relationship(ids[0], ids[1], relationship_id)?;
+
+        Ok(CreateItemRelationshipByNameResponse::Created {
+            from_id: ids[0].0,
+            to_id: ids[1].0,
+        })
     }
 
-    pub fn read(&mut self, path: &Path, id: u64, buf: &mut [u8]) -> Result<usize, ReadError> {
+    /// Metadata files are generated in full on every read and copied from offset, the socket is
+    /// a stream that is drained regardless of offset
+    pub fn read(
+        &mut self,
+        path: &Path,
+        id: u64,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, ReadError> {
         let parsed_path = self.parse_path(path)?;
-        match parsed_path {
+        let content = match parsed_path {
             PathPurpose::Socket => {
                 let f = self
                     .open_files
                     .get_mut(&id)
                     .ok_or(ReadError::FindResponseHandle)?;
-                f.read(buf).map_err(ReadError::Read)
+                return f.read(buf).map_err(ReadError::Read);
             }
-            PathPurpose::ItemId(id) => {
-                let content = get_item_id_file_contents(&id);
-                buf[0..content.len()].copy_from_slice(&content);
-                Ok(content.len())
+            PathPurpose::ItemId(id) => get_item_id_file_contents(&id),
+            PathPurpose::ItemName(id) => get_item_name_file_contents(&id, &self.db),
+            PathPurpose::RelationshipId(id) => get_relationship_id_file_contents(&id),
+            PathPurpose::RelationshipFromName(id) => {
+                get_relationship_from_name_file_contents(&id, &self.db)
+                    .map_err(ReadError::RelationshipFromName)?
             }
-            PathPurpose::ItemName(id) => {
-                let content = get_item_name_file_contents(&id, &self.db);
-                buf[0..content.len()].copy_from_slice(&content);
-                Ok(content.len())
+            PathPurpose::RelationshipToName(id) => {
+                get_relationship_to_name_file_contents(&id, &self.db)
+                    .map_err(ReadError::RelationshipToName)?
             }
-            PathPurpose::RelationshipId(id) => {
-                let content = get_relationship_id_file_contents(&id);
-                buf[0..content.len()].copy_from_slice(&content);
-                Ok(content.len())
+            PathPurpose::RelationshipDescription(id) => {
+                get_relationship_description_file_contents(&id, &self.db)
+                    .map_err(ReadError::RelationshipDescription)?
             }
-            PathPurpose::RelationshipFromName(id) => {
-                let content = get_relationship_from_name_file_contents(&id, &self.db)
-                    .map_err(ReadError::RelationshipFromName)?;
-                buf[0..content.len()].copy_from_slice(&content);
-                Ok(content.len())
+            PathPurpose::RelationshipDirection(id) => {
+                get_relationship_direction_file_contents(&id, &self.db)
+                    .map_err(ReadError::RelationshipDirection)?
             }
-            PathPurpose::RelationshipToName(id) => {
-                let content = get_relationship_to_name_file_contents(&id, &self.db)
-                    .map_err(ReadError::RelationshipToName)?;
-                buf[0..content.len()].copy_from_slice(&content);
-                Ok(content.len())
+            PathPurpose::RelationshipLink(_, from_id, to_id) => {
+                get_relationship_link_file_contents(&from_id, &to_id, &self.db)
             }
-            _ => Err(ReadError::UnhandledPath),
-        }
+            PathPurpose::Events | PathPurpose::Stats(_) | PathPurpose::ItemSize(_) => {
+                let snapshot = self.snapshots.get(&id).ok_or(ReadError::FindSnapshot)?;
+                let remaining = snapshot.get(offset..).unwrap_or_default();
+                let len = remaining.len().min(buf.len());
+                buf[..len].copy_from_slice(&remaining[..len]);
+                return Ok(len);
+            }
+            PathPurpose::Info(info) => get_info_file_contents(&info),
+            _ => return Err(ReadError::UnhandledPath),
+        };
+
+        let remaining = content.get(offset..).unwrap_or_default();
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        Ok(len)
     }
 
     pub fn release(&mut self, id: u64) {
         self.open_files.remove(&id);
+        self.snapshots.remove(&id);
+        self.handle_versions.remove(&id);
+        self.pending_requests.remove(&id);
+        if let Some((path, item_id)) = self.created_items.remove(&id) {
+            self.created_paths.insert(path, item_id);
+        }
     }
 
-    fn list_dir_contents(
-        &mut self,
+    /// Hands out a handle for an fd opened in a content folder, see [`FuseClient::passthrough_fd`]
+    pub fn open_passthrough_fd(&mut self, fd: i32) -> u64 {
+        let id = self.latest_open_id;
+        self.latest_open_id += 1;
+        self.passthrough_fds.insert(id, fd);
+        id
+    }
+
+    /// The fd behind a handle from [`FuseClient::open_passthrough_fd`], None for handles of
+    /// virtual files
+    pub fn passthrough_fd(&self, id: u64) -> Option<i32> {
+        self.passthrough_fds.get(&id).copied()
+    }
+
+    /// Forgets a passthrough handle, returning the fd for the caller to close
+    pub fn release_passthrough_fd(&mut self, id: u64) -> Option<i32> {
+        self.passthrough_fds.remove(&id)
+    }
+
+    /// The rules of all conditions are combined, so the item is checked with a single query
+    fn item_matches_conditions(
+        &self,
+        item_id: ItemId,
+        filter: &Filter,
+        all_filters: &[Filter],
+    ) -> Result<bool, ReadDirError> {
+        if filter.conditions.is_empty() {
+            return Ok(true);
+        }
+
+        let mut rules = Vec::new();
+        for condition_id in &filter.conditions {
+            let condition = all_filters
+                .iter()
+                .find(|candidate| candidate.id == *condition_id)
+                .ok_or(ReadDirError::FindFilter)?;
+            rules.extend(condition.rules.iter().cloned());
+        }
+
+        self.db
+            .item_matches(item_id, &rules)
+            .map_err(ReadDirError::RunFilter)
+    }
+
+    fn list_dir_contents(
+        &mut self,
         path: PathPurpose,
     ) -> Result<Box<dyn Iterator<Item = (PathPurpose, String)> + '_>, ReadDirError> {
         let ret: Box<dyn Iterator<Item = (PathPurpose, String)> + '_> = match path {
             PathPurpose::Root => {
                 let items_iter = [
                     (PathPurpose::Items, ITEMS_FOLDER[1..].to_string()),
+                    (
+                        PathPurpose::ItemsByName,
+                        ITEMS_BY_NAME_FOLDER[1..].to_string(),
+                    ),
+                    (PathPurpose::Recent, RECENT_FOLDER[1..].to_string()),
+                    (PathPurpose::Pinned, PINNED_FOLDER[1..].to_string()),
+                    (
+                        PathPurpose::EmptyContent,
+                        EMPTY_CONTENT_FOLDER[1..].to_string(),
+                    ),
+                    (PathPurpose::Trash, TRASH_FOLDER[1..].to_string()),
+                    (PathPurpose::Search, SEARCH_FOLDER[1..].to_string()),
+                    (PathPurpose::Intersect, INTERSECT_FOLDER[1..].to_string()),
+                    (PathPurpose::Events, "events".to_string()),
+                    (PathPurpose::Stats(StatsFormat::Text), "stats".to_string()),
+                    (
+                        PathPurpose::Stats(StatsFormat::Json),
+                        "stats.json".to_string(),
+                    ),
+                    (PathPurpose::Info(self.mount_info()), ".info".to_string()),
                     (
                         PathPurpose::Relationships,
                         RELATIONSHIPS_FOLDER[1..].to_string(),
@@ -479,17 +2089,134 @@ impl FuseClient {
                     .get_filters()
                     .map_err(ReadDirError::GetFilters)?
                     .into_iter()
+                    .filter(|filter| !filter.is_item_filter())
                     .map(|filter| (PathPurpose::Filter(filter.id), filter.name));
 
                 Box::new(items_iter.chain(filters_iter))
             }
             PathPurpose::Items => Box::new(
                 self.db
+                    .get_item_ids(0, None)
+                    .map_err(ReadDirError::GetItemIds)?
+                    .into_iter()
+                    .map(|id| (PathPurpose::Item(id), id.0.to_string())),
+            ),
+            PathPurpose::ItemsByName => {
+                let items = self
+                    .db
                     .get_items()
                     .map_err(ReadDirError::GetItems)?
                     .into_iter()
-                    .map(|item| (PathPurpose::Item(item.id), item.id.0.to_string())),
+                    .map(|item| (item.id, item.name))
+                    .collect();
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(_, name)| (PathPurpose::ItemByName(name.clone()), name)),
+                )
+            }
+            PathPurpose::Recent => {
+                let items = self
+                    .db
+                    .get_recent_items(self.recent_count)
+                    .map_err(ReadDirError::GetRecentItems)?;
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
+            PathPurpose::Pinned => {
+                let items = self
+                    .db
+                    .get_pinned_items()
+                    .map_err(ReadDirError::GetPinnedItems)?;
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
+            PathPurpose::EmptyContent => {
+                let mut items = Vec::new();
+                for item in self.db.get_items().map_err(ReadDirError::GetItems)? {
+                    if self.has_empty_content(item.id)? {
+                        items.push((item.id, item.name));
+                    }
+                }
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
+            PathPurpose::Trash => {
+                let items = self
+                    .db
+                    .get_trashed_items()
+                    .map_err(ReadDirError::GetTrashedItems)?;
+
+                Box::new(disambiguate_item_names(items).map(|(id, name)| {
+                    (
+                        PathPurpose::PassthroughPath(self.db.trash_folder_for_id(id)),
+                        name,
+                    )
+                }))
+            }
+            PathPurpose::Search => Box::new(
+                self.searches
+                    .iter()
+                    .map(|query| (PathPurpose::SearchQuery(query.clone()), query.clone())),
             ),
+            // Every combination of filters is valid, there is nothing sensible to list
+            PathPurpose::Intersect => Box::new(std::iter::empty()),
+            PathPurpose::FilterIntersection(filter_ids) => {
+                let item_ids = self
+                    .db
+                    .run_filters_intersection(&filter_ids)
+                    .map_err(ReadDirError::RunFiltersIntersection)?;
+
+                let items = item_ids
+                    .into_iter()
+                    .map(|item_id| {
+                        let name = self
+                            .db
+                            .get_item_by_id(item_id)
+                            .ok_or(ReadDirError::ItemIdNotInDatabase)?
+                            .name;
+                        Ok((item_id, name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
+            PathPurpose::SearchQuery(query) => {
+                let search = parse_search_query(&query).ok_or(ReadDirError::InvalidSearchQuery)?;
+                let item_ids = self
+                    .db
+                    .run_filter(&search.rules, None)
+                    .map_err(ReadDirError::RunFilter)?;
+
+                let mut items = Vec::new();
+                for item_id in item_ids {
+                    let name = self
+                        .db
+                        .get_item_by_id(item_id)
+                        .ok_or(ReadDirError::ItemIdNotInDatabase)?
+                        .name;
+                    if search.matches_name(&name) {
+                        items.push((item_id, name));
+                    }
+                }
+
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
             PathPurpose::Relationships => Box::new(
                 self.db
                     .get_relationships()
@@ -510,9 +2237,30 @@ impl FuseClient {
                         "from_name".to_string(),
                     ),
                     (PathPurpose::RelationshipToName(id), "to_name".to_string()),
+                    (
+                        PathPurpose::RelationshipDescription(id),
+                        "description".to_string(),
+                    ),
+                    (
+                        PathPurpose::RelationshipDirection(id),
+                        "direction".to_string(),
+                    ),
+                    (PathPurpose::RelationshipLinks(id), "links".to_string()),
                 ]
                 .into_iter(),
             ),
+            PathPurpose::RelationshipLinks(relationship_id) => Box::new(
+                self.db
+                    .get_links_for_relationship(relationship_id)
+                    .map_err(ReadDirError::GetRelationshipLinks)?
+                    .into_iter()
+                    .map(move |(from_id, to_id)| {
+                        (
+                            PathPurpose::RelationshipLink(relationship_id, from_id, to_id),
+                            format!("{}-{}", from_id.0, to_id.0),
+                        )
+                    }),
+            ),
             PathPurpose::Item(id) => {
                 let item = self
                     .db
@@ -532,29 +2280,46 @@ impl FuseClient {
                         )
                     },
                 );
+                let all_filters = self.db.get_filters().map_err(ReadDirError::GetFilters)?;
+                let mut filters = Vec::new();
+                for filter in all_filters.iter().filter(|filter| filter.is_item_filter()) {
+                    if self.item_matches_conditions(id, filter, &all_filters)? {
+                        filters.push((
+                            PathPurpose::ItemContextFilter(id, filter.id),
+                            filter.name.clone(),
+                        ));
+                    }
+                }
 
-                Box::new(names.chain([
-                    (
-                        PathPurpose::PassthroughPath(passthrough_path),
-                        "content".to_string(),
-                    ),
-                    (PathPurpose::ItemId(id), "id".to_string()),
-                    (PathPurpose::ItemName(id), "name".to_string()),
-                ]))
+                Box::new(
+                    names
+                        .chain([
+                            (
+                                PathPurpose::PassthroughPath(passthrough_path),
+                                "content".to_string(),
+                            ),
+                            (PathPurpose::ItemId(id), "id".to_string()),
+                            (PathPurpose::ItemName(id), "name".to_string()),
+                            (PathPurpose::ItemSize(id), "size".to_string()),
+                            (
+                                PathPurpose::ItemAllRelated(id),
+                                ALL_RELATED_FOLDER_NAME.to_string(),
+                            ),
+                        ])
+                        .chain(filters),
+                )
             }
-            PathPurpose::Filter(filter_id) => {
-                let filter = self
-                    .db
-                    .get_filters()
-                    .map_err(ReadDirError::GetFilters)?
-                    .into_iter()
-                    .find(|filter| filter.id == filter_id)
-                    .ok_or(ReadDirError::FindFilter)?;
+            PathPurpose::Filter(filter_id) | PathPurpose::ItemContextFilter(_, filter_id) => {
+                let context = match path {
+                    PathPurpose::ItemContextFilter(item_id, _) => Some(item_id),
+                    _ => None,
+                };
 
                 let item_ids = self
                     .db
-                    .run_filter(&filter.rules)
-                    .map_err(ReadDirError::RunFilter)?;
+                    .run_saved_filter(filter_id, context)
+                    .map_err(ReadDirError::RunFilter)?
+                    .ok_or(ReadDirError::FindFilter)?;
 
                 let item_it = item_ids.into_iter().map(|item_id| {
                     let name = self
@@ -562,12 +2327,15 @@ impl FuseClient {
                         .get_item_by_id(item_id)
                         .ok_or(ReadDirError::ItemIdNotInDatabase)?
                         .name;
-                    Ok((PathPurpose::ItemLink(item_id), name))
+                    Ok((item_id, name))
                 });
 
-                let item_it = item_it.collect::<Result<Vec<_>, _>>()?.into_iter();
+                let items = item_it.collect::<Result<Vec<_>, _>>()?;
 
-                Box::new(item_it)
+                Box::new(
+                    disambiguate_item_names(items)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
             }
             PathPurpose::ToolBins => {
                 let my_path = std::env::args().next().expect("no program name");
@@ -578,11 +2346,17 @@ impl FuseClient {
                     .to_path_buf();
 
                 let names = [
+                    "content-path",
                     "create-item",
+                    "create-items",
                     "create-item-relationship",
+                    "create-item-relationships",
+                    "create-item-relationship-by-name",
                     "create-relationship",
                     "create-filter",
                     "delete-item",
+                    "ingest",
+                    "update-item",
                 ];
 
                 Box::new(names.into_iter().map(move |name| {
@@ -594,55 +2368,82 @@ impl FuseClient {
             }
             PathPurpose::Socket
             | PathPurpose::ItemLink(_)
+            | PathPurpose::ItemByName(_)
             | PathPurpose::ItemId(_)
             | PathPurpose::ItemName(_)
+            | PathPurpose::ItemSize(_)
             | PathPurpose::RelationshipId(_)
             | PathPurpose::RelationshipFromName(_)
-            | PathPurpose::RelationshipToName(_) => return Err(ReadDirError::NotADirectory),
+            | PathPurpose::RelationshipToName(_)
+            | PathPurpose::RelationshipDescription(_)
+            | PathPurpose::RelationshipDirection(_)
+            | PathPurpose::RelationshipLink(_, _, _)
+            | PathPurpose::Events
+            | PathPurpose::Stats(_)
+            | PathPurpose::Info(_)
+            | PathPurpose::CreatedItem => return Err(ReadDirError::NotADirectory),
             PathPurpose::ItemRelationships(item_id, relationship_id, relationship_side) => {
+                let siblings = self
+                    .db
+                    .get_siblings(item_id, relationship_id, relationship_side)
+                    .map_err(ReadDirError::GetSiblings)?;
+
+                Box::new(
+                    disambiguate_item_names(siblings)
+                        .map(|(id, name)| (PathPurpose::ItemLink(id), name)),
+                )
+            }
+            PathPurpose::ItemAllRelated(item_id) => {
                 let item = self
                     .db
                     .get_item_by_id(item_id)
                     .ok_or(ReadDirError::ItemIdNotInDatabase)?;
+                let relationships: HashMap<RelationshipId, Relationship> = self
+                    .db
+                    .get_relationships()
+                    .map_err(ReadDirError::GetRelationships)?
+                    .into_iter()
+                    .map(|relationship| (relationship.id, relationship))
+                    .collect();
 
-                let item_relationships =
-                    item.relationships.into_iter().filter(move |relationship| {
-                        relationship.id == relationship_id && relationship.side == relationship_side
-                    });
-
-                let it = item_relationships.map(
-                    |item_relationship| -> Result<(PathPurpose, String), ItemId> {
-                        let sibling = self
-                            .db
-                            .get_item_by_id(item_relationship.sibling)
-                            .ok_or(item_relationship.sibling)?;
-                        Ok((PathPurpose::ItemLink(sibling.id), sibling.name))
-                    },
-                );
-
-                let it = it.filter_map(|item| match item {
-                    Ok(v) => Some(v),
-                    Err(id) => {
-                        log::error!("item {} not present in db", id.0);
-                        None
-                    }
-                });
+                // Every edge gets its own entry, a sibling related in several ways shows up once
+                // per relationship
+                let mut entries = Vec::new();
+                for item_relationship in item.relationships {
+                    let Some(relationship) = relationships.get(&item_relationship.id) else {
+                        continue;
+                    };
+                    let folder_name =
+                        relationship_folder_name(relationship, item_relationship.side);
+                    entries.push((
+                        PathPurpose::ItemLink(item_relationship.sibling),
+                        format!("{folder_name}#{}", item_relationship.sibling.0),
+                    ));
+                }
 
-                Box::new(it)
+                Box::new(entries.into_iter())
             }
             PathPurpose::PassthroughPath(p) => {
-                let it = fs::read_dir(p).map_err(ReadDirError::ReadDbDir)?.map(
-                    |item| -> Result<(PathPurpose, String), String> {
-                        let item = item.map_err(|e| e.to_string())?;
-                        Ok((
-                            PathPurpose::PassthroughPath(item.path()),
-                            item.file_name()
-                                .to_str()
-                                .ok_or_else(|| "failed to turn file name into string".to_string())?
-                                .to_string(),
-                        ))
-                    },
-                );
+                let entries = match fs::read_dir(&p) {
+                    Ok(v) => v,
+                    Err(e)
+                        if e.kind() == std::io::ErrorKind::NotFound
+                            && self.db.content_folder_owner(&p).is_some() =>
+                    {
+                        return Ok(Box::new(std::iter::empty()));
+                    }
+                    Err(e) => return Err(ReadDirError::ReadDbDir(e)),
+                };
+                let it = entries.map(|item| -> Result<(PathPurpose, String), String> {
+                    let item = item.map_err(|e| e.to_string())?;
+                    Ok((
+                        PathPurpose::PassthroughPath(item.path()),
+                        item.file_name()
+                            .to_str()
+                            .ok_or_else(|| "failed to turn file name into string".to_string())?
+                            .to_string(),
+                    ))
+                });
 
                 let it = it.filter_map(|item| match item {
                     Ok(v) => Some(v),
@@ -663,43 +2464,163 @@ impl FuseClient {
         Ok(ret)
     }
 
+    /// Lists the entries of a directory after the entry whose offset was passed in, 0 lists from
+    /// the start. Entries are produced lazily where possible, so callers that stop early avoid
+    /// materializing huge directories
+    ///
+    /// /items uses item ids as offsets, so a listing resumed after items were created or deleted
+    /// neither repeats nor skips any of the remaining items
     pub fn readdir(
         &mut self,
         path: &Path,
-    ) -> Result<impl Iterator<Item = DirEntry> + '_, ReadDirError> {
+        offset: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<DirEntry, ReadDirError>> + '_>, ReadDirError> {
         let parsed_path = self
             .parse_path(path)
             .map_err(|x| ReadDirError::ParsePath(Box::new(x)))?;
+
+        if let PathPurpose::Items = parsed_path {
+            let db = &self.db;
+            let after = ItemId(offset.try_into().expect("offset does not fit in i64"));
+            let it = paginate_item_ids(db, after).map(move |id| {
+                let id = id.map_err(ReadDirError::GetItemIds)?;
+                let offset = id.0.try_into().expect("item ids are positive");
+                to_dir_entry(PathPurpose::Item(id), id.0.to_string(), offset, db)
+            });
+            return Ok(Box::new(it));
+        }
+
         let dir_it = self.list_dir_contents(parsed_path)?.collect::<Vec<_>>();
-        let dir_it = dir_it.into_iter().map(|item| {
-            let ret = match path_purpose_to_filetype(&item.0, &self.db)
-                .map_err(ReadDirError::GetFiletype)?
-            {
-                Filetype::Dir => DirEntry::Dir(item.1.into()),
-                Filetype::Link => DirEntry::Link(item.1.into()),
-                Filetype::File(_) => DirEntry::File(item.1.into()),
-            };
-            Ok(ret)
-        });
+        let db = &self.db;
+        let dir_it = dir_it
+            .into_iter()
+            .enumerate()
+            .skip(offset)
+            .map(move |(i, (purpose, name))| to_dir_entry(purpose, name, i + 1, db));
+
+        Ok(Box::new(dir_it))
+    }
+
+    /// Link count of a directory as a real file system would report it, one for the entry in the
+    /// parent, one for "." and one for the ".." of each subdirectory. Subdirectories are told
+    /// apart by their purpose alone, so the files next to them are never evaluated
+    pub fn dir_link_count(&mut self, path: &Path) -> Result<usize, ReadDirError> {
+        let purpose = self
+            .parse_path(path)
+            .map_err(|x| ReadDirError::ParsePath(Box::new(x)))?;
+
+        let subdirs = match purpose {
+            PathPurpose::Items => self
+                .db
+                .count_items()
+                .map_err(ReadDirError::GetItemIds)?
+                .try_into()
+                .expect("item count should not be negative"),
+            // These only ever list links to items
+            PathPurpose::ItemsByName
+            | PathPurpose::Recent
+            | PathPurpose::Pinned
+            | PathPurpose::EmptyContent
+            | PathPurpose::Intersect
+            | PathPurpose::FilterIntersection(_)
+            | PathPurpose::SearchQuery(_)
+            | PathPurpose::Filter(_)
+            | PathPurpose::ItemContextFilter(_, _)
+            | PathPurpose::ItemRelationships(_, _, _)
+            | PathPurpose::ItemAllRelated(_) => 0,
+            // Unknown paths are reported as directories, but there is nothing in them
+            PathPurpose::Unknown => 0,
+            purpose => self
+                .list_dir_contents(purpose)?
+                .filter(|(purpose, _)| is_subdir(purpose))
+                .count(),
+        };
 
-        let dir_it = dir_it.collect::<Result<Vec<_>, _>>()?.into_iter();
-        Ok(dir_it)
+        Ok(2 + subdirs)
     }
 
     pub fn readlink(&mut self, path: &Path) -> Result<PathBuf, ReadLinkError> {
         let item_id = match self.parse_path(path).map_err(ReadLinkError::ParsePath)? {
             PathPurpose::ItemLink(item_id) => item_id,
+            PathPurpose::ItemByName(name) => self
+                .resolve_item_name(&name)
+                .map_err(ReadLinkError::ResolveItemName)?
+                .ok_or(ReadLinkError::ItemNameNotFound(name))?,
             _ => return Err(ReadLinkError::NotALink),
         };
 
-        let mut output_path = PathBuf::new();
-        let num_components = path.iter().count() - 2;
-        for _ in 0..num_components {
-            output_path.push("..")
+        Ok(item_link_target(path, item_id))
+    }
+
+    /// Resolves a name as presented in the items-by-name folder. Unique names map directly to
+    /// their item, names shared by several items are expected in the form name#id
+    fn has_empty_content(&self, id: ItemId) -> Result<bool, ReadDirError> {
+        let content_folder = self
+            .db
+            .content_folder_for_id(id)
+            .map_err(ReadDirError::GetContentFolder)?;
+        match fs::read_dir(content_folder) {
+            Ok(mut entries) => Ok(entries.next().is_none()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(ReadDirError::ReadContentFolder(e)),
+        }
+    }
+
+    /// Resolves a name listed in the empty content folder without listing it. Names there are
+    /// only disambiguated against other items with empty content, see [`disambiguate_item_names`]
+    fn resolve_empty_content_name(&self, name: &str) -> Result<Option<ItemId>, ParsePathError> {
+        let empty_items_named = |name: &str| -> Result<Vec<ItemId>, ParsePathError> {
+            let mut ret = Vec::new();
+            for id in self
+                .db
+                .get_item_by_name(name)
+                .map_err(ParsePathError::ResolveItemName)?
+            {
+                if self.has_empty_content(id)? {
+                    ret.push(id);
+                }
+            }
+            Ok(ret)
+        };
+
+        if let [id] = empty_items_named(name)?[..] {
+            return Ok(Some(id));
+        }
+
+        let Some((name, id)) = name.rsplit_once('#') else {
+            return Ok(None);
+        };
+
+        let Ok(id) = id.parse::<i64>() else {
+            return Ok(None);
+        };
+
+        let items = empty_items_named(name)?;
+        if items.len() < 2 {
+            return Ok(None);
+        }
+        Ok(items.into_iter().find(|item_id| item_id.0 == id))
+    }
+
+    fn resolve_item_name(&self, name: &str) -> Result<Option<ItemId>, QueryError> {
+        if let [id] = self.db.get_item_by_name(name)?[..] {
+            return Ok(Some(id));
         }
-        output_path.push(&ITEMS_FOLDER[1..]);
-        output_path.push(item_id.0.to_string());
-        Ok(output_path)
+
+        let Some((name, id)) = name.rsplit_once('#') else {
+            return Ok(None);
+        };
+
+        let Ok(id) = id.parse::<i64>() else {
+            return Ok(None);
+        };
+
+        let ret = self
+            .db
+            .get_item_by_name(name)?
+            .into_iter()
+            .find(|item_id| item_id.0 == id);
+        Ok(ret)
     }
 
     fn parse_path(&mut self, path: &Path) -> Result<PathPurpose, ParsePathError> {
@@ -723,6 +2644,51 @@ impl FuseClient {
             return Ok(PathPurpose::PassthroughPath(ret));
         }
 
+        // Intersections cannot be listed, so they are parsed from the name instead
+        if let PathPurpose::Intersect = parsed_parent {
+            return Ok(parse_filter_intersection(name)
+                .map(PathPurpose::FilterIntersection)
+                .unwrap_or(PathPurpose::Unknown));
+        }
+
+        // Listing the empty content folder reads every content folder, only the named item's is
+        // checked here
+        if let PathPurpose::EmptyContent = parsed_parent {
+            let ret = self
+                .resolve_empty_content_name(name)?
+                .map(PathPurpose::ItemLink)
+                .unwrap_or(PathPurpose::Unknown);
+            return Ok(ret);
+        }
+
+        // Only ids are listed in the items folder, but names resolve to their item so that files
+        // created there with create() can be found again
+        if let PathPurpose::Items = parsed_parent {
+            if self.created_items.values().any(|(p, _)| p == path) {
+                return Ok(PathPurpose::CreatedItem);
+            }
+
+            if let Some(&id) = self.created_paths.get(path) {
+                let still_named = self
+                    .db
+                    .get_item_by_id(id)
+                    .is_some_and(|item| item.name == name);
+                if still_named {
+                    return Ok(PathPurpose::ItemLink(id));
+                }
+                self.created_paths.remove(path);
+            }
+
+            if name.parse::<i64>().is_err() {
+                let ret = self
+                    .resolve_item_name(name)
+                    .map_err(ParsePathError::ResolveItemName)?
+                    .map(PathPurpose::ItemLink)
+                    .unwrap_or(PathPurpose::Unknown);
+                return Ok(ret);
+            }
+        }
+
         let Some(item) = self
             .list_dir_contents(parsed_parent)?
             .find(|item| item.1 == name)
@@ -733,3 +2699,2915 @@ impl FuseClient {
         Ok(item.0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        db::FilterOrder,
+        fuse::{
+            api::{
+                CreateItemRelationshipRequest, CreateItemRelationshipsRequest, CreateItemRequest,
+                DeleteItemRequest, GetContentPathRequest, GetRelationshipIdRequest, HelloRequest,
+                IngestError, PreviewFilterRequest, SearchItemsRequest, UpdateItemRequest,
+            },
+            FuseArgsError,
+        },
+    };
+    use std::{
+        os::{
+            fd::{AsRawFd, FromRawFd},
+            raw::c_int,
+        },
+        time::{SystemTime, UNIX_EPOCH},
+    };
+    use tempfile::TempDir;
+
+    struct Fixture {
+        _temp_dir: TempDir,
+        client: FuseClient,
+    }
+
+    const READ_WRITE: OpenFlags = OpenFlags {
+        write: true,
+        directory: false,
+    };
+
+    fn create_fixture() -> Fixture {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        Fixture {
+            _temp_dir: temp_dir,
+            client: FuseClient::new(db, false, DEFAULT_RECENT_COUNT, false),
+        }
+    }
+
+    fn list_names_unsorted(client: &mut FuseClient, path: &str, offset: usize) -> Vec<String> {
+        client
+            .readdir(Path::new(path), offset)
+            .expect("failed to read dir")
+            .map(|entry| {
+                let entry = entry.expect("failed to read dir entry");
+                entry.name.into_string().expect("name should be valid utf8")
+            })
+            .collect()
+    }
+
+    /// Reads a generated file through a handle in small pieces, like the kernel does for files
+    /// larger than a page
+    fn read_snapshot(client: &mut FuseClient, path: &str) -> Vec<u8> {
+        let OpenRet::Snapshot(handle) = client
+            .open(Path::new(path), OpenFlags::default())
+            .expect("failed to open file")
+        else {
+            panic!("expected snapshot");
+        };
+
+        let mut ret = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            let len = client
+                .read(Path::new(path), handle, ret.len(), &mut buf)
+                .expect("failed to read file");
+            if len == 0 {
+                break;
+            }
+            ret.extend_from_slice(&buf[..len]);
+        }
+        client.release(handle);
+        ret
+    }
+
+    fn list_names(client: &mut FuseClient, path: &str) -> Vec<String> {
+        let mut names = list_names_unsorted(client, path, 0);
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn duplicate_sibling_names() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child_1 = db.create_item("child").expect("failed to create item");
+        let child_2 = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child_1, relationship_id)
+            .expect("failed to add item relationship");
+        db.add_item_relationship(parent, child_2, relationship_id)
+            .expect("failed to add item relationship");
+
+        let children_path = format!("/items/{}/children", parent.0);
+        let names = list_names(&mut fixture.client, &children_path);
+        let expected_names = vec![
+            format!("child#{}", child_1.0),
+            format!("child#{}", child_2.0),
+        ];
+        assert_eq!(names, expected_names);
+
+        for (name, id) in expected_names.iter().zip([child_1, child_2]) {
+            let link = fixture
+                .client
+                .readlink(&Path::new(&children_path).join(name))
+                .expect("failed to read link");
+            assert_eq!(link, Path::new("../../../items").join(id.0.to_string()));
+        }
+    }
+
+    #[test]
+    fn missing_content_folder() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        let content_folder = fixture
+            .client
+            .db
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+        std::fs::remove_dir(&content_folder).expect("failed to remove content folder");
+
+        let item_path = format!("/items/{}", id.0);
+        let names = list_names(&mut fixture.client, &item_path);
+        assert!(names.contains(&"content".to_string()));
+
+        let filetype = fixture
+            .client
+            .get_filetype(&Path::new(&item_path).join("content"))
+            .expect("failed to get filetype");
+        assert!(matches!(filetype, Filetype::Dir));
+        assert!(list_names(&mut fixture.client, &format!("{item_path}/content")).is_empty());
+        assert!(list_names(&mut fixture.client, "/empty-content").contains(&"item".to_string()));
+
+        // Looking is not enough to bring the folder back, that only happens when writing into it
+        assert!(!content_folder.exists());
+        let new_file = fixture
+            .client
+            .get_passthrough_path(&Path::new(&item_path).join("content/file"))
+            .expect("failed to parse path")
+            .expect("expected passthrough path");
+        fixture
+            .client
+            .prepare_passthrough_create(&new_file)
+            .expect("failed to prepare create");
+        assert!(content_folder.is_dir());
+        std::fs::write(&new_file, "data").expect("failed to write file");
+    }
+
+    #[test]
+    fn ingest_directory() {
+        let mut fixture = create_fixture();
+        let request = ClientRequest::CreateItem(CreateItemRequest {
+            name: "notes".to_string(),
+        });
+        let ClientResponse::CreateItem(response) = send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected create item response");
+        };
+
+        // Stands in for the mount, ingest only ever writes through the item's content folder
+        let mount = tempfile::tempdir().expect("failed to create mount dir");
+        let item_dir = mount.path().join(
+            response
+                .path
+                .strip_prefix("/")
+                .expect("item path should be absolute"),
+        );
+        fs::create_dir_all(&item_dir).expect("failed to create item dir");
+        let content_folder = fixture
+            .client
+            .get_passthrough_path(&response.path.join("content"))
+            .expect("failed to parse path")
+            .expect("expected passthrough path");
+        std::os::unix::fs::symlink(content_folder, item_dir.join("content"))
+            .expect("failed to link content folder");
+
+        let source = tempfile::tempdir().expect("failed to create source dir");
+        let source_dir = source.path().join("notes");
+        fs::create_dir_all(source_dir.join("nested")).expect("failed to create source dir");
+        fs::write(source_dir.join("nested/todo.txt"), "hello").expect("failed to write file");
+
+        let dest = crate::fuse::api::ingest(mount.path(), &response.path, &source_dir)
+            .expect("failed to ingest");
+        assert_eq!(dest, item_dir.join("content/notes"));
+        let nested_path = response.path.join("content/notes/nested");
+        assert_eq!(
+            list_names(&mut fixture.client, &nested_path.to_string_lossy()),
+            ["todo.txt"]
+        );
+
+        let Err(IngestError::NoFileName(_)) =
+            crate::fuse::api::ingest(mount.path(), &response.path, Path::new("/"))
+        else {
+            panic!("expected missing file name");
+        };
+    }
+
+    #[test]
+    fn create_item_file() {
+        let mut fixture = create_fixture();
+        let existing = fixture
+            .client
+            .db
+            .create_item("existing")
+            .expect("failed to create item");
+
+        let handle = fixture
+            .client
+            .create(Path::new("/items/new"))
+            .expect("failed to create item");
+        let filetype = fixture
+            .client
+            .get_filetype(Path::new("/items/new"))
+            .expect("failed to get filetype");
+        assert!(matches!(filetype, Filetype::File(0)));
+        fixture.client.release(handle);
+
+        let items = fixture.client.db.get_items().expect("failed to get items");
+        let new_item = items
+            .iter()
+            .find(|item| item.name == "new")
+            .expect("new item should exist");
+        let names = list_names(&mut fixture.client, "/items");
+        assert!(names.contains(&new_item.id.0.to_string()));
+
+        let link = fixture
+            .client
+            .readlink(Path::new("/items/new"))
+            .expect("failed to read link");
+        assert_eq!(link, Path::new("../items").join(new_item.id.0.to_string()));
+
+        let ret = fixture
+            .client
+            .create(&Path::new("/items").join(existing.0.to_string()));
+        assert!(matches!(ret, Err(CreateError::ItemExists(id)) if id == existing));
+
+        let ret = fixture.client.create(Path::new("/relationships/new"));
+        assert!(matches!(ret, Err(CreateError::Unhandled)));
+    }
+
+    #[test]
+    fn create_item_file_with_duplicate_name() {
+        let mut fixture = create_fixture();
+        let existing = fixture
+            .client
+            .db
+            .create_item("new")
+            .expect("failed to create item");
+
+        let handle = fixture
+            .client
+            .create(Path::new("/items/new"))
+            .expect("failed to create item");
+        // echo x > /items/new writes to the created file before releasing it
+        fixture
+            .client
+            .write(Path::new("/items/new"), handle, b"x\n")
+            .expect("failed to write to created item");
+        fixture.client.release(handle);
+
+        let created = fixture
+            .client
+            .db
+            .get_item_by_name("new")
+            .expect("failed to get items")
+            .into_iter()
+            .find(|id| *id != existing)
+            .expect("created item should exist");
+        let link = fixture
+            .client
+            .readlink(Path::new("/items/new"))
+            .expect("failed to read link");
+        assert_eq!(link, Path::new("../items").join(created.0.to_string()));
+
+        // Once renamed the name goes back to resolving to the remaining item
+        fixture
+            .client
+            .db
+            .rename_item(created, "renamed")
+            .expect("failed to rename item");
+        let link = fixture
+            .client
+            .readlink(Path::new("/items/new"))
+            .expect("failed to read link");
+        assert_eq!(link, Path::new("../items").join(existing.0.to_string()));
+    }
+
+    #[test]
+    fn rename_content() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        let content_folder = fixture
+            .client
+            .db
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+        std::fs::write(content_folder.join("a"), "hello").expect("failed to write content");
+
+        let content_path = format!("/items/{}/content", id.0);
+        fixture
+            .client
+            .rename(
+                &Path::new(&content_path).join("a"),
+                &Path::new(&content_path).join("b"),
+            )
+            .expect("failed to rename");
+        assert!(!content_folder.join("a").exists());
+        assert_eq!(
+            std::fs::read_to_string(content_folder.join("b")).expect("failed to read content"),
+            "hello"
+        );
+
+        let item_path = format!("/items/{}", id.0);
+        let ret = fixture.client.rename(
+            &Path::new(&content_path).join("b"),
+            &Path::new(&item_path).join("b"),
+        );
+        assert!(matches!(ret, Err(RenameError::CrossesBoundary)));
+
+        let ret = fixture
+            .client
+            .rename(Path::new(&item_path), Path::new("/items/renamed"));
+        assert!(matches!(ret, Err(RenameError::Synthetic)));
+    }
+
+    #[test]
+    fn rename_filter() {
+        let mut fixture = create_fixture();
+        fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        fixture
+            .client
+            .db
+            .add_filter("tpyo", &[])
+            .expect("failed to add filter");
+        fixture
+            .client
+            .db
+            .add_filter("other", &[])
+            .expect("failed to add filter");
+
+        fixture
+            .client
+            .rename(Path::new("/tpyo"), Path::new("/typo"))
+            .expect("failed to rename filter");
+        let names = list_names(&mut fixture.client, "/");
+        assert!(names.contains(&"typo".to_string()));
+        assert!(!names.contains(&"tpyo".to_string()));
+
+        // Built in entries and other filters are never replaced, and filters stay at the root
+        for to in ["/items", "/other", "/search/typo"] {
+            let ret = fixture.client.rename(Path::new("/typo"), Path::new(to));
+            assert!(matches!(ret, Err(RenameError::Synthetic)), "{to}");
+        }
+        for from in ["/items", "/relationships", "/bin", "/.api_handle"] {
+            let ret = fixture
+                .client
+                .rename(Path::new(from), Path::new("/renamed"));
+            assert!(matches!(ret, Err(RenameError::Synthetic)), "{from}");
+        }
+    }
+
+    #[test]
+    fn empty_content_folder() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let empty = db.create_item("empty").expect("failed to create item");
+        let with_file = db.create_item("with file").expect("failed to create item");
+        let with_dir = db.create_item("with dir").expect("failed to create item");
+
+        let content_folder = db
+            .content_folder_for_id(with_file)
+            .expect("failed to get content folder");
+        std::fs::write(content_folder.join("file"), "hello").expect("failed to write content");
+        let content_folder = db
+            .content_folder_for_id(with_dir)
+            .expect("failed to get content folder");
+        std::fs::create_dir(content_folder.join("dir")).expect("failed to create dir");
+
+        let names = list_names(&mut fixture.client, "/empty-content");
+        assert_eq!(names, ["empty"]);
+
+        let link = fixture
+            .client
+            .readlink(Path::new("/empty-content/empty"))
+            .expect("failed to read link");
+        assert_eq!(link, Path::new("../items").join(empty.0.to_string()));
+
+        let filetype = fixture
+            .client
+            .get_filetype(Path::new("/empty-content/with file"))
+            .expect("failed to get filetype");
+        assert!(matches!(filetype, Filetype::Dir));
+    }
+
+    #[test]
+    fn empty_content_names_resolve_without_listing() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let empty_a = db.create_item("dup").expect("failed to create item");
+        let empty_b = db.create_item("dup").expect("failed to create item");
+        let with_file = db.create_item("single").expect("failed to create item");
+        let empty_single = db.create_item("single").expect("failed to create item");
+
+        let content_folder = db
+            .content_folder_for_id(with_file)
+            .expect("failed to get content folder");
+        std::fs::write(content_folder.join("file"), "hello").expect("failed to write content");
+
+        // Only one item named single has empty content, so it is listed without its id
+        let mut names = list_names(&mut fixture.client, "/empty-content");
+        names.sort();
+        let mut expected = vec![
+            format!("dup#{}", empty_a.0),
+            format!("dup#{}", empty_b.0),
+            "single".to_string(),
+        ];
+        expected.sort();
+        assert_eq!(names, expected);
+
+        for (name, id) in [
+            (format!("dup#{}", empty_a.0), empty_a),
+            (format!("dup#{}", empty_b.0), empty_b),
+            ("single".to_string(), empty_single),
+        ] {
+            let link = fixture
+                .client
+                .readlink(&Path::new("/empty-content").join(name))
+                .expect("failed to read link");
+            assert_eq!(link, Path::new("../items").join(id.0.to_string()));
+        }
+
+        for name in ["dup", &format!("single#{}", with_file.0), "missing"] {
+            let ret = fixture
+                .client
+                .readlink(&Path::new("/empty-content").join(name));
+            assert!(ret.is_err(), "{name} should not resolve");
+        }
+    }
+
+    #[test]
+    fn soft_delete_moves_item_to_trash() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let mut db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        let trashed = db.create_item("old").expect("failed to create item");
+        let purged = db.create_item("gone").expect("failed to create item");
+        let mut client = FuseClient::new(db, false, DEFAULT_RECENT_COUNT, true);
+
+        let delete = |client: &mut FuseClient, id: ItemId, trash| {
+            let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+            let OpenRet::Socket(handle) = client
+                .open(socket_path, READ_WRITE)
+                .expect("failed to open socket")
+            else {
+                panic!("expected socket");
+            };
+            let request = ClientRequest::DeleteItem(DeleteItemRequest { id: id.0, trash });
+            let request = crate::fuse::api::serialize_request(&request);
+            client
+                .write(socket_path, handle, &request)
+                .expect("failed to write request");
+            client.release(handle);
+        };
+
+        // The mount defaults to the trash, the request can still ask for a hard delete
+        delete(&mut client, trashed, None);
+        delete(&mut client, purged, Some(false));
+
+        assert!(list_names(&mut client, "/items").is_empty());
+        assert_eq!(list_names(&mut client, "/trash"), ["old"]);
+        assert_eq!(
+            client
+                .get_passthrough_path(Path::new("/trash/old"))
+                .expect("failed to parse path"),
+            Some(client.db.trash_folder_for_id(trashed))
+        );
+    }
+
+    #[test]
+    fn trash_is_read_only() {
+        let mut fixture = create_fixture();
+        let trashed = fixture
+            .client
+            .db
+            .create_item("old")
+            .expect("failed to create item");
+        let other = fixture
+            .client
+            .db
+            .create_item("other")
+            .expect("failed to create item");
+        let content_folder = fixture
+            .client
+            .db
+            .content_folder_for_id(trashed)
+            .expect("failed to get content folder");
+        fs::write(content_folder.join("notes"), "keep me").expect("failed to write content");
+        fixture
+            .client
+            .db
+            .trash_item(trashed)
+            .expect("failed to trash item");
+
+        for path in ["/trash/old", "/trash/old/notes", "/trash/old/new"] {
+            let Err(GetWritablePassthroughPathError::InTrash) = fixture
+                .client
+                .get_writable_passthrough_path(Path::new(path))
+            else {
+                panic!("{path} should not be writable");
+            };
+        }
+
+        let other_content = format!("/items/{}/content/notes", other.0);
+        for (from, to) in [
+            ("/trash/old/notes", other_content.as_str()),
+            ("/trash/old/notes", "/trash/old/renamed"),
+        ] {
+            let Err(RenameError::InTrash) = fixture.client.rename(Path::new(from), Path::new(to))
+            else {
+                panic!("renaming {from} to {to} should fail");
+            };
+        }
+
+        // Reading is still allowed, and the content comes back untouched
+        assert!(fixture
+            .client
+            .get_passthrough_path(Path::new("/trash/old/notes"))
+            .expect("failed to parse path")
+            .is_some());
+        assert!(fixture
+            .client
+            .get_writable_passthrough_path(Path::new(&other_content))
+            .expect("failed to parse path")
+            .is_some());
+        fixture
+            .client
+            .db
+            .restore_item(trashed)
+            .expect("failed to restore item");
+        assert_eq!(
+            fs::read_to_string(content_folder.join("notes")).expect("failed to read content"),
+            "keep me"
+        );
+    }
+
+    #[test]
+    fn stats_file() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let content_folder = db
+            .content_folder_for_id(child)
+            .expect("failed to get content folder");
+        std::fs::write(content_folder.join("file"), "hello").expect("failed to write content");
+
+        let names = list_names(&mut fixture.client, "/");
+        assert!(names.contains(&"stats".to_string()));
+        assert!(names.contains(&"stats.json".to_string()));
+
+        // Measuring the content on getattr would make listing the root as slow as reading stats
+        for name in ["/stats", "/stats.json"] {
+            assert!(matches!(
+                fixture
+                    .client
+                    .get_filetype(Path::new(name))
+                    .expect("failed to get filetype"),
+                Filetype::File(0)
+            ));
+        }
+
+        let stats: serde_json::Value =
+            serde_json::from_slice(&read_snapshot(&mut fixture.client, "/stats.json"))
+                .expect("stats should be json");
+        assert_eq!(stats["items"], 2);
+        assert_eq!(stats["relationships"], 1);
+        assert_eq!(stats["filters"], 0);
+        assert!(
+            stats["content_bytes"]
+                .as_u64()
+                .expect("content bytes should be a number")
+                >= 5
+        );
+
+        let text = read_snapshot(&mut fixture.client, "/stats");
+        let text = std::str::from_utf8(&text).expect("stats should be utf8");
+        assert!(text.starts_with("items: 2\nrelationships: 1\nfilters: 0\n"));
+
+        // A handle keeps reporting what was true when it was opened
+        let OpenRet::Snapshot(handle) = fixture
+            .client
+            .open(Path::new("/stats"), OpenFlags::default())
+            .expect("failed to open stats")
+        else {
+            panic!("expected snapshot");
+        };
+        fixture
+            .client
+            .db
+            .create_item("third")
+            .expect("failed to create item");
+        let mut buf = vec![0; 4096];
+        let len = fixture
+            .client
+            .read(Path::new("/stats"), handle, 0, &mut buf)
+            .expect("failed to read stats");
+        assert!(buf[..len].starts_with(b"items: 2\n"));
+        fixture.client.release(handle);
+    }
+
+    #[test]
+    fn info_file() {
+        let mut fixture = create_fixture();
+        fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        fixture.client.set_mountpoint("/mnt/todo".into());
+
+        assert!(list_names(&mut fixture.client, "/").contains(&".info".to_string()));
+
+        let Filetype::File(size) = fixture
+            .client
+            .get_filetype(Path::new("/.info"))
+            .expect("failed to get filetype")
+        else {
+            panic!(".info should be a file");
+        };
+        let mut buf = vec![0; 4096];
+        let len = fixture
+            .client
+            .read(Path::new("/.info"), 0, 0, &mut buf)
+            .expect("failed to read info");
+        assert_eq!(size, len);
+
+        let info: serde_json::Value =
+            serde_json::from_slice(&buf[..len]).expect("info should be json");
+        let db_path = fixture.client.db.db_path().expect("db should be on disk");
+        assert_eq!(
+            info["db_path"],
+            db_path.to_str().expect("db path should be utf8")
+        );
+        assert_eq!(info["mountpoint"], "/mnt/todo");
+        assert_eq!(info["pid"], std::process::id());
+        assert_eq!(info["schema_version"], crate::db::SCHEMA_VERSION);
+        assert_eq!(info["api_version"], API_VERSION);
+
+        let ret = fixture.client.open(Path::new("/.info"), READ_WRITE);
+        assert!(matches!(ret, Err(OpenError::ReadOnly)));
+    }
+
+    #[test]
+    fn batch_request() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        let parent = fixture
+            .client
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+
+        let request = ClientRequest::Batch(vec![
+            ClientRequest::CreateItem(CreateItemRequest {
+                name: "child".to_string(),
+            }),
+            ClientRequest::CreateItemRelationship(CreateItemRelationshipRequest {
+                from_id: parent.0,
+                to_id: parent.0 + 100,
+                relationship_id: relationship_id.0,
+            }),
+        ]);
+        let ClientResponse::Batch(BatchResponse::Failed { index, .. }) =
+            send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected the batch to fail");
+        };
+        assert_eq!(index, 1);
+        // The item created by the first request is rolled back with the rest of the batch
+        assert_eq!(
+            fixture
+                .client
+                .db
+                .count_items()
+                .expect("failed to count items"),
+            1
+        );
+
+        let request = ClientRequest::Batch(vec![
+            ClientRequest::CreateItem(CreateItemRequest {
+                name: "child".to_string(),
+            }),
+            ClientRequest::CreateItemRelationship(CreateItemRelationshipRequest {
+                from_id: parent.0,
+                to_id: parent.0 + 1,
+                relationship_id: relationship_id.0,
+            }),
+        ]);
+        let ClientResponse::Batch(BatchResponse::Completed(responses)) =
+            send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected the batch to complete");
+        };
+        let [Some(ClientResponse::CreateItem(created)), None] = &responses[..] else {
+            panic!("unexpected responses {responses:?}");
+        };
+        assert_eq!(
+            created.path,
+            Path::new(ITEMS_FOLDER).join((parent.0 + 1).to_string())
+        );
+        assert_eq!(
+            fixture
+                .client
+                .db
+                .get_links_for_relationship(relationship_id)
+                .expect("failed to get links"),
+            [(parent, ItemId(parent.0 + 1))]
+        );
+    }
+
+    #[test]
+    fn batch_rolls_back_failure_responses() {
+        let mut fixture = create_fixture();
+        fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        fixture
+            .client
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+
+        // Both fail without an error, only their responses say so
+        let failing_requests = [
+            ClientRequest::CreateItemRelationshipByName(CreateItemRelationshipByNameRequest {
+                relationship: "parents".to_string(),
+                from_name: "parent".to_string(),
+                to_name: "missing".to_string(),
+            }),
+            ClientRequest::CreateItems(vec![CreateItemRequest {
+                name: String::new(),
+            }]),
+        ];
+        for failing_request in failing_requests {
+            let request = ClientRequest::Batch(vec![
+                ClientRequest::CreateItem(CreateItemRequest {
+                    name: "child".to_string(),
+                }),
+                failing_request,
+            ]);
+            let ClientResponse::Batch(BatchResponse::Failed { index, .. }) =
+                send_request(&mut fixture.client, &request)
+            else {
+                panic!("expected the batch to fail");
+            };
+            assert_eq!(index, 1);
+            assert_eq!(
+                fixture
+                    .client
+                    .db
+                    .count_items()
+                    .expect("failed to count items"),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn batch_rejects_deletes() {
+        let mut fixture = create_fixture();
+        let item = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let request = ClientRequest::Batch(vec![
+            ClientRequest::CreateItem(CreateItemRequest {
+                name: "other".to_string(),
+            }),
+            ClientRequest::DeleteItem(DeleteItemRequest {
+                id: item.0,
+                trash: Some(false),
+            }),
+        ]);
+        let ClientResponse::Batch(BatchResponse::Failed { index, .. }) =
+            send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected the batch to fail");
+        };
+        assert_eq!(index, 1);
+
+        // Nothing in the batch ran
+        assert_eq!(
+            fixture
+                .client
+                .db
+                .count_items()
+                .expect("failed to count items"),
+            1
+        );
+        assert!(fixture.client.db.get_item_by_id(item).is_some());
+    }
+
+    #[test]
+    fn passthrough_handles_do_not_collide_with_sockets() {
+        let mut fixture = create_fixture();
+
+        // fd 0 is a valid fd, e.g. when stdin was closed before mounting
+        let passthrough = fixture.client.open_passthrough_fd(0);
+        assert_eq!(fixture.client.passthrough_fd(passthrough), Some(0));
+
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(socket) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+        assert_ne!(socket, passthrough);
+        assert_eq!(fixture.client.passthrough_fd(socket), None);
+
+        let request = ClientRequest::CreateItem(CreateItemRequest {
+            name: "item".to_string(),
+        });
+        let ClientResponse::CreateItem(_) = send_request(&mut fixture.client, &request) else {
+            panic!("unexpected response");
+        };
+        assert_eq!(fixture.client.passthrough_fd(passthrough), Some(0));
+
+        fixture.client.release(socket);
+        assert_eq!(fixture.client.release_passthrough_fd(passthrough), Some(0));
+        assert_eq!(fixture.client.passthrough_fd(passthrough), None);
+        assert_eq!(fixture.client.release_passthrough_fd(passthrough), None);
+    }
+
+    #[test]
+    fn fallocate_content_file() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/file", id.0)))
+            .expect("failed to parse path")
+            .expect("content file should be a passthrough path");
+        let file = std::fs::File::create(&path).expect("failed to create file");
+
+        const SIZE: crate::fuse::sys::off_t = 1024 * 1024;
+        let ret = unsafe { crate::fuse::fallocate_fd(file.as_raw_fd(), 0, 0, SIZE) };
+        assert_eq!(ret, 0);
+
+        let metadata = std::fs::metadata(&path).expect("failed to stat file");
+        assert_eq!(metadata.len(), SIZE as u64);
+    }
+
+    #[test]
+    fn append_through_two_handles() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/log", id.0)))
+            .expect("failed to parse path")
+            .expect("content file should be a passthrough path");
+        std::fs::write(&path, "").expect("failed to create file");
+
+        let flags = (crate::fuse::sys::O_WRONLY | crate::fuse::sys::O_APPEND) as c_int;
+        let fds = [(); 2].map(|_| {
+            let fd = unsafe { crate::fuse::open_passthrough_file(path.clone(), flags) };
+            assert!(fd >= 0);
+            fd
+        });
+
+        // Both handles think they are writing at the start, append has to win
+        for (fd, data) in fds.iter().zip([b"first\n", b"other\n"]).cycle().take(4) {
+            let ret = unsafe { crate::fuse::write_passthrough_fd(*fd, data, 0) };
+            assert_eq!(ret, data.len() as c_int);
+        }
+        for fd in fds {
+            drop(unsafe { std::fs::File::from_raw_fd(fd) });
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("failed to read content"),
+            "first\nother\nfirst\nother\n"
+        );
+    }
+
+    #[test]
+    fn read_back_through_read_write_handle() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/file", id.0)))
+            .expect("failed to parse path")
+            .expect("content file should be a passthrough path");
+        std::fs::write(&path, "").expect("failed to create file");
+
+        let fd =
+            unsafe { crate::fuse::open_passthrough_file(path, crate::fuse::sys::O_RDWR as c_int) };
+        assert!(fd >= 0);
+        let handle = fixture.client.open_passthrough_fd(fd);
+        let fd = fixture
+            .client
+            .passthrough_fd(handle)
+            .expect("handle should map to the fd");
+
+        let ret = unsafe { crate::fuse::write_passthrough_fd(fd, b"hello", 0) };
+        assert_eq!(ret, 5);
+        let mut buf = [0; 64];
+        let ret = unsafe { crate::fuse::read_passthrough_fd(fd, &mut buf, 0) };
+        assert_eq!(&buf[..ret as usize], b"hello");
+
+        let fd = fixture
+            .client
+            .release_passthrough_fd(handle)
+            .expect("handle should map to the fd");
+        drop(unsafe { std::fs::File::from_raw_fd(fd) });
+    }
+
+    #[test]
+    fn multi_megabyte_write() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/big", id.0)))
+            .expect("failed to parse path")
+            .expect("content file should be a passthrough path");
+        let fd = unsafe {
+            crate::fuse::create_passthrough_file(
+                path.clone(),
+                crate::fuse::sys::O_WRONLY as c_int,
+                0o644,
+            )
+        };
+        assert!(fd >= 0);
+
+        // Written the way the kernel hands it over, in chunks at increasing offsets. Whatever a
+        // write reports is where the next one continues from
+        let data: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut written = 0;
+        while written < data.len() {
+            let end = (written + 128 * 1024).min(data.len());
+            let ret = unsafe {
+                crate::fuse::write_passthrough_fd(
+                    fd,
+                    &data[written..end],
+                    written as crate::fuse::sys::off_t,
+                )
+            };
+            assert!(ret > 0, "write failed with {ret}");
+            written += ret as usize;
+        }
+        drop(unsafe { std::fs::File::from_raw_fd(fd) });
+
+        let metadata = std::fs::metadata(&path).expect("failed to stat file");
+        assert_eq!(metadata.len(), data.len() as u64);
+        assert_eq!(std::fs::read(&path).expect("failed to read file"), data);
+    }
+
+    #[test]
+    fn content_symlink_round_trip() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/link", id.0)))
+            .expect("failed to parse path")
+            .expect("content link should be a passthrough path");
+        let target = c"../elsewhere/file";
+        let ret = unsafe { crate::fuse::create_passthrough_symlink(target, path.clone()) };
+        assert_eq!(ret, 0);
+
+        let mut buf = [0xff; 64];
+        let ret = unsafe { crate::fuse::read_passthrough_link(path.clone(), &mut buf) };
+        assert_eq!(ret, 0);
+        let read = std::ffi::CStr::from_bytes_until_nul(&buf).expect("link should be terminated");
+        assert_eq!(read, target);
+
+        // Targets that do not fit are truncated, but still terminated
+        let mut buf = [0xff; 4];
+        let ret = unsafe { crate::fuse::read_passthrough_link(path.clone(), &mut buf) };
+        assert_eq!(ret, 0);
+        assert_eq!(&buf, b"../\0");
+
+        let ret = unsafe { crate::fuse::create_passthrough_symlink(target, path) };
+        assert_eq!(ret, -(crate::fuse::sys::EEXIST as c_int));
+    }
+
+    #[test]
+    fn content_hardlink_count() {
+        use std::os::unix::fs::MetadataExt;
+
+        let mut fixture = create_fixture();
+        let a = fixture
+            .client
+            .db
+            .create_item("a")
+            .expect("failed to create item");
+        let b = fixture
+            .client
+            .db
+            .create_item("b")
+            .expect("failed to create item");
+
+        let passthrough_path = |client: &mut FuseClient, path: String| {
+            client
+                .get_passthrough_path(Path::new(&path))
+                .expect("failed to parse path")
+        };
+        let original = passthrough_path(&mut fixture.client, format!("/items/{}/content/f", a.0))
+            .expect("content file should be a passthrough path");
+        std::fs::write(&original, "hello").expect("failed to write content");
+        let link = passthrough_path(&mut fixture.client, format!("/items/{}/content/f", b.0));
+
+        let ret = unsafe {
+            crate::fuse::link_passthrough_files(
+                &fixture.client.db,
+                Some(original.clone()),
+                link.clone(),
+            )
+        };
+        assert_eq!(ret, 0);
+        let link = link.expect("content file should be a passthrough path");
+        for path in [&original, &link] {
+            let metadata = std::fs::metadata(path).expect("failed to stat file");
+            assert_eq!(metadata.nlink(), 2);
+        }
+        assert_eq!(
+            std::fs::read_to_string(&link).expect("failed to read link"),
+            "hello"
+        );
+
+        // Virtual paths have no passthrough path to link to
+        let name = passthrough_path(&mut fixture.client, format!("/items/{}/name", a.0));
+        let ret = unsafe {
+            crate::fuse::link_passthrough_files(&fixture.client.db, Some(original), name)
+        };
+        assert_eq!(ret, -(crate::fuse::sys::EXDEV as c_int));
+    }
+
+    #[test]
+    fn create_content_file_exclusive() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+
+        let path = fixture
+            .client
+            .get_passthrough_path(Path::new(&format!("/items/{}/content/file", id.0)))
+            .expect("failed to parse path")
+            .expect("content file should be a passthrough path");
+
+        // O_CREAT is left out on purpose, create implies it
+        let flags = (crate::fuse::sys::O_WRONLY | crate::fuse::sys::O_EXCL) as c_int;
+        let fd = unsafe { crate::fuse::create_passthrough_file(path.clone(), flags, 0o644) };
+        assert!(fd >= 0);
+        drop(unsafe { std::fs::File::from_raw_fd(fd) });
+        assert!(path.exists());
+
+        let ret = unsafe { crate::fuse::create_passthrough_file(path, flags, 0o644) };
+        assert_eq!(ret, -(crate::fuse::sys::EEXIST as c_int));
+    }
+
+    #[test]
+    fn all_related_items() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let parents = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let blocked_by = db
+            .add_relationship("blocked_by", "blocking")
+            .expect("failed to create relationship");
+        let item = db.create_item("item").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        let blocker = db.create_item("blocker").expect("failed to create item");
+        db.add_item_relationship(item, child, parents)
+            .expect("failed to add item relationship");
+        db.add_item_relationship(item, child, blocked_by)
+            .expect("failed to add item relationship");
+        db.add_item_relationship(blocker, item, blocked_by)
+            .expect("failed to add item relationship");
+
+        let related_path = format!("/items/{}/related", item.0);
+        let names = list_names(&mut fixture.client, &related_path);
+        assert_eq!(
+            names,
+            vec![
+                format!("blocked_by#{}", blocker.0),
+                format!("blocking#{}", child.0),
+                format!("children#{}", child.0),
+            ]
+        );
+
+        let link = fixture
+            .client
+            .readlink(&Path::new(&related_path).join(format!("blocked_by#{}", blocker.0)))
+            .expect("failed to read link");
+        assert_eq!(
+            link,
+            Path::new("../../../items").join(blocker.0.to_string())
+        );
+    }
+
+    #[test]
+    fn open_checks_flags() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let name_path = format!("/items/{}/name", id.0);
+        let from_name_path = format!("/relationships/{}/from_name", relationship_id.0);
+        let item_path = format!("/items/{}", id.0);
+        let directory = OpenFlags {
+            write: false,
+            directory: true,
+        };
+
+        let Err(OpenError::ReadOnly) = fixture.client.open(Path::new(&name_path), READ_WRITE)
+        else {
+            panic!("generated metadata should not open for writing");
+        };
+        let Err(OpenError::NotADirectory) = fixture.client.open(Path::new(&name_path), directory)
+        else {
+            panic!("files should not open as directories");
+        };
+        let Ok(OpenRet::Noop { .. }) = fixture.client.open(Path::new(&from_name_path), READ_WRITE)
+        else {
+            panic!("relationship names are writable");
+        };
+
+        let Ok(OpenRet::Noop { direct_io: false }) =
+            fixture.client.open(Path::new(&item_path), directory)
+        else {
+            panic!("directories should open as directories");
+        };
+        let Err(OpenError::IsADirectory) = fixture.client.open(Path::new(&item_path), READ_WRITE)
+        else {
+            panic!("directories should not open for writing");
+        };
+        let Err(OpenError::NotFound) = fixture
+            .client
+            .open(Path::new("/does-not-exist"), OpenFlags::default())
+        else {
+            panic!("missing paths should not open");
+        };
+    }
+
+    #[test]
+    fn item_size_file() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        let item_path = format!("/items/{}", id.0);
+        let size_path = format!("{item_path}/size");
+        let content_path = format!("{item_path}/content");
+
+        let read_size = |client: &mut FuseClient| {
+            String::from_utf8(read_snapshot(client, &size_path)).expect("size should be utf8")
+        };
+        assert_eq!(read_size(&mut fixture.client), "0\n");
+        assert_eq!(
+            fixture
+                .client
+                .get_content_size(Path::new(&item_path))
+                .expect("failed to get content size"),
+            Some(0)
+        );
+
+        let content_folder = fixture
+            .client
+            .db
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+        std::fs::write(content_folder.join("a"), "hello").expect("failed to write content");
+        std::fs::create_dir(content_folder.join("nested")).expect("failed to create dir");
+        std::fs::write(content_folder.join("nested/b"), "world!").expect("failed to write content");
+
+        // Measured on every open, content is written straight to the content folder
+        assert_eq!(read_size(&mut fixture.client), "11\n");
+        for path in [&item_path, &content_path] {
+            assert_eq!(
+                fixture
+                    .client
+                    .get_content_size(Path::new(path))
+                    .expect("failed to get content size"),
+                Some(11)
+            );
+        }
+        assert_eq!(
+            fixture
+                .client
+                .get_content_size(Path::new(&format!("{content_path}/nested")))
+                .expect("failed to get content size"),
+            None
+        );
+        assert!(matches!(
+            fixture
+                .client
+                .get_filetype(Path::new(&size_path))
+                .expect("failed to get filetype"),
+            Filetype::File(0)
+        ));
+        assert!(list_names(&mut fixture.client, &format!("/items/{}", id.0))
+            .contains(&"size".to_string()));
+    }
+
+    #[test]
+    fn rename_visible_on_next_read() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("old")
+            .expect("failed to create item");
+        let name_path = format!("/items/{}/name", id.0);
+        let name_path = Path::new(&name_path);
+
+        let OpenRet::Noop { direct_io: true } = fixture
+            .client
+            .open(name_path, OpenFlags::default())
+            .expect("failed to open")
+        else {
+            panic!("metadata files should bypass the page cache");
+        };
+
+        let mut buf = [0; 64];
+        let len = fixture
+            .client
+            .read(name_path, 0, 0, &mut buf)
+            .expect("failed to read name");
+        assert_eq!(&buf[..len], b"old\n");
+
+        fixture
+            .client
+            .db
+            .rename_item(id, "new")
+            .expect("failed to rename item");
+        let len = fixture
+            .client
+            .read(name_path, 0, 0, &mut buf)
+            .expect("failed to read name");
+        assert_eq!(&buf[..len], b"new\n");
+
+        // direct_io reads are not clamped to the file size by the kernel, reads past the end
+        // have to come back empty for readers to stop
+        let len = fixture
+            .client
+            .read(name_path, 0, 2, &mut buf)
+            .expect("failed to read name");
+        assert_eq!(&buf[..len], b"w\n");
+        let len = fixture
+            .client
+            .read(name_path, 0, 4, &mut buf)
+            .expect("failed to read name");
+        assert_eq!(len, 0);
+    }
+
+    #[test]
+    fn filter_intersection_folder() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let milk = db.create_item("milk").expect("failed to create item");
+        let oat_milk = db.create_item("oat milk").expect("failed to create item");
+        let oats = db.create_item("oats").expect("failed to create item");
+        for (parent, child) in [(milk, oat_milk), (oat_milk, oats)] {
+            db.add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+        }
+        let has_side = |side| {
+            [ItemFilterRule::RelationshipCount(
+                side,
+                relationship_id,
+                CompareOp::GreaterEqual,
+                1,
+            )]
+        };
+        let parents = db
+            .add_filter("parents", &has_side(RelationshipSide::Source))
+            .expect("failed to add filter");
+        let children = db
+            .add_filter("children", &has_side(RelationshipSide::Dest))
+            .expect("failed to add filter");
+
+        let path = format!("/intersect/{}+{}", parents.0, children.0);
+        assert_eq!(list_names(&mut fixture.client, &path), vec!["oat milk"]);
+
+        assert!(list_names(&mut fixture.client, "/intersect").is_empty());
+        assert!(matches!(
+            fixture
+                .client
+                .parse_path(Path::new("/intersect/1+oat"))
+                .expect("failed to parse path"),
+            PathPurpose::Unknown
+        ));
+    }
+
+    #[test]
+    fn fuse_client_args() {
+        let parse = |args: &[&str]| {
+            crate::fuse::parse_fuse_client_args(args.iter().map(|arg| arg.to_string()))
+        };
+
+        let args = parse(&["todo-fs", "-o", "ro", "--recent-count", "5", "mnt", "-f"])
+            .expect("failed to parse args");
+        assert!(args.readonly);
+        assert_eq!(args.recent_count, 5);
+        assert_eq!(args.mountpoint, Some(PathBuf::from("mnt")));
+        assert_eq!(args.fuse_args, [c"todo-fs", c"-o", c"ro", c"mnt", c"-f"]);
+
+        for trailing in ["-o", "--recent-count"] {
+            let Err(FuseArgsError::MissingValue(arg)) = parse(&["todo-fs", "mnt", trailing]) else {
+                panic!("expected missing value for {trailing}");
+            };
+            assert_eq!(arg, trailing);
+        }
+
+        let Err(FuseArgsError::InvalidRecentCount(count, _)) =
+            parse(&["todo-fs", "--recent-count", "-1"])
+        else {
+            panic!("expected invalid recent count");
+        };
+        assert_eq!(count, "-1");
+
+        let Err(FuseArgsError::NulByte(arg)) = parse(&["todo-fs", "mn\0t"]) else {
+            panic!("expected nul byte error");
+        };
+        assert_eq!(arg, "mn\0t");
+    }
+
+    #[test]
+    fn metadata_value_newlines() {
+        for input in ["name", "name\n", "name\n\n", "name\r\n"] {
+            assert_eq!(normalize_metadata_value(input), "name");
+            assert_eq!(with_newline_as_vec(input.to_string()), b"name\n");
+        }
+    }
+
+    #[test]
+    fn symmetric_relationship_folders() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let related = db
+            .add_relationship("linked", "linked")
+            .expect("failed to create relationship");
+        let a = db.create_item("a").expect("failed to create item");
+        let b = db.create_item("b").expect("failed to create item");
+        let c = db.create_item("c").expect("failed to create item");
+        db.add_item_relationship(a, b, related)
+            .expect("failed to add item relationship");
+        db.add_item_relationship(c, a, related)
+            .expect("failed to add item relationship");
+
+        let item_path = format!("/items/{}", a.0);
+        let names = list_names(&mut fixture.client, &item_path);
+        assert!(names.contains(&"linked(out)".to_string()));
+        assert!(names.contains(&"linked(in)".to_string()));
+        assert_eq!(
+            list_names(&mut fixture.client, &format!("{item_path}/linked(out)")),
+            vec!["b"]
+        );
+        assert_eq!(
+            list_names(&mut fixture.client, &format!("{item_path}/linked(in)")),
+            vec!["c"]
+        );
+
+        // b only has the in folder so far, links can still be added to the out folder
+        fixture
+            .client
+            .symlink(
+                Path::new(&format!("../../{}", c.0)),
+                Path::new(&format!("/items/{}/linked(out)/c", b.0)),
+            )
+            .expect("failed to create symlink");
+        assert_eq!(
+            list_names(&mut fixture.client, &format!("/items/{}/linked(out)", b.0)),
+            vec!["c"]
+        );
+    }
+
+    #[test]
+    fn events_file() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        db.rename_item(child, "renamed child")
+            .expect("failed to rename item");
+        db.rename_relationship(relationship_id, Some("ancestors"), None)
+            .expect("failed to rename relationship");
+
+        // Rendering every event on getattr would make listing the root as slow as reading it
+        assert!(matches!(
+            fixture
+                .client
+                .get_filetype(Path::new("/events"))
+                .expect("failed to get filetype"),
+            Filetype::File(0)
+        ));
+
+        let events = read_snapshot(&mut fixture.client, "/events");
+        let kinds: Vec<String> = std::str::from_utf8(&events)
+            .expect("events should be utf8")
+            .lines()
+            .map(|line| {
+                let event: serde_json::Value =
+                    serde_json::from_str(line).expect("event should be json");
+                event["kind"]
+                    .as_str()
+                    .expect("kind should be a string")
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "relationship_added",
+                "item_created",
+                "item_created",
+                "item_relationship_added",
+                "item_renamed",
+                "relationship_renamed",
+            ]
+        );
+    }
+
+    #[test]
+    fn item_context_filter() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent_1 = db.create_item("parent 1").expect("failed to create item");
+        let parent_2 = db.create_item("parent 2").expect("failed to create item");
+        for (parent, child_name) in [(parent_1, "a"), (parent_1, "b"), (parent_2, "c")] {
+            let child = db.create_item(child_name).expect("failed to create item");
+            db.add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+        }
+        db.add_filter(
+            "kids",
+            &[ItemFilterRule::HasRelationshipWithVariableItem(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+        )
+        .expect("failed to add filter");
+
+        assert!(!list_names(&mut fixture.client, "/").contains(&"kids".to_string()));
+
+        let names = list_names(&mut fixture.client, &format!("/items/{}/kids", parent_1.0));
+        assert_eq!(names, ["a", "b"]);
+
+        let names = list_names(&mut fixture.client, &format!("/items/{}/kids", parent_2.0));
+        assert_eq!(names, ["c"]);
+    }
+
+    #[test]
+    fn item_filter_conditions() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let has_children = db
+            .add_filter(
+                "has_children",
+                &[ItemFilterRule::RelationshipCount(
+                    RelationshipSide::Source,
+                    relationship_id,
+                    CompareOp::GreaterEqual,
+                    1,
+                )],
+            )
+            .expect("failed to add filter");
+        db.add_ordered_filter("everything", &[], &[has_children], None)
+            .expect("failed to add filter");
+
+        let root_names = list_names(&mut fixture.client, "/");
+        assert!(root_names.contains(&"has_children".to_string()));
+        assert!(!root_names.contains(&"everything".to_string()));
+
+        let parent_names = list_names(&mut fixture.client, &format!("/items/{}", parent.0));
+        assert!(parent_names.contains(&"everything".to_string()));
+        let names = list_names(
+            &mut fixture.client,
+            &format!("/items/{}/everything", parent.0),
+        );
+        assert_eq!(names, ["child", "parent"]);
+
+        let child_names = list_names(&mut fixture.client, &format!("/items/{}", child.0));
+        assert!(!child_names.contains(&"everything".to_string()));
+    }
+
+    #[test]
+    fn read_relationship_description() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship_with_description("parents", "children", "family tree")
+            .expect("failed to create relationship");
+
+        let path = format!("/relationships/{}/description", relationship_id.0);
+        let names = list_names(
+            &mut fixture.client,
+            &format!("/relationships/{}", relationship_id.0),
+        );
+        assert!(names.contains(&"description".to_string()));
+
+        let mut buf = [0; 64];
+        let len = fixture
+            .client
+            .read(Path::new(&path), 0, 0, &mut buf)
+            .expect("failed to read description");
+        assert_eq!(&buf[..len], b"family tree\n");
+    }
+
+    #[test]
+    fn list_relationship_links() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        let other = db.create_item("other").expect("failed to create item");
+        for to_id in [child, other] {
+            db.add_item_relationship(parent, to_id, relationship_id)
+                .expect("failed to add item relationship");
+        }
+
+        let relationship_path = format!("/relationships/{}", relationship_id.0);
+        let names = list_names(&mut fixture.client, &relationship_path);
+        assert!(names.contains(&"links".to_string()));
+
+        let links_path = format!("{relationship_path}/links");
+        assert_eq!(
+            list_names(&mut fixture.client, &links_path),
+            [
+                format!("{}-{}", parent.0, child.0),
+                format!("{}-{}", parent.0, other.0)
+            ]
+        );
+
+        let link_path = format!("{links_path}/{}-{}", parent.0, child.0);
+        let Filetype::File(len) = fixture
+            .client
+            .get_filetype(Path::new(&link_path))
+            .expect("failed to get filetype")
+        else {
+            panic!("expected link to be a file");
+        };
+        let mut buf = [0; 64];
+        let read_len = fixture
+            .client
+            .read(Path::new(&link_path), 0, 0, &mut buf)
+            .expect("failed to read link");
+        assert_eq!(&buf[..read_len], b"parent -> child\n");
+        assert_eq!(read_len, len);
+    }
+
+    #[test]
+    fn item_link_target_depth() {
+        assert_eq!(
+            item_link_target(Path::new("/filter/item"), ItemId(5)),
+            Path::new("../items/5")
+        );
+        assert_eq!(
+            item_link_target(Path::new("/items/3/children/item"), ItemId(5)),
+            Path::new("../../../items/5")
+        );
+        assert_eq!(
+            item_link_target(Path::new("/item"), ItemId(5)),
+            Path::new("items/5")
+        );
+        assert_eq!(
+            item_link_target(Path::new("/"), ItemId(5)),
+            Path::new("items/5")
+        );
+    }
+
+    #[test]
+    fn readlink_resolves_to_item_folder() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let parents = db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child, parents)
+            .expect("failed to add item relationship");
+        db.add_filter("all", &[]).expect("failed to add filter");
+
+        // Joining the target onto the link's folder has to land on the item itself
+        for link in [
+            "/all/child".to_string(),
+            format!("/items/{}/children/child", parent.0),
+        ] {
+            let link = Path::new(&link);
+            let target = fixture.client.readlink(link).expect("failed to read link");
+            let mut resolved = link.parent().expect("link has a parent").to_path_buf();
+            for component in target.iter() {
+                if component == ".." {
+                    resolved.pop();
+                } else {
+                    resolved.push(component);
+                }
+            }
+            assert_eq!(resolved, Path::new(&format!("/items/{}", child.0)));
+        }
+    }
+
+    #[test]
+    fn symlink_creates_item_relationship() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        db.add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        let grandchild = db.create_item("grandchild").expect("failed to create item");
+
+        let children_path = format!("/items/{}/children", parent.0);
+        fixture
+            .client
+            .symlink(
+                Path::new(&format!("../../{}", child.0)),
+                &Path::new(&children_path).join("anything"),
+            )
+            .expect("failed to link child");
+        assert_eq!(list_names(&mut fixture.client, &children_path), ["child"]);
+
+        // Links in the parents folder point the other way
+        let parents_path = format!("/items/{}/parents", grandchild.0);
+        fixture
+            .client
+            .symlink(
+                Path::new(&format!("/mnt/todo/items/{}", child.0)),
+                &Path::new(&parents_path).join("child"),
+            )
+            .expect("failed to link parent");
+        let names = list_names(&mut fixture.client, &format!("/items/{}/children", child.0));
+        assert_eq!(names, ["grandchild"]);
+
+        let Err(SymlinkError::InvalidTarget) = fixture.client.symlink(
+            Path::new("../../../relationships"),
+            &Path::new(&children_path).join("bad"),
+        ) else {
+            panic!("expected invalid target");
+        };
+
+        let Err(SymlinkError::Unhandled) = fixture.client.symlink(
+            Path::new(&format!("../{}", child.0)),
+            &Path::new("/items").join("bad"),
+        ) else {
+            panic!("expected symlink outside relationship folder to be unhandled");
+        };
+    }
+
+    #[test]
+    fn pin_items_with_symlinks() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let first = db.create_item("first").expect("failed to create item");
+        let second = db.create_item("second").expect("failed to create item");
+
+        assert!(list_names(&mut fixture.client, "/").contains(&"pinned".to_string()));
+        assert!(list_names(&mut fixture.client, "/pinned").is_empty());
+
+        fixture
+            .client
+            .symlink(
+                Path::new(&format!("../items/{}", first.0)),
+                Path::new("/pinned/anything"),
+            )
+            .expect("failed to pin item");
+        fixture
+            .client
+            .symlink(
+                Path::new(&format!("/mnt/todo/items/{}", second.0)),
+                Path::new("/pinned/second"),
+            )
+            .expect("failed to pin item");
+        assert_eq!(
+            list_names(&mut fixture.client, "/pinned"),
+            ["first", "second"]
+        );
+        assert_eq!(
+            fixture
+                .client
+                .readlink(Path::new("/pinned/first"))
+                .expect("failed to read link"),
+            Path::new(&format!("../items/{}", first.0))
+        );
+
+        let Err(SymlinkError::InvalidTarget) = fixture
+            .client
+            .symlink(Path::new("../relationships"), Path::new("/pinned/bad"))
+        else {
+            panic!("expected invalid target");
+        };
+
+        fixture
+            .client
+            .unlink(Path::new("/pinned/first"))
+            .expect("failed to unpin item");
+        assert_eq!(list_names(&mut fixture.client, "/pinned"), ["second"]);
+        assert_eq!(
+            fixture
+                .client
+                .db
+                .get_pinned_items()
+                .expect("failed to get pinned items"),
+            [(second, "second".to_string())]
+        );
+    }
+
+    #[test]
+    fn unlink_item_relationship() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child_1 = db.create_item("child").expect("failed to create item");
+        let child_2 = db.create_item("child").expect("failed to create item");
+        for child in [child_1, child_2] {
+            db.add_item_relationship(parent, child, relationship_id)
+                .expect("failed to add item relationship");
+        }
+        db.add_filter(
+            "no_parents",
+            &[ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+        )
+        .expect("failed to add filter");
+
+        let Err(UnlinkError::Computed) = fixture.client.unlink(Path::new("/no_parents/parent"))
+        else {
+            panic!("expected filter links to be rejected");
+        };
+
+        // Removing from the child's side removes the same relationship
+        let path = format!("/items/{}/parents/parent", child_1.0);
+        fixture
+            .client
+            .unlink(Path::new(&path))
+            .expect("failed to unlink");
+
+        let children_path = format!("/items/{}/children", parent.0);
+        let names = list_names(&mut fixture.client, &children_path);
+        assert_eq!(names, ["child"]);
+
+        let name = format!("child#{}", child_2.0);
+        let Err(UnlinkError::NotFound) = fixture
+            .client
+            .unlink(&Path::new(&children_path).join(&name))
+        else {
+            panic!("disambiguated name should no longer be listed");
+        };
+
+        fixture
+            .client
+            .unlink(&Path::new(&children_path).join("child"))
+            .expect("failed to unlink");
+        assert!(fixture
+            .client
+            .db
+            .get_item_by_id(parent)
+            .expect("item should exist")
+            .relationships
+            .is_empty());
+    }
+
+    #[test]
+    fn dir_link_count() {
+        let mut fixture = create_fixture();
+        for name in ["a", "b", "c"] {
+            fixture
+                .client
+                .db
+                .create_item(name)
+                .expect("failed to create item");
+        }
+
+        let count = fixture
+            .client
+            .dir_link_count(Path::new("/items"))
+            .expect("failed to count links");
+        assert_eq!(count, 2 + 3);
+
+        // Only the content and related folders are directories in an item folder without
+        // relationships
+        let count = fixture
+            .client
+            .dir_link_count(Path::new("/items/1"))
+            .expect("failed to count links");
+        assert_eq!(count, 2 + 2);
+    }
+
+    #[test]
+    fn dir_link_count_skips_file_contents() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("a")
+            .expect("failed to create item");
+
+        // A content folder that is not a folder breaks the size file of the item, and the stats
+        // files of the root once the content root is not a folder either. Counting links must not
+        // need either of them
+        let content_folder = fixture
+            .client
+            .db
+            .content_folder_for_id(id)
+            .expect("failed to get content folder");
+        fs::remove_dir(&content_folder).expect("failed to remove content folder");
+        fs::write(&content_folder, b"").expect("failed to replace content folder");
+        let item_path = format!("/items/{}", id.0);
+        let size_path = format!("{item_path}/size");
+        assert!(fixture
+            .client
+            .open(Path::new(&size_path), OpenFlags::default())
+            .is_err());
+
+        let count = fixture
+            .client
+            .dir_link_count(Path::new(&item_path))
+            .expect("failed to count links");
+        assert_eq!(count, 2 + 1);
+
+        let fs_root = fixture.client.db.fs_root().to_path_buf();
+        fs::remove_dir_all(&fs_root).expect("failed to remove content root");
+        fs::write(&fs_root, b"").expect("failed to replace content root");
+        assert!(fixture
+            .client
+            .open(Path::new("/stats"), OpenFlags::default())
+            .is_err());
+
+        let count = fixture
+            .client
+            .dir_link_count(Path::new("/"))
+            .expect("failed to count links");
+        assert!(count > 2);
+        let count = fixture
+            .client
+            .dir_link_count(Path::new("/empty-content"))
+            .expect("failed to count links");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn item_and_relationship_timestamps() {
+        let mut fixture = create_fixture();
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time before epoch")
+            .as_secs() as i64;
+        let item_id = fixture
+            .client
+            .db
+            .create_item("test")
+            .expect("failed to create item");
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time before epoch")
+            .as_secs() as i64;
+
+        let item_path = format!("/items/{}", item_id.0);
+        let relationship_path = format!("/relationships/{}", relationship_id.0);
+        for path in [
+            item_path.clone(),
+            format!("{item_path}/name"),
+            relationship_path.clone(),
+            format!("{relationship_path}/to_name"),
+        ] {
+            let timestamp = fixture
+                .client
+                .get_timestamp(Path::new(&path))
+                .expect("failed to get timestamp")
+                .expect("path should have a timestamp");
+            assert!((before..=after).contains(&timestamp), "path: {path}");
+        }
+
+        let timestamp = fixture
+            .client
+            .get_timestamp(Path::new("/items"))
+            .expect("failed to get timestamp");
+        assert_eq!(timestamp, None);
+    }
+
+    #[test]
+    fn read_relationship_direction() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+
+        let path = format!("/relationships/{}/direction", relationship_id.0);
+        let mut buf = [0; 64];
+        let len = fixture
+            .client
+            .read(Path::new(&path), 0, 0, &mut buf)
+            .expect("failed to read direction");
+        assert_eq!(&buf[..len], b"source:parents dest:children\n");
+    }
+
+    #[test]
+    fn write_relationship_names() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parnets", "childs")
+            .expect("failed to create relationship");
+
+        let relationship_path = Path::new("/relationships").join(relationship_id.0.to_string());
+        fixture
+            .client
+            .write(&relationship_path.join("from_name"), 0, b"parents\n")
+            .expect("failed to write from_name");
+        fixture
+            .client
+            .write(&relationship_path.join("to_name"), 0, b"children")
+            .expect("failed to write to_name");
+
+        let relationship = fixture
+            .client
+            .db
+            .get_relationship(relationship_id)
+            .expect("failed to get relationship")
+            .expect("relationship does not exist");
+        assert_eq!(relationship.from_name, "parents");
+        assert_eq!(relationship.to_name, "children");
+    }
+
+    #[test]
+    fn recent_items() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let mut db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        for name in ["first", "second", "third"] {
+            db.create_item(name).expect("failed to create item");
+        }
+        let mut client = FuseClient::new(db, false, 2, false);
+
+        let names = list_names_unsorted(&mut client, "/recent", 0);
+        assert_eq!(names, ["third", "second"]);
+
+        let target = client
+            .readlink(Path::new("/recent/third"))
+            .expect("failed to read link");
+        let third_id = client
+            .db
+            .get_item_by_name("third")
+            .expect("failed to query")[0];
+        assert_eq!(target, Path::new("../items").join(third_id.0.to_string()));
+
+        client
+            .db
+            .create_item("fourth")
+            .expect("failed to create item");
+        let names = list_names_unsorted(&mut client, "/recent", 0);
+        assert_eq!(names, ["fourth", "third"]);
+    }
+
+    fn send_request(client: &mut FuseClient, request: &ClientRequest) -> ClientResponse {
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let request = crate::fuse::api::serialize_request(request);
+        client
+            .write(socket_path, id, &request)
+            .expect("failed to write request");
+
+        // Read a page at a time like the kernel would, large responses take several reads
+        let mut response = Vec::new();
+        let mut buf = vec![0; 4096];
+        loop {
+            let len = client
+                .read(socket_path, id, response.len(), &mut buf)
+                .expect("failed to read response");
+            if len == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..len]);
+        }
+        client.release(id);
+        serde_json::from_slice(&response).expect("failed to parse response")
+    }
+
+    #[test]
+    fn second_request_replaces_unread_response() {
+        let mut fixture = create_fixture();
+        let parents = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to add relationship");
+
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let read_response = |client: &mut FuseClient| {
+            let mut response = Vec::new();
+            let mut buf = vec![0; 4096];
+            loop {
+                let len = client
+                    .read(socket_path, id, response.len(), &mut buf)
+                    .expect("failed to read response");
+                if len == 0 {
+                    break;
+                }
+                response.extend_from_slice(&buf[..len]);
+            }
+            serde_json::from_slice::<ClientResponse>(&response).expect("failed to parse response")
+        };
+
+        for name in ["missing", "parents"] {
+            let request = ClientRequest::GetRelationshipId(GetRelationshipIdRequest {
+                name: name.to_string(),
+            });
+            let request = crate::fuse::api::serialize_request(&request);
+            fixture
+                .client
+                .write(socket_path, id, &request)
+                .expect("failed to write request");
+        }
+
+        let ClientResponse::GetRelationshipId(GetRelationshipIdResponse::Found { id: found }) =
+            read_response(&mut fixture.client)
+        else {
+            panic!("expected only the second response");
+        };
+        assert_eq!(found, parents.0);
+
+        // Once drained the handle can be reused for the next request
+        let request = ClientRequest::GetRelationshipId(GetRelationshipIdRequest {
+            name: "missing".to_string(),
+        });
+        let request = crate::fuse::api::serialize_request(&request);
+        fixture
+            .client
+            .write(socket_path, id, &request)
+            .expect("failed to write request");
+        let ClientResponse::GetRelationshipId(GetRelationshipIdResponse::NotFound) =
+            read_response(&mut fixture.client)
+        else {
+            panic!("unexpected response");
+        };
+
+        fixture.client.release(id);
+    }
+
+    #[test]
+    fn preview_filter_request() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.create_item("other").expect("failed to create item");
+        db.add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let filters = [ItemFilterRule::NoRelationship(
+            RelationshipSide::Dest,
+            relationship_id,
+        )];
+        let request = ClientRequest::PreviewFilter(PreviewFilterRequest {
+            filters: vec![ItemFilterRule::NoRelationship(
+                RelationshipSide::Dest,
+                relationship_id,
+            )],
+        });
+        let ClientResponse::PreviewFilter(mut previewed) =
+            send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected preview");
+        };
+        previewed.sort();
+        assert_eq!(
+            fixture
+                .client
+                .db
+                .get_filters()
+                .expect("failed to get filters")
+                .len(),
+            0
+        );
+
+        fixture
+            .client
+            .db
+            .add_filter("roots", &filters)
+            .expect("failed to add filter");
+        let mut listed: Vec<(i64, String)> = list_names(&mut fixture.client, "/roots")
+            .into_iter()
+            .map(|name| {
+                let link = fixture
+                    .client
+                    .readlink(&Path::new("/roots").join(&name))
+                    .expect("failed to read link");
+                let id = link
+                    .file_name()
+                    .and_then(|id| id.to_str())
+                    .and_then(|id| id.parse().ok())
+                    .expect("link should end in an item id");
+                (id, name)
+            })
+            .collect();
+        listed.sort();
+
+        assert_eq!(previewed, listed);
+        assert_eq!(previewed.len(), 2);
+
+        let request = ClientRequest::CountFilterMatches(PreviewFilterRequest {
+            filters: filters.to_vec(),
+        });
+        let ClientResponse::CountFilterMatches(count) = send_request(&mut fixture.client, &request)
+        else {
+            panic!("expected count");
+        };
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn ordered_filter_listing() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        for name in ["banana", "apple", "cherry"] {
+            db.create_item(name).expect("failed to create item");
+        }
+        db.add_ordered_filter("sorted", &[], &[], Some(FilterOrder::NameDesc))
+            .expect("failed to add filter");
+
+        let filters = db.get_filters().expect("failed to get filters");
+        assert_eq!(filters[0].order, Some(FilterOrder::NameDesc));
+
+        assert_eq!(
+            list_names_unsorted(&mut fixture.client, "/sorted", 0),
+            ["cherry", "banana", "apple"]
+        );
+    }
+
+    #[test]
+    fn large_socket_response() {
+        let mut fixture = create_fixture();
+        let requests = (0..1000)
+            .map(|i| CreateItemRequest {
+                name: format!("item {i}"),
+            })
+            .collect();
+        let request = ClientRequest::CreateItems(requests);
+
+        let response = send_request(&mut fixture.client, &request);
+        let serialized = serde_json::to_vec(&response).expect("failed to serialize response");
+        assert!(serialized.len() > 8192);
+
+        let ClientResponse::CreateItems(CreateItemsResponse::Created(items)) = response else {
+            panic!("expected created items");
+        };
+        assert_eq!(items.len(), 1000);
+    }
+
+    #[test]
+    fn create_item_relationships_request() {
+        let mut fixture = create_fixture();
+        let relationship_id = fixture
+            .client
+            .db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = fixture
+            .client
+            .db
+            .create_item("parent")
+            .expect("failed to create item");
+        let children: Vec<ItemId> = (0..50)
+            .map(|i| {
+                fixture
+                    .client
+                    .db
+                    .create_item(&format!("child {i}"))
+                    .expect("failed to create item")
+            })
+            .collect();
+
+        let links = children
+            .iter()
+            .chain(&children[..1])
+            .map(|child| CreateItemRelationshipRequest {
+                relationship_id: relationship_id.0,
+                from_id: parent.0,
+                to_id: child.0,
+            })
+            .collect();
+        let request = ClientRequest::CreateItemRelationships(CreateItemRelationshipsRequest {
+            links,
+            abort_on_duplicate: false,
+        });
+        let response = send_request(&mut fixture.client, &request);
+
+        let ClientResponse::CreateItemRelationships(CreateItemRelationshipsResponse::Created(
+            created,
+        )) = response
+        else {
+            panic!("expected created links");
+        };
+        assert_eq!(created.len(), 51);
+        assert!(created[..50]
+            .iter()
+            .all(|link| *link == CreatedItemRelationship::Added));
+        assert_eq!(created[50], CreatedItemRelationship::Duplicate);
+
+        let children_path = format!("/items/{}/children", parent.0);
+        assert_eq!(list_names(&mut fixture.client, &children_path).len(), 50);
+
+        let request = ClientRequest::CreateItemRelationships(CreateItemRelationshipsRequest {
+            links: vec![CreateItemRelationshipRequest {
+                relationship_id: relationship_id.0,
+                from_id: parent.0,
+                to_id: children[1].0,
+            }],
+            abort_on_duplicate: true,
+        });
+        let response = send_request(&mut fixture.client, &request);
+        let ClientResponse::CreateItemRelationships(CreateItemRelationshipsResponse::Failed {
+            index: Some(0),
+            ..
+        }) = response
+        else {
+            panic!("expected duplicate to fail the batch");
+        };
+    }
+
+    #[test]
+    fn search_items_request() {
+        let mut fixture = create_fixture();
+        for name in ["tomato", "potato", "tomatillo", "tom%"] {
+            fixture
+                .client
+                .db
+                .create_item(name)
+                .expect("failed to create item");
+        }
+
+        let request = ClientRequest::SearchItems(SearchItemsRequest {
+            prefix: "tom".to_string(),
+            limit: 2,
+        });
+        let ClientResponse::SearchItems(items) = send_request(&mut fixture.client, &request) else {
+            panic!("expected search results");
+        };
+        let names: Vec<_> = items.into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["tom%", "tomatillo"]);
+    }
+
+    #[test]
+    fn get_content_path_request() {
+        let mut fixture = create_fixture();
+        let item_id = fixture
+            .client
+            .db
+            .create_item("item")
+            .expect("failed to create item");
+        let expected = fixture
+            .client
+            .db
+            .content_folder_for_id(item_id)
+            .expect("failed to get content folder");
+
+        let request = ClientRequest::GetContentPath(GetContentPathRequest { id: item_id.0 });
+        let response = send_request(&mut fixture.client, &request);
+        let ClientResponse::GetContentPath(GetContentPathResponse::Found { path }) = response
+        else {
+            panic!("expected content path");
+        };
+        assert_eq!(path, expected);
+        assert!(path.is_absolute());
+
+        let request = ClientRequest::GetContentPath(GetContentPathRequest { id: item_id.0 + 1 });
+        let response = send_request(&mut fixture.client, &request);
+        let ClientResponse::GetContentPath(GetContentPathResponse::ItemNotFound) = response else {
+            panic!("expected missing item");
+        };
+    }
+
+    #[test]
+    fn hello_version_mismatch() {
+        let mut fixture = create_fixture();
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let hello = ClientRequest::Hello(HelloRequest {
+            version: API_VERSION + 1,
+        });
+        let hello = crate::fuse::api::serialize_request(&hello);
+        fixture
+            .client
+            .write(socket_path, id, &hello)
+            .expect("hello should be answered even on mismatch");
+
+        let mut response = vec![0; 4096];
+        let len = fixture
+            .client
+            .read(socket_path, id, 0, &mut response)
+            .expect("failed to read response");
+        let response: ClientResponse =
+            serde_json::from_slice(&response[..len]).expect("failed to parse response");
+        let ClientResponse::Hello(response) = response else {
+            panic!("expected hello response");
+        };
+        assert_eq!(response.version, API_VERSION);
+
+        let request = ClientRequest::CreateItem(CreateItemRequest {
+            name: "test".to_string(),
+        });
+        let request = crate::fuse::api::serialize_request(&request);
+        let Err(WriteError::IncompatibleVersion(version)) =
+            fixture.client.write(socket_path, id, &request)
+        else {
+            panic!("expected requests after a mismatched hello to be rejected");
+        };
+        assert_eq!(version, API_VERSION + 1);
+        fixture.client.release(id);
+
+        // Matching versions and clients that skip the handshake keep working
+        let ClientResponse::Hello(_) = send_request(
+            &mut fixture.client,
+            &ClientRequest::Hello(HelloRequest {
+                version: API_VERSION,
+            }),
+        ) else {
+            panic!("expected hello response");
+        };
+        let ClientResponse::CreateItem(_) = send_request(
+            &mut fixture.client,
+            &ClientRequest::CreateItem(CreateItemRequest {
+                name: "test".to_string(),
+            }),
+        ) else {
+            panic!("expected create item response");
+        };
+    }
+
+    #[test]
+    fn request_waits_for_newline() {
+        let mut fixture = create_fixture();
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let request = ClientRequest::CreateItem(CreateItemRequest {
+            name: "test".to_string(),
+        });
+        let request = crate::fuse::api::serialize_request(&request);
+        let (body, newline) = request.split_at(request.len() - 1);
+        assert_eq!(newline, b"\n");
+
+        // The body alone is valid json, it is still not a request until the newline arrives
+        let count_items =
+            |client: &FuseClient| client.db.count_items().expect("failed to count items");
+        let mut buf = vec![0; 4096];
+        fixture
+            .client
+            .write(socket_path, id, body)
+            .expect("failed to write body");
+        assert_eq!(count_items(&fixture.client), 0);
+        let len = fixture
+            .client
+            .read(socket_path, id, 0, &mut buf)
+            .expect("failed to read response");
+        assert_eq!(len, 0);
+
+        fixture
+            .client
+            .write(socket_path, id, newline)
+            .expect("failed to write newline");
+        assert_eq!(count_items(&fixture.client), 1);
+        let len = fixture
+            .client
+            .read(socket_path, id, 0, &mut buf)
+            .expect("failed to read response");
+        let response: ClientResponse =
+            serde_json::from_slice(&buf[..len]).expect("failed to parse response");
+        assert!(matches!(response, ClientResponse::CreateItem(_)));
+    }
+
+    #[test]
+    fn create_items_split_across_writes() {
+        let mut fixture = create_fixture();
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let request = ClientRequest::CreateItems(
+            (0..1000)
+                .map(|i| CreateItemRequest {
+                    name: format!("item {i}"),
+                })
+                .collect(),
+        );
+        let request = crate::fuse::api::serialize_request(&request);
+
+        // The kernel splits large writes, mimic that
+        for chunk in request.chunks(4096) {
+            fixture
+                .client
+                .write(socket_path, id, chunk)
+                .expect("failed to write request");
+        }
+
+        let mut response = Vec::new();
+        let mut buf = vec![0; 4096];
+        loop {
+            let len = fixture
+                .client
+                .read(socket_path, id, 0, &mut buf)
+                .expect("failed to read response");
+            if len == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..len]);
+        }
+        fixture.client.release(id);
+
+        let response: ClientResponse =
+            serde_json::from_slice(&response).expect("failed to parse response");
+        let ClientResponse::CreateItems(CreateItemsResponse::Created(responses)) = response else {
+            panic!("expected created items");
+        };
+        assert_eq!(responses.len(), 1000);
+
+        let names = list_names(&mut fixture.client, "/items");
+        assert_eq!(names.len(), 1000);
+    }
+
+    #[test]
+    fn requests_end_at_newlines() {
+        let mut fixture = create_fixture();
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = fixture
+            .client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let mut requests = Vec::new();
+        for name in ["first", "second"] {
+            let request = ClientRequest::CreateItem(CreateItemRequest {
+                name: name.to_string(),
+            });
+            requests.extend(crate::fuse::api::serialize_request(&request));
+        }
+
+        // One write carries a whole request and the start of the next
+        let (start, rest) = requests.split_at(requests.len() - 5);
+        fixture
+            .client
+            .write(socket_path, id, start)
+            .expect("failed to write requests");
+        let count_items =
+            |client: &FuseClient| client.db.count_items().expect("failed to count items");
+        assert_eq!(count_items(&fixture.client), 1);
+
+        fixture
+            .client
+            .write(socket_path, id, rest)
+            .expect("failed to write requests");
+        fixture.client.release(id);
+
+        assert_eq!(count_items(&fixture.client), 2);
+    }
+
+    #[test]
+    fn create_item_relationship_by_name() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        let dup_1 = db.create_item("dup").expect("failed to create item");
+        let dup_2 = db.create_item("dup").expect("failed to create item");
+
+        let request = CreateItemRelationshipByNameRequest {
+            relationship: "children".to_string(),
+            from_name: "parent".to_string(),
+            to_name: "dup".to_string(),
+        };
+        let response = send_request(
+            &mut fixture.client,
+            &ClientRequest::CreateItemRelationshipByName(request),
+        );
+        let ClientResponse::CreateItemRelationshipByName(
+            CreateItemRelationshipByNameResponse::AmbiguousItemName { name, candidates },
+        ) = response
+        else {
+            panic!("expected ambiguous name response");
+        };
+        assert_eq!(name, "dup");
+        assert_eq!(candidates, [dup_1.0, dup_2.0]);
+
+        let request = CreateItemRelationshipByNameRequest {
+            relationship: relationship_id.0.to_string(),
+            from_name: "parent".to_string(),
+            to_name: "child".to_string(),
+        };
+        let response = send_request(
+            &mut fixture.client,
+            &ClientRequest::CreateItemRelationshipByName(request),
+        );
+        let ClientResponse::CreateItemRelationshipByName(
+            CreateItemRelationshipByNameResponse::Created { from_id, to_id },
+        ) = response
+        else {
+            panic!("expected created response");
+        };
+        assert_eq!((from_id, to_id), (parent.0, child.0));
+
+        let names = list_names(
+            &mut fixture.client,
+            &format!("/items/{}/children", parent.0),
+        );
+        assert_eq!(names, ["child"]);
+    }
+
+    #[test]
+    fn update_item_name() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let parent = db.create_item("parent").expect("failed to create item");
+        let child = db.create_item("child").expect("failed to create item");
+        db.add_item_relationship(parent, child, relationship_id)
+            .expect("failed to add item relationship");
+
+        let request = UpdateItemRequest {
+            id: child.0,
+            name: Some("renamed".to_string()),
+        };
+        let response = send_request(&mut fixture.client, &ClientRequest::UpdateItem(request));
+        let ClientResponse::UpdateItem(response) = response else {
+            panic!("expected update item response");
+        };
+        assert_eq!(response.path, Path::new("/items").join(child.0.to_string()));
+
+        let item = fixture
+            .client
+            .db
+            .get_item_by_id(child)
+            .expect("item should still exist");
+        assert_eq!(item.name, "renamed");
+        assert_eq!(item.relationships.len(), 1);
+
+        let names = list_names(
+            &mut fixture.client,
+            &format!("/items/{}/children", parent.0),
+        );
+        assert_eq!(names, ["renamed"]);
+
+        // Omitted fields are left alone
+        let response = send_request(
+            &mut fixture.client,
+            &ClientRequest::UpdateItem(UpdateItemRequest {
+                id: child.0,
+                name: None,
+            }),
+        );
+        assert!(matches!(response, ClientResponse::UpdateItem(_)));
+        let item = fixture
+            .client
+            .db
+            .get_item_by_id(child)
+            .expect("item should still exist");
+        assert_eq!(item.name, "renamed");
+    }
+
+    #[test]
+    fn item_xattrs() {
+        let mut fixture = create_fixture();
+        let id = fixture
+            .client
+            .db
+            .create_item("groceries")
+            .expect("failed to create item");
+        let created_at = fixture
+            .client
+            .db
+            .get_item_created_at(id)
+            .expect("failed to get timestamp")
+            .expect("new items have a timestamp");
+
+        let item_path = Path::new("/items").join(id.0.to_string());
+        let xattrs = fixture
+            .client
+            .get_xattrs(&item_path)
+            .expect("failed to get xattrs")
+            .expect("items should have xattrs");
+        assert_eq!(
+            xattrs,
+            [
+                Xattr {
+                    name: "user.todofs.id".to_string(),
+                    value: id.0.to_string().into_bytes(),
+                },
+                Xattr {
+                    name: "user.todofs.name".to_string(),
+                    value: b"groceries".to_vec(),
+                },
+                Xattr {
+                    name: "user.todofs.created_at".to_string(),
+                    value: created_at.to_string().into_bytes(),
+                },
+            ]
+        );
+
+        // The timestamp survives an export and import
+        let mut exported = Vec::new();
+        fixture
+            .client
+            .db
+            .export_json(&mut exported)
+            .expect("failed to export db");
+        let mut imported_fixture = create_fixture();
+        imported_fixture
+            .client
+            .db
+            .import_json(exported.as_slice())
+            .expect("failed to import db");
+        let imported_xattrs = imported_fixture
+            .client
+            .get_xattrs(&item_path)
+            .expect("failed to get xattrs")
+            .expect("items should have xattrs");
+        assert_eq!(imported_xattrs, xattrs);
+
+        let xattrs = fixture
+            .client
+            .get_xattrs(Path::new("/relationships"))
+            .expect("failed to get xattrs");
+        assert!(xattrs.is_none());
+    }
+
+    #[test]
+    fn readdir_items_from_offset() {
+        let mut fixture = create_fixture();
+        let ids = (0..5000)
+            .map(|i| fixture.client.db.create_item(&format!("item {i}")))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to create items");
+        let names: Vec<String> = ids.iter().map(|id| id.0.to_string()).collect();
+
+        assert_eq!(list_names_unsorted(&mut fixture.client, "/items", 0), names);
+        assert_eq!(
+            list_names_unsorted(&mut fixture.client, "/items", 4990),
+            names[4990..]
+        );
+
+        // Resuming where a previous call stopped sees every entry exactly once, even when items
+        // before that point are deleted in between
+        let last = fixture
+            .client
+            .readdir(Path::new("/items"), 0)
+            .expect("failed to read dir")
+            .take(300)
+            .last()
+            .expect("listing should not be empty")
+            .expect("failed to read dir entry");
+        assert_eq!(last.name, names[299].as_str());
+        for id in &ids[..10] {
+            fixture
+                .client
+                .db
+                .delete_item(*id)
+                .expect("failed to delete item");
+        }
+        let rest = list_names_unsorted(&mut fixture.client, "/items", last.offset);
+        assert_eq!(rest, names[300..]);
+    }
+
+    #[test]
+    fn search() {
+        let mut fixture = create_fixture();
+        let db = &mut fixture.client.db;
+        let relationship_id = db
+            .add_relationship("parents", "children")
+            .expect("failed to create relationship");
+        let groceries = db.create_item("groceries").expect("failed to create item");
+        let milk = db.create_item("buy milk").expect("failed to create item");
+        db.create_item("more groceries")
+            .expect("failed to create item");
+        db.add_item_relationship(groceries, milk, relationship_id)
+            .expect("failed to add item relationship");
+
+        let queries = [
+            (
+                "name~groceries".to_string(),
+                vec!["groceries", "more groceries"],
+            ),
+            (
+                format!("has:{}:source", relationship_id.0),
+                vec!["groceries"],
+            ),
+            (
+                format!("no:{}:dest", relationship_id.0),
+                vec!["groceries", "more groceries"],
+            ),
+            (
+                format!("name~groceries no:{}:source", relationship_id.0),
+                vec!["more groceries"],
+            ),
+        ];
+
+        for (query, expected) in &queries {
+            let path = Path::new("/search").join(query);
+            fixture
+                .client
+                .mkdir(&path)
+                .expect("failed to create search");
+            let names = list_names(&mut fixture.client, &format!("/search/{query}"));
+            assert_eq!(&names, expected, "query: {query}");
+        }
+
+        let mut expected_searches: Vec<String> =
+            queries.into_iter().map(|(query, _)| query).collect();
+        expected_searches.sort();
+        assert_eq!(
+            list_names(&mut fixture.client, "/search"),
+            expected_searches
+        );
+
+        for query in [" ", "bogus", "has:abc:source", "no:1:sideways"] {
+            let Err(MkdirError::InvalidQuery) =
+                fixture.client.mkdir(&Path::new("/search").join(query))
+            else {
+                panic!("expected invalid query for {query:?}");
+            };
+        }
+
+        fixture
+            .client
+            .rmdir(&Path::new("/search").join("name~groceries"))
+            .expect("failed to remove search");
+        assert!(!list_names(&mut fixture.client, "/search").contains(&"name~groceries".to_string()));
+    }
+
+    #[test]
+    fn readonly_rejects_socket_writes() {
+        let temp_dir = tempfile::tempdir().expect("failed to create db dir");
+        let db = Db::new(temp_dir.path().into()).expect("failed to create db");
+        let mut client = FuseClient::new(db, true, DEFAULT_RECENT_COUNT, false);
+
+        let socket_path = Path::new(crate::fuse::api::API_HANDLE_PATH);
+        let OpenRet::Socket(id) = client
+            .open(socket_path, READ_WRITE)
+            .expect("failed to open socket")
+        else {
+            panic!("expected socket");
+        };
+
+        let request = br#"{"type": "create_item", "data": {"name": "test"}}"#;
+        let Err(WriteError::ReadOnly) = client.write(socket_path, id, request) else {
+            panic!("expected read only error");
+        };
+
+        assert!(client
+            .db
+            .get_items()
+            .expect("failed to get items")
+            .is_empty());
+        let root_entries = client
+            .readdir(Path::new("/"), 0)
+            .expect("reads should still work when read only");
+        assert!(root_entries.count() > 0);
+    }
+}
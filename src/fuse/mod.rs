@@ -5,13 +5,17 @@ use std::{
     sync::{Mutex, MutexGuard},
 };
 
-use log::warn;
+use log::{debug, warn};
+use thiserror::Error;
 
-use crate::db::Db;
+use crate::db::{CreateItemError, Db};
 
-use client::{DirEntry, FuseClient};
+use client::FuseClient;
 
-use self::client::{Filetype, OpenRet};
+use self::client::{
+    CreateError, Filetype, GetWritablePassthroughPathError, MkdirError, OpenError, OpenFlags,
+    OpenRet, RenameError, RmdirError, SymlinkError, UnlinkError,
+};
 
 pub mod api;
 mod client;
@@ -42,6 +46,14 @@ macro_rules! c_call_errno_neg_1 {
     };
 }
 
+macro_rules! return_if_readonly {
+    ($client:expr) => {
+        if $client.is_readonly() {
+            return -(sys::EROFS as c_int);
+        }
+    };
+}
+
 macro_rules! log_error_chain {
     ($s:expr, $err:expr) => {{
         use std::error::Error;
@@ -61,6 +73,21 @@ macro_rules! log_error_chain {
     }};
 }
 
+/// Passthrough path for operations that change what is at path, returning EROFS from the calling
+/// function for the content of trashed items
+macro_rules! writable_passthrough_path_or_return {
+    ($client:expr, $path:expr) => {
+        match $client.get_writable_passthrough_path($path) {
+            Ok(v) => v,
+            Err(GetWritablePassthroughPathError::InTrash) => return -(sys::EROFS as c_int),
+            Err(e) => {
+                log_error_chain!("failed to get passthrough path", e);
+                return -1;
+            }
+        }
+    };
+}
+
 unsafe fn c_to_rust_path(s: *const c_char) -> &'static Path {
     Path::new(
         CStr::from_ptr(s)
@@ -80,6 +107,35 @@ unsafe fn get_client() -> MutexGuard<'static, FuseClient> {
     (*client).lock().expect("poisoned lock")
 }
 
+unsafe fn fill_stat(statbuf: *mut sys::stat, filetype: &Filetype) {
+    match filetype {
+        Filetype::Dir => {
+            (*statbuf).st_mode = sys::S_IFDIR | 0o755;
+            (*statbuf).st_nlink = 2;
+        }
+        Filetype::Link => {
+            (*statbuf).st_mode = sys::S_IFLNK | 0o777;
+            (*statbuf).st_nlink = 1;
+        }
+        Filetype::File(size) => {
+            (*statbuf).st_mode = sys::S_IFREG | 0o666;
+            (*statbuf).st_nlink = 1;
+            (*statbuf).st_size = (*size).try_into().expect("file size did not fit in i64");
+        }
+    }
+}
+
+/// Item folders and their content folders report the total size of the content as their size
+unsafe fn fill_content_size(client: &mut FuseClient, path: &Path, statbuf: *mut sys::stat) {
+    match client.get_content_size(path) {
+        Ok(Some(size)) => {
+            (*statbuf).st_size = size.try_into().expect("content size did not fit in i64");
+        }
+        Ok(None) => (),
+        Err(e) => log_error_chain!("failed to get content size", e),
+    }
+}
+
 unsafe extern "C" fn fuse_client_getattr(path: *const c_char, statbuf: *mut sys::stat) -> c_int {
     let mut client = get_client();
     let rust_path = c_to_rust_path(path);
@@ -92,24 +148,41 @@ unsafe extern "C" fn fuse_client_getattr(path: *const c_char, statbuf: *mut sys:
     if let Some(p) = passthrough_path {
         use sys::lstat;
         let ret = c_call_errno_neg_1!(lstat, rust_to_c_path(p).as_ptr(), statbuf);
+        if ret == 0 {
+            fill_content_size(&mut client, rust_path, statbuf);
+        }
         return ret;
     }
 
-    match client.get_filetype(rust_path) {
-        Ok(Filetype::Dir) => {
-            (*statbuf).st_mode = sys::S_IFDIR | 0o755;
-        }
-        Ok(Filetype::Link) => {
-            (*statbuf).st_mode = sys::S_IFLNK | 0o777;
-        }
-        Ok(Filetype::File(size)) => {
-            (*statbuf).st_mode = sys::S_IFREG | 0o666;
-            (*statbuf).st_size = size.try_into().expect("file size did not fit in i64");
-        }
+    let filetype = match client.get_filetype(rust_path) {
+        Ok(filetype) => filetype,
         Err(e) => {
             log_error_chain!("failed to get attr", e);
             return -1;
         }
+    };
+
+    fill_stat(statbuf, &filetype);
+
+    match client.get_timestamp(rust_path) {
+        Ok(Some(timestamp)) => {
+            (*statbuf).st_mtim.tv_sec = timestamp;
+            (*statbuf).st_ctim.tv_sec = timestamp;
+        }
+        Ok(None) => (),
+        Err(e) => log_error_chain!("failed to get timestamp", e),
+    }
+
+    if let Filetype::Dir = filetype {
+        fill_content_size(&mut client, rust_path, statbuf);
+        match client.dir_link_count(rust_path) {
+            Ok(nlink) => {
+                (*statbuf).st_nlink = nlink
+                    .try_into()
+                    .expect("link count does not fit in nlink_t")
+            }
+            Err(e) => log_error_chain!("failed to count directory links", e),
+        }
     }
 
     0
@@ -119,24 +192,41 @@ unsafe extern "C" fn fuse_client_readdir(
     path: *const c_char,
     buf: *mut c_void,
     mut filler: sys::fuse_fill_dir_t,
-    _offset: sys::off_t,
+    offset: sys::off_t,
     _info: *mut sys::fuse_file_info,
 ) -> c_int {
     let mut client = get_client();
     let filler = filler.as_mut().expect("fuse provided invalid dir filler");
 
-    let it = unwrap_or_return!(client.readdir(c_to_rust_path(path)), "readdir");
+    // Every entry carries the offset to resume after it, so the kernel continues exactly where
+    // the buffer filled up
+    let offset: usize = offset.try_into().expect("negative readdir offset");
+    let it = unwrap_or_return!(client.readdir(c_to_rust_path(path), offset), "readdir");
 
     for item in it {
-        // FIXME: fill stat buf
-        let name = match item {
-            DirEntry::Dir(name) => name,
-            DirEntry::File(name) => name,
-            DirEntry::Link(name) => name,
+        let item = unwrap_or_return!(item, "read dir entry");
+        let mut statbuf: sys::stat = MaybeUninit::zeroed().assume_init();
+
+        // Prefer the real metadata for passthrough paths, but fall back to what we know about
+        // the entry if that fails
+        let passthrough_stat_ret = match item.passthrough_path {
+            Some(p) => sys::lstat(rust_to_c_path(p).as_ptr(), &mut statbuf),
+            None => -1,
         };
-        let name =
-            CString::new(name.into_encoded_bytes()).expect("rust paths should be valid cstrings");
-        filler(buf, name.as_ptr(), std::ptr::null(), 0);
+
+        if passthrough_stat_ret == -1 {
+            fill_stat(&mut statbuf, &item.filetype);
+        }
+
+        let name = CString::new(item.name.into_encoded_bytes())
+            .expect("rust paths should be valid cstrings");
+        let next_offset = item
+            .offset
+            .try_into()
+            .expect("readdir offset does not fit in off_t");
+        if filler(buf, name.as_ptr(), &statbuf, next_offset) != 0 {
+            break;
+        }
     }
 
     0
@@ -149,30 +239,48 @@ unsafe extern "C" fn fuse_client_open(
     let mut client = get_client();
     let rust_path = c_to_rust_path(path);
 
-    let passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(rust_path),
-        "get passthrough path"
-    );
+    let access_mode = (*info).flags & sys::O_ACCMODE as c_int;
+    let flags = OpenFlags {
+        write: access_mode != sys::O_RDONLY as c_int || (*info).flags & sys::O_TRUNC as c_int != 0,
+        directory: (*info).flags & sys::O_DIRECTORY as c_int != 0,
+    };
+    let passthrough_path = if flags.write {
+        return_if_readonly!(client);
+        writable_passthrough_path_or_return!(client, rust_path)
+    } else {
+        unwrap_or_return!(
+            client.get_passthrough_path(rust_path),
+            "get passthrough path"
+        )
+    };
 
     if let Some(p) = passthrough_path {
-        use sys::open;
-        println!("Trying to open: {:?}", p);
-        let ret = c_call_errno_neg_1!(open, rust_to_c_path(p).as_ptr(), (*info).flags);
-        (*info).fh = ret.try_into().expect("file handle cannot caset to u64");
+        let ret = open_passthrough_file(p, (*info).flags);
+        if ret < 0 {
+            return ret;
+        }
+        (*info).fh = client.open_passthrough_fd(ret);
         return 0;
     }
 
-    match client.open(rust_path) {
-        Ok(OpenRet::Socket(id)) => {
+    match client.open(rust_path, flags) {
+        Ok(OpenRet::Socket(id) | OpenRet::Snapshot(id)) => {
             (*info).fh = id;
             (*info).set_direct_io(1);
             0
         }
-        Ok(OpenRet::Noop) => 0,
+        Ok(OpenRet::Noop { direct_io }) => {
+            (*info).set_direct_io(direct_io.into());
+            0
+        }
         Ok(OpenRet::Unhandled) => {
             log::error!("Unhandled open for {rust_path:?}");
             -1
         }
+        Err(OpenError::NotFound) => -(sys::ENOENT as c_int),
+        Err(OpenError::NotADirectory) => -(sys::ENOTDIR as c_int),
+        Err(OpenError::IsADirectory) => -(sys::EISDIR as c_int),
+        Err(OpenError::ReadOnly) => -(sys::EACCES as c_int),
         Err(e) => {
             log::error!("Failed to open {rust_path:?}: {e}");
             -1
@@ -180,41 +288,78 @@ unsafe extern "C" fn fuse_client_open(
     }
 }
 
+/// Opens a file in a content folder with the flags the application asked for, so O_APPEND and
+/// friends keep applying to every write through the handle. Returns the new fd, or a negated errno
+unsafe fn open_passthrough_file(path: PathBuf, flags: c_int) -> c_int {
+    use sys::open;
+    debug!("opening passthrough file {path:?}");
+    c_call_errno_neg_1!(open, rust_to_c_path(path).as_ptr(), flags)
+}
+
+/// Opens a file in a content folder on behalf of create. Returns the new fd, or a negated errno.
+/// O_CREAT is implied by the operation so it is added if the caller left it out, O_EXCL is
+/// passed along for open to fail with EEXIST if the file is already there
+unsafe fn create_passthrough_file(path: PathBuf, flags: c_int, mode: sys::mode_t) -> c_int {
+    use sys::open;
+    let flags = flags | sys::O_CREAT as c_int;
+    debug!("creating passthrough file {path:?} with flags {flags:#o}");
+    c_call_errno_neg_1!(open, rust_to_c_path(path).as_ptr(), flags, mode)
+}
+
 unsafe extern "C" fn fuse_client_create(
     path: *const c_char,
     mode: sys::mode_t,
     info: *mut sys::fuse_file_info,
 ) -> c_int {
     let mut client = get_client();
+    return_if_readonly!(client);
     let rust_path = c_to_rust_path(path);
 
-    let passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(rust_path),
-        "get passthrough path"
-    );
+    let passthrough_path = writable_passthrough_path_or_return!(client, rust_path);
 
     if let Some(p) = passthrough_path {
-        use sys::open;
-        let ret = c_call_errno_neg_1!(open, rust_to_c_path(p).as_ptr(), (*info).flags, mode);
-        (*info).fh = ret.try_into().expect("file handle cannot cast to u64");
+        unwrap_or_return!(
+            client.prepare_passthrough_create(&p),
+            "prepare passthrough create"
+        );
+        let ret = create_passthrough_file(p, (*info).flags, mode);
+        if ret < 0 {
+            return ret;
+        }
+        (*info).fh = client.open_passthrough_fd(ret);
         return 0;
     }
 
-    warn!("mapped_path in create {:?}", rust_path);
-
-    -1
+    match client.create(rust_path) {
+        Ok(id) => {
+            (*info).fh = id;
+            0
+        }
+        Err(CreateError::ItemExists(_)) => -(sys::EEXIST as c_int),
+        Err(CreateError::InvalidName)
+        | Err(CreateError::NumericName)
+        | Err(CreateError::CreateItem(CreateItemError::InvalidItemName(_))) => {
+            -(sys::EINVAL as c_int)
+        }
+        Err(CreateError::Unhandled) => {
+            warn!("attempted create on unhandled path {:?}", rust_path);
+            -(sys::EPERM as c_int)
+        }
+        Err(e) => {
+            log_error_chain!("failed to create", e);
+            -1
+        }
+    }
 }
 unsafe extern "C" fn fuse_client_chmod(
     path: *const ::std::os::raw::c_char,
     mode: sys::mode_t,
 ) -> ::std::os::raw::c_int {
     let mut client = get_client();
+    return_if_readonly!(client);
     let rust_path = c_to_rust_path(path);
 
-    let passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(rust_path),
-        "get passthrough path"
-    );
+    let passthrough_path = writable_passthrough_path_or_return!(client, rust_path);
 
     if let Some(p) = passthrough_path {
         use sys::chmod;
@@ -229,6 +374,7 @@ unsafe extern "C" fn fuse_client_chown(
     _arg2: sys::uid_t,
     _arg3: sys::gid_t,
 ) -> ::std::os::raw::c_int {
+    return_if_readonly!(get_client());
     warn!("unimplemented chown");
     0
 }
@@ -236,6 +382,7 @@ unsafe extern "C" fn fuse_client_truncate(
     _arg1: *const ::std::os::raw::c_char,
     _arg2: sys::off_t,
 ) -> ::std::os::raw::c_int {
+    return_if_readonly!(get_client());
     warn!("unimplemented truncate");
     0
 }
@@ -244,10 +391,21 @@ unsafe extern "C" fn fuse_client_utimens(
     _arg1: *const ::std::os::raw::c_char,
     _tv: *const sys::timespec,
 ) -> ::std::os::raw::c_int {
+    return_if_readonly!(get_client());
     warn!("unimplemented utimens");
     0
 }
 
+/// Writes to an open passthrough file. Returns the number of bytes written, or a negated errno
+///
+/// pwrite may write less than requested, what was actually written is reported so the kernel
+/// retries the remainder. Files opened with O_APPEND ignore the offset and always write at the end
+unsafe fn write_passthrough_fd(fd: c_int, buf: &[u8], offset: sys::off_t) -> c_int {
+    use sys::pwrite;
+    let ret = c_call_errno_neg_1!(pwrite, fd, buf.as_ptr() as *const c_void, buf.len(), offset);
+    ret.try_into().expect("write returned invalid return code")
+}
+
 unsafe extern "C" fn fuse_client_write(
     path: *const ::std::os::raw::c_char,
     buf: *const ::std::os::raw::c_char,
@@ -256,45 +414,29 @@ unsafe extern "C" fn fuse_client_write(
     info: *mut sys::fuse_file_info,
 ) -> ::std::os::raw::c_int {
     let mut client = get_client();
+    return_if_readonly!(client);
     let rust_path = c_to_rust_path(path);
-    let passthrough_path = client.get_passthrough_path(rust_path);
-
-    match passthrough_path {
-        Ok(Some(passthrough_path)) => {
-            if (*info).fh == 0 {
-                use sys::open;
-                let ret = c_call_errno_neg_1!(
-                    open,
-                    rust_to_c_path(passthrough_path).as_ptr(),
-                    sys::O_WRONLY as i32
-                );
-                (*info).fh = ret.try_into().expect("file handle cannot cast to u64");
-            }
 
-            use sys::pwrite;
-            let ret = c_call_errno_neg_1!(
-                pwrite,
-                (*info)
-                    .fh
-                    .try_into()
-                    .expect("file handle is not a valid i32"),
-                buf as *mut c_void,
-                size,
-                offset
-            );
-
-            ret.try_into().expect("write returned invalid return code")
-        }
-        Ok(None) => {
-            let rust_buf = std::slice::from_raw_parts(buf as *const u8, size);
-            unwrap_or_return!(client.write((*info).fh, rust_buf), "write");
-            size.try_into().expect("failed to cast size to i32")
-        }
-        Err(e) => {
-            log::error!("Failed to resolve write path: {e}");
-            -1
-        }
+    let rust_buf = std::slice::from_raw_parts(buf as *const u8, size);
+    if let Some(fd) = client.passthrough_fd((*info).fh) {
+        return write_passthrough_fd(fd, rust_buf, offset);
     }
+
+    unwrap_or_return!(client.write(rust_path, (*info).fh, rust_buf), "write");
+    size.try_into().expect("failed to cast size to i32")
+}
+
+/// Reads from an open passthrough file. Returns the number of bytes read, or a negated errno
+unsafe fn read_passthrough_fd(fd: c_int, buf: &mut [u8], offset: sys::off_t) -> c_int {
+    use sys::pread;
+    let ret = c_call_errno_neg_1!(
+        pread,
+        fd,
+        buf.as_mut_ptr() as *mut c_void,
+        buf.len(),
+        offset
+    );
+    ret.try_into().expect("return value not castable to i32")
 }
 
 unsafe extern "C" fn fuse_client_read(
@@ -306,45 +448,31 @@ unsafe extern "C" fn fuse_client_read(
 ) -> ::std::os::raw::c_int {
     let mut client = get_client();
     let rust_path = c_to_rust_path(path);
-    let passthrough_path = client.get_passthrough_path(rust_path);
-
-    match passthrough_path {
-        Ok(Some(passthrough_path)) => {
-            if (*info).fh == 0 {
-                use sys::open;
-                let ret = c_call_errno_neg_1!(
-                    open,
-                    rust_to_c_path(passthrough_path).as_ptr(),
-                    sys::O_RDONLY as i32
-                );
-                (*info).fh = ret.try_into().expect("file handle cannot cast to u64");
-            }
-
-            use sys::pread;
-            let ret = c_call_errno_neg_1!(
-                pread,
-                (*info)
-                    .fh
-                    .try_into()
-                    .expect("file handle is not a valid i32"),
-                buf as *mut c_void,
-                size,
-                offset
-            );
 
-            ret.try_into().expect("return value not castable to i32")
-        }
-        Ok(None) => {
-            let rust_buf = std::slice::from_raw_parts_mut(buf as *mut u8, size);
-            unwrap_or_return!(client.read(rust_path, (*info).fh, rust_buf), "read")
-                .try_into()
-                .expect("failed to cast usize to i32")
-        }
-        Err(e) => {
-            log::error!("Failed to resolve read path: {e}");
-            -1
-        }
+    let rust_buf = std::slice::from_raw_parts_mut(buf as *mut u8, size);
+    if let Some(fd) = client.passthrough_fd((*info).fh) {
+        return read_passthrough_fd(fd, rust_buf, offset);
     }
+
+    let offset: usize = offset.try_into().expect("negative read offset");
+    unwrap_or_return!(client.read(rust_path, (*info).fh, offset, rust_buf), "read")
+        .try_into()
+        .expect("failed to cast usize to i32")
+}
+
+/// Reads the target of a link in a content folder into buf, nul terminated and truncated to fit
+/// like fuse expects. Returns 0, or a negated errno
+unsafe fn read_passthrough_link(path: PathBuf, buf: &mut [u8]) -> c_int {
+    use sys::readlink;
+    debug!("reading passthrough link {path:?}");
+    let len = c_call_errno_neg_1!(
+        readlink,
+        rust_to_c_path(path).as_ptr(),
+        buf.as_mut_ptr() as *mut c_char,
+        buf.len() - 1
+    );
+    buf[len as usize] = 0;
+    0
 }
 
 unsafe extern "C" fn fuse_client_readlink(
@@ -363,14 +491,8 @@ unsafe extern "C" fn fuse_client_readlink(
     };
 
     if let Some(passthrough_path) = passthrough_path {
-        use sys::readlink;
-        println!("resolved as passthrough path: {passthrough_path:?}");
-        return c_call_errno_neg_1!(
-            readlink,
-            rust_to_c_path(passthrough_path).as_ptr(),
-            buf,
-            bufsize
-        ) as i32;
+        let buf = std::slice::from_raw_parts_mut(buf as *mut u8, bufsize);
+        return read_passthrough_link(passthrough_path, buf);
     }
 
     let link = match client.readlink(rust_path) {
@@ -381,7 +503,7 @@ unsafe extern "C" fn fuse_client_readlink(
         }
     };
 
-    println!("Resolved link: {link:?}");
+    debug!("resolved link {rust_path:?} to {link:?}");
     let link = link.into_os_string().into_encoded_bytes();
 
     let copy_size = link.len().min(bufsize - 1);
@@ -391,86 +513,332 @@ unsafe extern "C" fn fuse_client_readlink(
     0
 }
 
-unsafe extern "C" fn fuse_client_flush(
-    _path: *const c_char,
-    _info: *mut sys::fuse_file_info,
-) -> ::std::os::raw::c_int {
-    // No cache to clear
-    0
+unsafe extern "C" fn fuse_client_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    let mut client = get_client();
+    return_if_readonly!(client);
+    let passthrough_path = writable_passthrough_path_or_return!(client, c_to_rust_path(linkpath));
+
+    let Some(passthrough_path) = passthrough_path else {
+        return match client.symlink(c_to_rust_path(target), c_to_rust_path(linkpath)) {
+            Ok(()) => 0,
+            Err(SymlinkError::InvalidTarget) => -(sys::EINVAL as c_int),
+            Err(SymlinkError::Unhandled) => {
+                warn!("attempted symlink on non-passthrough path");
+                -(sys::EPERM as c_int)
+            }
+            Err(e) => {
+                log_error_chain!("failed to symlink", e);
+                -1
+            }
+        };
+    };
+
+    unwrap_or_return!(
+        client.prepare_passthrough_create(&passthrough_path),
+        "prepare passthrough create"
+    );
+    create_passthrough_symlink(CStr::from_ptr(target), passthrough_path)
 }
 
-unsafe extern "C" fn fuse_client_release(
+/// Creates a link in a content folder. target is stored verbatim, relative links are interpreted
+/// relative to the link itself. Returns 0, or a negated errno
+unsafe fn create_passthrough_symlink(target: &CStr, path: PathBuf) -> c_int {
+    use sys::symlink;
+    c_call_errno_neg_1!(symlink, target.as_ptr(), rust_to_c_path(path).as_ptr())
+}
+
+unsafe extern "C" fn fuse_client_link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    let mut client = get_client();
+    return_if_readonly!(client);
+    // A link to a trashed file would allow changing it from outside of the trash
+    let old_passthrough_path =
+        writable_passthrough_path_or_return!(client, c_to_rust_path(oldpath));
+    let new_passthrough_path =
+        writable_passthrough_path_or_return!(client, c_to_rust_path(newpath));
+
+    if let Some(p) = &new_passthrough_path {
+        unwrap_or_return!(
+            client.prepare_passthrough_create(p),
+            "prepare passthrough create"
+        );
+    }
+
+    link_passthrough_files(&client.db, old_passthrough_path, new_passthrough_path)
+}
+
+/// Hardlinks can only live within content folders, anything else is on the other side of the
+/// synthetic/real boundary. Returns 0, or a negated errno
+unsafe fn link_passthrough_files(db: &Db, old: Option<PathBuf>, new: Option<PathBuf>) -> c_int {
+    match (old, new) {
+        (Some(old), Some(new)) if db.is_content_path(&old) && db.is_content_path(&new) => {
+            use sys::link;
+            c_call_errno_neg_1!(
+                link,
+                rust_to_c_path(old).as_ptr(),
+                rust_to_c_path(new).as_ptr()
+            )
+        }
+        _ => -(sys::EXDEV as c_int),
+    }
+}
+
+/// Follows the xattr convention of returning the required size when size is 0
+unsafe fn copy_xattr_buf(data: &[u8], buf: *mut c_char, size: usize) -> c_int {
+    let len: c_int = data.len().try_into().expect("xattr too large");
+    if size == 0 {
+        return len;
+    }
+
+    if size < data.len() {
+        return -(sys::ERANGE as c_int);
+    }
+
+    std::ptr::copy(data.as_ptr(), buf as *mut u8, data.len());
+    len
+}
+
+unsafe extern "C" fn fuse_client_getxattr(
     path: *const c_char,
-    info: *mut sys::fuse_file_info,
+    name: *const c_char,
+    value: *mut c_char,
+    size: usize,
 ) -> c_int {
     let mut client = get_client();
     let rust_path = c_to_rust_path(path);
-    let passthrough_path = client.get_passthrough_path(rust_path);
+    let passthrough_path = unwrap_or_return!(
+        client.get_passthrough_path(rust_path),
+        "get passthrough path"
+    );
 
-    match passthrough_path {
-        Ok(Some(_)) => {
-            use sys::close;
-            c_call_errno_neg_1!(close, (*info).fh as i32)
-        }
-        Ok(None) => {
-            client.release((*info).fh);
-            0
-        }
-        Err(e) => {
-            log::error!("Failed to retrieve passthrough path: {e}");
-            -1
-        }
+    if let Some(p) = passthrough_path {
+        use sys::lgetxattr;
+        return c_call_errno_neg_1!(
+            lgetxattr,
+            rust_to_c_path(p).as_ptr(),
+            name,
+            value as *mut c_void,
+            size
+        ) as c_int;
+    }
+
+    let Some(xattrs) = unwrap_or_return!(client.get_xattrs(rust_path), "get xattrs") else {
+        return -(sys::ENOTSUP as c_int);
+    };
+
+    let name = CStr::from_ptr(name).to_bytes();
+    match xattrs.iter().find(|xattr| xattr.name.as_bytes() == name) {
+        Some(xattr) => copy_xattr_buf(&xattr.value, value, size),
+        None => -(sys::ENODATA as c_int),
     }
 }
-unsafe extern "C" fn fuse_client_unlink(path: *const c_char) -> c_int {
+
+unsafe extern "C" fn fuse_client_listxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: usize,
+) -> c_int {
     let mut client = get_client();
+    let rust_path = c_to_rust_path(path);
     let passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(c_to_rust_path(path)),
+        client.get_passthrough_path(rust_path),
         "get passthrough path"
     );
+
     if let Some(p) = passthrough_path {
-        use sys::unlink;
-        c_call_errno_neg_1!(unlink, rust_to_c_path(p).as_ptr())
-    } else {
-        warn!("attempted unlink on non-passthrough path");
-        -1
+        use sys::llistxattr;
+        return c_call_errno_neg_1!(llistxattr, rust_to_c_path(p).as_ptr(), list, size) as c_int;
+    }
+
+    let Some(xattrs) = unwrap_or_return!(client.get_xattrs(rust_path), "get xattrs") else {
+        return -(sys::ENOTSUP as c_int);
+    };
+
+    let mut names = Vec::new();
+    for xattr in xattrs {
+        names.extend_from_slice(xattr.name.as_bytes());
+        names.push(0);
     }
+
+    copy_xattr_buf(&names, list, size)
 }
 
-unsafe extern "C" fn fuse_client_rename(from: *const c_char, to: *const c_char) -> c_int {
+unsafe extern "C" fn fuse_client_mkdir(path: *const c_char, mode: sys::mode_t) -> c_int {
     let mut client = get_client();
-    let from_passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(c_to_rust_path(from)),
-        "get from passthrough path"
-    );
+    let rust_path = c_to_rust_path(path);
+    let passthrough_path = writable_passthrough_path_or_return!(client, rust_path);
 
-    let to_passthrough_path = unwrap_or_return!(
-        client.get_passthrough_path(c_to_rust_path(to)),
-        "get from passthrough path"
-    );
+    if let Some(p) = passthrough_path {
+        return_if_readonly!(client);
+        unwrap_or_return!(
+            client.prepare_passthrough_create(&p),
+            "prepare passthrough create"
+        );
+        use sys::mkdir;
+        return c_call_errno_neg_1!(mkdir, rust_to_c_path(p).as_ptr(), mode);
+    }
+
+    match client.mkdir(rust_path) {
+        Ok(()) => 0,
+        Err(MkdirError::InvalidQuery) => -(sys::ENOENT as c_int),
+        Err(MkdirError::Unhandled) => -(sys::EPERM as c_int),
+        Err(e) => {
+            log_error_chain!("failed to mkdir", e);
+            -1
+        }
+    }
+}
 
-    let Some(from_passthrough_path) = from_passthrough_path else {
-        warn!("source path of rename not a passthrough path");
-        return -1;
+unsafe extern "C" fn fuse_client_rmdir(path: *const c_char) -> c_int {
+    let mut client = get_client();
+    let rust_path = c_to_rust_path(path);
+    let passthrough_path = writable_passthrough_path_or_return!(client, rust_path);
+
+    if let Some(p) = passthrough_path {
+        return_if_readonly!(client);
+        use sys::rmdir;
+        return c_call_errno_neg_1!(rmdir, rust_to_c_path(p).as_ptr());
+    }
+
+    match client.rmdir(rust_path) {
+        Ok(()) => 0,
+        Err(RmdirError::NotFound) => -(sys::ENOENT as c_int),
+        Err(RmdirError::Unhandled) => -(sys::EPERM as c_int),
+        Err(e) => {
+            log_error_chain!("failed to rmdir", e);
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn fuse_client_flush(
+    _path: *const c_char,
+    _info: *mut sys::fuse_file_info,
+) -> ::std::os::raw::c_int {
+    // No cache to clear
+    0
+}
+
+unsafe extern "C" fn fuse_client_fsync(
+    _path: *const c_char,
+    datasync: c_int,
+    info: *mut sys::fuse_file_info,
+) -> c_int {
+    let client = get_client();
+    let Some(fd) = client.passthrough_fd((*info).fh) else {
+        // Virtual files are backed by the db, nothing to flush
+        return 0;
     };
 
-    let Some(to_passthrough_path) = to_passthrough_path else {
-        warn!("dest path of rename not a passthrough path");
-        return -1;
+    if datasync != 0 {
+        use sys::fdatasync;
+        c_call_errno_neg_1!(fdatasync, fd)
+    } else {
+        use sys::fsync;
+        c_call_errno_neg_1!(fsync, fd)
+    }
+}
+
+/// Preallocates space in an open passthrough file. Only the default mode is supported as
+/// posix_fallocate has no way to punch holes or keep the size unchanged
+unsafe fn fallocate_fd(fd: c_int, mode: c_int, offset: sys::off_t, length: sys::off_t) -> c_int {
+    if mode != 0 {
+        return -(sys::EOPNOTSUPP as c_int);
+    }
+
+    // posix_fallocate returns the error directly instead of setting errno
+    -sys::posix_fallocate(fd, offset, length)
+}
+
+unsafe extern "C" fn fuse_client_fallocate(
+    _path: *const c_char,
+    mode: c_int,
+    offset: sys::off_t,
+    length: sys::off_t,
+    info: *mut sys::fuse_file_info,
+) -> c_int {
+    let client = get_client();
+    return_if_readonly!(client);
+    let Some(fd) = client.passthrough_fd((*info).fh) else {
+        // Virtual files are generated on read, there is nothing to preallocate
+        return -(sys::EOPNOTSUPP as c_int);
     };
 
-    println!(
-        "Renaming {} -> {}",
-        from_passthrough_path.display(),
-        to_passthrough_path.display()
-    );
-    use sys::rename;
-    c_call_errno_neg_1!(
-        rename,
-        rust_to_c_path(from_passthrough_path).as_ptr(),
-        rust_to_c_path(to_passthrough_path).as_ptr()
-    )
+    fallocate_fd(fd, mode, offset, length)
+}
+
+unsafe extern "C" fn fuse_client_statfs(_path: *const c_char, statbuf: *mut sys::statvfs) -> c_int {
+    let client = get_client();
+    let item_count = unwrap_or_return!(client.db.count_items(), "count items");
+
+    *statbuf = MaybeUninit::zeroed().assume_init();
+    (*statbuf).f_bsize = 4096;
+    (*statbuf).f_namemax = 255;
+    // Every item is a directory in /items, report those as the used inodes
+    (*statbuf).f_files = item_count
+        .try_into()
+        .expect("item count should not be negative");
+    0
+}
+
+unsafe extern "C" fn fuse_client_release(
+    _path: *const c_char,
+    info: *mut sys::fuse_file_info,
+) -> c_int {
+    let mut client = get_client();
+
+    if let Some(fd) = client.release_passthrough_fd((*info).fh) {
+        use sys::close;
+        return c_call_errno_neg_1!(close, fd);
+    }
+
+    client.release((*info).fh);
+    0
 }
+unsafe extern "C" fn fuse_client_unlink(path: *const c_char) -> c_int {
+    let mut client = get_client();
+    return_if_readonly!(client);
+    let passthrough_path = writable_passthrough_path_or_return!(client, c_to_rust_path(path));
+    if let Some(p) = passthrough_path {
+        use sys::unlink;
+        return c_call_errno_neg_1!(unlink, rust_to_c_path(p).as_ptr());
+    }
+
+    match client.unlink(c_to_rust_path(path)) {
+        Ok(()) => 0,
+        Err(UnlinkError::NotFound) => -(sys::ENOENT as c_int),
+        Err(UnlinkError::Computed) | Err(UnlinkError::Unhandled) => {
+            warn!("attempted unlink on non-passthrough path");
+            -(sys::EPERM as c_int)
+        }
+        Err(e) => {
+            log_error_chain!("failed to unlink", e);
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn fuse_client_rename(from: *const c_char, to: *const c_char) -> c_int {
+    let mut client = get_client();
+    return_if_readonly!(client);
+
+    match client.rename(c_to_rust_path(from), c_to_rust_path(to)) {
+        Ok(()) => 0,
+        Err(RenameError::NotFound) => -(sys::ENOENT as c_int),
+        // mv falls back to copy and delete when it sees EXDEV
+        Err(RenameError::CrossesBoundary) => -(sys::EXDEV as c_int),
+        Err(RenameError::Synthetic) => -(sys::EPERM as c_int),
+        Err(RenameError::InTrash) => -(sys::EROFS as c_int),
+        Err(RenameError::Rename(e)) => match e.raw_os_error() {
+            Some(errno) => -errno,
+            None => -(sys::EIO as c_int),
+        },
+        Err(e) => {
+            log_error_chain!("failed to rename", e);
+            -1
+        }
+    }
+}
+
 const fn generate_fuse_ops() -> sys::fuse_operations {
     unsafe {
         let mut ops: sys::fuse_operations = MaybeUninit::zeroed().assume_init();
@@ -485,7 +853,16 @@ const fn generate_fuse_ops() -> sys::fuse_operations {
         ops.write = Some(fuse_client_write);
         ops.read = Some(fuse_client_read);
         ops.flush = Some(fuse_client_flush);
+        ops.fsync = Some(fuse_client_fsync);
+        ops.fallocate = Some(fuse_client_fallocate);
+        ops.statfs = Some(fuse_client_statfs);
         ops.readlink = Some(fuse_client_readlink);
+        ops.symlink = Some(fuse_client_symlink);
+        ops.link = Some(fuse_client_link);
+        ops.mkdir = Some(fuse_client_mkdir);
+        ops.rmdir = Some(fuse_client_rmdir);
+        ops.getxattr = Some(fuse_client_getxattr);
+        ops.listxattr = Some(fuse_client_listxattr);
         ops.release = Some(fuse_client_release);
         ops.unlink = Some(fuse_client_unlink);
         ops.rename = Some(fuse_client_rename);
@@ -493,12 +870,105 @@ const fn generate_fuse_ops() -> sys::fuse_operations {
     }
 }
 
-pub fn run_fuse_client(db: Db, args: impl Iterator<Item = String>) {
-    let mut client = Mutex::new(FuseClient::new(db));
-    let args: Vec<CString> = args
-        .map(|s| CString::new(s).expect("input args not valid c strings"))
-        .collect();
+#[derive(Debug, Error)]
+pub enum FuseArgsError {
+    #[error("no argument after {0}")]
+    MissingValue(String),
+    #[error("--recent-count {0:?} is not a valid count")]
+    InvalidRecentCount(String, #[source] std::num::ParseIntError),
+    #[error("argument {0:?} contains a nul byte")]
+    NulByte(String),
+}
+
+struct FuseClientArgs {
+    readonly: bool,
+    recent_count: usize,
+    soft_delete: bool,
+    mountpoint: Option<PathBuf>,
+    // Everything todo-fs does not handle itself, passed on to libfuse
+    fuse_args: Vec<CString>,
+}
+
+fn parse_fuse_client_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<FuseClientArgs, FuseArgsError> {
+    let mut readonly = false;
+    let mut recent_count = client::DEFAULT_RECENT_COUNT;
+    let mut soft_delete = false;
+    let mut mountpoint = None;
+    // The program name stays first for libfuse, and is not a mountpoint candidate
+    let mut fuse_args: Vec<String> = args.next().into_iter().collect();
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--readonly" => readonly = true,
+            "--soft-delete" => soft_delete = true,
+            "-o" => {
+                let options = args
+                    .next()
+                    .ok_or_else(|| FuseArgsError::MissingValue(arg.clone()))?;
+                // Keep the client consistent with a read only mount so socket requests are
+                // rejected with EROFS as well
+                if options.split(',').any(|option| option == "ro") {
+                    readonly = true;
+                }
+                fuse_args.push(arg);
+                fuse_args.push(options);
+            }
+            "--recent-count" => {
+                let count = args
+                    .next()
+                    .ok_or_else(|| FuseArgsError::MissingValue(arg.clone()))?;
+                recent_count = count
+                    .parse()
+                    .map_err(|e| FuseArgsError::InvalidRecentCount(count, e))?;
+            }
+            _ => {
+                // fuse takes the first argument that is not an option as the mountpoint
+                if mountpoint.is_none() && !arg.starts_with('-') {
+                    mountpoint = Some(PathBuf::from(&arg));
+                }
+                fuse_args.push(arg);
+            }
+        }
+    }
+
+    let fuse_args = fuse_args
+        .into_iter()
+        .map(|arg| {
+            CString::new(arg).map_err(|e| {
+                FuseArgsError::NulByte(String::from_utf8_lossy(&e.into_vec()).into_owned())
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(FuseClientArgs {
+        readonly,
+        recent_count,
+        soft_delete,
+        mountpoint,
+        fuse_args,
+    })
+}
+
+/// Runs the file system until it is unmounted, returning the exit code from libfuse.
+///
+/// fuse_main installs SIGINT, SIGTERM and SIGHUP handlers that exit the loop and unmount, so by
+/// the time it returns no operation can be holding the client lock
+pub fn run_fuse_client(db: Db, args: impl Iterator<Item = String>) -> Result<c_int, FuseArgsError> {
+    let FuseClientArgs {
+        readonly,
+        recent_count,
+        soft_delete,
+        mountpoint,
+        fuse_args: args,
+    } = parse_fuse_client_args(args)?;
+
     let mut args: Vec<*mut i8> = args.into_iter().map(|s| s.into_raw()).collect();
+    let mut client = FuseClient::new(db, readonly, recent_count, soft_delete);
+    if let Some(mountpoint) = mountpoint {
+        client.set_mountpoint(std::fs::canonicalize(&mountpoint).unwrap_or(mountpoint));
+    }
+    let mut client = Mutex::new(client);
 
     let mut args = sys::fuse_args {
         argc: args
@@ -509,7 +979,7 @@ pub fn run_fuse_client(db: Db, args: impl Iterator<Item = String>) {
         allocated: 0,
     };
 
-    unsafe {
+    let ret = unsafe {
         let ret = sys::fuse_opt_parse(&mut args, std::ptr::null_mut(), std::ptr::null_mut(), None);
         if ret == -1 {
             panic!("Failed to parse fuse args");
@@ -521,6 +991,14 @@ pub fn run_fuse_client(db: Db, args: impl Iterator<Item = String>) {
             &FUSE_CLIENT_OPERATIONS,
             std::mem::size_of_val(&FUSE_CLIENT_OPERATIONS),
             &mut client as *mut Mutex<FuseClient> as *mut c_void,
-        );
+        )
+    };
+
+    let client = client.into_inner().expect("poisoned lock");
+    if let Err(e) = client.into_db().close() {
+        log_error_chain!("failed to close database", e);
+        return Ok(1);
     }
+
+    Ok(ret)
 }
@@ -4,5 +4,7 @@
 #include <fcntl.h>
 #include <unistd.h>
 #include <sys/stat.h>
+#include <sys/xattr.h>
 #include <fuse.h>
 #include <stdio.h>
+#include <errno.h>